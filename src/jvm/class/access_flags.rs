@@ -0,0 +1,571 @@
+use std::{fmt, ops::BitOr};
+
+use crate::jvm::class::error::ClassFormatError;
+
+// Several bits mean different things depending on where they appear (0x0020 is ACC_SUPER on
+// a class and ACC_SYNCHRONIZED on a method, 0x0040 is ACC_VOLATILE or ACC_BRIDGE, 0x0080 is
+// ACC_TRANSIENT or ACC_VARARGS), so each context gets its own flags type.
+macro_rules! access_flags {
+    ($name:ident { $($flag:ident = $value:literal,)* }) => {
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name(pub u16);
+
+        impl $name {
+            $(pub const $flag: $name = $name($value);)*
+
+            const NAMES: &'static [($name, &'static str)] =
+                &[$(($name::$flag, concat!("ACC_", stringify!($flag))),)*];
+
+            pub fn contains(self, flags: $name) -> bool {
+                self.0 & flags.0 == flags.0
+            }
+
+            // The JVMS names of the set flags, e.g. ["ACC_PUBLIC", "ACC_STATIC"].
+            pub fn names(self) -> Vec<&'static str> {
+                $name::NAMES
+                    .iter()
+                    .filter(|(flag, _)| self.contains(*flag))
+                    .map(|(_, name)| *name)
+                    .collect()
+            }
+
+            fn count(self, flags: &[$name]) -> usize {
+                flags.iter().filter(|flag| self.contains(**flag)).count()
+            }
+        }
+
+        impl From<u16> for $name {
+            fn from(flags: u16) -> $name {
+                $name(flags)
+            }
+        }
+
+        impl BitOr for $name {
+            type Output = $name;
+
+            fn bitor(self, rhs: $name) -> $name {
+                $name(self.0 | rhs.0)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "(0x{:04x}) {}", self.0, self.names().join(", "))
+            }
+        }
+    };
+}
+
+access_flags!(ClassAccessFlags {
+    PUBLIC = 0x0001,
+    FINAL = 0x0010,
+    SUPER = 0x0020,
+    INTERFACE = 0x0200,
+    ABSTRACT = 0x0400,
+    SYNTHETIC = 0x1000,
+    ANNOTATION = 0x2000,
+    ENUM = 0x4000,
+    MODULE = 0x8000,
+});
+
+access_flags!(FieldAccessFlags {
+    PUBLIC = 0x0001,
+    PRIVATE = 0x0002,
+    PROTECTED = 0x0004,
+    STATIC = 0x0008,
+    FINAL = 0x0010,
+    VOLATILE = 0x0040,
+    TRANSIENT = 0x0080,
+    SYNTHETIC = 0x1000,
+    ENUM = 0x4000,
+});
+
+access_flags!(MethodAccessFlags {
+    PUBLIC = 0x0001,
+    PRIVATE = 0x0002,
+    PROTECTED = 0x0004,
+    STATIC = 0x0008,
+    FINAL = 0x0010,
+    SYNCHRONIZED = 0x0020,
+    BRIDGE = 0x0040,
+    VARARGS = 0x0080,
+    NATIVE = 0x0100,
+    ABSTRACT = 0x0400,
+    STRICT = 0x0800,
+    SYNTHETIC = 0x1000,
+});
+
+access_flags!(InnerClassAccessFlags {
+    PUBLIC = 0x0001,
+    PRIVATE = 0x0002,
+    PROTECTED = 0x0004,
+    STATIC = 0x0008,
+    FINAL = 0x0010,
+    INTERFACE = 0x0200,
+    ABSTRACT = 0x0400,
+    SYNTHETIC = 0x1000,
+    ANNOTATION = 0x2000,
+    ENUM = 0x4000,
+});
+
+// Writes the modifiers whose flag is set, separated by spaces.
+fn write_modifiers(f: &mut fmt::Formatter<'_>, modifiers: &[(bool, &str)]) -> fmt::Result {
+    let modifiers: Vec<&str> = modifiers
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, modifier)| *modifier)
+        .collect();
+    f.write_str(&modifiers.join(" "))
+}
+
+fn illegal(flags: u16, reason: &str) -> ClassFormatError {
+    ClassFormatError::new(format!("illegal access flags 0x{:04x}: {}", flags, reason))
+}
+
+impl ClassAccessFlags {
+    pub fn is_interface(self) -> bool {
+        self.contains(ClassAccessFlags::INTERFACE)
+    }
+
+    // The declaration keyword: "class", "interface", "@interface", "enum" or "module".
+    pub fn kind(self) -> &'static str {
+        if self.contains(ClassAccessFlags::MODULE) {
+            "module"
+        } else if self.contains(ClassAccessFlags::ANNOTATION) {
+            "@interface"
+        } else if self.is_interface() {
+            "interface"
+        } else if self.contains(ClassAccessFlags::ENUM) {
+            "enum"
+        } else {
+            "class"
+        }
+    }
+
+    // JVMS 4.1
+    pub fn validate(self) -> Result<(), ClassFormatError> {
+        if self.contains(ClassAccessFlags::MODULE) {
+            if self != ClassAccessFlags::MODULE {
+                return Err(illegal(self.0, "ACC_MODULE with other flags"));
+            }
+            return Ok(());
+        }
+        if self.is_interface() {
+            if !self.contains(ClassAccessFlags::ABSTRACT) {
+                return Err(illegal(self.0, "interface without ACC_ABSTRACT"));
+            }
+            if self.count(&[
+                ClassAccessFlags::FINAL,
+                ClassAccessFlags::SUPER,
+                ClassAccessFlags::ENUM,
+            ]) > 0
+            {
+                return Err(illegal(
+                    self.0,
+                    "interface with ACC_FINAL, ACC_SUPER or ACC_ENUM",
+                ));
+            }
+        } else {
+            if self.contains(ClassAccessFlags::ANNOTATION) {
+                return Err(illegal(self.0, "ACC_ANNOTATION without ACC_INTERFACE"));
+            }
+            if self.contains(ClassAccessFlags::FINAL | ClassAccessFlags::ABSTRACT) {
+                return Err(illegal(self.0, "both ACC_FINAL and ACC_ABSTRACT"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Modifiers as javap prints them; "abstract" is implied for interfaces and left out.
+impl fmt::Display for ClassAccessFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_modifiers(
+            f,
+            &[
+                (self.contains(ClassAccessFlags::PUBLIC), "public"),
+                (self.contains(ClassAccessFlags::FINAL), "final"),
+                (
+                    self.contains(ClassAccessFlags::ABSTRACT) && !self.is_interface(),
+                    "abstract",
+                ),
+            ],
+        )
+    }
+}
+
+impl FieldAccessFlags {
+    pub fn is_static(self) -> bool {
+        self.contains(FieldAccessFlags::STATIC)
+    }
+
+    // JVMS 4.5
+    pub fn validate(self, in_interface: bool) -> Result<(), ClassFormatError> {
+        if in_interface {
+            let required =
+                FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC | FieldAccessFlags::FINAL;
+            if !self.contains(required) {
+                return Err(illegal(
+                    self.0,
+                    "interface field without ACC_PUBLIC, ACC_STATIC and ACC_FINAL",
+                ));
+            }
+            if self.0 & !(required | FieldAccessFlags::SYNTHETIC).0 != 0 {
+                return Err(illegal(self.0, "interface field with other flags"));
+            }
+            return Ok(());
+        }
+        let visibility = [
+            FieldAccessFlags::PUBLIC,
+            FieldAccessFlags::PRIVATE,
+            FieldAccessFlags::PROTECTED,
+        ];
+        if self.count(&visibility) > 1 {
+            return Err(illegal(
+                self.0,
+                "more than one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED",
+            ));
+        }
+        if self.contains(FieldAccessFlags::FINAL | FieldAccessFlags::VOLATILE) {
+            return Err(illegal(self.0, "both ACC_FINAL and ACC_VOLATILE"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FieldAccessFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_modifiers(
+            f,
+            &[
+                (self.contains(FieldAccessFlags::PUBLIC), "public"),
+                (self.contains(FieldAccessFlags::PRIVATE), "private"),
+                (self.contains(FieldAccessFlags::PROTECTED), "protected"),
+                (self.contains(FieldAccessFlags::STATIC), "static"),
+                (self.contains(FieldAccessFlags::FINAL), "final"),
+                (self.contains(FieldAccessFlags::VOLATILE), "volatile"),
+                (self.contains(FieldAccessFlags::TRANSIENT), "transient"),
+            ],
+        )
+    }
+}
+
+impl MethodAccessFlags {
+    pub fn is_static(self) -> bool {
+        self.contains(MethodAccessFlags::STATIC)
+    }
+
+    pub fn is_abstract(self) -> bool {
+        self.contains(MethodAccessFlags::ABSTRACT)
+    }
+
+    pub fn is_native(self) -> bool {
+        self.contains(MethodAccessFlags::NATIVE)
+    }
+
+    // JVMS 4.6; the rules depend on the method name and the class version.
+    pub fn validate(
+        self,
+        name: &str,
+        in_interface: bool,
+        major_version: u16,
+    ) -> Result<(), ClassFormatError> {
+        // Only ACC_STATIC of a class initializer is significant (and required from 51.0).
+        if name == "<clinit>" {
+            return Ok(());
+        }
+        let visibility = [
+            MethodAccessFlags::PUBLIC,
+            MethodAccessFlags::PRIVATE,
+            MethodAccessFlags::PROTECTED,
+        ];
+        if self.count(&visibility) > 1 {
+            return Err(illegal(
+                self.0,
+                "more than one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED",
+            ));
+        }
+        if in_interface {
+            if self.count(&[
+                MethodAccessFlags::PROTECTED,
+                MethodAccessFlags::FINAL,
+                MethodAccessFlags::SYNCHRONIZED,
+                MethodAccessFlags::NATIVE,
+            ]) > 0
+            {
+                return Err(illegal(
+                    self.0,
+                    "interface method with ACC_PROTECTED, ACC_FINAL, ACC_SYNCHRONIZED or ACC_NATIVE",
+                ));
+            }
+            if major_version < 52 {
+                if !self.contains(MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT) {
+                    return Err(illegal(
+                        self.0,
+                        "interface method without ACC_PUBLIC and ACC_ABSTRACT before version 52",
+                    ));
+                }
+            } else if self.count(&[MethodAccessFlags::PUBLIC, MethodAccessFlags::PRIVATE]) != 1 {
+                return Err(illegal(
+                    self.0,
+                    "interface method without exactly one of ACC_PUBLIC and ACC_PRIVATE",
+                ));
+            }
+        }
+        if self.is_abstract()
+            && self.count(&[
+                MethodAccessFlags::PRIVATE,
+                MethodAccessFlags::STATIC,
+                MethodAccessFlags::FINAL,
+                MethodAccessFlags::SYNCHRONIZED,
+                MethodAccessFlags::NATIVE,
+            ]) > 0
+        {
+            return Err(illegal(
+                self.0,
+                "ACC_ABSTRACT with ACC_PRIVATE, ACC_STATIC, ACC_FINAL, ACC_SYNCHRONIZED or ACC_NATIVE",
+            ));
+        }
+        // ACC_STRICT only means something from 46 (JDK 1.2) up to 60; from 61 on every method
+        // is strict and the flag is ignored.
+        if self.is_abstract()
+            && self.contains(MethodAccessFlags::STRICT)
+            && (46..=60).contains(&major_version)
+        {
+            return Err(illegal(
+                self.0,
+                "ACC_ABSTRACT with ACC_STRICT in a class file of version 46 to 60",
+            ));
+        }
+        if name == "<init>" {
+            let allowed = MethodAccessFlags::PUBLIC
+                | MethodAccessFlags::PRIVATE
+                | MethodAccessFlags::PROTECTED
+                | MethodAccessFlags::VARARGS
+                | MethodAccessFlags::STRICT
+                | MethodAccessFlags::SYNTHETIC;
+            if in_interface || self.0 & !allowed.0 != 0 {
+                return Err(illegal(
+                    self.0,
+                    "instance initialization method with other flags",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MethodAccessFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_modifiers(
+            f,
+            &[
+                (self.contains(MethodAccessFlags::PUBLIC), "public"),
+                (self.contains(MethodAccessFlags::PRIVATE), "private"),
+                (self.contains(MethodAccessFlags::PROTECTED), "protected"),
+                (self.contains(MethodAccessFlags::ABSTRACT), "abstract"),
+                (self.contains(MethodAccessFlags::STATIC), "static"),
+                (self.contains(MethodAccessFlags::FINAL), "final"),
+                (
+                    self.contains(MethodAccessFlags::SYNCHRONIZED),
+                    "synchronized",
+                ),
+                (self.contains(MethodAccessFlags::NATIVE), "native"),
+                (self.contains(MethodAccessFlags::STRICT), "strictfp"),
+            ],
+        )
+    }
+}
+
+impl InnerClassAccessFlags {
+    pub fn is_interface(self) -> bool {
+        self.contains(InnerClassAccessFlags::INTERFACE)
+    }
+
+    // JVMS 4.7.6 only lists the flags; these are the rules of 4.1 that still apply to the
+    // source-level modifiers recorded here.
+    pub fn validate(self) -> Result<(), ClassFormatError> {
+        let visibility = [
+            InnerClassAccessFlags::PUBLIC,
+            InnerClassAccessFlags::PRIVATE,
+            InnerClassAccessFlags::PROTECTED,
+        ];
+        if self.count(&visibility) > 1 {
+            return Err(illegal(
+                self.0,
+                "more than one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED",
+            ));
+        }
+        if self.contains(InnerClassAccessFlags::ANNOTATION) && !self.is_interface() {
+            return Err(illegal(self.0, "ACC_ANNOTATION without ACC_INTERFACE"));
+        }
+        if self.contains(InnerClassAccessFlags::FINAL | InnerClassAccessFlags::ABSTRACT) {
+            return Err(illegal(self.0, "both ACC_FINAL and ACC_ABSTRACT"));
+        }
+        Ok(())
+    }
+}
+
+// Modifiers of a member class; "abstract" and "static" are implied for interfaces.
+impl fmt::Display for InnerClassAccessFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_modifiers(
+            f,
+            &[
+                (self.contains(InnerClassAccessFlags::PUBLIC), "public"),
+                (self.contains(InnerClassAccessFlags::PRIVATE), "private"),
+                (self.contains(InnerClassAccessFlags::PROTECTED), "protected"),
+                (
+                    self.contains(InnerClassAccessFlags::ABSTRACT) && !self.is_interface(),
+                    "abstract",
+                ),
+                (
+                    self.contains(InnerClassAccessFlags::STATIC) && !self.is_interface(),
+                    "static",
+                ),
+                (self.contains(InnerClassAccessFlags::FINAL), "final"),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn method(flags: MethodAccessFlags, name: &str, in_interface: bool, major: u16) -> String {
+        match flags.validate(name, in_interface, major) {
+            Ok(()) => String::from("ok"),
+            Err(e) => e.reason,
+        }
+    }
+
+    #[test]
+    fn abstract_strict_methods_are_only_illegal_from_46_to_60() {
+        let flags =
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT | MethodAccessFlags::STRICT;
+        let illegal = "illegal access flags 0x0c01: \
+                       ACC_ABSTRACT with ACC_STRICT in a class file of version 46 to 60";
+        assert_eq!(method(flags, "f", false, 45), "ok");
+        assert_eq!(method(flags, "f", false, 46), illegal);
+        assert_eq!(method(flags, "f", false, 60), illegal);
+        assert_eq!(method(flags, "f", false, 61), "ok");
+        assert_eq!(method(flags, "f", true, 52), illegal);
+        assert_eq!(method(flags, "f", true, 61), "ok");
+        let strict = MethodAccessFlags::PUBLIC | MethodAccessFlags::STRICT;
+        assert_eq!(method(strict, "f", false, 52), "ok");
+    }
+
+    #[test]
+    fn method_flag_rules() {
+        use MethodAccessFlags as M;
+        let cases = [
+            (M::PUBLIC | M::PRIVATE, "f", false, 52, "more than one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED"),
+            (M::ABSTRACT | M::FINAL, "f", false, 52, "ACC_ABSTRACT with ACC_PRIVATE, ACC_STATIC, ACC_FINAL, ACC_SYNCHRONIZED or ACC_NATIVE"),
+            (M::ABSTRACT | M::NATIVE, "f", false, 61, "ACC_ABSTRACT with ACC_PRIVATE, ACC_STATIC, ACC_FINAL, ACC_SYNCHRONIZED or ACC_NATIVE"),
+            (M::PUBLIC | M::ABSTRACT, "f", true, 51, "ok"),
+            (M::PUBLIC, "f", true, 51, "interface method without ACC_PUBLIC and ACC_ABSTRACT before version 52"),
+            (M::PRIVATE, "f", true, 52, "ok"),
+            (M::PUBLIC | M::STATIC, "f", true, 52, "ok"),
+            (M::STATIC, "f", true, 52, "interface method without exactly one of ACC_PUBLIC and ACC_PRIVATE"),
+            (M::PUBLIC | M::SYNCHRONIZED, "f", true, 52, "interface method with ACC_PROTECTED, ACC_FINAL, ACC_SYNCHRONIZED or ACC_NATIVE"),
+            (M::PUBLIC | M::VARARGS | M::SYNTHETIC, "<init>", false, 52, "ok"),
+            (M::PUBLIC | M::STATIC, "<init>", false, 52, "instance initialization method with other flags"),
+            (M::PUBLIC, "<init>", true, 52, "instance initialization method with other flags"),
+            (M::PUBLIC | M::PRIVATE | M::ABSTRACT, "<clinit>", true, 61, "ok"),
+        ];
+        for (flags, name, in_interface, major, expected) in cases {
+            let result = method(flags, name, in_interface, major);
+            let result = result.split(": ").last().unwrap();
+            assert_eq!(
+                result, expected,
+                "{:?} {} {} {}",
+                flags, name, in_interface, major
+            );
+        }
+    }
+
+    #[test]
+    fn class_field_and_inner_class_flag_rules() {
+        use ClassAccessFlags as C;
+        assert_eq!(C::MODULE.validate(), Ok(()));
+        assert!((C::MODULE | C::PUBLIC).validate().is_err());
+        assert_eq!(
+            (C::PUBLIC | C::INTERFACE | C::ABSTRACT | C::ANNOTATION).validate(),
+            Ok(())
+        );
+        assert!((C::INTERFACE).validate().is_err());
+        assert!((C::INTERFACE | C::ABSTRACT | C::SUPER).validate().is_err());
+        assert!((C::ANNOTATION).validate().is_err());
+        assert!((C::FINAL | C::ABSTRACT).validate().is_err());
+
+        use FieldAccessFlags as F;
+        assert_eq!((F::PUBLIC | F::STATIC | F::FINAL).validate(true), Ok(()));
+        assert!((F::PUBLIC | F::STATIC).validate(true).is_err());
+        assert!((F::PUBLIC | F::STATIC | F::FINAL | F::TRANSIENT)
+            .validate(true)
+            .is_err());
+        assert!((F::FINAL | F::VOLATILE).validate(false).is_err());
+        assert!((F::PRIVATE | F::PROTECTED).validate(false).is_err());
+
+        use InnerClassAccessFlags as I;
+        assert_eq!(
+            (I::PRIVATE | I::STATIC | I::ABSTRACT | I::INTERFACE).validate(),
+            Ok(())
+        );
+        assert!((I::PUBLIC | I::PROTECTED).validate().is_err());
+        assert!((I::ANNOTATION).validate().is_err());
+        assert!((I::FINAL | I::ABSTRACT).validate().is_err());
+    }
+
+    #[test]
+    fn display_as_javap_modifiers() {
+        use MethodAccessFlags as M;
+        let all = M(0xffff);
+        assert_eq!(
+            all.to_string(),
+            "public private protected abstract static final synchronized native strictfp"
+        );
+        assert_eq!(
+            (M::PUBLIC | M::STATIC | M::VARARGS | M::BRIDGE | M::SYNTHETIC).to_string(),
+            "public static"
+        );
+        assert_eq!(M(0).to_string(), "");
+
+        let field = FieldAccessFlags::PROTECTED
+            | FieldAccessFlags::TRANSIENT
+            | FieldAccessFlags::VOLATILE
+            | FieldAccessFlags::ENUM;
+        assert_eq!(field.to_string(), "protected volatile transient");
+
+        let interface =
+            ClassAccessFlags::PUBLIC | ClassAccessFlags::INTERFACE | ClassAccessFlags::ABSTRACT;
+        assert_eq!(interface.to_string(), "public");
+        assert_eq!(interface.kind(), "interface");
+        let class = ClassAccessFlags::PUBLIC | ClassAccessFlags::ABSTRACT | ClassAccessFlags::SUPER;
+        assert_eq!(class.to_string(), "public abstract");
+        assert_eq!(
+            (ClassAccessFlags::ENUM | ClassAccessFlags::FINAL).kind(),
+            "enum"
+        );
+
+        let member = InnerClassAccessFlags::PRIVATE
+            | InnerClassAccessFlags::STATIC
+            | InnerClassAccessFlags::FINAL;
+        assert_eq!(member.to_string(), "private static final");
+        let nested_interface = InnerClassAccessFlags::STATIC
+            | InnerClassAccessFlags::ABSTRACT
+            | InnerClassAccessFlags::INTERFACE;
+        assert_eq!(nested_interface.to_string(), "");
+
+        assert_eq!(
+            format!("{:?}", M::PUBLIC | M::STRICT),
+            "(0x0801) ACC_PUBLIC, ACC_STRICT"
+        );
+        assert_eq!(M::PUBLIC | M::STRICT, M::from(0x0801));
+        assert_eq!(
+            (M::PUBLIC | M::STRICT).names(),
+            vec!["ACC_PUBLIC", "ACC_STRICT"]
+        );
+    }
+}
@@ -0,0 +1,661 @@
+use crate::jvm::class::{attribute::Attribute, constant_pool::*, error::ClassFormatError};
+use crate::utils::bytecode_reader::BytecodeReader;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeAnnotations {
+    pub annotations: Vec<Annotation>,
+}
+
+impl AttributeAnnotations {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeAnnotations, ClassFormatError> {
+        Ok(AttributeAnnotations {
+            annotations: Annotation::read_table(reader, constant_pool)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeParameterAnnotations {
+    // One list per formal parameter.
+    pub parameter_annotations: Vec<Vec<Annotation>>,
+}
+
+impl AttributeParameterAnnotations {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeParameterAnnotations, ClassFormatError> {
+        let num_parameters = reader.u8()?;
+        let mut parameter_annotations: Vec<Vec<Annotation>> = Vec::new();
+        for i in 0..num_parameters {
+            parameter_annotations.push(
+                Annotation::read_table(reader, constant_pool)
+                    .map_err(|e| e.within(format!("parameter {}", i)))?,
+            );
+        }
+        Ok(AttributeParameterAnnotations {
+            parameter_annotations,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeTypeAnnotations {
+    pub annotations: Vec<TypeAnnotation>,
+}
+
+impl AttributeTypeAnnotations {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeTypeAnnotations, ClassFormatError> {
+        let num_annotations = reader.u16()?;
+        let mut annotations: Vec<TypeAnnotation> = Vec::new();
+        for _ in 0..num_annotations {
+            annotations.push(TypeAnnotation::new(reader, constant_pool)?);
+        }
+        Ok(AttributeTypeAnnotations { annotations })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeAnnotationDefault {
+    pub default_value: ElementValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub type_descriptor: Indexed<String>, // e.g. "Lorg/junit/Test;"
+    pub element_value_pairs: Vec<ElementValuePair>,
+}
+
+impl Annotation {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<Annotation, ClassFormatError> {
+        let type_descriptor = constant_pool.read_utf8_ref(reader)?;
+        let num_element_value_pairs = reader.u16()?;
+        let mut element_value_pairs: Vec<ElementValuePair> = Vec::new();
+        for _ in 0..num_element_value_pairs {
+            let name = constant_pool.read_utf8_ref(reader)?;
+            let value = ElementValue::new(reader, constant_pool)
+                .map_err(|e| e.within(format!("{}.{}", type_descriptor, name)))?;
+            element_value_pairs.push(ElementValuePair { name, value });
+        }
+        Ok(Annotation {
+            type_descriptor,
+            element_value_pairs,
+        })
+    }
+
+    fn read_table(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<Vec<Annotation>, ClassFormatError> {
+        let num_annotations = reader.u16()?;
+        let mut annotations: Vec<Annotation> = Vec::new();
+        for _ in 0..num_annotations {
+            annotations.push(Annotation::new(reader, constant_pool)?);
+        }
+        Ok(annotations)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&ElementValue> {
+        self.element_value_pairs
+            .iter()
+            .find(|pair| pair.name == name)
+            .map(|pair| &pair.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementValuePair {
+    pub name: Indexed<String>,
+    pub value: ElementValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElementValue {
+    Const(Indexed<ConstValue>), // tag: B C D F I J S Z s
+    Enum {
+        type_name: Indexed<String>, // field descriptor of the enum type
+        const_name: Indexed<String>,
+    }, // tag: e
+    Class(Indexed<String>),     // tag: c, a return descriptor such as "Ljava/lang/String;" or "V"
+    Annotation(Annotation),     // tag: @
+    Array(Vec<ElementValue>),   // tag: [
+}
+
+impl ElementValue {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<ElementValue, ClassFormatError> {
+        let tag_offset = reader.offset;
+        let tag = reader.u8()?;
+        let value_offset = reader.offset;
+        let element_value = match tag {
+            b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => {
+                let const_value_index = CpIndex::read(reader)?;
+                let value = constant_pool.indexed(const_value_index, |constant_pool, index| {
+                    ConstValue::resolve(constant_pool, tag, index)
+                });
+                ElementValue::Const(value.map_err(|e| e.with_offset(value_offset))?)
+            }
+            b'e' => ElementValue::Enum {
+                type_name: constant_pool.read_utf8_ref(reader)?,
+                const_name: constant_pool.read_utf8_ref(reader)?,
+            },
+            b'c' => ElementValue::Class(constant_pool.read_utf8_ref(reader)?),
+            b'@' => ElementValue::Annotation(Annotation::new(reader, constant_pool)?),
+            b'[' => {
+                let num_values = reader.u16()?;
+                let mut values: Vec<ElementValue> = Vec::new();
+                for _ in 0..num_values {
+                    values.push(ElementValue::new(reader, constant_pool)?);
+                }
+                ElementValue::Array(values)
+            }
+            _ => {
+                return Err(ClassFormatError::at(
+                    tag_offset,
+                    format!("unknown element_value tag: {:?}", tag as char),
+                ))
+            }
+        };
+        Ok(element_value)
+    }
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            ElementValue::Const(value) => value.tag(),
+            ElementValue::Enum { .. } => b'e',
+            ElementValue::Class(_) => b'c',
+            ElementValue::Annotation(_) => b'@',
+            ElementValue::Array(_) => b'[',
+        }
+    }
+}
+
+// The resolved constant of a B C D F I J S Z or s element_value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstValue {
+    Byte(i8),
+    Char(u16),
+    Double(f64),
+    Float(f32),
+    Int(i32),
+    Long(i64),
+    Short(i16),
+    Boolean(bool),
+    String(String),
+}
+
+impl ConstValue {
+    // The constant at `index` as the element_value with `tag` has it.
+    pub fn resolve(
+        constant_pool: &ConstantPool,
+        tag: u8,
+        index: CpIndex,
+    ) -> Result<ConstValue, ClassFormatError> {
+        Ok(match tag {
+            b'B' => ConstValue::Byte(constant_pool.integer(index)? as i8),
+            b'C' => ConstValue::Char(constant_pool.integer(index)? as u16),
+            b'D' => ConstValue::Double(constant_pool.double(index)?),
+            b'F' => ConstValue::Float(constant_pool.float(index)?),
+            b'I' => ConstValue::Int(constant_pool.integer(index)?),
+            b'J' => ConstValue::Long(constant_pool.long(index)?),
+            b'S' => ConstValue::Short(constant_pool.integer(index)? as i16),
+            b'Z' => ConstValue::Boolean(constant_pool.integer(index)? != 0),
+            _ => ConstValue::String(constant_pool.utf8(index)?),
+        })
+    }
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            ConstValue::Byte(_) => b'B',
+            ConstValue::Char(_) => b'C',
+            ConstValue::Double(_) => b'D',
+            ConstValue::Float(_) => b'F',
+            ConstValue::Int(_) => b'I',
+            ConstValue::Long(_) => b'J',
+            ConstValue::Short(_) => b'S',
+            ConstValue::Boolean(_) => b'Z',
+            ConstValue::String(_) => b's',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAnnotation {
+    pub target_type: u8,
+    pub target_info: TargetInfo,
+    pub type_path: Vec<TypePathEntry>,
+    pub annotation: Annotation,
+}
+
+impl TypeAnnotation {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<TypeAnnotation, ClassFormatError> {
+        let target_type_offset = reader.offset;
+        let target_type = reader.u8()?;
+        let target_info = match target_type {
+            0x00 | 0x01 => TargetInfo::TypeParameter {
+                type_parameter_index: reader.u8()?,
+            },
+            0x10 => TargetInfo::Supertype {
+                supertype_index: reader.u16()?,
+            },
+            0x11 | 0x12 => TargetInfo::TypeParameterBound {
+                type_parameter_index: reader.u8()?,
+                bound_index: reader.u8()?,
+            },
+            0x13..=0x15 => TargetInfo::Empty,
+            0x16 => TargetInfo::FormalParameter {
+                formal_parameter_index: reader.u8()?,
+            },
+            0x17 => TargetInfo::Throws {
+                throws_type_index: reader.u16()?,
+            },
+            0x40 | 0x41 => {
+                let table_length = reader.u16()?;
+                let mut table: Vec<LocalvarTarget> = Vec::new();
+                for _ in 0..table_length {
+                    table.push(LocalvarTarget {
+                        start_pc: reader.u16()?,
+                        length: reader.u16()?,
+                        index: reader.u16()?,
+                    });
+                }
+                TargetInfo::Localvar(table)
+            }
+            0x42 => TargetInfo::Catch {
+                exception_table_index: reader.u16()?,
+            },
+            0x43..=0x46 => TargetInfo::Offset {
+                offset: reader.u16()?,
+            },
+            0x47..=0x4B => TargetInfo::TypeArgument {
+                offset: reader.u16()?,
+                type_argument_index: reader.u8()?,
+            },
+            _ => {
+                return Err(ClassFormatError::at(
+                    target_type_offset,
+                    format!("unknown type annotation target_type: 0x{:02X}", target_type),
+                ))
+            }
+        };
+        let path_length = reader.u8()?;
+        let mut type_path: Vec<TypePathEntry> = Vec::new();
+        for _ in 0..path_length {
+            type_path.push(TypePathEntry {
+                type_path_kind: reader.u8()?,
+                type_argument_index: reader.u8()?,
+            });
+        }
+        let annotation = Annotation::new(reader, constant_pool)?;
+        Ok(TypeAnnotation {
+            target_type,
+            target_info,
+            type_path,
+            annotation,
+        })
+    }
+}
+
+// target_info (JVMS 4.7.20.1), the comments list the target_type values of each form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetInfo {
+    TypeParameter {
+        type_parameter_index: u8,
+    }, // 0x00 0x01
+    Supertype {
+        supertype_index: u16, // 65535 is the superclass
+    }, // 0x10
+    TypeParameterBound {
+        type_parameter_index: u8,
+        bound_index: u8,
+    }, // 0x11 0x12
+    Empty, // 0x13 0x14 0x15
+    FormalParameter {
+        formal_parameter_index: u8,
+    }, // 0x16
+    Throws {
+        throws_type_index: u16,
+    }, // 0x17
+    Localvar(Vec<LocalvarTarget>), // 0x40 0x41
+    Catch {
+        exception_table_index: u16,
+    }, // 0x42
+    Offset {
+        offset: u16,
+    }, // 0x43 - 0x46
+    TypeArgument {
+        offset: u16,
+        type_argument_index: u8,
+    }, // 0x47 - 0x4B
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalvarTarget {
+    pub start_pc: u16,
+    pub length: u16,
+    pub index: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypePathEntry {
+    // 0: deeper in an array type, 1: deeper in a nested type,
+    // 2: on a wildcard bound, 3: on a type argument
+    pub type_path_kind: u8,
+    pub type_argument_index: u8,
+}
+
+// Declaration annotations from both RuntimeVisibleAnnotations and RuntimeInvisibleAnnotations.
+pub fn annotations(attributes: &[Attribute]) -> Vec<&Annotation> {
+    let mut annotations: Vec<&Annotation> = Vec::new();
+    for attribute in attributes {
+        match attribute {
+            Attribute::RuntimeVisibleAnnotations(a) | Attribute::RuntimeInvisibleAnnotations(a) => {
+                annotations.extend(a.annotations.iter());
+            }
+            _ => {}
+        }
+    }
+    annotations
+}
+
+pub fn type_annotations(attributes: &[Attribute]) -> Vec<&TypeAnnotation> {
+    let mut annotations: Vec<&TypeAnnotation> = Vec::new();
+    for attribute in attributes {
+        match attribute {
+            Attribute::RuntimeVisibleTypeAnnotations(a)
+            | Attribute::RuntimeInvisibleTypeAnnotations(a) => {
+                annotations.extend(a.annotations.iter());
+            }
+            _ => {}
+        }
+    }
+    annotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::constant_pool_builder::ConstantPoolBuilder;
+
+    // A pool holding `utf8` at #1, #2, ... followed by `integers`.
+    fn pool(utf8: &[&str], integers: &[i32]) -> ConstantPool {
+        let mut builder = ConstantPoolBuilder::new();
+        for s in utf8 {
+            builder.utf8(s).unwrap();
+        }
+        for value in integers {
+            builder.integer(*value).unwrap();
+        }
+        builder.build()
+    }
+
+    fn read<T>(
+        bytes: &[u8],
+        constant_pool: &ConstantPool,
+        new: fn(&mut BytecodeReader, &ConstantPool) -> Result<T, ClassFormatError>,
+    ) -> Result<T, ClassFormatError> {
+        let mut reader = BytecodeReader::new(bytes.to_vec());
+        let value = new(&mut reader, constant_pool)?;
+        assert_eq!(reader.offset, bytes.len(), "trailing bytes");
+        Ok(value)
+    }
+
+    fn marker(type_descriptor: &str) -> Annotation {
+        Annotation {
+            type_descriptor: type_descriptor.into(),
+            element_value_pairs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn element_values_of_every_compound_tag() {
+        // As javac writes
+        //   @A(e = E.X, c = String.class, r = @Retention(RUNTIME), a = {1, 2}, v = void.class)
+        let constant_pool = pool(
+            &[
+                "LA;",
+                "e",
+                "LE;",
+                "X",
+                "c",
+                "Ljava/lang/String;",
+                "r",
+                "Ljava/lang/annotation/Retention;",
+                "value",
+                "Ljava/lang/annotation/RetentionPolicy;",
+                "RUNTIME",
+                "a",
+                "v",
+                "V",
+            ],
+            &[1, 2],
+        );
+        #[rustfmt::skip]
+        let bytes = [
+            0, 1,             // num_annotations
+            0, 1, 0, 5,       // LA; with 5 pairs
+            0, 2, b'e', 0, 3, 0, 4,
+            0, 5, b'c', 0, 6,
+            0, 7, b'@', 0, 8, 0, 1, 0, 9, b'e', 0, 10, 0, 11,
+            0, 12, b'[', 0, 2, b'I', 0, 15, b'I', 0, 16,
+            0, 13, b'c', 0, 14,
+        ];
+        let attribute = read(&bytes, &constant_pool, AttributeAnnotations::new).unwrap();
+        let retention = Annotation {
+            type_descriptor: "Ljava/lang/annotation/Retention;".into(),
+            element_value_pairs: vec![ElementValuePair {
+                name: "value".into(),
+                value: ElementValue::Enum {
+                    type_name: "Ljava/lang/annotation/RetentionPolicy;".into(),
+                    const_name: "RUNTIME".into(),
+                },
+            }],
+        };
+        let expected = Annotation {
+            type_descriptor: "LA;".into(),
+            element_value_pairs: vec![
+                ElementValuePair {
+                    name: "e".into(),
+                    value: ElementValue::Enum {
+                        type_name: "LE;".into(),
+                        const_name: "X".into(),
+                    },
+                },
+                ElementValuePair {
+                    name: "c".into(),
+                    value: ElementValue::Class("Ljava/lang/String;".into()),
+                },
+                ElementValuePair {
+                    name: "r".into(),
+                    value: ElementValue::Annotation(retention),
+                },
+                ElementValuePair {
+                    name: "a".into(),
+                    value: ElementValue::Array(vec![
+                        ElementValue::Const(Indexed::new(ConstValue::Int(1))),
+                        ElementValue::Const(Indexed::new(ConstValue::Int(2))),
+                    ]),
+                },
+                ElementValuePair {
+                    name: "v".into(),
+                    value: ElementValue::Class("V".into()),
+                },
+            ],
+        };
+        assert_eq!(attribute.annotations, vec![expected]);
+
+        let annotation = &attribute.annotations[0];
+        assert_eq!(annotation.type_descriptor.index, Some(CpIndex(1)));
+        match annotation.value("a") {
+            Some(ElementValue::Array(values)) => {
+                let tags: Vec<u8> = values.iter().map(ElementValue::tag).collect();
+                assert_eq!(tags, b"II");
+                match &values[1] {
+                    ElementValue::Const(value) => assert_eq!(value.index, Some(CpIndex(16))),
+                    value => panic!("unexpected {:?}", value),
+                }
+            }
+            value => panic!("unexpected {:?}", value),
+        }
+        assert_eq!(annotation.value("x"), None);
+    }
+
+    #[test]
+    fn const_element_values_resolve_by_tag() {
+        let constant_pool = pool(&["LA;", "v", "s"], &[65, 0, -1]);
+        let element_values = |tag: u8, index: u8| -> Result<ConstValue, ClassFormatError> {
+            let bytes = [0, 1, 0, 1, 0, 1, 0, 2, tag, 0, index];
+            let attribute = read(&bytes, &constant_pool, AttributeAnnotations::new)?;
+            match &attribute.annotations[0].element_value_pairs[0].value {
+                ElementValue::Const(value) => Ok(value.value.clone()),
+                value => panic!("unexpected {:?}", value),
+            }
+        };
+        assert_eq!(element_values(b'B', 4), Ok(ConstValue::Byte(65)));
+        assert_eq!(element_values(b'C', 4), Ok(ConstValue::Char(b'A' as u16)));
+        assert_eq!(element_values(b'S', 6), Ok(ConstValue::Short(-1)));
+        assert_eq!(element_values(b'I', 6), Ok(ConstValue::Int(-1)));
+        assert_eq!(element_values(b'Z', 5), Ok(ConstValue::Boolean(false)));
+        assert_eq!(element_values(b'Z', 4), Ok(ConstValue::Boolean(true)));
+        assert_eq!(
+            element_values(b's', 3),
+            Ok(ConstValue::String(String::from("s")))
+        );
+
+        let e = element_values(b'J', 4).unwrap_err();
+        assert_eq!(e.offset, Some(9));
+        assert_eq!(e.location, vec!["LA;.v"]);
+        let e = element_values(b'x', 4).unwrap_err();
+        assert_eq!(e.offset, Some(8));
+        assert_eq!(e.reason, "unknown element_value tag: 'x'");
+    }
+
+    #[test]
+    fn parameter_annotations_have_a_table_per_parameter() {
+        let constant_pool = pool(&["LA;", "LB;"], &[]);
+        // Three parameters: @A @B, none, @B.
+        let bytes = [3, 0, 2, 0, 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 1, 0, 2, 0, 0];
+        let attribute = read(&bytes, &constant_pool, AttributeParameterAnnotations::new).unwrap();
+        assert_eq!(
+            attribute.parameter_annotations,
+            vec![
+                vec![marker("LA;"), marker("LB;")],
+                vec![],
+                vec![marker("LB;")]
+            ]
+        );
+
+        let e = read(
+            &bytes[..17],
+            &constant_pool,
+            AttributeParameterAnnotations::new,
+        )
+        .unwrap_err();
+        assert_eq!(e.location, vec!["parameter 2"]);
+    }
+
+    #[test]
+    fn type_annotation_targets_and_paths() {
+        let constant_pool = pool(&["LT;"], &[]);
+        // The targets javac writes for C<@P X extends @T Object> implements Comparable<@T
+        // String>, a @T String[] field and a method body with locals, catch, instanceof and
+        // casts; every annotation is the marker #1.
+        #[rustfmt::skip]
+        let cases: Vec<(Vec<u8>, TargetInfo, Vec<TypePathEntry>)> = vec![
+            (vec![0x00, 0, 0], TargetInfo::TypeParameter { type_parameter_index: 0 }, vec![]),
+            (vec![0x01, 1, 0], TargetInfo::TypeParameter { type_parameter_index: 1 }, vec![]),
+            (
+                vec![0x10, 0, 0, 1, 3, 0],
+                TargetInfo::Supertype { supertype_index: 0 },
+                vec![TypePathEntry { type_path_kind: 3, type_argument_index: 0 }],
+            ),
+            (
+                vec![0x10, 0xFF, 0xFF, 0],
+                TargetInfo::Supertype { supertype_index: 65535 },
+                vec![],
+            ),
+            (
+                vec![0x11, 0, 1, 0],
+                TargetInfo::TypeParameterBound { type_parameter_index: 0, bound_index: 1 },
+                vec![],
+            ),
+            (
+                vec![0x13, 1, 0, 0],
+                TargetInfo::Empty,
+                vec![TypePathEntry { type_path_kind: 0, type_argument_index: 0 }],
+            ),
+            (vec![0x14, 0], TargetInfo::Empty, vec![]),
+            (vec![0x15, 0], TargetInfo::Empty, vec![]),
+            (vec![0x16, 0, 0], TargetInfo::FormalParameter { formal_parameter_index: 0 }, vec![]),
+            (vec![0x17, 0, 0, 0], TargetInfo::Throws { throws_type_index: 0 }, vec![]),
+            (
+                vec![0x40, 0, 2, 0, 2, 0, 20, 0, 3, 0, 30, 0, 4, 0, 3, 0],
+                TargetInfo::Localvar(vec![
+                    LocalvarTarget { start_pc: 2, length: 20, index: 3 },
+                    LocalvarTarget { start_pc: 30, length: 4, index: 3 },
+                ]),
+                vec![],
+            ),
+            (vec![0x41, 0, 0, 0], TargetInfo::Localvar(vec![]), vec![]),
+            (vec![0x42, 0, 1, 0], TargetInfo::Catch { exception_table_index: 1 }, vec![]),
+            (vec![0x43, 0, 13, 0], TargetInfo::Offset { offset: 13 }, vec![]),
+            (
+                vec![0x44, 0, 20, 2, 3, 0, 2, 0],
+                TargetInfo::Offset { offset: 20 },
+                vec![
+                    TypePathEntry { type_path_kind: 3, type_argument_index: 0 },
+                    TypePathEntry { type_path_kind: 2, type_argument_index: 0 },
+                ],
+            ),
+            (vec![0x45, 0, 4, 0], TargetInfo::Offset { offset: 4 }, vec![]),
+            (vec![0x46, 1, 0, 0], TargetInfo::Offset { offset: 256 }, vec![]),
+            (
+                vec![0x47, 0, 3, 0, 1, 1, 0],
+                TargetInfo::TypeArgument { offset: 3, type_argument_index: 0 },
+                vec![TypePathEntry { type_path_kind: 1, type_argument_index: 0 }],
+            ),
+            (
+                vec![0x4B, 0, 9, 1, 0],
+                TargetInfo::TypeArgument { offset: 9, type_argument_index: 1 },
+                vec![],
+            ),
+        ];
+        for (target, target_info, type_path) in cases {
+            let mut bytes = vec![0, 1];
+            bytes.extend(&target);
+            bytes.extend([0, 1, 0, 0]);
+            let attribute = read(&bytes, &constant_pool, AttributeTypeAnnotations::new)
+                .unwrap_or_else(|e| panic!("{:02X?}: {}", target, e));
+            let expected = TypeAnnotation {
+                target_type: target[0],
+                target_info,
+                type_path,
+                annotation: marker("LT;"),
+            };
+            assert_eq!(attribute.annotations, vec![expected], "{:02X?}", target);
+        }
+
+        let e = read(
+            &[0, 1, 0x20, 0, 0, 1, 0, 0],
+            &constant_pool,
+            AttributeTypeAnnotations::new,
+        )
+        .unwrap_err();
+        assert_eq!(e.offset, Some(2));
+        assert_eq!(e.reason, "unknown type annotation target_type: 0x20");
+    }
+}
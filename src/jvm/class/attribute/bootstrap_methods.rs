@@ -0,0 +1,294 @@
+use crate::jvm::class::{constant_pool::*, error::ClassFormatError};
+use crate::utils::bytecode_reader::BytecodeReader;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeBootstrapMethods {
+    // Indexed by the bootstrap_method_attr_index of InvokeDynamic and Dynamic constants.
+    pub bootstrap_methods: Vec<BootstrapMethod>,
+}
+
+impl AttributeBootstrapMethods {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeBootstrapMethods, ClassFormatError> {
+        let num_bootstrap_methods = reader.u16()?;
+        let mut bootstrap_methods: Vec<BootstrapMethod> = Vec::new();
+        for i in 0..num_bootstrap_methods {
+            bootstrap_methods.push(
+                BootstrapMethod::new(reader, constant_pool)
+                    .map_err(|e| e.within(format!("bootstrap method {}", i)))?,
+            );
+        }
+        Ok(AttributeBootstrapMethods { bootstrap_methods })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BootstrapMethod {
+    pub bootstrap_method: MethodHandle,
+    pub bootstrap_arguments: Vec<LoadableConstant>,
+    // The indices the bootstrap method and its arguments were read from. Like ClassRef::index
+    // they only tell the writer which of duplicate constants to refer to, and are left out of
+    // comparisons.
+    pub bootstrap_method_ref: CpIndex,
+    pub bootstrap_argument_indices: Vec<CpIndex>,
+}
+
+impl PartialEq for BootstrapMethod {
+    fn eq(&self, other: &BootstrapMethod) -> bool {
+        self.bootstrap_method == other.bootstrap_method
+            && self.bootstrap_arguments == other.bootstrap_arguments
+    }
+}
+
+impl BootstrapMethod {
+    fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<BootstrapMethod, ClassFormatError> {
+        let offset = reader.offset;
+        let bootstrap_method_ref = CpIndex::read(reader)?;
+        let bootstrap_method = constant_pool
+            .method_handle(bootstrap_method_ref)
+            .map_err(|e| e.with_offset(offset))?;
+        let num_bootstrap_arguments = reader.u16()?;
+        let mut bootstrap_arguments: Vec<LoadableConstant> = Vec::new();
+        let mut bootstrap_argument_indices: Vec<CpIndex> = Vec::new();
+        for _ in 0..num_bootstrap_arguments {
+            let offset = reader.offset;
+            let index = CpIndex::read(reader)?;
+            bootstrap_arguments.push(
+                constant_pool
+                    .loadable(index)
+                    .map_err(|e| e.with_offset(offset))?,
+            );
+            bootstrap_argument_indices.push(index);
+        }
+        Ok(BootstrapMethod {
+            bootstrap_method,
+            bootstrap_arguments,
+            bootstrap_method_ref,
+            bootstrap_argument_indices,
+        })
+    }
+}
+
+// An invokedynamic call site or a dynamically-computed constant together with the bootstrap
+// method that links it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicCallSite {
+    pub kind: DynamicKind,
+    pub name: String,
+    pub descriptor: String, // a method descriptor for invokedynamic, a field descriptor for condy
+    pub bootstrap_method: MethodHandle,
+    pub bootstrap_arguments: Vec<LoadableConstant>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        access_flags::{ClassAccessFlags, MethodAccessFlags},
+        attribute::Attribute,
+        class_builder::ClassBuilder,
+        constant_pool_builder::ConstantPoolBuilder,
+        instruction::Instruction,
+        version::ClassVersion,
+    };
+    use crate::utils::bytecode_writer::BytecodeWriter;
+
+    fn invoke_static(class_name: &str, name: &str, descriptor: &str) -> MethodHandle {
+        MethodHandle {
+            reference_kind: ReferenceKind::InvokeStatic,
+            member: MemberRef {
+                kind: MemberKind::Method,
+                class_name: String::from(class_name),
+                name: String::from(name),
+                descriptor: String::from(descriptor),
+            },
+        }
+    }
+
+    fn metafactory() -> MethodHandle {
+        invoke_static(
+            "java/lang/invoke/LambdaMetafactory",
+            "metafactory",
+            "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;\
+             Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodType;\
+             Ljava/lang/invoke/MethodHandle;Ljava/lang/invoke/MethodType;)\
+             Ljava/lang/invoke/CallSite;",
+        )
+    }
+
+    #[test]
+    fn lambda_and_condy_call_sites_resolve_through_bootstrap_methods() {
+        // As javac writes `Runnable r = () -> {};`, plus a ConstantBootstraps.nullConstant
+        // dynamic constant.
+        let implementation = invoke_static("Lambdas", "lambda$run$0", "()V");
+        let null_constant = invoke_static(
+            "java/lang/invoke/ConstantBootstraps",
+            "nullConstant",
+            "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/Class;)\
+             Ljava/lang/Object;",
+        );
+        let arguments = vec![
+            LoadableConstant::MethodType(String::from("()V")),
+            LoadableConstant::MethodHandle(implementation.clone()),
+            LoadableConstant::MethodType(String::from("()V")),
+        ];
+        let condy = DynamicConstant {
+            bootstrap_method_attr_index: 1,
+            name: String::from("_"),
+            descriptor: String::from("Ljava/lang/Object;"),
+        };
+        let mut builder = ClassBuilder::new(
+            ClassAccessFlags::PUBLIC,
+            "Lambdas",
+            Some("java/lang/Object"),
+        )
+        .unwrap();
+        builder.version(ClassVersion::new(55, 0));
+        builder
+            .method_with_code(MethodAccessFlags::STATIC, "run", "()V", |code| {
+                code.invokedynamic(0, "run", "()Ljava/lang/Runnable;")?;
+                code.emit(Instruction::Pop)?;
+                code.ldc(&LoadableConstant::Dynamic(condy.clone()))?;
+                code.emit(Instruction::Pop)?;
+                code.emit(Instruction::Return)
+            })
+            .unwrap();
+        let flags =
+            MethodAccessFlags::PRIVATE | MethodAccessFlags::STATIC | MethodAccessFlags::SYNTHETIC;
+        builder
+            .method_with_code(flags, "lambda$run$0", "()V", |code| {
+                code.emit(Instruction::Return)
+            })
+            .unwrap();
+        let mut bootstrap = |handle: &MethodHandle, arguments: &[LoadableConstant]| {
+            let constant_pool = builder.constant_pool();
+            BootstrapMethod {
+                bootstrap_method: handle.clone(),
+                bootstrap_arguments: arguments.to_vec(),
+                bootstrap_method_ref: constant_pool.method_handle(handle).unwrap(),
+                bootstrap_argument_indices: arguments
+                    .iter()
+                    .map(|argument| constant_pool.loadable(argument).unwrap())
+                    .collect(),
+            }
+        };
+        let bootstrap_methods = vec![
+            bootstrap(&metafactory(), &arguments),
+            bootstrap(&null_constant, &[]),
+        ];
+        builder.attribute(Attribute::BootstrapMethods(AttributeBootstrapMethods {
+            bootstrap_methods,
+        }));
+        let class = builder.build().unwrap();
+
+        let code = &class.methods[0].code().unwrap().code;
+        let instructions = Instruction::decode_all(code).unwrap();
+        let (lambda_index, condy_index) = match &instructions[..] {
+            [(_, Instruction::Invokedynamic(lambda)), _, (_, Instruction::Ldc(condy)), _, _] => {
+                (lambda, condy)
+            }
+            instructions => panic!("unexpected code {:?}", instructions),
+        };
+
+        assert_eq!(
+            class.dynamic_call_site(*lambda_index),
+            Ok(DynamicCallSite {
+                kind: DynamicKind::InvokeDynamic,
+                name: String::from("run"),
+                descriptor: String::from("()Ljava/lang/Runnable;"),
+                bootstrap_method: metafactory(),
+                bootstrap_arguments: arguments,
+            })
+        );
+        assert_eq!(
+            class.dynamic_call_site(*condy_index),
+            Ok(DynamicCallSite {
+                kind: DynamicKind::Dynamic,
+                name: String::from("_"),
+                descriptor: String::from("Ljava/lang/Object;"),
+                bootstrap_method: null_constant,
+                bootstrap_arguments: Vec::new(),
+            })
+        );
+        let bootstrap = &class.bootstrap_methods()[0];
+        assert_eq!(
+            class
+                .constant_pool
+                .method_handle(bootstrap.bootstrap_argument_indices[1]),
+            Ok(implementation)
+        );
+
+        let e = class.dynamic_call_site(CpIndex(1)).unwrap_err();
+        assert!(
+            e.reason
+                .contains("is not a InvokeDynamic or Dynamic constant"),
+            "{}",
+            e
+        );
+        let mut class = class;
+        class
+            .attributes
+            .retain(|attribute| !matches!(attribute, Attribute::BootstrapMethods(_)));
+        let e = class.dynamic_call_site(*condy_index).unwrap_err();
+        assert_eq!(
+            e.reason,
+            format!(
+                "constant {} refers to bootstrap method 1 but the class has 0",
+                condy_index
+            )
+        );
+    }
+
+    #[test]
+    fn bootstrap_methods_reject_other_constants() {
+        let mut cp = ConstantPoolBuilder::new();
+        let handle = cp.method_handle(&metafactory()).unwrap();
+        let method_type = cp.method_type("()V").unwrap();
+        let name_and_type = cp.name_and_type("run", "()V").unwrap();
+        let mut interface_handle = metafactory();
+        interface_handle.reference_kind = ReferenceKind::InvokeInterface;
+        let interface_handle = cp.method_handle(&interface_handle).unwrap();
+        let constant_pool = cp.build();
+        let read = |methods: &[(CpIndex, &[CpIndex])]| {
+            let mut writer = BytecodeWriter::new();
+            writer.u16(methods.len() as u16);
+            for (method, arguments) in methods {
+                writer.u16(method.0);
+                writer.u16(arguments.len() as u16);
+                for argument in *arguments {
+                    writer.u16(argument.0);
+                }
+            }
+            let mut reader = BytecodeReader::new(writer.data);
+            AttributeBootstrapMethods::new(&mut reader, &constant_pool)
+        };
+
+        let attribute = read(&[(handle, &[method_type, handle])]).unwrap();
+        assert_eq!(
+            attribute.bootstrap_methods[0].bootstrap_arguments,
+            vec![
+                LoadableConstant::MethodType(String::from("()V")),
+                LoadableConstant::MethodHandle(metafactory()),
+            ]
+        );
+
+        let e = read(&[(handle, &[]), (method_type, &[])]).unwrap_err();
+        assert_eq!(e.location, vec!["bootstrap method 1"]);
+        assert_eq!(e.offset, Some(6));
+        assert!(e.reason.contains("is not a MethodHandle constant"), "{}", e);
+
+        let e = read(&[(handle, &[method_type, name_and_type])]).unwrap_err();
+        assert_eq!(e.location, vec!["bootstrap method 0"]);
+        assert_eq!(e.offset, Some(8));
+
+        // REF_invokeInterface must refer to an InterfaceMethodref.
+        let e = read(&[(interface_handle, &[])]).unwrap_err();
+        assert_eq!(e.offset, Some(2));
+        assert!(e.reason.contains("of kind"), "{}", e);
+    }
+}
@@ -0,0 +1,394 @@
+use crate::jvm::class::{
+    access_flags::InnerClassAccessFlags, attribute::*, constant_pool::*, error::ClassFormatError,
+    signature::TypeSignature,
+};
+use crate::utils::bytecode_reader::BytecodeReader;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeInnerClasses {
+    pub classes: Vec<InnerClassInfo>,
+}
+
+impl AttributeInnerClasses {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeInnerClasses, ClassFormatError> {
+        let number_of_classes = reader.u16()?;
+        let mut classes: Vec<InnerClassInfo> = Vec::new();
+        for _ in 0..number_of_classes {
+            let inner_class = constant_pool.read_class_ref(reader)?;
+            let outer_class = read_optional(reader, |index| constant_pool.class_ref(index))?;
+            let inner_name = read_optional(reader, |index| constant_pool.utf8_ref(index))?;
+            let inner_class_access_flags = InnerClassAccessFlags(reader.u16()?);
+            classes.push(InnerClassInfo {
+                inner_class,
+                outer_class,
+                inner_name,
+                inner_class_access_flags,
+            });
+        }
+        Ok(AttributeInnerClasses { classes })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InnerClassInfo {
+    pub inner_class: ClassRef,
+    pub outer_class: Option<ClassRef>, // None for top level, local and anonymous classes
+    pub inner_name: Option<Indexed<String>>, // None for anonymous classes
+    pub inner_class_access_flags: InnerClassAccessFlags,
+}
+
+#[derive(Debug, Clone, Eq)]
+pub struct AttributeEnclosingMethod {
+    pub class: ClassRef,
+    // None when the class is not immediately enclosed by a method or constructor,
+    // e.g. an anonymous class in a field initializer.
+    pub method_name: Option<String>,
+    pub method_descriptor: Option<String>,
+    // The NameAndType the method was read from, a hint as the index of a ClassRef.
+    pub method_index: Option<CpIndex>,
+}
+
+impl PartialEq for AttributeEnclosingMethod {
+    fn eq(&self, other: &AttributeEnclosingMethod) -> bool {
+        self.class == other.class
+            && self.method_name == other.method_name
+            && self.method_descriptor == other.method_descriptor
+    }
+}
+
+impl AttributeEnclosingMethod {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeEnclosingMethod, ClassFormatError> {
+        let class = constant_pool.read_class_ref(reader)?;
+        let method = read_optional(reader, |index| {
+            constant_pool.indexed(index, ConstantPool::name_and_type)
+        })?;
+        let (method_name, method_descriptor, method_index) = match method {
+            Some(Indexed {
+                value: (name, descriptor),
+                index,
+            }) => (Some(name), Some(descriptor), index),
+            None => (None, None, None),
+        };
+        Ok(AttributeEnclosingMethod {
+            class,
+            method_name,
+            method_descriptor,
+            method_index,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeNestHost {
+    pub host_class: ClassRef,
+}
+
+// Used by both NestMembers and PermittedSubclasses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeClasses {
+    pub classes: Vec<ClassRef>,
+}
+
+impl AttributeClasses {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeClasses, ClassFormatError> {
+        let number_of_classes = reader.u16()?;
+        let mut classes: Vec<ClassRef> = Vec::new();
+        for _ in 0..number_of_classes {
+            classes.push(constant_pool.read_class_ref(reader)?);
+        }
+        Ok(AttributeClasses { classes })
+    }
+}
+
+#[derive(Debug)]
+pub struct AttributeRecord {
+    pub components: Vec<RecordComponentInfo>,
+}
+
+impl AttributeRecord {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeRecord, ClassFormatError> {
+        let components_count = reader.u16()?;
+        let mut components: Vec<RecordComponentInfo> = Vec::new();
+        for _ in 0..components_count {
+            let name = constant_pool.read_utf8_ref(reader)?;
+            let descriptor = constant_pool.read_utf8_ref(reader)?;
+            let (attributes, attribute_name_indices) = read_attributes(reader, constant_pool)
+                .map_err(|e| e.within(format!("record component {}", name)))?;
+            components.push(RecordComponentInfo {
+                name,
+                descriptor,
+                attributes,
+                attribute_name_indices,
+            });
+        }
+        Ok(AttributeRecord { components })
+    }
+}
+
+#[derive(Debug)]
+pub struct RecordComponentInfo {
+    pub name: Indexed<String>,
+    pub descriptor: Indexed<String>,
+    pub attributes: Vec<Attribute>,
+    pub attribute_name_indices: Vec<CpIndex>,
+}
+
+impl RecordComponentInfo {
+    pub fn signature(&self) -> Result<Option<TypeSignature>, ClassFormatError> {
+        for attribute in &self.attributes {
+            if let Attribute::Signature(a) = attribute {
+                return TypeSignature::parse_field(&a.signature).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn annotations(&self) -> Vec<&Annotation> {
+        annotations(&self.attributes)
+    }
+}
+
+// Reads a u2 constant pool index where 0 means "absent".
+fn read_optional<T>(
+    reader: &mut BytecodeReader,
+    resolve: impl FnOnce(CpIndex) -> Result<T, ClassFormatError>,
+) -> Result<Option<T>, ClassFormatError> {
+    let offset = reader.offset;
+    let index = CpIndex::read(reader)?;
+    if index.is_none() {
+        return Ok(None);
+    }
+    resolve(index).map(Some).map_err(|e| e.with_offset(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::constant_pool_builder::ConstantPoolBuilder;
+    use crate::utils::bytecode_writer::BytecodeWriter;
+
+    fn read<T>(
+        writer: &BytecodeWriter,
+        constant_pool: &ConstantPool,
+        new: fn(&mut BytecodeReader, &ConstantPool) -> Result<T, ClassFormatError>,
+    ) -> Result<T, ClassFormatError> {
+        let mut reader = BytecodeReader::new(writer.data.clone());
+        let value = new(&mut reader, constant_pool)?;
+        assert_eq!(reader.offset, writer.data.len(), "trailing bytes");
+        Ok(value)
+    }
+
+    #[test]
+    fn inner_classes_of_member_local_and_anonymous_classes() {
+        // As javac writes them for Outer with a member class Inner, a static nested class N, and
+        // in a method an anonymous class and a local class Local.
+        let mut cp = ConstantPoolBuilder::new();
+        let outer = cp.class("Outer").unwrap();
+        let inner = cp.class("Outer$Inner").unwrap();
+        let anonymous = cp.class("Outer$1").unwrap();
+        let local = cp.class("Outer$1Local").unwrap();
+        let nested = cp.class("Outer$N").unwrap();
+        let inner_name = cp.utf8("Inner").unwrap();
+        let local_name = cp.utf8("Local").unwrap();
+        let nested_name = cp.utf8("N").unwrap();
+        let constant_pool = cp.build();
+
+        let mut writer = BytecodeWriter::new();
+        writer.u16(4);
+        for (class, outer_class, name, flags) in [
+            (inner, outer, inner_name, 0x0000),
+            (anonymous, CpIndex(0), CpIndex(0), 0x0000),
+            (local, CpIndex(0), local_name, 0x0000),
+            (nested, outer, nested_name, 0x0008),
+        ] {
+            writer.u16(class.0);
+            writer.u16(outer_class.0);
+            writer.u16(name.0);
+            writer.u16(flags);
+        }
+        let attribute = read(&writer, &constant_pool, AttributeInnerClasses::new).unwrap();
+        let info =
+            |inner_class: &str, outer_class: Option<&str>, inner_name: Option<&str>, flags| {
+                InnerClassInfo {
+                    inner_class: ClassRef::new(inner_class),
+                    outer_class: outer_class.map(ClassRef::new),
+                    inner_name: inner_name.map(Indexed::new),
+                    inner_class_access_flags: flags,
+                }
+            };
+        let none = InnerClassAccessFlags(0);
+        assert_eq!(
+            attribute.classes,
+            vec![
+                info("Outer$Inner", Some("Outer"), Some("Inner"), none),
+                info("Outer$1", None, None, none),
+                info("Outer$1Local", None, Some("Local"), none),
+                info(
+                    "Outer$N",
+                    Some("Outer"),
+                    Some("N"),
+                    InnerClassAccessFlags::STATIC
+                ),
+            ]
+        );
+        assert_eq!(attribute.classes[0].inner_class.index, Some(inner));
+        assert_eq!(
+            attribute.classes[0].outer_class.as_ref().unwrap().index,
+            Some(outer)
+        );
+        assert_eq!(
+            attribute.classes[3].inner_name.as_ref().unwrap().index,
+            Some(nested_name)
+        );
+
+        // An inner_name_index naming a Class constant.
+        writer.data[6..8].copy_from_slice(&outer.0.to_be_bytes());
+        let e = read(&writer, &constant_pool, AttributeInnerClasses::new).unwrap_err();
+        assert_eq!(e.offset, Some(6));
+    }
+
+    #[test]
+    fn enclosing_method_of_local_and_field_initializer_classes() {
+        let mut cp = ConstantPoolBuilder::new();
+        let outer = cp.class("Outer").unwrap();
+        let method = cp.name_and_type("run", "(I)V").unwrap();
+        let constant_pool = cp.build();
+
+        let mut writer = BytecodeWriter::new();
+        writer.u16(outer.0);
+        writer.u16(method.0);
+        let attribute = read(&writer, &constant_pool, AttributeEnclosingMethod::new).unwrap();
+        assert_eq!(attribute.class, "Outer");
+        assert_eq!(attribute.method_name.as_deref(), Some("run"));
+        assert_eq!(attribute.method_descriptor.as_deref(), Some("(I)V"));
+        assert_eq!(attribute.method_index, Some(method));
+
+        // An anonymous class in a field initializer has no enclosing method.
+        writer.data[2..4].copy_from_slice(&[0, 0]);
+        let attribute = read(&writer, &constant_pool, AttributeEnclosingMethod::new).unwrap();
+        assert_eq!(
+            attribute,
+            AttributeEnclosingMethod {
+                class: ClassRef::new("Outer"),
+                method_name: None,
+                method_descriptor: None,
+                method_index: None,
+            }
+        );
+
+        writer.data[2..4].copy_from_slice(&outer.0.to_be_bytes());
+        let e = read(&writer, &constant_pool, AttributeEnclosingMethod::new).unwrap_err();
+        assert_eq!(e.offset, Some(2));
+        assert!(e.reason.contains("is not a NameAndType constant"), "{}", e);
+    }
+
+    #[test]
+    fn nest_host_members_and_permitted_subclasses() {
+        let mut cp = ConstantPoolBuilder::new();
+        let host = cp.class("Shape").unwrap();
+        let circle = cp.class("Shape$Circle").unwrap();
+        let square = cp.class("Shape$Square").unwrap();
+        let name = cp.utf8("Shape$Triangle").unwrap();
+        let constant_pool = cp.build();
+
+        // NestHost is read by Attribute::read_body; NestMembers and PermittedSubclasses share
+        // AttributeClasses.
+        let mut writer = BytecodeWriter::new();
+        writer.u16(2);
+        writer.u16(circle.0);
+        writer.u16(square.0);
+        let attribute = read(&writer, &constant_pool, AttributeClasses::new).unwrap();
+        assert_eq!(
+            attribute.classes,
+            vec![ClassRef::new("Shape$Circle"), ClassRef::new("Shape$Square")]
+        );
+        let indices: Vec<Option<CpIndex>> = attribute.classes.iter().map(|c| c.index).collect();
+        assert_eq!(indices, vec![Some(circle), Some(square)]);
+
+        let mut writer = BytecodeWriter::new();
+        writer.u16(0);
+        let attribute = read(&writer, &constant_pool, AttributeClasses::new).unwrap();
+        assert!(attribute.classes.is_empty());
+
+        let mut writer = BytecodeWriter::new();
+        writer.u16(2);
+        writer.u16(host.0);
+        writer.u16(name.0);
+        let e = read(&writer, &constant_pool, AttributeClasses::new).unwrap_err();
+        assert_eq!(e.offset, Some(4));
+
+        let mut cp = ConstantPoolBuilder::from_pool(&constant_pool);
+        let nest_host = cp.utf8("NestHost").unwrap();
+        let constant_pool = cp.build();
+        let mut writer = BytecodeWriter::new();
+        writer.u16(nest_host.0);
+        writer.u32(2);
+        writer.u16(host.0);
+        let mut reader = BytecodeReader::new(writer.data);
+        match Attribute::new(&mut reader, &constant_pool).unwrap() {
+            (Attribute::NestHost(a), name_index) => {
+                assert_eq!(a.host_class, "Shape");
+                assert_eq!(name_index, nest_host);
+            }
+            (attribute, _) => panic!("unexpected {:?}", attribute),
+        }
+    }
+
+    #[test]
+    fn record_components_with_their_attributes() {
+        // record Point(int x, List<String> names), as javac writes its Record attribute.
+        let mut cp = ConstantPoolBuilder::new();
+        let x = cp.utf8("x").unwrap();
+        let int = cp.utf8("I").unwrap();
+        let names = cp.utf8("names").unwrap();
+        let list = cp.utf8("Ljava/util/List;").unwrap();
+        let signature_name = cp.utf8("Signature").unwrap();
+        let signature = cp.utf8("Ljava/util/List<Ljava/lang/String;>;").unwrap();
+        let constant_pool = cp.build();
+
+        let mut writer = BytecodeWriter::new();
+        writer.u16(2);
+        writer.u16(x.0);
+        writer.u16(int.0);
+        writer.u16(0);
+        writer.u16(names.0);
+        writer.u16(list.0);
+        writer.u16(1);
+        writer.u16(signature_name.0);
+        writer.u32(2);
+        writer.u16(signature.0);
+        let attribute = read(&writer, &constant_pool, AttributeRecord::new).unwrap();
+        let [x_component, names_component] = &attribute.components[..] else {
+            panic!("unexpected components {:?}", attribute.components);
+        };
+        assert_eq!(x_component.name, "x");
+        assert_eq!(x_component.descriptor, "I");
+        assert!(x_component.attributes.is_empty());
+        assert_eq!(x_component.signature(), Ok(None));
+        assert_eq!(names_component.name.index, Some(names));
+        assert_eq!(names_component.descriptor, "Ljava/util/List;");
+        assert_eq!(names_component.attribute_name_indices, vec![signature_name]);
+        let signature = names_component.signature().unwrap().unwrap();
+        assert_eq!(signature.java_name(), "java.util.List<java.lang.String>");
+        assert!(names_component.annotations().is_empty());
+
+        // A component attribute whose name is not a Utf8 constant.
+        let offset = writer.data.len() - 8;
+        writer.data[offset..offset + 2].copy_from_slice(&[0, 0]);
+        let e = read(&writer, &constant_pool, AttributeRecord::new).unwrap_err();
+        assert_eq!(e.location, vec!["record component names"]);
+        assert_eq!(e.offset, Some(offset));
+    }
+}
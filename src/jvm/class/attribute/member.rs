@@ -0,0 +1,384 @@
+use crate::jvm::class::{attribute::*, constant_pool::*, error::ClassFormatError};
+use crate::utils::{bytecode_reader::BytecodeReader, modified_utf8};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeExceptions {
+    // The checked exceptions a method is declared to throw.
+    pub exception_index_table: Vec<ClassRef>,
+}
+
+impl AttributeExceptions {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeExceptions, ClassFormatError> {
+        let number_of_exceptions = reader.u16()?;
+        let mut exception_index_table: Vec<ClassRef> = Vec::new();
+        for _ in 0..number_of_exceptions {
+            exception_index_table.push(constant_pool.read_class_ref(reader)?);
+        }
+        Ok(AttributeExceptions {
+            exception_index_table,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeMethodParameters {
+    pub parameters: Vec<MethodParameter>,
+}
+
+impl AttributeMethodParameters {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeMethodParameters, ClassFormatError> {
+        let parameters_count = reader.u8()?;
+        let mut parameters: Vec<MethodParameter> = Vec::new();
+        for _ in 0..parameters_count {
+            let offset = reader.offset;
+            let name_index = CpIndex::read(reader)?;
+            let name = if name_index.is_none() {
+                None
+            } else {
+                Some(
+                    constant_pool
+                        .utf8_ref(name_index)
+                        .map_err(|e| e.with_offset(offset))?,
+                )
+            };
+            let access_flags = reader.u16()?;
+            parameters.push(MethodParameter { name, access_flags });
+        }
+        Ok(AttributeMethodParameters { parameters })
+    }
+}
+
+pub const ACC_FINAL: u16 = 0x0010;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodParameter {
+    pub name: Option<Indexed<String>>, // None for a formal parameter with no name
+    pub access_flags: u16,             // ACC_FINAL, ACC_SYNTHETIC, ACC_MANDATED
+}
+
+impl MethodParameter {
+    pub fn is_final(&self) -> bool {
+        self.access_flags & ACC_FINAL != 0
+    }
+
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags & ACC_SYNTHETIC != 0
+    }
+
+    pub fn is_mandated(&self) -> bool {
+        self.access_flags & ACC_MANDATED != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeLocalVariableTypeTable {
+    pub local_variable_type_table: Vec<LocalVariableTypeInfo>,
+}
+
+impl AttributeLocalVariableTypeTable {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeLocalVariableTypeTable, ClassFormatError> {
+        let local_variable_type_table_length = reader.u16()?;
+        let mut local_variable_type_table: Vec<LocalVariableTypeInfo> = Vec::new();
+        for _ in 0..local_variable_type_table_length {
+            let start_pc = reader.u16()?;
+            let length = reader.u16()?;
+            let name = constant_pool.read_utf8_ref(reader)?;
+            let signature = constant_pool.read_utf8_ref(reader)?;
+            let index = reader.u16()?;
+            local_variable_type_table.push(LocalVariableTypeInfo {
+                start_pc,
+                length,
+                name,
+                signature,
+                index,
+            });
+        }
+        Ok(AttributeLocalVariableTypeTable {
+            local_variable_type_table,
+        })
+    }
+}
+
+// Same as LocalVariableInfo, but with a field signature (parse with
+// TypeSignature::parse_field) instead of a descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalVariableTypeInfo {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name: Indexed<String>,
+    pub signature: Indexed<String>,
+    pub index: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeSourceDebugExtension {
+    // Kept as read; not null terminated and not required to be valid modified UTF-8.
+    pub debug_extension: Vec<u8>,
+}
+
+impl AttributeSourceDebugExtension {
+    // The extension as text, e.g. an SMAP (JSR 45) written by Kotlin or JSP compilers.
+    pub fn text(&self) -> String {
+        match modified_utf8::decode(&self.debug_extension) {
+            Ok(utf16) => String::from_utf16_lossy(&utf16),
+            Err(_) => String::from_utf8_lossy(&self.debug_extension).into_owned(),
+        }
+    }
+}
+
+pub fn is_deprecated(attributes: &[Attribute]) -> bool {
+    attributes
+        .iter()
+        .any(|attribute| matches!(attribute, Attribute::Deprecated))
+}
+
+pub fn is_synthetic(attributes: &[Attribute]) -> bool {
+    attributes
+        .iter()
+        .any(|attribute| matches!(attribute, Attribute::Synthetic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        access_flags::MethodAccessFlags, constant_pool_builder::ConstantPoolBuilder, method::Method,
+    };
+    use crate::utils::bytecode_writer::BytecodeWriter;
+
+    // attribute_name_index, attribute_length and `body`.
+    fn attribute(constant_pool: &mut ConstantPoolBuilder, name: &str, body: &[u8]) -> Vec<u8> {
+        let mut writer = BytecodeWriter::new();
+        writer.u16(constant_pool.utf8(name).unwrap().0);
+        writer.u32(body.len() as u32);
+        writer.bytes(body);
+        writer.data
+    }
+
+    fn read(bytes: Vec<u8>, constant_pool: &ConstantPool) -> Result<Attribute, ClassFormatError> {
+        let len = bytes.len();
+        let mut reader = BytecodeReader::new(bytes);
+        let (attribute, _) = Attribute::new(&mut reader, constant_pool)?;
+        assert_eq!(reader.offset, len, "trailing bytes");
+        Ok(attribute)
+    }
+
+    #[test]
+    fn exceptions_in_throws_order() {
+        let mut cp = ConstantPoolBuilder::new();
+        let io = cp.class("java/io/IOException").unwrap();
+        let interrupted = cp.class("java/lang/InterruptedException").unwrap();
+        let mut body = BytecodeWriter::new();
+        body.u16(2);
+        body.u16(io.0);
+        body.u16(interrupted.0);
+        let bytes = attribute(&mut cp, "Exceptions", &body.data);
+        let constant_pool = cp.build();
+        let Attribute::Exceptions(a) = read(bytes.clone(), &constant_pool).unwrap() else {
+            panic!("not Exceptions");
+        };
+        assert_eq!(
+            a.exception_index_table,
+            vec![
+                ClassRef::new("java/io/IOException"),
+                ClassRef::new("java/lang/InterruptedException")
+            ]
+        );
+        assert_eq!(a.exception_index_table[1].index, Some(interrupted));
+
+        // One class fewer than exception_index_table_length says.
+        let mut bytes = bytes;
+        bytes.truncate(bytes.len() - 2);
+        bytes[5] -= 2;
+        let e = read(bytes, &constant_pool).unwrap_err();
+        assert_eq!(e.location, vec!["attribute Exceptions"]);
+    }
+
+    #[test]
+    fn method_parameters_with_and_without_names() {
+        // javac -parameters for an inner class constructor Outer.Inner(final int count): the
+        // mandated outer instance is named this$0, then the final parameter; an absent name
+        // is index 0.
+        let mut cp = ConstantPoolBuilder::new();
+        let outer = cp.utf8("this$0").unwrap();
+        let count = cp.utf8("count").unwrap();
+        let class = cp.class("Outer").unwrap();
+        #[rustfmt::skip]
+        let body = [
+            3,
+            (outer.0 >> 8) as u8, outer.0 as u8, 0x80, 0x10, // ACC_FINAL ACC_MANDATED
+            (count.0 >> 8) as u8, count.0 as u8, 0x00, 0x10, // ACC_FINAL
+            0, 0, 0x10, 0x00,                                // ACC_SYNTHETIC
+        ];
+        let bytes = attribute(&mut cp, "MethodParameters", &body);
+        let constant_pool = cp.build();
+        let Attribute::MethodParameters(a) = read(bytes.clone(), &constant_pool).unwrap() else {
+            panic!("not MethodParameters");
+        };
+        let names: Vec<Option<&str>> = a
+            .parameters
+            .iter()
+            .map(|p| p.name.as_ref().map(|name| name.as_str()))
+            .collect();
+        assert_eq!(names, vec![Some("this$0"), Some("count"), None]);
+        assert_eq!(a.parameters[1].name.as_ref().unwrap().index, Some(count));
+        let flags: Vec<(bool, bool, bool)> = a
+            .parameters
+            .iter()
+            .map(|p| (p.is_final(), p.is_synthetic(), p.is_mandated()))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                (true, false, true),
+                (true, false, false),
+                (false, true, false)
+            ]
+        );
+
+        // A name_index naming a Class constant.
+        let mut bytes = bytes;
+        bytes[11..13].copy_from_slice(&class.0.to_be_bytes());
+        let e = read(bytes, &constant_pool).unwrap_err();
+        assert_eq!(e.location, vec!["attribute MethodParameters"]);
+        assert_eq!(e.offset, Some(11));
+    }
+
+    #[test]
+    fn constant_value_keeps_its_index() {
+        let mut cp = ConstantPoolBuilder::new();
+        let value = cp.long(1 << 40).unwrap();
+        let bytes = attribute(&mut cp, "ConstantValue", &value.0.to_be_bytes());
+        let constant_pool = cp.build();
+        let Attribute::ConstantValue(a) = read(bytes.clone(), &constant_pool).unwrap() else {
+            panic!("not ConstantValue");
+        };
+        assert_eq!(a.constantvalue_index, value);
+        assert_eq!(constant_pool.long(a.constantvalue_index), Ok(1 << 40));
+
+        // attribute_length must be 2.
+        let mut bytes = bytes;
+        bytes[5] = 4;
+        bytes.extend([0, 0]);
+        let e = read(bytes, &constant_pool).unwrap_err();
+        assert_eq!(e.reason, "attribute_length is 4 but 2 bytes were read");
+        assert_eq!(e.offset, Some(6));
+    }
+
+    #[test]
+    fn local_variable_tables_of_a_generic_method() {
+        // javac -g for
+        //   static <T> T first(List<T> list) { T t = list.get(0); return t; }
+        // with the LocalVariableTypeTable split in two, as a Code attribute may have it.
+        let mut cp = ConstantPoolBuilder::new();
+        let list = cp.utf8("list").unwrap();
+        let list_descriptor = cp.utf8("Ljava/util/List;").unwrap();
+        let list_signature = cp.utf8("Ljava/util/List<TT;>;").unwrap();
+        let t = cp.utf8("t").unwrap();
+        let object = cp.utf8("Ljava/lang/Object;").unwrap();
+        let type_variable = cp.utf8("TT;").unwrap();
+        let entry = |start_pc: u16, length: u16, name: CpIndex, descriptor: CpIndex, index| {
+            let mut writer = BytecodeWriter::new();
+            writer.u16(start_pc);
+            writer.u16(length);
+            writer.u16(name.0);
+            writer.u16(descriptor.0);
+            writer.u16(index);
+            writer.data
+        };
+        let table = |entries: &[Vec<u8>]| {
+            let mut body = (entries.len() as u16).to_be_bytes().to_vec();
+            body.extend(entries.concat());
+            body
+        };
+        let code_attributes = vec![
+            attribute(
+                &mut cp,
+                "LocalVariableTable",
+                &table(&[
+                    entry(0, 13, list, list_descriptor, 0),
+                    entry(11, 2, t, object, 1),
+                ]),
+            ),
+            attribute(
+                &mut cp,
+                "LocalVariableTypeTable",
+                &table(&[entry(0, 13, list, list_signature, 0)]),
+            ),
+            attribute(
+                &mut cp,
+                "LocalVariableTypeTable",
+                &table(&[entry(11, 2, t, type_variable, 1)]),
+            ),
+        ];
+        #[rustfmt::skip]
+        let code = [
+            0x2A, 0x03, 0xB9, 0, 0, 2, 0, // aload_0 iconst_0 invokeinterface (unresolved)
+            0x4C, 0x2B, 0xB0,             // astore_1 aload_1 areturn
+        ];
+        let mut body = BytecodeWriter::new();
+        body.u16(2);
+        body.u16(2);
+        body.u32(code.len() as u32);
+        body.bytes(&code);
+        body.u16(0);
+        body.u16(code_attributes.len() as u16);
+        for attribute in &code_attributes {
+            body.bytes(attribute);
+        }
+        let code = attribute(&mut cp, "Code", &body.data);
+        let constant_pool = cp.build();
+        let code = read(code, &constant_pool).unwrap();
+        let method = Method {
+            access_flags: MethodAccessFlags::STATIC,
+            name: "first".into(),
+            descriptor: "(Ljava/util/List;)Ljava/lang/Object;".into(),
+            attributes: vec![code],
+            attribute_name_indices: Vec::new(),
+        };
+
+        let variables: Vec<(u16, u16, &str, &str, u16)> = method
+            .local_variables()
+            .iter()
+            .map(|v| {
+                (
+                    v.start_pc,
+                    v.length,
+                    v.name.as_str(),
+                    v.descriptor.as_str(),
+                    v.index,
+                )
+            })
+            .collect();
+        assert_eq!(
+            variables,
+            vec![
+                (0, 13, "list", "Ljava/util/List;", 0),
+                (11, 2, "t", "Ljava/lang/Object;", 1)
+            ]
+        );
+        let types: Vec<(u16, &str, &str, u16)> = method
+            .local_variable_types()
+            .iter()
+            .map(|v| (v.start_pc, v.name.as_str(), v.signature.as_str(), v.index))
+            .collect();
+        assert_eq!(
+            types,
+            vec![(0, "list", "Ljava/util/List<TT;>;", 0), (11, "t", "TT;", 1)]
+        );
+        assert_eq!(
+            method.local_variable_types()[1].signature.index,
+            Some(type_variable)
+        );
+        assert_eq!(method.code().unwrap().attribute_name_indices.len(), 3);
+    }
+}
@@ -0,0 +1,349 @@
+pub mod annotation;
+pub mod bootstrap_methods;
+pub mod class_structure;
+pub mod member;
+pub mod module;
+pub mod stack_map_table;
+
+use std::vec;
+
+use crate::jvm::class::{constant_pool::*, error::ClassFormatError};
+use crate::utils::bytecode_reader::BytecodeReader;
+
+pub use annotation::*;
+pub use bootstrap_methods::*;
+pub use class_structure::*;
+pub use member::*;
+pub use module::*;
+pub use stack_map_table::*;
+
+#[derive(Debug)]
+pub enum Attribute {
+    ConstantValue(AttributeConstantValue),
+    Code(AttributeCode),
+    LineNumberTable(AttributeLineNumberTable),
+    SourceFile(AttributeSourceFile),
+    LocalVariableTable(AttributeLocalVariableTable),
+    StackMapTable(AttributeStackMapTable),
+    Signature(AttributeSignature),
+    RuntimeVisibleAnnotations(AttributeAnnotations),
+    RuntimeInvisibleAnnotations(AttributeAnnotations),
+    RuntimeVisibleParameterAnnotations(AttributeParameterAnnotations),
+    RuntimeInvisibleParameterAnnotations(AttributeParameterAnnotations),
+    RuntimeVisibleTypeAnnotations(AttributeTypeAnnotations),
+    RuntimeInvisibleTypeAnnotations(AttributeTypeAnnotations),
+    AnnotationDefault(AttributeAnnotationDefault),
+    InnerClasses(AttributeInnerClasses),
+    EnclosingMethod(AttributeEnclosingMethod),
+    NestHost(AttributeNestHost),
+    NestMembers(AttributeClasses),
+    PermittedSubclasses(AttributeClasses),
+    Record(AttributeRecord),
+    Module(AttributeModule),
+    ModulePackages(AttributeModulePackages),
+    ModuleMainClass(AttributeModuleMainClass),
+    Exceptions(AttributeExceptions),
+    MethodParameters(AttributeMethodParameters),
+    LocalVariableTypeTable(AttributeLocalVariableTypeTable),
+    Deprecated,
+    Synthetic,
+    SourceDebugExtension(AttributeSourceDebugExtension),
+    BootstrapMethods(AttributeBootstrapMethods),
+    // Any attribute that is not modelled above, kept verbatim.
+    Unknown { name: String, bytes: Vec<u8> },
+}
+impl Attribute {
+    pub fn name(&self) -> &str {
+        match self {
+            Attribute::ConstantValue(_) => "ConstantValue",
+            Attribute::Code(_) => "Code",
+            Attribute::LineNumberTable(_) => "LineNumberTable",
+            Attribute::SourceFile(_) => "SourceFile",
+            Attribute::LocalVariableTable(_) => "LocalVariableTable",
+            Attribute::StackMapTable(_) => "StackMapTable",
+            Attribute::Signature(_) => "Signature",
+            Attribute::RuntimeVisibleAnnotations(_) => "RuntimeVisibleAnnotations",
+            Attribute::RuntimeInvisibleAnnotations(_) => "RuntimeInvisibleAnnotations",
+            Attribute::RuntimeVisibleParameterAnnotations(_) => {
+                "RuntimeVisibleParameterAnnotations"
+            }
+            Attribute::RuntimeInvisibleParameterAnnotations(_) => {
+                "RuntimeInvisibleParameterAnnotations"
+            }
+            Attribute::RuntimeVisibleTypeAnnotations(_) => "RuntimeVisibleTypeAnnotations",
+            Attribute::RuntimeInvisibleTypeAnnotations(_) => "RuntimeInvisibleTypeAnnotations",
+            Attribute::AnnotationDefault(_) => "AnnotationDefault",
+            Attribute::InnerClasses(_) => "InnerClasses",
+            Attribute::EnclosingMethod(_) => "EnclosingMethod",
+            Attribute::NestHost(_) => "NestHost",
+            Attribute::NestMembers(_) => "NestMembers",
+            Attribute::PermittedSubclasses(_) => "PermittedSubclasses",
+            Attribute::Record(_) => "Record",
+            Attribute::Module(_) => "Module",
+            Attribute::ModulePackages(_) => "ModulePackages",
+            Attribute::ModuleMainClass(_) => "ModuleMainClass",
+            Attribute::Exceptions(_) => "Exceptions",
+            Attribute::MethodParameters(_) => "MethodParameters",
+            Attribute::LocalVariableTypeTable(_) => "LocalVariableTypeTable",
+            Attribute::Deprecated => "Deprecated",
+            Attribute::Synthetic => "Synthetic",
+            Attribute::SourceDebugExtension(_) => "SourceDebugExtension",
+            Attribute::BootstrapMethods(_) => "BootstrapMethods",
+            Attribute::Unknown { name, .. } => name,
+        }
+    }
+
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<(Attribute, CpIndex), ClassFormatError> {
+        let offset = reader.offset;
+        let name_index = CpIndex::read(reader)?;
+        let attribute_name = constant_pool
+            .utf8(name_index)
+            .map_err(|e| e.with_offset(offset))?;
+        let attribute = Attribute::read_body(reader, constant_pool, &attribute_name)
+            .map_err(|e| e.within(format!("attribute {}", attribute_name)))?;
+        Ok((attribute, name_index))
+    }
+
+    fn read_body(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+        attribute_name: &str,
+    ) -> Result<Attribute, ClassFormatError> {
+        let attribute_length = reader.u32()? as usize;
+        let attribute_offset = reader.offset;
+        let attribute = match attribute_name {
+            "ConstantValue" => {
+                let constantvalue_index = CpIndex::read(reader)?;
+                Attribute::ConstantValue(AttributeConstantValue {
+                    constantvalue_index,
+                })
+            }
+            "Code" => {
+                let max_stack = reader.u16()?;
+                let max_locals = reader.u16()?;
+                let code_length = reader.u32()?;
+                let code: Vec<u8> = reader.read_as_vec(code_length as usize)?;
+                let exception_table_length = reader.u16()?;
+                let mut exception_table: Vec<ExceptionInfo> = Vec::new();
+                for _ in 0..exception_table_length {
+                    let start_pc = reader.u16()?;
+                    let end_pc = reader.u16()?;
+                    let handler_pc = reader.u16()?;
+                    let catch_type = CpIndex::read(reader)?;
+                    exception_table.push(ExceptionInfo {
+                        start_pc,
+                        end_pc,
+                        handler_pc,
+                        catch_type,
+                    });
+                }
+                let (attributes, attribute_name_indices) = read_attributes(reader, constant_pool)?;
+                Attribute::Code(AttributeCode {
+                    max_stack,
+                    max_locals,
+                    code,
+                    exception_table,
+                    attributes,
+                    attribute_name_indices,
+                })
+            }
+            "LineNumberTable" => {
+                let line_number_table_length = reader.u16()?;
+                let mut line_number_table: Vec<Vec<u16>> = Vec::new();
+                for _ in 0..line_number_table_length {
+                    line_number_table.push(vec![reader.u16()?, reader.u16()?]);
+                }
+                Attribute::LineNumberTable(AttributeLineNumberTable { line_number_table })
+            }
+            "SourceFile" => {
+                let sourcefile = constant_pool.read_utf8_ref(reader)?;
+                Attribute::SourceFile(AttributeSourceFile { sourcefile })
+            }
+            "LocalVariableTable" => {
+                let local_variable_table_length = reader.u16()?;
+                let mut local_variable_table: Vec<LocalVariableInfo> = Vec::new();
+                for _ in 0..local_variable_table_length {
+                    let start_pc = reader.u16()?;
+                    let length = reader.u16()?;
+                    let name = constant_pool.read_utf8_ref(reader)?;
+                    let descriptor = constant_pool.read_utf8_ref(reader)?;
+                    let index = reader.u16()?;
+
+                    let local_variable_info = LocalVariableInfo {
+                        start_pc,
+                        length,
+                        name,
+                        descriptor,
+                        index,
+                    };
+                    local_variable_table.push(local_variable_info);
+                }
+                Attribute::LocalVariableTable(AttributeLocalVariableTable {
+                    local_variable_table,
+                })
+            }
+            "StackMapTable" => {
+                Attribute::StackMapTable(AttributeStackMapTable::new(reader, constant_pool)?)
+            }
+            "Signature" => {
+                let signature = constant_pool.read_utf8_ref(reader)?;
+                Attribute::Signature(AttributeSignature { signature })
+            }
+            "RuntimeVisibleAnnotations" => Attribute::RuntimeVisibleAnnotations(
+                AttributeAnnotations::new(reader, constant_pool)?,
+            ),
+            "RuntimeInvisibleAnnotations" => Attribute::RuntimeInvisibleAnnotations(
+                AttributeAnnotations::new(reader, constant_pool)?,
+            ),
+            "RuntimeVisibleParameterAnnotations" => Attribute::RuntimeVisibleParameterAnnotations(
+                AttributeParameterAnnotations::new(reader, constant_pool)?,
+            ),
+            "RuntimeInvisibleParameterAnnotations" => {
+                Attribute::RuntimeInvisibleParameterAnnotations(AttributeParameterAnnotations::new(
+                    reader,
+                    constant_pool,
+                )?)
+            }
+            "RuntimeVisibleTypeAnnotations" => Attribute::RuntimeVisibleTypeAnnotations(
+                AttributeTypeAnnotations::new(reader, constant_pool)?,
+            ),
+            "RuntimeInvisibleTypeAnnotations" => Attribute::RuntimeInvisibleTypeAnnotations(
+                AttributeTypeAnnotations::new(reader, constant_pool)?,
+            ),
+            "AnnotationDefault" => Attribute::AnnotationDefault(AttributeAnnotationDefault {
+                default_value: ElementValue::new(reader, constant_pool)?,
+            }),
+            "InnerClasses" => {
+                Attribute::InnerClasses(AttributeInnerClasses::new(reader, constant_pool)?)
+            }
+            "EnclosingMethod" => {
+                Attribute::EnclosingMethod(AttributeEnclosingMethod::new(reader, constant_pool)?)
+            }
+            "NestHost" => {
+                let host_class = constant_pool.read_class_ref(reader)?;
+                Attribute::NestHost(AttributeNestHost { host_class })
+            }
+            "NestMembers" => Attribute::NestMembers(AttributeClasses::new(reader, constant_pool)?),
+            "PermittedSubclasses" => {
+                Attribute::PermittedSubclasses(AttributeClasses::new(reader, constant_pool)?)
+            }
+            "Record" => Attribute::Record(AttributeRecord::new(reader, constant_pool)?),
+            "Module" => Attribute::Module(AttributeModule::new(reader, constant_pool)?),
+            "ModulePackages" => {
+                Attribute::ModulePackages(AttributeModulePackages::new(reader, constant_pool)?)
+            }
+            "ModuleMainClass" => {
+                let main_class = constant_pool.read_class_ref(reader)?;
+                Attribute::ModuleMainClass(AttributeModuleMainClass { main_class })
+            }
+            "Exceptions" => Attribute::Exceptions(AttributeExceptions::new(reader, constant_pool)?),
+            "MethodParameters" => {
+                Attribute::MethodParameters(AttributeMethodParameters::new(reader, constant_pool)?)
+            }
+            "LocalVariableTypeTable" => Attribute::LocalVariableTypeTable(
+                AttributeLocalVariableTypeTable::new(reader, constant_pool)?,
+            ),
+            "Deprecated" => Attribute::Deprecated,
+            "Synthetic" => Attribute::Synthetic,
+            "SourceDebugExtension" => {
+                let debug_extension = reader.read_as_vec(attribute_length)?;
+                Attribute::SourceDebugExtension(AttributeSourceDebugExtension { debug_extension })
+            }
+            "BootstrapMethods" => {
+                Attribute::BootstrapMethods(AttributeBootstrapMethods::new(reader, constant_pool)?)
+            }
+            _ => {
+                let bytes = reader.read_as_vec(attribute_length)?;
+                Attribute::Unknown {
+                    name: String::from(attribute_name),
+                    bytes,
+                }
+            }
+        };
+        if reader.offset - attribute_offset != attribute_length {
+            return Err(ClassFormatError::at(
+                attribute_offset,
+                format!(
+                    "attribute_length is {} but {} bytes were read",
+                    attribute_length,
+                    reader.offset - attribute_offset
+                ),
+            ));
+        }
+        Ok(attribute)
+    }
+}
+
+// attributes_count followed by the attributes, and the index each attribute name was read
+// from. Writers use these indices while they still name the attribute at the same position,
+// as hints like the index of a ClassRef; attributes added to the model need none.
+pub fn read_attributes(
+    reader: &mut BytecodeReader,
+    constant_pool: &ConstantPool,
+) -> Result<(Vec<Attribute>, Vec<CpIndex>), ClassFormatError> {
+    let attributes_count = reader.u16()?;
+    let mut attributes: Vec<Attribute> = Vec::new();
+    let mut name_indices: Vec<CpIndex> = Vec::new();
+    for _ in 0..attributes_count {
+        let (attribute, name_index) = Attribute::new(reader, constant_pool)?;
+        attributes.push(attribute);
+        name_indices.push(name_index);
+    }
+    Ok((attributes, name_indices))
+}
+
+#[derive(Debug)]
+pub struct AttributeConstantValue {
+    // pub constantvalue: Constant,
+    pub constantvalue_index: CpIndex,
+}
+
+#[derive(Debug)]
+pub struct AttributeCode {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: Vec<u8>,
+    pub exception_table: Vec<ExceptionInfo>,
+    pub attributes: Vec<Attribute>,
+    pub attribute_name_indices: Vec<CpIndex>,
+}
+
+#[derive(Debug)]
+pub struct ExceptionInfo {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    pub catch_type: CpIndex,
+}
+
+#[derive(Debug)]
+pub struct AttributeLineNumberTable {
+    pub line_number_table: Vec<Vec<u16>>,
+}
+
+#[derive(Debug)]
+pub struct AttributeSignature {
+    // Parse with ClassSignature, MethodSignature or TypeSignature::parse_field.
+    pub signature: Indexed<String>,
+}
+
+#[derive(Debug)]
+pub struct AttributeSourceFile {
+    pub sourcefile: Indexed<String>,
+}
+#[derive(Debug)]
+pub struct AttributeLocalVariableTable {
+    pub local_variable_table: Vec<LocalVariableInfo>,
+}
+
+#[derive(Debug)]
+pub struct LocalVariableInfo {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name: Indexed<String>,
+    pub descriptor: Indexed<String>,
+    pub index: u16,
+}
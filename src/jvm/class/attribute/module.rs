@@ -0,0 +1,366 @@
+use crate::jvm::class::{constant_pool::*, error::ClassFormatError};
+use crate::utils::bytecode_reader::BytecodeReader;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeModule {
+    pub module_name: Indexed<String>,
+    pub module_flags: u16,
+    pub module_version: Option<Indexed<String>>,
+    pub requires: Vec<ModuleRequires>,
+    pub exports: Vec<ModuleExports>,
+    pub opens: Vec<ModuleExports>,
+    pub uses: Vec<ClassRef>, // service interfaces
+    pub provides: Vec<ModuleProvides>,
+}
+
+impl AttributeModule {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeModule, ClassFormatError> {
+        let module_name = read_module(reader, constant_pool)?;
+        let module_flags = reader.u16()?;
+        let module_version = read_optional_utf8(reader, constant_pool)?;
+
+        let requires_count = reader.u16()?;
+        let mut requires: Vec<ModuleRequires> = Vec::new();
+        for _ in 0..requires_count {
+            requires.push(ModuleRequires {
+                module: read_module(reader, constant_pool)?,
+                flags: reader.u16()?,
+                version: read_optional_utf8(reader, constant_pool)?,
+            });
+        }
+
+        let exports = ModuleExports::read_table(reader, constant_pool)?;
+        let opens = ModuleExports::read_table(reader, constant_pool)?;
+
+        let uses_count = reader.u16()?;
+        let mut uses: Vec<ClassRef> = Vec::new();
+        for _ in 0..uses_count {
+            uses.push(constant_pool.read_class_ref(reader)?);
+        }
+
+        let provides_count = reader.u16()?;
+        let mut provides: Vec<ModuleProvides> = Vec::new();
+        for _ in 0..provides_count {
+            let service = constant_pool.read_class_ref(reader)?;
+            let provides_with_count = reader.u16()?;
+            let mut with: Vec<ClassRef> = Vec::new();
+            for _ in 0..provides_with_count {
+                with.push(constant_pool.read_class_ref(reader)?);
+            }
+            provides.push(ModuleProvides { service, with });
+        }
+
+        Ok(AttributeModule {
+            module_name,
+            module_flags,
+            module_version,
+            requires,
+            exports,
+            opens,
+            uses,
+            provides,
+        })
+    }
+}
+
+pub const ACC_OPEN: u16 = 0x0020;
+pub const ACC_TRANSITIVE: u16 = 0x0020;
+pub const ACC_STATIC_PHASE: u16 = 0x0040;
+pub const ACC_SYNTHETIC: u16 = 0x1000;
+pub const ACC_MANDATED: u16 = 0x8000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleRequires {
+    pub module: Indexed<String>,
+    pub flags: u16,
+    pub version: Option<Indexed<String>>,
+}
+
+impl ModuleRequires {
+    pub fn is_transitive(&self) -> bool {
+        self.flags & ACC_TRANSITIVE != 0
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.flags & ACC_STATIC_PHASE != 0
+    }
+
+    pub fn is_mandated(&self) -> bool {
+        self.flags & ACC_MANDATED != 0
+    }
+}
+
+// An `exports` or `opens` directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleExports {
+    pub package: Indexed<String>,
+    pub flags: u16,
+    pub to: Vec<Indexed<String>>, // empty when unqualified
+}
+
+impl ModuleExports {
+    fn read_table(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<Vec<ModuleExports>, ClassFormatError> {
+        let count = reader.u16()?;
+        let mut table: Vec<ModuleExports> = Vec::new();
+        for _ in 0..count {
+            let package = read_package(reader, constant_pool)?;
+            let flags = reader.u16()?;
+            let to_count = reader.u16()?;
+            let mut to: Vec<Indexed<String>> = Vec::new();
+            for _ in 0..to_count {
+                to.push(read_module(reader, constant_pool)?);
+            }
+            table.push(ModuleExports { package, flags, to });
+        }
+        Ok(table)
+    }
+
+    pub fn is_qualified(&self) -> bool {
+        !self.to.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleProvides {
+    pub service: ClassRef,
+    pub with: Vec<ClassRef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeModulePackages {
+    pub packages: Vec<Indexed<String>>,
+}
+
+impl AttributeModulePackages {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeModulePackages, ClassFormatError> {
+        let package_count = reader.u16()?;
+        let mut packages: Vec<Indexed<String>> = Vec::new();
+        for _ in 0..package_count {
+            packages.push(read_package(reader, constant_pool)?);
+        }
+        Ok(AttributeModulePackages { packages })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeModuleMainClass {
+    pub main_class: ClassRef,
+}
+
+fn read_module(
+    reader: &mut BytecodeReader,
+    constant_pool: &ConstantPool,
+) -> Result<Indexed<String>, ClassFormatError> {
+    let offset = reader.offset;
+    let index = CpIndex::read(reader)?;
+    constant_pool
+        .module_ref(index)
+        .map_err(|e| e.with_offset(offset))
+}
+
+fn read_package(
+    reader: &mut BytecodeReader,
+    constant_pool: &ConstantPool,
+) -> Result<Indexed<String>, ClassFormatError> {
+    let offset = reader.offset;
+    let index = CpIndex::read(reader)?;
+    constant_pool
+        .package_ref(index)
+        .map_err(|e| e.with_offset(offset))
+}
+
+fn read_optional_utf8(
+    reader: &mut BytecodeReader,
+    constant_pool: &ConstantPool,
+) -> Result<Option<Indexed<String>>, ClassFormatError> {
+    let offset = reader.offset;
+    let index = CpIndex::read(reader)?;
+    if index.is_none() {
+        return Ok(None);
+    }
+    constant_pool
+        .utf8_ref(index)
+        .map(Some)
+        .map_err(|e| e.with_offset(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{attribute::Attribute, Class};
+
+    // javac 17 and `jar --create --main-class com.example.app.Main --module-version 1.0` for
+    //   module com.example.app {
+    //       requires transitive java.sql;
+    //       requires static java.desktop;
+    //       exports com.example.spi;
+    //       exports com.example.internal to java.base;
+    //       opens com.example.app;
+    //       uses com.example.spi.Service;
+    //       provides com.example.spi.Service with com.example.app.Main;
+    //   }
+    // jar adds the ModulePackages and ModuleMainClass attributes.
+    #[rustfmt::skip]
+    const MODULE_INFO: [u8; 422] = [
+        0xCA, 0xFE, 0xBA, 0xBE, 0x00, 0x00, 0x00, 0x3D, 0x00, 0x1C, 0x01, 0x00,
+        0x0B, 0x6D, 0x6F, 0x64, 0x75, 0x6C, 0x65, 0x2D, 0x69, 0x6E, 0x66, 0x6F,
+        0x07, 0x00, 0x01, 0x01, 0x00, 0x10, 0x6D, 0x6F, 0x64, 0x75, 0x6C, 0x65,
+        0x2D, 0x69, 0x6E, 0x66, 0x6F, 0x2E, 0x6A, 0x61, 0x76, 0x61, 0x01, 0x00,
+        0x0F, 0x63, 0x6F, 0x6D, 0x2E, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65,
+        0x2E, 0x61, 0x70, 0x70, 0x13, 0x00, 0x04, 0x01, 0x00, 0x03, 0x31, 0x2E,
+        0x30, 0x01, 0x00, 0x14, 0x63, 0x6F, 0x6D, 0x2F, 0x65, 0x78, 0x61, 0x6D,
+        0x70, 0x6C, 0x65, 0x2F, 0x61, 0x70, 0x70, 0x2F, 0x4D, 0x61, 0x69, 0x6E,
+        0x07, 0x00, 0x07, 0x01, 0x00, 0x0F, 0x63, 0x6F, 0x6D, 0x2F, 0x65, 0x78,
+        0x61, 0x6D, 0x70, 0x6C, 0x65, 0x2F, 0x61, 0x70, 0x70, 0x14, 0x00, 0x09,
+        0x01, 0x00, 0x14, 0x63, 0x6F, 0x6D, 0x2F, 0x65, 0x78, 0x61, 0x6D, 0x70,
+        0x6C, 0x65, 0x2F, 0x69, 0x6E, 0x74, 0x65, 0x72, 0x6E, 0x61, 0x6C, 0x14,
+        0x00, 0x0B, 0x01, 0x00, 0x0F, 0x63, 0x6F, 0x6D, 0x2F, 0x65, 0x78, 0x61,
+        0x6D, 0x70, 0x6C, 0x65, 0x2F, 0x73, 0x70, 0x69, 0x14, 0x00, 0x0D, 0x01,
+        0x00, 0x09, 0x6A, 0x61, 0x76, 0x61, 0x2E, 0x62, 0x61, 0x73, 0x65, 0x13,
+        0x00, 0x0F, 0x01, 0x00, 0x07, 0x31, 0x37, 0x2E, 0x30, 0x2E, 0x31, 0x35,
+        0x01, 0x00, 0x08, 0x6A, 0x61, 0x76, 0x61, 0x2E, 0x73, 0x71, 0x6C, 0x13,
+        0x00, 0x12, 0x01, 0x00, 0x0C, 0x6A, 0x61, 0x76, 0x61, 0x2E, 0x64, 0x65,
+        0x73, 0x6B, 0x74, 0x6F, 0x70, 0x13, 0x00, 0x14, 0x01, 0x00, 0x17, 0x63,
+        0x6F, 0x6D, 0x2F, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x2F, 0x73,
+        0x70, 0x69, 0x2F, 0x53, 0x65, 0x72, 0x76, 0x69, 0x63, 0x65, 0x07, 0x00,
+        0x16, 0x01, 0x00, 0x0A, 0x53, 0x6F, 0x75, 0x72, 0x63, 0x65, 0x46, 0x69,
+        0x6C, 0x65, 0x01, 0x00, 0x06, 0x4D, 0x6F, 0x64, 0x75, 0x6C, 0x65, 0x01,
+        0x00, 0x0E, 0x4D, 0x6F, 0x64, 0x75, 0x6C, 0x65, 0x50, 0x61, 0x63, 0x6B,
+        0x61, 0x67, 0x65, 0x73, 0x01, 0x00, 0x0F, 0x4D, 0x6F, 0x64, 0x75, 0x6C,
+        0x65, 0x4D, 0x61, 0x69, 0x6E, 0x43, 0x6C, 0x61, 0x73, 0x73, 0x80, 0x00,
+        0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04,
+        0x00, 0x18, 0x00, 0x00, 0x00, 0x02, 0x00, 0x03, 0x00, 0x19, 0x00, 0x00,
+        0x00, 0x3E, 0x00, 0x05, 0x00, 0x00, 0x00, 0x06, 0x00, 0x03, 0x00, 0x10,
+        0x80, 0x00, 0x00, 0x11, 0x00, 0x13, 0x00, 0x20, 0x00, 0x11, 0x00, 0x15,
+        0x00, 0x40, 0x00, 0x11, 0x00, 0x02, 0x00, 0x0E, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x0C, 0x00, 0x00, 0x00, 0x01, 0x00, 0x10, 0x00, 0x01, 0x00, 0x0A,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x17, 0x00, 0x01, 0x00, 0x17,
+        0x00, 0x01, 0x00, 0x08, 0x00, 0x1A, 0x00, 0x00, 0x00, 0x08, 0x00, 0x03,
+        0x00, 0x0A, 0x00, 0x0C, 0x00, 0x0E, 0x00, 0x1B, 0x00, 0x00, 0x00, 0x02,
+        0x00, 0x08,
+    ];
+
+    #[test]
+    fn module_info_attributes() {
+        let class = Class::new(&MODULE_INFO[..]).unwrap();
+        assert!(class.is_module());
+        let module = match &class.attributes[1] {
+            Attribute::Module(a) => a,
+            attribute => panic!("unexpected {:?}", attribute),
+        };
+        assert_eq!(module.module_name, "com.example.app");
+        assert_eq!(module.module_name.index, Some(CpIndex(5)));
+        assert_eq!(module.module_flags, 0);
+        assert_eq!(module.module_version.as_ref().unwrap(), "1.0");
+
+        let requires: Vec<(&str, u16, Option<&str>)> = module
+            .requires
+            .iter()
+            .map(|r| {
+                (
+                    r.module.as_str(),
+                    r.flags,
+                    r.version.as_deref().map(String::as_str),
+                )
+            })
+            .collect();
+        assert_eq!(
+            requires,
+            vec![
+                ("java.base", ACC_MANDATED, Some("17.0.15")),
+                ("java.sql", ACC_TRANSITIVE, Some("17.0.15")),
+                ("java.desktop", ACC_STATIC_PHASE, Some("17.0.15")),
+            ]
+        );
+        assert!(module.requires[0].is_mandated());
+        assert!(module.requires[1].is_transitive() && !module.requires[1].is_static());
+        assert!(module.requires[2].is_static() && !module.requires[2].is_transitive());
+
+        assert_eq!(
+            module.exports,
+            vec![
+                ModuleExports {
+                    package: "com/example/spi".into(),
+                    flags: 0,
+                    to: vec![],
+                },
+                ModuleExports {
+                    package: "com/example/internal".into(),
+                    flags: 0,
+                    to: vec!["java.base".into()],
+                },
+            ]
+        );
+        assert!(!module.exports[0].is_qualified() && module.exports[1].is_qualified());
+        assert_eq!(module.exports[1].to[0].index, Some(CpIndex(16)));
+        assert_eq!(
+            module.opens,
+            vec![ModuleExports {
+                package: "com/example/app".into(),
+                flags: 0,
+                to: vec![],
+            }]
+        );
+        assert_eq!(module.uses, vec![ClassRef::new("com/example/spi/Service")]);
+        assert_eq!(
+            module.provides,
+            vec![ModuleProvides {
+                service: ClassRef::new("com/example/spi/Service"),
+                with: vec![ClassRef::new("com/example/app/Main")],
+            }]
+        );
+
+        match &class.attributes[2] {
+            Attribute::ModulePackages(a) => assert_eq!(
+                a.packages,
+                vec!["com/example/app", "com/example/internal", "com/example/spi"]
+            ),
+            attribute => panic!("unexpected {:?}", attribute),
+        }
+        match &class.attributes[3] {
+            Attribute::ModuleMainClass(a) => {
+                assert_eq!(a.main_class, "com/example/app/Main");
+                assert_eq!(a.main_class.index, Some(CpIndex(8)));
+            }
+            attribute => panic!("unexpected {:?}", attribute),
+        }
+
+        let descriptor = class.module_descriptor().unwrap().unwrap();
+        assert_eq!(descriptor.name, "com.example.app");
+        assert_eq!(descriptor.version.as_deref(), Some("1.0"));
+        assert!(!descriptor.is_open());
+        assert!(descriptor.requires("java.sql").is_some());
+        assert!(descriptor.requires("java.xml").is_none());
+        assert!(descriptor.exports_to("com/example/spi", "any.module"));
+        assert!(descriptor.exports_to("com/example/internal", "java.base"));
+        assert!(!descriptor.exports_to("com/example/internal", "java.sql"));
+        assert!(!descriptor.exports_to("com/example/app", "java.base"));
+        assert!(descriptor.opens_to("com/example/app", "java.base"));
+        assert!(!descriptor.opens_to("com/example/spi", "java.base"));
+        assert_eq!(descriptor.packages.len(), 3);
+        assert_eq!(descriptor.main_class.unwrap(), "com/example/app/Main");
+
+        assert_eq!(class.to_bytes().unwrap(), MODULE_INFO);
+    }
+
+    #[test]
+    fn module_reference_to_a_package_constant() {
+        // The first requires points at the Package com/example/app instead of the Module
+        // java.base.
+        let mut bytes = MODULE_INFO;
+        bytes[347] = 10;
+        let e = Class::new(&bytes[..]).unwrap_err();
+        assert_eq!(e.location.last().unwrap(), "attribute Module");
+        assert_eq!(e.offset, Some(346));
+        assert!(e.reason.contains("is not a Module constant"), "{}", e);
+    }
+}
@@ -0,0 +1,432 @@
+use crate::jvm::class::{
+    constant_pool::*,
+    descriptor::{FieldType, MethodDescriptor},
+    error::ClassFormatError,
+    method::Method,
+};
+use crate::utils::bytecode_reader::BytecodeReader;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeStackMapTable {
+    pub entries: Vec<StackMapFrame>,
+}
+
+impl AttributeStackMapTable {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<AttributeStackMapTable, ClassFormatError> {
+        let number_of_entries = reader.u16()?;
+        let mut entries: Vec<StackMapFrame> = Vec::new();
+        for i in 0..number_of_entries {
+            entries.push(
+                StackMapFrame::new(reader, constant_pool)
+                    .map_err(|e| e.within(format!("frame #{}", i)))?,
+            );
+        }
+        Ok(AttributeStackMapTable { entries })
+    }
+
+    // The bytecode offset each frame applies to. The first frame is at offset_delta, every
+    // following one at previous + offset_delta + 1 (JVMS 4.7.4).
+    pub fn offsets(&self) -> Vec<(u32, &StackMapFrame)> {
+        let mut offsets = Vec::new();
+        let mut previous: Option<u32> = None;
+        for frame in &self.entries {
+            let offset = match previous {
+                None => frame.offset_delta() as u32,
+                Some(p) => p + frame.offset_delta() as u32 + 1,
+            };
+            offsets.push((offset, frame));
+            previous = Some(offset);
+        }
+        offsets
+    }
+
+    // Expands the frames of the code of `method`, a method of `this_class`, into full
+    // (offset, locals, stack) states, starting from the implicit initial frame of the method.
+    // Like the frames themselves, the states hold one entry per value: a long or double is a
+    // single entry, not followed by a Top for its second slot.
+    pub fn expand(
+        &self,
+        this_class: &str,
+        method: &Method,
+    ) -> Result<Vec<ExpandedFrame>, ClassFormatError> {
+        let mut frames: Vec<ExpandedFrame> = Vec::new();
+        let mut locals = initial_locals(this_class, method)?;
+        for (i, (offset, frame)) in self.offsets().into_iter().enumerate() {
+            let stack = match frame {
+                StackMapFrame::Same { .. } | StackMapFrame::SameExtended { .. } => Vec::new(),
+                StackMapFrame::SameLocals1StackItem { stack, .. }
+                | StackMapFrame::SameLocals1StackItemExtended { stack, .. } => {
+                    vec![stack.clone()]
+                }
+                StackMapFrame::Chop { k, .. } => {
+                    if *k as usize > locals.len() {
+                        return Err(ClassFormatError::new(format!(
+                            "frame #{} chops {} locals but only {} are defined",
+                            i,
+                            k,
+                            locals.len()
+                        )));
+                    }
+                    locals.truncate(locals.len() - *k as usize);
+                    Vec::new()
+                }
+                StackMapFrame::Append {
+                    locals: appended, ..
+                } => {
+                    locals.extend(appended.iter().cloned());
+                    Vec::new()
+                }
+                StackMapFrame::Full {
+                    locals: full_locals,
+                    stack,
+                    ..
+                } => {
+                    locals = full_locals.clone();
+                    stack.clone()
+                }
+            };
+            frames.push(ExpandedFrame {
+                offset,
+                locals: locals.clone(),
+                stack,
+            });
+        }
+        Ok(frames)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackMapFrame {
+    Same {
+        offset_delta: u8, // frame_type: 0-63
+    },
+    SameLocals1StackItem {
+        offset_delta: u8, // frame_type: 64-127
+        stack: VerificationTypeInfo,
+    },
+    SameLocals1StackItemExtended {
+        offset_delta: u16, // frame_type: 247
+        stack: VerificationTypeInfo,
+    },
+    Chop {
+        k: u8, // frame_type: 248-250, k = 251 - frame_type
+        offset_delta: u16,
+    },
+    SameExtended {
+        offset_delta: u16, // frame_type: 251
+    },
+    Append {
+        offset_delta: u16, // frame_type: 252-254, locals.len() = frame_type - 251
+        locals: Vec<VerificationTypeInfo>,
+    },
+    Full {
+        offset_delta: u16, // frame_type: 255
+        locals: Vec<VerificationTypeInfo>,
+        stack: Vec<VerificationTypeInfo>,
+    },
+}
+
+impl StackMapFrame {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<StackMapFrame, ClassFormatError> {
+        let frame_type_offset = reader.offset;
+        let frame_type = reader.u8()?;
+        let frame = match frame_type {
+            0..=63 => StackMapFrame::Same {
+                offset_delta: frame_type,
+            },
+            64..=127 => StackMapFrame::SameLocals1StackItem {
+                offset_delta: frame_type - 64,
+                stack: VerificationTypeInfo::new(reader, constant_pool)?,
+            },
+            247 => StackMapFrame::SameLocals1StackItemExtended {
+                offset_delta: reader.u16()?,
+                stack: VerificationTypeInfo::new(reader, constant_pool)?,
+            },
+            248..=250 => StackMapFrame::Chop {
+                k: 251 - frame_type,
+                offset_delta: reader.u16()?,
+            },
+            251 => StackMapFrame::SameExtended {
+                offset_delta: reader.u16()?,
+            },
+            252..=254 => {
+                let offset_delta = reader.u16()?;
+                let mut locals: Vec<VerificationTypeInfo> = Vec::new();
+                for _ in 0..frame_type - 251 {
+                    locals.push(VerificationTypeInfo::new(reader, constant_pool)?);
+                }
+                StackMapFrame::Append {
+                    offset_delta,
+                    locals,
+                }
+            }
+            255 => {
+                let offset_delta = reader.u16()?;
+                let number_of_locals = reader.u16()?;
+                let mut locals: Vec<VerificationTypeInfo> = Vec::new();
+                for _ in 0..number_of_locals {
+                    locals.push(VerificationTypeInfo::new(reader, constant_pool)?);
+                }
+                let number_of_stack_items = reader.u16()?;
+                let mut stack: Vec<VerificationTypeInfo> = Vec::new();
+                for _ in 0..number_of_stack_items {
+                    stack.push(VerificationTypeInfo::new(reader, constant_pool)?);
+                }
+                StackMapFrame::Full {
+                    offset_delta,
+                    locals,
+                    stack,
+                }
+            }
+            _ => {
+                return Err(ClassFormatError::at(
+                    frame_type_offset,
+                    format!("reserved stack map frame type: {}", frame_type),
+                ))
+            }
+        };
+        Ok(frame)
+    }
+
+    pub fn frame_type(&self) -> u8 {
+        match self {
+            StackMapFrame::Same { offset_delta } => *offset_delta,
+            StackMapFrame::SameLocals1StackItem { offset_delta, .. } => 64 + *offset_delta,
+            StackMapFrame::SameLocals1StackItemExtended { .. } => 247,
+            StackMapFrame::Chop { k, .. } => 251 - *k,
+            StackMapFrame::SameExtended { .. } => 251,
+            StackMapFrame::Append { locals, .. } => 251 + locals.len() as u8,
+            StackMapFrame::Full { .. } => 255,
+        }
+    }
+
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            StackMapFrame::Same { offset_delta }
+            | StackMapFrame::SameLocals1StackItem { offset_delta, .. } => *offset_delta as u16,
+            StackMapFrame::SameLocals1StackItemExtended { offset_delta, .. }
+            | StackMapFrame::Chop { offset_delta, .. }
+            | StackMapFrame::SameExtended { offset_delta }
+            | StackMapFrame::Append { offset_delta, .. }
+            | StackMapFrame::Full { offset_delta, .. } => *offset_delta,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationTypeInfo {
+    Top,                // tag: 0
+    Integer,            // tag: 1
+    Float,              // tag: 2
+    Double,             // tag: 3
+    Long,               // tag: 4
+    Null,               // tag: 5
+    UninitializedThis,  // tag: 6
+    Object(ClassRef),   // tag: 7
+    Uninitialized(u16), // tag: 8, offset of the `new` instruction
+}
+
+impl VerificationTypeInfo {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<VerificationTypeInfo, ClassFormatError> {
+        let tag_offset = reader.offset;
+        let tag = reader.u8()?;
+        let info = match tag {
+            0 => VerificationTypeInfo::Top,
+            1 => VerificationTypeInfo::Integer,
+            2 => VerificationTypeInfo::Float,
+            3 => VerificationTypeInfo::Double,
+            4 => VerificationTypeInfo::Long,
+            5 => VerificationTypeInfo::Null,
+            6 => VerificationTypeInfo::UninitializedThis,
+            7 => VerificationTypeInfo::Object(constant_pool.read_class_ref(reader)?),
+            8 => VerificationTypeInfo::Uninitialized(reader.u16()?),
+            _ => {
+                return Err(ClassFormatError::at(
+                    tag_offset,
+                    format!("unknown verification type tag: {}", tag),
+                ))
+            }
+        };
+        Ok(info)
+    }
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            VerificationTypeInfo::Top => 0,
+            VerificationTypeInfo::Integer => 1,
+            VerificationTypeInfo::Float => 2,
+            VerificationTypeInfo::Double => 3,
+            VerificationTypeInfo::Long => 4,
+            VerificationTypeInfo::Null => 5,
+            VerificationTypeInfo::UninitializedThis => 6,
+            VerificationTypeInfo::Object(_) => 7,
+            VerificationTypeInfo::Uninitialized(_) => 8,
+        }
+    }
+
+    // The type a value of the field type has in locals and on the operand stack, where
+    // boolean, byte, char and short values are ints.
+    pub fn of(field_type: &FieldType) -> VerificationTypeInfo {
+        match field_type {
+            FieldType::Byte
+            | FieldType::Char
+            | FieldType::Int
+            | FieldType::Short
+            | FieldType::Boolean => VerificationTypeInfo::Integer,
+            FieldType::Float => VerificationTypeInfo::Float,
+            FieldType::Long => VerificationTypeInfo::Long,
+            FieldType::Double => VerificationTypeInfo::Double,
+            FieldType::Object(class_name) => {
+                VerificationTypeInfo::Object(ClassRef::new(class_name))
+            }
+            FieldType::Array(_) => {
+                VerificationTypeInfo::Object(ClassRef::new(field_type.to_string()))
+            }
+        }
+    }
+
+    // Long and Double take two local variable slots but a single entry in a frame.
+    pub fn is_category2(&self) -> bool {
+        matches!(
+            self,
+            VerificationTypeInfo::Long | VerificationTypeInfo::Double
+        )
+    }
+}
+
+// The locals of the implicit initial frame of a method (JVMS 4.10.1.6): `this`, which is
+// uninitialized in a constructor other than Object's, then the parameters, one entry each.
+pub fn initial_locals(
+    this_class: &str,
+    method: &Method,
+) -> Result<Vec<VerificationTypeInfo>, ClassFormatError> {
+    let descriptor = MethodDescriptor::parse(&method.descriptor)?;
+    let mut locals = Vec::new();
+    if !method.access_flags.is_static() {
+        if method.name == "<init>" && this_class != "java/lang/Object" {
+            locals.push(VerificationTypeInfo::UninitializedThis);
+        } else {
+            locals.push(VerificationTypeInfo::Object(ClassRef::new(this_class)));
+        }
+    }
+    locals.extend(descriptor.parameters.iter().map(VerificationTypeInfo::of));
+    Ok(locals)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpandedFrame {
+    pub offset: u32,
+    pub locals: Vec<VerificationTypeInfo>,
+    pub stack: Vec<VerificationTypeInfo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::access_flags::MethodAccessFlags;
+
+    fn method(access_flags: MethodAccessFlags, name: &str, descriptor: &str) -> Method {
+        Method {
+            access_flags,
+            name: name.into(),
+            descriptor: descriptor.into(),
+            attributes: Vec::new(),
+            attribute_name_indices: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn initial_locals_of_instance_static_and_constructor() {
+        let instance = method(MethodAccessFlags::PUBLIC, "f", "(J[ILjava/lang/String;Z)V");
+        assert_eq!(
+            initial_locals("A", &instance).unwrap(),
+            vec![
+                VerificationTypeInfo::Object(ClassRef::new("A")),
+                VerificationTypeInfo::Long,
+                VerificationTypeInfo::Object(ClassRef::new("[I")),
+                VerificationTypeInfo::Object(ClassRef::new("java/lang/String")),
+                VerificationTypeInfo::Integer,
+            ]
+        );
+        let static_method = method(MethodAccessFlags::STATIC, "g", "(DF)V");
+        assert_eq!(
+            initial_locals("A", &static_method).unwrap(),
+            vec![VerificationTypeInfo::Double, VerificationTypeInfo::Float]
+        );
+        let constructor = method(MethodAccessFlags::PUBLIC, "<init>", "()V");
+        assert_eq!(
+            initial_locals("A", &constructor).unwrap(),
+            vec![VerificationTypeInfo::UninitializedThis]
+        );
+        assert_eq!(
+            initial_locals("java/lang/Object", &constructor).unwrap(),
+            vec![VerificationTypeInfo::Object(ClassRef::new(
+                "java/lang/Object"
+            ))]
+        );
+    }
+
+    #[test]
+    fn expand_counts_long_as_one_entry() {
+        // static void f(long): append int, chop 2 (the int and the long), full, same with
+        // one stack item.
+        let table = AttributeStackMapTable {
+            entries: vec![
+                StackMapFrame::Append {
+                    offset_delta: 3,
+                    locals: vec![VerificationTypeInfo::Integer],
+                },
+                StackMapFrame::Chop {
+                    k: 2,
+                    offset_delta: 4,
+                },
+                StackMapFrame::Full {
+                    offset_delta: 0,
+                    locals: vec![VerificationTypeInfo::Double],
+                    stack: vec![VerificationTypeInfo::Null],
+                },
+                StackMapFrame::SameLocals1StackItem {
+                    offset_delta: 70 - 64,
+                    stack: VerificationTypeInfo::Long,
+                },
+            ],
+        };
+        let frames = table
+            .expand("A", &method(MethodAccessFlags::STATIC, "f", "(J)V"))
+            .unwrap();
+        let offsets: Vec<u32> = frames.iter().map(|frame| frame.offset).collect();
+        assert_eq!(offsets, vec![3, 8, 9, 16]);
+        assert_eq!(
+            frames[0].locals,
+            vec![VerificationTypeInfo::Long, VerificationTypeInfo::Integer]
+        );
+        assert!(frames[1].locals.is_empty());
+        assert_eq!(frames[2].locals, vec![VerificationTypeInfo::Double]);
+        assert_eq!(frames[2].stack, vec![VerificationTypeInfo::Null]);
+        assert_eq!(frames[3].locals, vec![VerificationTypeInfo::Double]);
+        assert_eq!(frames[3].stack, vec![VerificationTypeInfo::Long]);
+    }
+
+    #[test]
+    fn expand_rejects_chopping_more_locals_than_defined() {
+        let table = AttributeStackMapTable {
+            entries: vec![StackMapFrame::Chop {
+                k: 2,
+                offset_delta: 0,
+            }],
+        };
+        let error = table
+            .expand("A", &method(MethodAccessFlags::STATIC, "f", "(J)V"))
+            .unwrap_err();
+        assert!(error.reason.contains("chops 2 locals"), "{}", error);
+    }
+}
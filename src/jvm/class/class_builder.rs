@@ -0,0 +1,346 @@
+use crate::jvm::class::{
+    access_flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+    attribute::Attribute,
+    code_builder::CodeBuilder,
+    constant_pool::{ClassRef, CpIndex},
+    constant_pool_builder::ConstantPoolBuilder,
+    descriptor::*,
+    error::ClassFormatError,
+    field::Field,
+    method::Method,
+    stack_map::{NoClasses, SuperclassResolver},
+    version::ClassVersion,
+    Class,
+};
+
+// Builds a class in memory: constants are shared through one ConstantPoolBuilder, and method
+// bodies are emitted with a CodeBuilder.
+//
+//     let mut builder = ClassBuilder::new(ClassAccessFlags::PUBLIC, "Add", Some("java/lang/Object"))?;
+//     builder.method_with_code(MethodAccessFlags::STATIC, "add", "(II)I", |code| {
+//         code.emit(Instruction::Iload0)?;
+//         code.emit(Instruction::Iload1)?;
+//         code.emit(Instruction::Iadd)?;
+//         code.emit(Instruction::Ireturn)
+//     })?;
+//     let bytes = builder.build()?.to_bytes()?;
+#[derive(Debug)]
+pub struct ClassBuilder {
+    version: ClassVersion,
+    constant_pool: ConstantPoolBuilder,
+    access_flags: ClassAccessFlags,
+    this_class: String,
+    super_class: Option<String>,
+    interfaces: Vec<CpIndex>,
+    fields: Vec<Field>,
+    methods: Vec<Method>,
+    attributes: Vec<Attribute>,
+}
+
+impl ClassBuilder {
+    // Builds a version 49 class by default: the last version verified by type inference, so
+    // code with branches needs no StackMapTable. Later versions get one computed by build.
+    pub fn new(
+        access_flags: ClassAccessFlags,
+        this_class: &str,
+        super_class: Option<&str>,
+    ) -> Result<ClassBuilder, ClassFormatError> {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        check_class_name(this_class)?;
+        constant_pool.class(this_class)?;
+        if let Some(super_class) = super_class {
+            check_class_name(super_class)?;
+            constant_pool.class(super_class)?;
+        }
+        Ok(ClassBuilder {
+            version: ClassVersion::new(49, 0),
+            constant_pool,
+            access_flags,
+            this_class: String::from(this_class),
+            super_class: super_class.map(String::from),
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+        })
+    }
+
+    pub fn version(&mut self, version: ClassVersion) -> &mut ClassBuilder {
+        self.version = version;
+        self
+    }
+
+    // For constants that attributes refer to by index, e.g. a ConstantValue.
+    pub fn constant_pool(&mut self) -> &mut ConstantPoolBuilder {
+        &mut self.constant_pool
+    }
+
+    pub fn interface(&mut self, interface: &str) -> Result<(), ClassFormatError> {
+        check_class_name(interface)?;
+        let index = self.constant_pool.class(interface)?;
+        self.interfaces.push(index);
+        Ok(())
+    }
+
+    // Adds a field; attributes can be added to the returned field.
+    pub fn field(
+        &mut self,
+        access_flags: FieldAccessFlags,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<&mut Field, ClassFormatError> {
+        let location = format!("field {}{}", name, descriptor);
+        if !is_unqualified_name(name) {
+            return Err(ClassFormatError::new("invalid field name").within(location));
+        }
+        FieldType::parse(descriptor).map_err(|e| e.within(location.clone()))?;
+        if self
+            .fields
+            .iter()
+            .any(|f| f.name == name && f.descriptor == descriptor)
+        {
+            return Err(ClassFormatError::new("duplicate field").within(location));
+        }
+        self.constant_pool.utf8(name)?;
+        self.constant_pool.utf8(descriptor)?;
+        self.fields.push(Field {
+            access_flags,
+            name: name.into(),
+            descriptor: descriptor.into(),
+            attributes: Vec::new(),
+            attribute_name_indices: Vec::new(),
+        });
+        Ok(self.fields.last_mut().unwrap())
+    }
+
+    // Adds a method without code, as abstract and native methods are; attributes can be added
+    // to the returned method.
+    pub fn method(
+        &mut self,
+        access_flags: MethodAccessFlags,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<&mut Method, ClassFormatError> {
+        let location = format!("method {}{}", name, descriptor);
+        if !is_method_name(name) {
+            return Err(ClassFormatError::new("invalid method name").within(location));
+        }
+        MethodDescriptor::parse(descriptor).map_err(|e| e.within(location.clone()))?;
+        if self
+            .methods
+            .iter()
+            .any(|m| m.name == name && m.descriptor == descriptor)
+        {
+            return Err(ClassFormatError::new("duplicate method").within(location));
+        }
+        self.constant_pool.utf8(name)?;
+        self.constant_pool.utf8(descriptor)?;
+        self.methods.push(Method {
+            access_flags,
+            name: name.into(),
+            descriptor: descriptor.into(),
+            attributes: Vec::new(),
+            attribute_name_indices: Vec::new(),
+        });
+        Ok(self.methods.last_mut().unwrap())
+    }
+
+    // Adds a method whose Code attribute `emit` fills in.
+    pub fn method_with_code<F>(
+        &mut self,
+        access_flags: MethodAccessFlags,
+        name: &str,
+        descriptor: &str,
+        emit: F,
+    ) -> Result<&mut Method, ClassFormatError>
+    where
+        F: FnOnce(&mut CodeBuilder) -> Result<(), ClassFormatError>,
+    {
+        let location = format!("method {}{}", name, descriptor);
+        // Checked by `method` too, but the descriptor is needed first for the locals.
+        MethodDescriptor::parse(descriptor).map_err(|e| e.within(location.clone()))?;
+        let mut code = CodeBuilder::for_method(&mut self.constant_pool, access_flags, descriptor)?;
+        emit(&mut code).map_err(|e| e.within(location.clone()))?;
+        let code = code.finish().map_err(|e| e.within(location))?;
+        self.constant_pool.utf8("Code")?;
+        let method = self.method(access_flags, name, descriptor)?;
+        method.attributes.push(Attribute::Code(code));
+        Ok(method)
+    }
+
+    pub fn attribute(&mut self, attribute: Attribute) {
+        self.attributes.push(attribute);
+    }
+
+    // The class as it reads back from its class file, so that the constant pool also holds
+    // the attribute names; it is format checked (JVMS 4.8). From version 50 on the methods get
+    // a StackMapTable computed without knowing any class, so code where two different classes
+    // meet fails to build and needs build_with.
+    pub fn build(self) -> Result<Class, ClassFormatError> {
+        self.build_with(&NoClasses)
+    }
+
+    // As build, with `resolver` answering the common superclasses for the stack map frames,
+    // e.g. a ClassLoader holding the classes the code uses.
+    pub fn build_with(self, resolver: &dyn SuperclassResolver) -> Result<Class, ClassFormatError> {
+        let mut class = Class {
+            minor_version: self.version.minor,
+            major_version: self.version.major,
+            constant_pool: self.constant_pool.build(),
+            access_flags: self.access_flags,
+            this_class: ClassRef::new(self.this_class),
+            super_class: self.super_class.map(ClassRef::new),
+            interfaces: self.interfaces,
+            fields: self.fields,
+            methods: self.methods,
+            attributes: self.attributes,
+            attribute_name_indices: Vec::new(),
+        };
+        if self.version.requires_stack_map_table() {
+            class.compute_stack_maps(resolver)?;
+        }
+        let class = Class::new(&class.to_bytes()?[..])?;
+        class.check_format()?;
+        Ok(class)
+    }
+}
+
+fn check_class_name(class_name: &str) -> Result<(), ClassFormatError> {
+    if !is_class_constant_name(class_name) {
+        return Err(ClassFormatError::new(format!(
+            "invalid class name \"{}\"",
+            class_name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{attribute::stack_map_table::StackMapFrame, instruction::Instruction};
+
+    // static int max(int a, int b) { return a >= b ? a : b; }
+    fn max_class(version: ClassVersion) -> Result<Class, ClassFormatError> {
+        let mut builder =
+            ClassBuilder::new(ClassAccessFlags::PUBLIC, "p/Max", Some("java/lang/Object"))?;
+        builder.version(version);
+        builder.method_with_code(
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+            "max",
+            "(II)I",
+            |code| {
+                let second = code.new_label();
+                code.emit(Instruction::Iload0)?;
+                code.emit(Instruction::Iload1)?;
+                code.jump(Instruction::IfIcmplt, second)?;
+                code.emit(Instruction::Iload0)?;
+                code.emit(Instruction::Ireturn)?;
+                code.bind(second)?;
+                code.emit(Instruction::Iload1)?;
+                code.emit(Instruction::Ireturn)
+            },
+        )?;
+        builder.build()
+    }
+
+    #[test]
+    fn built_classes_read_back_with_computed_code_limits() {
+        let class = max_class(ClassVersion::new(49, 0)).unwrap();
+        assert_eq!(class.this_class, "p/Max");
+        assert_eq!(class.super_class.as_ref().unwrap(), "java/lang/Object");
+        let code = class.methods[0].code().unwrap();
+        assert_eq!((code.max_stack, code.max_locals), (2, 2));
+        assert!(code.attributes.is_empty());
+        // The attribute names were added to the pool while building.
+        assert_eq!(
+            class
+                .constant_pool
+                .utf8(class.methods[0].attribute_name_indices[0])
+                .as_deref(),
+            Ok("Code")
+        );
+        assert_eq!(
+            Class::new(&class.to_bytes().unwrap()[..])
+                .unwrap()
+                .to_bytes(),
+            class.to_bytes()
+        );
+    }
+
+    #[test]
+    fn type_checked_versions_get_a_stack_map_table() {
+        let class = max_class(ClassVersion::new(52, 0)).unwrap();
+        let code = class.methods[0].code().unwrap();
+        match &code.attributes[..] {
+            [Attribute::StackMapTable(table)] => assert!(matches!(
+                &table.entries[..],
+                [StackMapFrame::Same { offset_delta: 7 }]
+            )),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_and_duplicate_members_are_rejected() {
+        let error = |result: Result<(), ClassFormatError>| result.unwrap_err().to_string();
+        assert_eq!(
+            error(ClassBuilder::new(ClassAccessFlags::PUBLIC, "p.A", None).map(|_| ())),
+            "invalid class name \"p.A\""
+        );
+        let mut builder = ClassBuilder::new(ClassAccessFlags::PUBLIC, "A", None).unwrap();
+        assert_eq!(
+            error(builder.interface("java.io.Serializable").map(|_| ())),
+            "invalid class name \"java.io.Serializable\""
+        );
+        builder.field(FieldAccessFlags::PRIVATE, "f", "I").unwrap();
+        assert_eq!(
+            error(
+                builder
+                    .field(FieldAccessFlags::PUBLIC, "f", "I")
+                    .map(|_| ())
+            ),
+            "field fI: duplicate field"
+        );
+        builder.field(FieldAccessFlags::PRIVATE, "f", "J").unwrap();
+        assert_eq!(
+            error(
+                builder
+                    .field(FieldAccessFlags::PRIVATE, "a.b", "I")
+                    .map(|_| ())
+            ),
+            "field a.bI: invalid field name"
+        );
+        assert!(builder.field(FieldAccessFlags::PRIVATE, "g", "V").is_err());
+        assert_eq!(
+            error(
+                builder
+                    .method(MethodAccessFlags::PUBLIC, "<f>", "()V")
+                    .map(|_| ())
+            ),
+            "method <f>()V: invalid method name"
+        );
+        builder
+            .method(MethodAccessFlags::ABSTRACT, "m", "()V")
+            .unwrap();
+        assert_eq!(
+            error(
+                builder
+                    .method(MethodAccessFlags::PUBLIC, "m", "()V")
+                    .map(|_| ())
+            ),
+            "method m()V: duplicate method"
+        );
+        assert_eq!(
+            error(
+                builder
+                    .method_with_code(MethodAccessFlags::STATIC, "n", "()V", |code| {
+                        let label = code.new_label();
+                        code.jump(Instruction::Goto, label)
+                    })
+                    .map(|_| ())
+            ),
+            "method n()V: label 0 is never bound"
+        );
+    }
+}
@@ -0,0 +1,915 @@
+use std::collections::HashMap;
+
+use crate::jvm::class::{
+    access_flags::MethodAccessFlags,
+    attribute::{Attribute, AttributeCode, ExceptionInfo},
+    constant_pool::*,
+    constant_pool_builder::ConstantPoolBuilder,
+    descriptor::MethodDescriptor,
+    error::ClassFormatError,
+    instruction::Instruction,
+};
+use crate::utils::bytecode_writer::BytecodeWriter;
+
+// A position in the code, bound with CodeBuilder::bind. Branches and exception handlers may
+// refer to a label before it is bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+// A branch offset to fill in once the label is bound.
+#[derive(Debug)]
+struct Fixup {
+    pc: usize,      // of the branch, offsets are relative to it
+    operand: usize, // where the offset goes
+    label: Label,
+    wide: bool, // a 4 byte offset of goto_w, jsr_w or a switch
+}
+
+#[derive(Debug)]
+struct Handler {
+    start: Label,
+    end: Label,
+    handler: Label,
+    catch_type: CpIndex,
+}
+
+// Emits the code of one method into a Code attribute. Constants are added to the pool of the
+// class being built, branch offsets are filled in from labels, and max_stack and max_locals
+// are computed by finish.
+#[derive(Debug)]
+pub struct CodeBuilder<'a> {
+    constant_pool: &'a mut ConstantPoolBuilder,
+    code: BytecodeWriter,
+    labels: Vec<Option<usize>>,
+    fixups: Vec<Fixup>,
+    handlers: Vec<Handler>,
+    max_locals: u16,
+    attributes: Vec<Attribute>,
+}
+
+impl<'a> CodeBuilder<'a> {
+    // `max_locals` starts at the slots taken by the parameters; see for_method.
+    pub fn new(constant_pool: &'a mut ConstantPoolBuilder, max_locals: u16) -> CodeBuilder<'a> {
+        CodeBuilder {
+            constant_pool,
+            code: BytecodeWriter::new(),
+            labels: Vec::new(),
+            fixups: Vec::new(),
+            handlers: Vec::new(),
+            max_locals,
+            attributes: Vec::new(),
+        }
+    }
+
+    // The parameters of a method, after `this` unless it is static, are its first locals.
+    pub fn for_method(
+        constant_pool: &'a mut ConstantPoolBuilder,
+        access_flags: MethodAccessFlags,
+        descriptor: &str,
+    ) -> Result<CodeBuilder<'a>, ClassFormatError> {
+        let descriptor = MethodDescriptor::parse(descriptor)?;
+        let this_slots = if access_flags.is_static() { 0 } else { 1 };
+        Ok(CodeBuilder::new(
+            constant_pool,
+            descriptor.parameter_slots() + this_slots,
+        ))
+    }
+
+    // The pc of the next instruction.
+    pub fn pc(&self) -> usize {
+        self.code.offset()
+    }
+
+    pub fn constant_pool(&mut self) -> &mut ConstantPoolBuilder {
+        self.constant_pool
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    // Binds the label to the pc of the next instruction.
+    pub fn bind(&mut self, label: Label) -> Result<(), ClassFormatError> {
+        if let Some(pc) = self.labels[label.0] {
+            return Err(ClassFormatError::new(format!(
+                "label {} is already bound to pc {}",
+                label.0, pc
+            )));
+        }
+        self.labels[label.0] = Some(self.pc());
+        Ok(())
+    }
+
+    // Appends an instruction as it is; branch offsets in it are taken to be already resolved.
+    pub fn emit(&mut self, instruction: Instruction) -> Result<(), ClassFormatError> {
+        if let Some(slots) = instruction.local_slots() {
+            self.max_locals = self.max_locals.max(slots);
+        }
+        let pc = self.pc();
+        instruction
+            .encode(&mut self.code)
+            .map_err(|e| e.within(format!("instruction at pc {}", pc)))
+    }
+
+    // A branch with a 2 byte offset to the label, e.g. `jump(Instruction::IfIcmpge, end)`.
+    pub fn jump(
+        &mut self,
+        branch: fn(i16) -> Instruction,
+        label: Label,
+    ) -> Result<(), ClassFormatError> {
+        self.branch(branch(0), label, false)
+    }
+
+    // goto_w or jsr_w to the label.
+    pub fn jump_wide(
+        &mut self,
+        branch: fn(i32) -> Instruction,
+        label: Label,
+    ) -> Result<(), ClassFormatError> {
+        self.branch(branch(0), label, true)
+    }
+
+    fn branch(
+        &mut self,
+        instruction: Instruction,
+        label: Label,
+        wide: bool,
+    ) -> Result<(), ClassFormatError> {
+        let pc = self.pc();
+        if instruction.branch_targets(pc).is_empty() {
+            return Err(ClassFormatError::new(format!(
+                "{} is not a branch instruction",
+                instruction.name()
+            )));
+        }
+        self.emit(instruction)?;
+        self.fixups.push(Fixup {
+            pc,
+            operand: pc + 1,
+            label,
+            wide,
+        });
+        Ok(())
+    }
+
+    // A tableswitch over low..=low + targets.len() - 1.
+    pub fn tableswitch(
+        &mut self,
+        low: i32,
+        default: Label,
+        targets: &[Label],
+    ) -> Result<(), ClassFormatError> {
+        let high = i32::try_from(targets.len())
+            .ok()
+            .and_then(|len| len.checked_sub(1))
+            .and_then(|last| low.checked_add(last))
+            .ok_or_else(|| {
+                ClassFormatError::new(format!(
+                    "tableswitch from {} with {} targets",
+                    low,
+                    targets.len()
+                ))
+            })?;
+        let pc = self.pc();
+        self.emit(Instruction::Tableswitch {
+            default: 0,
+            low,
+            high,
+            offsets: vec![0; targets.len()],
+        })?;
+        let operands = switch_operands(pc);
+        self.switch_fixup(pc, operands, default);
+        for (i, target) in targets.iter().enumerate() {
+            self.switch_fixup(pc, operands + 12 + 4 * i, *target);
+        }
+        Ok(())
+    }
+
+    // A lookupswitch; the pairs are sorted by key as the instruction requires.
+    pub fn lookupswitch(
+        &mut self,
+        default: Label,
+        pairs: &[(i32, Label)],
+    ) -> Result<(), ClassFormatError> {
+        let mut pairs = pairs.to_vec();
+        pairs.sort_by_key(|(key, _)| *key);
+        if let Some(w) = pairs.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(ClassFormatError::new(format!(
+                "lookupswitch with duplicate key {}",
+                w[0].0
+            )));
+        }
+        let pc = self.pc();
+        self.emit(Instruction::Lookupswitch {
+            default: 0,
+            pairs: pairs.iter().map(|(key, _)| (*key, 0)).collect(),
+        })?;
+        let operands = switch_operands(pc);
+        self.switch_fixup(pc, operands, default);
+        for (i, (_, target)) in pairs.iter().enumerate() {
+            self.switch_fixup(pc, operands + 12 + 8 * i, *target);
+        }
+        Ok(())
+    }
+
+    fn switch_fixup(&mut self, pc: usize, operand: usize, label: Label) {
+        self.fixups.push(Fixup {
+            pc,
+            operand,
+            label,
+            wide: true,
+        });
+    }
+
+    // The shortest instruction that pushes the int: iconst_<i>, bipush, sipush or ldc.
+    pub fn iconst(&mut self, value: i32) -> Result<(), ClassFormatError> {
+        let instruction = match value {
+            -1 => Instruction::IconstM1,
+            0 => Instruction::Iconst0,
+            1 => Instruction::Iconst1,
+            2 => Instruction::Iconst2,
+            3 => Instruction::Iconst3,
+            4 => Instruction::Iconst4,
+            5 => Instruction::Iconst5,
+            _ => match (i8::try_from(value), i16::try_from(value)) {
+                (Ok(value), _) => Instruction::Bipush(value),
+                (_, Ok(value)) => Instruction::Sipush(value),
+                _ => return self.ldc(&LoadableConstant::Integer(value)),
+            },
+        };
+        self.emit(instruction)
+    }
+
+    // ldc, ldc_w or ldc2_w, whichever the constant and its index need.
+    pub fn ldc(&mut self, constant: &LoadableConstant) -> Result<(), ClassFormatError> {
+        let index = self.constant_pool.loadable(constant)?;
+        let instruction = match constant {
+            LoadableConstant::Long(_) | LoadableConstant::Double(_) => Instruction::Ldc2W(index),
+            LoadableConstant::Dynamic(dynamic)
+                if matches!(dynamic.descriptor.as_str(), "J" | "D") =>
+            {
+                Instruction::Ldc2W(index)
+            }
+            _ if index.0 <= u8::MAX as u16 => Instruction::Ldc(index),
+            _ => Instruction::LdcW(index),
+        };
+        self.emit(instruction)
+    }
+
+    // Any instruction that takes a member reference, e.g. invokestatic of an interface method,
+    // which the methods below do not cover.
+    pub fn member(
+        &mut self,
+        instruction: fn(CpIndex) -> Instruction,
+        member: &MemberRef,
+    ) -> Result<(), ClassFormatError> {
+        let index = self.constant_pool.member_ref(member)?;
+        self.emit(instruction(index))
+    }
+
+    fn member_ref(
+        &mut self,
+        instruction: fn(CpIndex) -> Instruction,
+        kind: MemberKind,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        self.member(
+            instruction,
+            &MemberRef {
+                kind,
+                class_name: String::from(class_name),
+                name: String::from(name),
+                descriptor: String::from(descriptor),
+            },
+        )
+    }
+
+    pub fn getstatic(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        let field = MemberKind::Field;
+        self.member_ref(Instruction::Getstatic, field, class_name, name, descriptor)
+    }
+
+    pub fn putstatic(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        let field = MemberKind::Field;
+        self.member_ref(Instruction::Putstatic, field, class_name, name, descriptor)
+    }
+
+    pub fn getfield(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        let field = MemberKind::Field;
+        self.member_ref(Instruction::Getfield, field, class_name, name, descriptor)
+    }
+
+    pub fn putfield(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        let field = MemberKind::Field;
+        self.member_ref(Instruction::Putfield, field, class_name, name, descriptor)
+    }
+
+    pub fn invokevirtual(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        let method = MemberKind::Method;
+        self.member_ref(
+            Instruction::Invokevirtual,
+            method,
+            class_name,
+            name,
+            descriptor,
+        )
+    }
+
+    pub fn invokespecial(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        let method = MemberKind::Method;
+        self.member_ref(
+            Instruction::Invokespecial,
+            method,
+            class_name,
+            name,
+            descriptor,
+        )
+    }
+
+    pub fn invokestatic(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        let method = MemberKind::Method;
+        self.member_ref(
+            Instruction::Invokestatic,
+            method,
+            class_name,
+            name,
+            descriptor,
+        )
+    }
+
+    // The count operand is derived from the descriptor.
+    pub fn invokeinterface(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        let count = MethodDescriptor::parse(descriptor)?.parameter_slots() + 1;
+        let index = self.constant_pool.member_ref(&MemberRef {
+            kind: MemberKind::InterfaceMethod,
+            class_name: String::from(class_name),
+            name: String::from(name),
+            descriptor: String::from(descriptor),
+        })?;
+        self.emit(Instruction::Invokeinterface {
+            index,
+            count: count as u8,
+        })
+    }
+
+    // `bootstrap_method_attr_index` is the index into the BootstrapMethods attribute, which
+    // the caller adds to the class.
+    pub fn invokedynamic(
+        &mut self,
+        bootstrap_method_attr_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<(), ClassFormatError> {
+        let index = self.constant_pool.dynamic(
+            DynamicKind::InvokeDynamic,
+            &DynamicConstant {
+                bootstrap_method_attr_index,
+                name: String::from(name),
+                descriptor: String::from(descriptor),
+            },
+        )?;
+        self.emit(Instruction::Invokedynamic(index))
+    }
+
+    pub fn new_object(&mut self, class_name: &str) -> Result<(), ClassFormatError> {
+        let index = self.constant_pool.class(class_name)?;
+        self.emit(Instruction::New(index))
+    }
+
+    pub fn anewarray(&mut self, class_name: &str) -> Result<(), ClassFormatError> {
+        let index = self.constant_pool.class(class_name)?;
+        self.emit(Instruction::Anewarray(index))
+    }
+
+    pub fn checkcast(&mut self, class_name: &str) -> Result<(), ClassFormatError> {
+        let index = self.constant_pool.class(class_name)?;
+        self.emit(Instruction::Checkcast(index))
+    }
+
+    pub fn instanceof(&mut self, class_name: &str) -> Result<(), ClassFormatError> {
+        let index = self.constant_pool.class(class_name)?;
+        self.emit(Instruction::Instanceof(index))
+    }
+
+    // `array_type` is an array descriptor such as "[[I".
+    pub fn multianewarray(
+        &mut self,
+        array_type: &str,
+        dimensions: u8,
+    ) -> Result<(), ClassFormatError> {
+        let index = self.constant_pool.class(array_type)?;
+        self.emit(Instruction::Multianewarray { index, dimensions })
+    }
+
+    // An exception handler for [start, end); `None` catches everything, as for finally.
+    pub fn try_catch(
+        &mut self,
+        start: Label,
+        end: Label,
+        handler: Label,
+        catch_type: Option<&str>,
+    ) -> Result<(), ClassFormatError> {
+        let catch_type = match catch_type {
+            Some(class_name) => self.constant_pool.class(class_name)?,
+            None => CpIndex(0),
+        };
+        self.handlers.push(Handler {
+            start,
+            end,
+            handler,
+            catch_type,
+        });
+        Ok(())
+    }
+
+    // An attribute of the Code attribute, such as a LineNumberTable.
+    pub fn attribute(&mut self, attribute: Attribute) {
+        self.attributes.push(attribute);
+    }
+
+    pub fn finish(self) -> Result<AttributeCode, ClassFormatError> {
+        let CodeBuilder {
+            constant_pool,
+            mut code,
+            labels,
+            fixups,
+            handlers,
+            max_locals,
+            attributes,
+        } = self;
+        if code.offset() == 0 || code.offset() > u16::MAX as usize {
+            return Err(ClassFormatError::new(format!(
+                "code_length {} is not in 1..=65535",
+                code.offset()
+            )));
+        }
+        let resolve = |label: Label| -> Result<usize, ClassFormatError> {
+            labels[label.0]
+                .ok_or_else(|| ClassFormatError::new(format!("label {} is never bound", label.0)))
+        };
+
+        for fixup in &fixups {
+            let target = resolve(fixup.label)?;
+            let offset = target as i64 - fixup.pc as i64;
+            if fixup.wide {
+                code.patch_u32(fixup.operand, offset as i32 as u32);
+            } else {
+                let offset = i16::try_from(offset).map_err(|_| {
+                    ClassFormatError::new(format!(
+                        "branch at pc {} to pc {} does not fit in a 2 byte offset",
+                        fixup.pc, target
+                    ))
+                })?;
+                code.patch_u16(fixup.operand, offset as u16);
+            }
+        }
+
+        let mut exception_table: Vec<ExceptionInfo> = Vec::new();
+        for handler in &handlers {
+            let (start_pc, end_pc) = (resolve(handler.start)?, resolve(handler.end)?);
+            if start_pc >= end_pc {
+                return Err(ClassFormatError::new(format!(
+                    "exception handler range [{}, {}) is empty",
+                    start_pc, end_pc
+                )));
+            }
+            exception_table.push(ExceptionInfo {
+                start_pc: start_pc as u16,
+                end_pc: end_pc as u16,
+                handler_pc: resolve(handler.handler)? as u16,
+                catch_type: handler.catch_type,
+            });
+        }
+
+        let max_stack = max_stack(&code.data, &exception_table, constant_pool.constant_pool())?;
+        Ok(AttributeCode {
+            max_stack,
+            max_locals,
+            code: code.data,
+            exception_table,
+            attributes,
+            attribute_name_indices: Vec::new(),
+        })
+    }
+}
+
+// Switch operands start after 0-3 bytes of padding, at a multiple of four.
+fn switch_operands(pc: usize) -> usize {
+    (pc + 1).next_multiple_of(4)
+}
+
+// The deepest the operand stack gets, in slots, following every path from the start of the
+// code and from each exception handler. Fails on underflow, on an instruction reached with
+// two different stack depths, and on code that runs past its end.
+pub fn max_stack(
+    code: &[u8],
+    exception_table: &[ExceptionInfo],
+    constant_pool: &ConstantPool,
+) -> Result<u16, ClassFormatError> {
+    let instructions = Instruction::decode_all(code)?;
+    let index_of: HashMap<usize, usize> = instructions
+        .iter()
+        .enumerate()
+        .map(|(i, (pc, _))| (*pc, i))
+        .collect();
+    let mut depths: Vec<Option<u16>> = vec![None; instructions.len()];
+    let mut pending: Vec<(usize, u16)> = vec![(0, 0)];
+    pending.extend(exception_table.iter().map(|e| (e.handler_pc as usize, 1)));
+
+    let mut max_stack = 0;
+    while let Some((pc, depth)) = pending.pop() {
+        let i = match index_of.get(&pc) {
+            Some(i) => *i,
+            None if pc == code.len() => {
+                return Err(ClassFormatError::new(
+                    "execution can fall off the end of the code",
+                ))
+            }
+            None => {
+                return Err(ClassFormatError::new(format!(
+                    "pc {} is not the start of an instruction",
+                    pc
+                )))
+            }
+        };
+        match depths[i] {
+            Some(d) if d == depth => continue,
+            Some(d) => {
+                return Err(ClassFormatError::new(format!(
+                    "pc {} is reached with stack depths {} and {}",
+                    pc, d, depth
+                )))
+            }
+            None => depths[i] = Some(depth),
+        }
+        max_stack = max_stack.max(depth);
+
+        let instruction = &instructions[i].1;
+        let (pops, pushes) = instruction
+            .stack_effect(constant_pool)
+            .map_err(|e| e.within(format!("instruction at pc {}", pc)))?;
+        if pops > depth {
+            return Err(ClassFormatError::new(format!(
+                "stack underflow at pc {}: {} pops {} slots from a stack of {}",
+                pc,
+                instruction.name(),
+                pops,
+                depth
+            )));
+        }
+        let after = u16::try_from(depth as u32 - pops as u32 + pushes as u32)
+            .map_err(|_| ClassFormatError::new(format!("stack overflow at pc {}", pc)))?;
+        max_stack = max_stack.max(after);
+
+        for target in instruction.branch_targets(pc) {
+            pending.push((target, after));
+        }
+        if instruction.falls_through() {
+            let next = instructions.get(i + 1).map_or(code.len(), |(pc, _)| *pc);
+            // The return address jsr pushes is popped by the subroutine before ret comes back.
+            let next_depth = match instruction {
+                Instruction::Jsr(_) | Instruction::JsrW(_) => depth,
+                _ => after,
+            };
+            pending.push((next, next_depth));
+        }
+    }
+    Ok(max_stack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instructions(code: &AttributeCode) -> Vec<(usize, Instruction)> {
+        Instruction::decode_all(&code.code).unwrap()
+    }
+
+    fn error(result: Result<AttributeCode, ClassFormatError>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn forward_and_backward_branches_to_labels() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        // static int count(int n) { int i = 0; while (i < n) i++; return i; }
+        let mut code =
+            CodeBuilder::for_method(&mut constant_pool, MethodAccessFlags::STATIC, "(I)I").unwrap();
+        let (top, end) = (code.new_label(), code.new_label());
+        code.iconst(0).unwrap();
+        code.emit(Instruction::Istore1).unwrap();
+        code.bind(top).unwrap();
+        code.emit(Instruction::Iload1).unwrap();
+        code.emit(Instruction::Iload0).unwrap();
+        code.jump(Instruction::IfIcmpge, end).unwrap();
+        code.emit(Instruction::Iinc { index: 1, value: 1 }).unwrap();
+        code.jump(Instruction::Goto, top).unwrap();
+        code.bind(end).unwrap();
+        code.emit(Instruction::Iload1).unwrap();
+        code.emit(Instruction::Ireturn).unwrap();
+        let code = code.finish().unwrap();
+
+        assert_eq!(
+            instructions(&code),
+            vec![
+                (0, Instruction::Iconst0),
+                (1, Instruction::Istore1),
+                (2, Instruction::Iload1),
+                (3, Instruction::Iload0),
+                (4, Instruction::IfIcmpge(9)),
+                (7, Instruction::Iinc { index: 1, value: 1 }),
+                (10, Instruction::Goto(-8)),
+                (13, Instruction::Iload1),
+                (14, Instruction::Ireturn),
+            ]
+        );
+        assert_eq!((code.max_stack, code.max_locals), (2, 2));
+    }
+
+    #[test]
+    fn switches_and_wide_jumps_take_4_byte_offsets() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let mut code = CodeBuilder::new(&mut constant_pool, 1);
+        let (one, two, default) = (code.new_label(), code.new_label(), code.new_label());
+        code.emit(Instruction::Iload0).unwrap();
+        // Two bytes of padding put the operands at pc 4.
+        code.tableswitch(1, default, &[one, two]).unwrap();
+        code.bind(one).unwrap();
+        code.emit(Instruction::Iconst1).unwrap();
+        code.emit(Instruction::Ireturn).unwrap();
+        code.bind(two).unwrap();
+        code.emit(Instruction::Iload0).unwrap();
+        // No padding at pc 27; the pairs are sorted by key.
+        code.lookupswitch(default, &[(10, one), (-5, two)]).unwrap();
+        code.bind(default).unwrap();
+        code.jump_wide(Instruction::GotoW, one).unwrap();
+        let code = code.finish().unwrap();
+
+        assert_eq!(
+            instructions(&code),
+            vec![
+                (0, Instruction::Iload0),
+                (
+                    1,
+                    Instruction::Tableswitch {
+                        default: 51,
+                        low: 1,
+                        high: 2,
+                        offsets: vec![23, 25],
+                    }
+                ),
+                (24, Instruction::Iconst1),
+                (25, Instruction::Ireturn),
+                (26, Instruction::Iload0),
+                (
+                    27,
+                    Instruction::Lookupswitch {
+                        default: 25,
+                        pairs: vec![(-5, -1), (10, -3)],
+                    }
+                ),
+                (52, Instruction::GotoW(-28)),
+            ]
+        );
+        assert_eq!((code.max_stack, code.max_locals), (1, 1));
+    }
+
+    #[test]
+    fn branches_beyond_a_2_byte_offset_need_jump_wide() {
+        let far = |wide: bool| {
+            let mut constant_pool = ConstantPoolBuilder::new();
+            let mut code = CodeBuilder::new(&mut constant_pool, 0);
+            let end = code.new_label();
+            if wide {
+                code.jump_wide(Instruction::GotoW, end).unwrap();
+            } else {
+                code.jump(Instruction::Goto, end).unwrap();
+            }
+            for _ in 0..32765 {
+                code.emit(Instruction::Nop).unwrap();
+            }
+            code.bind(end).unwrap();
+            code.emit(Instruction::Return).unwrap();
+            code.finish()
+        };
+        assert_eq!(
+            error(far(false)),
+            "branch at pc 0 to pc 32768 does not fit in a 2 byte offset"
+        );
+        let code = far(true).unwrap();
+        assert_eq!(instructions(&code)[0], (0, Instruction::GotoW(32770)));
+    }
+
+    #[test]
+    fn exception_handlers_cover_their_label_range() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let mut code =
+            CodeBuilder::for_method(&mut constant_pool, MethodAccessFlags::STATIC, "()V").unwrap();
+        let (start, end, handler, finally) = (
+            code.new_label(),
+            code.new_label(),
+            code.new_label(),
+            code.new_label(),
+        );
+        code.try_catch(start, end, handler, Some("java/lang/Exception"))
+            .unwrap();
+        code.try_catch(start, handler, finally, None).unwrap();
+        code.emit(Instruction::Nop).unwrap();
+        code.bind(start).unwrap();
+        code.emit(Instruction::AconstNull).unwrap();
+        code.emit(Instruction::Athrow).unwrap();
+        code.bind(end).unwrap();
+        code.bind(handler).unwrap();
+        code.emit(Instruction::Astore0).unwrap();
+        code.emit(Instruction::Return).unwrap();
+        code.bind(finally).unwrap();
+        code.emit(Instruction::Athrow).unwrap();
+        let code = code.finish().unwrap();
+
+        let exception = constant_pool.class("java/lang/Exception").unwrap();
+        let table: Vec<(u16, u16, u16, CpIndex)> = code
+            .exception_table
+            .iter()
+            .map(|e| (e.start_pc, e.end_pc, e.handler_pc, e.catch_type))
+            .collect();
+        assert_eq!(table, vec![(1, 3, 3, exception), (1, 3, 5, CpIndex(0))]);
+        // Handlers start with the exception on the stack.
+        assert_eq!((code.max_stack, code.max_locals), (1, 1));
+
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let mut code = CodeBuilder::new(&mut constant_pool, 0);
+        let (start, handler) = (code.new_label(), code.new_label());
+        code.try_catch(start, start, handler, None).unwrap();
+        code.bind(start).unwrap();
+        code.bind(handler).unwrap();
+        code.emit(Instruction::Return).unwrap();
+        assert_eq!(
+            error(code.finish()),
+            "exception handler range [0, 0) is empty"
+        );
+    }
+
+    #[test]
+    fn max_locals_from_parameters_and_wide_locals() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let code = CodeBuilder::for_method(
+            &mut constant_pool,
+            MethodAccessFlags::PUBLIC,
+            "(JLjava/lang/String;D)V",
+        )
+        .unwrap();
+        assert_eq!(code.max_locals, 6);
+        let mut code = code;
+        code.emit(Instruction::Lconst0).unwrap();
+        code.emit(Instruction::Lstore(300)).unwrap();
+        code.emit(Instruction::Iinc {
+            index: 7,
+            value: 1000,
+        })
+        .unwrap();
+        code.emit(Instruction::Return).unwrap();
+        let code = code.finish().unwrap();
+
+        // wide lstore and wide iinc.
+        assert_eq!(
+            instructions(&code),
+            vec![
+                (0, Instruction::Lconst0),
+                (1, Instruction::Lstore(300)),
+                (
+                    5,
+                    Instruction::Iinc {
+                        index: 7,
+                        value: 1000
+                    }
+                ),
+                (11, Instruction::Return),
+            ]
+        );
+        assert_eq!((code.max_stack, code.max_locals), (2, 302));
+    }
+
+    #[test]
+    fn iconst_and_ldc_pick_the_shortest_instruction() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        for i in 0..300 {
+            constant_pool.utf8(&format!("padding {}", i)).unwrap();
+        }
+        let mut code = CodeBuilder::new(&mut constant_pool, 0);
+        for value in [-1, 5, -128, 1000, 100_000] {
+            code.iconst(value).unwrap();
+        }
+        code.ldc(&LoadableConstant::Long(1)).unwrap();
+        code.emit(Instruction::Return).unwrap();
+        let code = code.finish().unwrap();
+        let instructions: Vec<Instruction> =
+            instructions(&code).into_iter().map(|(_, i)| i).collect();
+        let integer = constant_pool
+            .loadable(&LoadableConstant::Integer(100_000))
+            .unwrap();
+        let long = constant_pool.loadable(&LoadableConstant::Long(1)).unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::IconstM1,
+                Instruction::Iconst5,
+                Instruction::Bipush(-128),
+                Instruction::Sipush(1000),
+                Instruction::LdcW(integer),
+                Instruction::Ldc2W(long),
+                Instruction::Return,
+            ]
+        );
+        assert_eq!(code.max_stack, 7);
+    }
+
+    #[test]
+    fn label_and_stack_errors() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let mut code = CodeBuilder::new(&mut constant_pool, 0);
+        let label = code.new_label();
+        code.bind(label).unwrap();
+        assert_eq!(
+            code.bind(label).unwrap_err().to_string(),
+            "label 0 is already bound to pc 0"
+        );
+        assert_eq!(
+            code.jump(|_| Instruction::Nop, label)
+                .unwrap_err()
+                .to_string(),
+            "nop is not a branch instruction"
+        );
+        assert_eq!(
+            code.lookupswitch(label, &[(1, label), (1, label)])
+                .unwrap_err()
+                .to_string(),
+            "lookupswitch with duplicate key 1"
+        );
+        let unbound = code.new_label();
+        code.jump(Instruction::Goto, unbound).unwrap();
+        assert_eq!(error(code.finish()), "label 1 is never bound");
+
+        let cases: [(&[Instruction], &str); 3] = [
+            (&[], "code_length 0 is not in 1..=65535"),
+            (
+                &[Instruction::Nop],
+                "execution can fall off the end of the code",
+            ),
+            (
+                &[Instruction::Pop, Instruction::Return],
+                "stack underflow at pc 0: pop pops 1 slots from a stack of 0",
+            ),
+        ];
+        for (instructions, expected) in cases {
+            let mut constant_pool = ConstantPoolBuilder::new();
+            let mut code = CodeBuilder::new(&mut constant_pool, 0);
+            for instruction in instructions {
+                code.emit(instruction.clone()).unwrap();
+            }
+            assert_eq!(error(code.finish()), expected);
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::utils::bytecode_reader::BytecodeReader;
+use crate::jvm::class::{constant_pool::CpIndex, error::ClassFormatError};
+use crate::utils::{bytecode_reader::BytecodeReader, modified_utf8};
 
 #[derive(Debug, Clone)]
 pub enum Constant {
@@ -19,134 +20,212 @@ pub enum Constant {
     InvokeDynamic(ConstantInvokeDynamic),           // tag: 0x12 18
     Module(ConstantModule),                         // tag: 0x13 19
     Package(ConstantPackage),                       // tag: 0x14 20
+    Unusable,                                       // index 0 and the slot after Long/Double
 }
 
 impl Constant {
-    pub fn new(reader: &mut BytecodeReader) -> Constant {
-        let tag = reader.u8();
-        match tag {
+    pub fn new(reader: &mut BytecodeReader) -> Result<Constant, ClassFormatError> {
+        let tag_offset = reader.offset;
+        let tag = reader.u8()?;
+        let constant = match tag {
             0x1_u8 => {
-                let length = reader.u16();
-                let bytes = reader.read_as_string(length as usize);
-                return Constant::Utf8(ConstantUtf8 { bytes });
+                let length = reader.u16()?;
+                let utf16 = reader.read_as_utf16(length as usize)?;
+                Constant::Utf8(ConstantUtf8::from_utf16(utf16))
             }
             0x3_u8 => {
-                let bytes = reader.u32() as i32;
-                return Constant::Integer(ConstantInteger { bytes });
+                let bytes = reader.u32()? as i32;
+                Constant::Integer(ConstantInteger { bytes })
             }
             0x4_u8 => {
-                let bytes = reader.u32() as f32;
-                return Constant::Float(ConstantFloat { bytes });
+                let bytes = f32::from_bits(reader.u32()?);
+                Constant::Float(ConstantFloat { bytes })
             }
             0x5_u8 => {
-                let bytes = i64::from_be_bytes(
-                    reader
-                        .read_as_vec(8)
-                        .try_into()
-                        .expect("Failed try bytes into i64."),
-                );
-                return Constant::Long(ConstantLong { bytes });
+                let bytes = reader.read_as_u64(8)? as i64;
+                Constant::Long(ConstantLong { bytes })
             }
             0x6_u8 => {
-                let bytes = f64::from_be_bytes(
-                    reader
-                        .read_as_vec(8)
-                        .try_into()
-                        .expect("Failed try bytes into f64."),
-                );
-                return Constant::Double(ConstantDouble { bytes });
+                let bytes = f64::from_bits(reader.read_as_u64(8)?);
+                Constant::Double(ConstantDouble { bytes })
             }
             0x7_u8 => {
-                let name_index = reader.u16();
-                return Constant::Class(ConstantClass { name_index });
+                let name_index = CpIndex::read(reader)?;
+                Constant::Class(ConstantClass { name_index })
             }
             0x8_u8 => {
-                let string_index = reader.u16();
-                return Constant::String(ConstantString { string_index });
+                let string_index = CpIndex::read(reader)?;
+                Constant::String(ConstantString { string_index })
             }
             0x9_u8 => {
-                let class_index = reader.u16();
-                let name_and_type_index = reader.u16();
-                return Constant::Fieldref(ConstantFieldref {
+                let class_index = CpIndex::read(reader)?;
+                let name_and_type_index = CpIndex::read(reader)?;
+                Constant::Fieldref(ConstantFieldref {
                     class_index,
                     name_and_type_index,
-                });
+                })
             }
             0xa_u8 => {
-                let class_index = reader.u16();
-                let name_and_type_index = reader.u16();
-                return Constant::Methodref(ConstantMethodref {
+                let class_index = CpIndex::read(reader)?;
+                let name_and_type_index = CpIndex::read(reader)?;
+                Constant::Methodref(ConstantMethodref {
                     class_index,
                     name_and_type_index,
-                });
+                })
             }
             0xb_u8 => {
-                let class_index = reader.u16();
-                let name_and_type_index = reader.u16();
-                return Constant::InterfaceMethodref(ConstantInterfaceMethodref {
+                let class_index = CpIndex::read(reader)?;
+                let name_and_type_index = CpIndex::read(reader)?;
+                Constant::InterfaceMethodref(ConstantInterfaceMethodref {
                     class_index,
                     name_and_type_index,
-                });
+                })
             }
             0xc_u8 => {
-                let name_index = reader.u16();
-                let descriptor_index = reader.u16();
-                return Constant::NameAndType(ConstantNameAndType {
+                let name_index = CpIndex::read(reader)?;
+                let descriptor_index = CpIndex::read(reader)?;
+                Constant::NameAndType(ConstantNameAndType {
                     name_index,
                     descriptor_index,
-                });
+                })
             }
             0xf_u8 => {
-                let reference_kind = reader.u8();
-                let reference_index = reader.u16();
-                return Constant::MethodHandle(ConstantMethodHandle {
+                let reference_kind = reader.u8()?;
+                let reference_index = CpIndex::read(reader)?;
+                Constant::MethodHandle(ConstantMethodHandle {
                     reference_kind,
                     reference_index,
-                });
+                })
             }
             0x10_u8 => {
-                let descriptor_index = reader.u16();
-                return Constant::MethodType(ConstantMethodType { descriptor_index });
+                let descriptor_index = CpIndex::read(reader)?;
+                Constant::MethodType(ConstantMethodType { descriptor_index })
             }
             0x11_u8 => {
-                let bootstrap_method_attr_index = reader.u16();
-                let name_and_type_index = reader.u16();
-                return Constant::Dynamic(ConstantDynamic {
+                let bootstrap_method_attr_index = reader.u16()?;
+                let name_and_type_index = CpIndex::read(reader)?;
+                Constant::Dynamic(ConstantDynamic {
                     bootstrap_method_attr_index,
                     name_and_type_index,
-                });
+                })
             }
             0x12_u8 => {
-                let bootstrap_method_attr_index = reader.u16();
-                let name_and_type_index = reader.u16();
-                return Constant::InvokeDynamic(ConstantInvokeDynamic {
+                let bootstrap_method_attr_index = reader.u16()?;
+                let name_and_type_index = CpIndex::read(reader)?;
+                Constant::InvokeDynamic(ConstantInvokeDynamic {
                     bootstrap_method_attr_index,
                     name_and_type_index,
-                });
+                })
             }
             0x13_u8 => {
-                let name_index = reader.u16();
-                return Constant::Module(ConstantModule { name_index });
+                let name_index = CpIndex::read(reader)?;
+                Constant::Module(ConstantModule { name_index })
             }
             0x14_u8 => {
-                let name_index = reader.u16();
-                return Constant::Package(ConstantPackage { name_index });
+                let name_index = CpIndex::read(reader)?;
+                Constant::Package(ConstantPackage { name_index })
             }
-            _ => panic!("unsupported constant tag: {}", tag),
+            _ => {
+                return Err(ClassFormatError::at(
+                    tag_offset,
+                    format!("unsupported constant tag: {}", tag),
+                ))
+            }
+        };
+        Ok(constant)
+    }
+
+    // Long and Double take up two entries in the constant pool (JVMS 4.4.5).
+    pub fn is_wide(&self) -> bool {
+        matches!(self, Constant::Long(_) | Constant::Double(_))
+    }
+
+    // The name javap shows for the kind of constant, e.g. "Methodref".
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Constant::Utf8(_) => "Utf8",
+            Constant::Integer(_) => "Integer",
+            Constant::Float(_) => "Float",
+            Constant::Long(_) => "Long",
+            Constant::Double(_) => "Double",
+            Constant::Class(_) => "Class",
+            Constant::String(_) => "String",
+            Constant::Fieldref(_) => "Fieldref",
+            Constant::Methodref(_) => "Methodref",
+            Constant::InterfaceMethodref(_) => "InterfaceMethodref",
+            Constant::NameAndType(_) => "NameAndType",
+            Constant::MethodHandle(_) => "MethodHandle",
+            Constant::MethodType(_) => "MethodType",
+            Constant::Dynamic(_) => "Dynamic",
+            Constant::InvokeDynamic(_) => "InvokeDynamic",
+            Constant::Module(_) => "Module",
+            Constant::Package(_) => "Package",
+            Constant::Unusable => "Unusable",
         }
     }
 
-    pub fn read_utf8_data(constant_pool: &Vec<Constant>, constant_index: u16) -> String {
-        match &constant_pool[constant_index as usize - 1] {
-            Constant::Utf8(c) => String::from(&c.bytes),
-            _ => panic!("read Utf8: wrong ConstantUtf8 index."),
+    // The first class file version that may contain the constant (JVMS 4.4, table 4.4-B).
+    pub fn min_major_version(&self) -> u16 {
+        match self {
+            Constant::MethodHandle(_) | Constant::MethodType(_) | Constant::InvokeDynamic(_) => 51,
+            Constant::Module(_) | Constant::Package(_) => 53,
+            Constant::Dynamic(_) => 55,
+            _ => 45,
+        }
+    }
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            Constant::Unusable => 0,
+            Constant::Utf8(_) => 1,
+            Constant::Integer(_) => 3,
+            Constant::Float(_) => 4,
+            Constant::Long(_) => 5,
+            Constant::Double(_) => 6,
+            Constant::Class(_) => 7,
+            Constant::String(_) => 8,
+            Constant::Fieldref(_) => 9,
+            Constant::Methodref(_) => 10,
+            Constant::InterfaceMethodref(_) => 11,
+            Constant::NameAndType(_) => 12,
+            Constant::MethodHandle(_) => 15,
+            Constant::MethodType(_) => 16,
+            Constant::Dynamic(_) => 17,
+            Constant::InvokeDynamic(_) => 18,
+            Constant::Module(_) => 19,
+            Constant::Package(_) => 20,
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ConstantUtf8 {
+    // The string with unpaired surrogates replaced by U+FFFD, see `utf16` for the exact value.
     pub bytes: String,
+    pub utf16: Vec<u16>,
+}
+
+impl ConstantUtf8 {
+    pub fn from_utf16(utf16: Vec<u16>) -> ConstantUtf8 {
+        ConstantUtf8 {
+            bytes: String::from_utf16_lossy(&utf16),
+            utf16,
+        }
+    }
+
+    // The modified UTF-8 encoding written to the class file.
+    pub fn encode(&self) -> Vec<u8> {
+        modified_utf8::encode(&self.utf16)
+    }
+}
+
+impl From<&str> for ConstantUtf8 {
+    fn from(s: &str) -> ConstantUtf8 {
+        ConstantUtf8 {
+            bytes: String::from(s),
+            utf16: s.encode_utf16().collect(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -167,57 +246,57 @@ pub struct ConstantDouble {
 }
 #[derive(Debug, Clone)]
 pub struct ConstantClass {
-    pub name_index: u16,
+    pub name_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantString {
-    pub string_index: u16,
+    pub string_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantFieldref {
-    pub class_index: u16,
-    pub name_and_type_index: u16,
+    pub class_index: CpIndex,
+    pub name_and_type_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantMethodref {
-    pub class_index: u16,
-    pub name_and_type_index: u16,
+    pub class_index: CpIndex,
+    pub name_and_type_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantInterfaceMethodref {
-    pub class_index: u16,
-    pub name_and_type_index: u16,
+    pub class_index: CpIndex,
+    pub name_and_type_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantNameAndType {
-    pub name_index: u16,
-    pub descriptor_index: u16,
+    pub name_index: CpIndex,
+    pub descriptor_index: CpIndex,
 }
 
 #[derive(Debug, Clone)]
 pub struct ConstantMethodHandle {
     pub reference_kind: u8,
-    pub reference_index: u16,
+    pub reference_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantMethodType {
-    pub descriptor_index: u16,
+    pub descriptor_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantDynamic {
     pub bootstrap_method_attr_index: u16,
-    pub name_and_type_index: u16,
+    pub name_and_type_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantInvokeDynamic {
     pub bootstrap_method_attr_index: u16,
-    pub name_and_type_index: u16,
+    pub name_and_type_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantModule {
-    pub name_index: u16,
+    pub name_index: CpIndex,
 }
 #[derive(Debug, Clone)]
 pub struct ConstantPackage {
-    pub name_index: u16,
+    pub name_index: CpIndex,
 }
@@ -0,0 +1,793 @@
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Deref, Index},
+};
+
+use crate::jvm::class::{constant::*, error::ClassFormatError};
+use crate::utils::bytecode_reader::BytecodeReader;
+
+// A 1-based index into the constant pool, as stored in the class file. Index 0 never refers
+// to an entry and is used by the class file format to mean "none".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct CpIndex(pub u16);
+
+impl CpIndex {
+    pub fn read(reader: &mut BytecodeReader) -> Result<CpIndex, ClassFormatError> {
+        Ok(CpIndex(reader.u16()?))
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl From<u16> for CpIndex {
+    fn from(index: u16) -> CpIndex {
+        CpIndex(index)
+    }
+}
+
+impl fmt::Display for CpIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+// A class named by a Class constant. The index it was read from is kept so that writing the
+// class back refers to the same constant when the pool has duplicates. The index is only a
+// hint, so two references to the same class are equal whatever their index. Classes added to
+// the model have none and are looked up by name.
+#[derive(Debug, Clone, Eq)]
+pub struct ClassRef {
+    pub name: String,
+    pub index: Option<CpIndex>,
+}
+
+impl ClassRef {
+    pub fn new(name: impl Into<String>) -> ClassRef {
+        ClassRef {
+            name: name.into(),
+            index: None,
+        }
+    }
+}
+
+impl PartialEq for ClassRef {
+    fn eq(&self, other: &ClassRef) -> bool {
+        self.name == other.name
+    }
+}
+
+impl PartialOrd for ClassRef {
+    fn partial_cmp(&self, other: &ClassRef) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ClassRef {
+    fn cmp(&self, other: &ClassRef) -> Ordering {
+        self.name.cmp(&other.name)
+    }
+}
+
+impl PartialEq<str> for ClassRef {
+    fn eq(&self, other: &str) -> bool {
+        self.name == other
+    }
+}
+
+impl PartialEq<&str> for ClassRef {
+    fn eq(&self, other: &&str) -> bool {
+        self.name == *other
+    }
+}
+
+impl PartialEq<ClassRef> for String {
+    fn eq(&self, other: &ClassRef) -> bool {
+        *self == other.name
+    }
+}
+
+impl Borrow<str> for ClassRef {
+    fn borrow(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Hash for ClassRef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl Deref for ClassRef {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.name
+    }
+}
+
+impl From<&str> for ClassRef {
+    fn from(name: &str) -> ClassRef {
+        ClassRef::new(name)
+    }
+}
+
+impl From<String> for ClassRef {
+    fn from(name: String) -> ClassRef {
+        ClassRef::new(name)
+    }
+}
+
+impl fmt::Display for ClassRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+// Any other value resolved from the constant pool, most often the string of a Utf8 constant,
+// with the index it was read from. Like the index of a ClassRef it is only a hint: values are
+// equal whatever their index, and values added to the model have none.
+#[derive(Debug, Clone, Default)]
+pub struct Indexed<T> {
+    pub value: T,
+    pub index: Option<CpIndex>,
+}
+
+impl<T> Indexed<T> {
+    pub fn new(value: impl Into<T>) -> Indexed<T> {
+        Indexed {
+            value: value.into(),
+            index: None,
+        }
+    }
+
+    fn at(value: T, index: CpIndex) -> Indexed<T> {
+        Indexed {
+            value,
+            index: Some(index),
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Indexed<T> {
+    fn eq(&self, other: &Indexed<T>) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Indexed<T> {}
+
+impl<T: PartialOrd> PartialOrd for Indexed<T> {
+    fn partial_cmp(&self, other: &Indexed<T>) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<T: Ord> Ord for Indexed<T> {
+    fn cmp(&self, other: &Indexed<T>) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<T: Hash> Hash for Indexed<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+
+impl<T> Deref for Indexed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Indexed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl From<&str> for Indexed<String> {
+    fn from(value: &str) -> Indexed<String> {
+        Indexed::new(value)
+    }
+}
+
+impl From<String> for Indexed<String> {
+    fn from(value: String) -> Indexed<String> {
+        Indexed::new(value)
+    }
+}
+
+impl PartialEq<str> for Indexed<String> {
+    fn eq(&self, other: &str) -> bool {
+        self.value == other
+    }
+}
+
+impl PartialEq<&str> for Indexed<String> {
+    fn eq(&self, other: &&str) -> bool {
+        self.value == *other
+    }
+}
+
+impl PartialEq<Indexed<String>> for String {
+    fn eq(&self, other: &Indexed<String>) -> bool {
+        *self == other.value
+    }
+}
+
+impl Borrow<str> for Indexed<String> {
+    fn borrow(&self) -> &str {
+        &self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstantPool {
+    // entries[0] and the slot following every Long/Double are Constant::Unusable, so the
+    // vector is indexed directly by CpIndex and its length is constant_pool_count.
+    pub entries: Vec<Constant>,
+}
+
+impl ConstantPool {
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool_count: u16,
+    ) -> Result<ConstantPool, ClassFormatError> {
+        if constant_pool_count == 0 {
+            return Err(ClassFormatError::at(
+                reader.offset - 2,
+                "constant_pool_count must be at least 1",
+            ));
+        }
+        let mut entries: Vec<Constant> = vec![Constant::Unusable];
+        while entries.len() < constant_pool_count as usize {
+            let index = entries.len();
+            let offset = reader.offset;
+            let constant = Constant::new(reader)
+                .map_err(|e| e.within(format!("constant pool entry #{}", index)))?;
+            let is_wide = constant.is_wide();
+            entries.push(constant);
+            if is_wide {
+                if entries.len() == constant_pool_count as usize {
+                    return Err(ClassFormatError::at(
+                        offset,
+                        format!(
+                            "8-byte constant at #{} needs two slots but constant_pool_count is {}",
+                            index, constant_pool_count
+                        ),
+                    )
+                    .within(format!("constant pool entry #{}", index)));
+                }
+                entries.push(Constant::Unusable);
+            }
+        }
+        Ok(ConstantPool { entries })
+    }
+
+    // constant_pool_count as written in the class file.
+    pub fn count(&self) -> u16 {
+        self.entries.len() as u16
+    }
+
+    // Every usable entry together with its index.
+    pub fn iter(&self) -> impl Iterator<Item = (CpIndex, &Constant)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !matches!(c, Constant::Unusable))
+            .map(|(i, c)| (CpIndex(i as u16), c))
+    }
+
+    pub fn get(&self, index: CpIndex) -> Result<&Constant, ClassFormatError> {
+        match self.entries.get(index.0 as usize) {
+            Some(Constant::Unusable) if index.0 != 0 => Err(ClassFormatError::new(format!(
+                "constant pool index {} is the unusable slot after a Long or Double",
+                index
+            ))),
+            Some(constant) if index.0 != 0 => Ok(constant),
+            _ => Err(ClassFormatError::new(format!(
+                "constant pool index {} out of range #1..#{}",
+                index,
+                self.entries.len() - 1
+            ))),
+        }
+    }
+
+    pub fn utf8(&self, index: CpIndex) -> Result<String, ClassFormatError> {
+        match self.get(index)? {
+            Constant::Utf8(c) => Ok(String::from(&c.bytes)),
+            c => Err(ConstantPool::mismatch(index, "Utf8", c)),
+        }
+    }
+
+    pub fn utf8_ref(&self, index: CpIndex) -> Result<Indexed<String>, ClassFormatError> {
+        Ok(Indexed::at(self.utf8(index)?, index))
+    }
+
+    pub fn integer(&self, index: CpIndex) -> Result<i32, ClassFormatError> {
+        match self.get(index)? {
+            Constant::Integer(c) => Ok(c.bytes),
+            c => Err(ConstantPool::mismatch(index, "Integer", c)),
+        }
+    }
+
+    pub fn float(&self, index: CpIndex) -> Result<f32, ClassFormatError> {
+        match self.get(index)? {
+            Constant::Float(c) => Ok(c.bytes),
+            c => Err(ConstantPool::mismatch(index, "Float", c)),
+        }
+    }
+
+    pub fn long(&self, index: CpIndex) -> Result<i64, ClassFormatError> {
+        match self.get(index)? {
+            Constant::Long(c) => Ok(c.bytes),
+            c => Err(ConstantPool::mismatch(index, "Long", c)),
+        }
+    }
+
+    pub fn double(&self, index: CpIndex) -> Result<f64, ClassFormatError> {
+        match self.get(index)? {
+            Constant::Double(c) => Ok(c.bytes),
+            c => Err(ConstantPool::mismatch(index, "Double", c)),
+        }
+    }
+
+    pub fn class_name(&self, index: CpIndex) -> Result<String, ClassFormatError> {
+        match self.get(index)? {
+            Constant::Class(c) => self.utf8(c.name_index),
+            c => Err(ConstantPool::mismatch(index, "Class", c)),
+        }
+    }
+
+    // The value of a CONSTANT_String.
+    pub fn class_ref(&self, index: CpIndex) -> Result<ClassRef, ClassFormatError> {
+        Ok(ClassRef {
+            name: self.class_name(index)?,
+            index: Some(index),
+        })
+    }
+
+    pub fn string(&self, index: CpIndex) -> Result<String, ClassFormatError> {
+        match self.get(index)? {
+            Constant::String(c) => self.utf8(c.string_index),
+            c => Err(ConstantPool::mismatch(index, "String", c)),
+        }
+    }
+
+    // (name, descriptor) of a CONSTANT_NameAndType.
+    pub fn name_and_type(&self, index: CpIndex) -> Result<(String, String), ClassFormatError> {
+        match self.get(index)? {
+            Constant::NameAndType(c) => {
+                Ok((self.utf8(c.name_index)?, self.utf8(c.descriptor_index)?))
+            }
+            c => Err(ConstantPool::mismatch(index, "NameAndType", c)),
+        }
+    }
+
+    // A Fieldref, Methodref or InterfaceMethodref with its class and NameAndType resolved.
+    pub fn member_ref(&self, index: CpIndex) -> Result<MemberRef, ClassFormatError> {
+        let (kind, class_index, name_and_type_index) = match self.get(index)? {
+            Constant::Fieldref(c) => (MemberKind::Field, c.class_index, c.name_and_type_index),
+            Constant::Methodref(c) => (MemberKind::Method, c.class_index, c.name_and_type_index),
+            Constant::InterfaceMethodref(c) => (
+                MemberKind::InterfaceMethod,
+                c.class_index,
+                c.name_and_type_index,
+            ),
+            c => return Err(ConstantPool::mismatch(index, "Fieldref or Methodref", c)),
+        };
+        let class_name = self.class_name(class_index)?;
+        let (name, descriptor) = self.name_and_type(name_and_type_index)?;
+        Ok(MemberRef {
+            kind,
+            class_name,
+            name,
+            descriptor,
+        })
+    }
+
+    // The descriptor of a CONSTANT_MethodType.
+    pub fn method_type(&self, index: CpIndex) -> Result<String, ClassFormatError> {
+        match self.get(index)? {
+            Constant::MethodType(c) => self.utf8(c.descriptor_index),
+            c => Err(ConstantPool::mismatch(index, "MethodType", c)),
+        }
+    }
+
+    // A CONSTANT_MethodHandle with its reference checked against the kind (JVMS 4.4.8).
+    pub fn method_handle(&self, index: CpIndex) -> Result<MethodHandle, ClassFormatError> {
+        let c = match self.get(index)? {
+            Constant::MethodHandle(c) => c,
+            c => return Err(ConstantPool::mismatch(index, "MethodHandle", c)),
+        };
+        let reference_kind = ReferenceKind::from_u8(c.reference_kind).ok_or_else(|| {
+            ClassFormatError::new(format!(
+                "method handle {} has invalid reference_kind {}",
+                index, c.reference_kind
+            ))
+        })?;
+        let member = self.member_ref(c.reference_index)?;
+        let valid = match reference_kind {
+            ReferenceKind::GetField
+            | ReferenceKind::GetStatic
+            | ReferenceKind::PutField
+            | ReferenceKind::PutStatic => member.kind == MemberKind::Field,
+            ReferenceKind::InvokeVirtual | ReferenceKind::NewInvokeSpecial => {
+                member.kind == MemberKind::Method
+            }
+            ReferenceKind::InvokeStatic | ReferenceKind::InvokeSpecial => {
+                member.kind != MemberKind::Field
+            }
+            ReferenceKind::InvokeInterface => member.kind == MemberKind::InterfaceMethod,
+        };
+        if !valid {
+            return Err(ClassFormatError::new(format!(
+                "method handle {} of kind {} refers to {:?} {}.{}",
+                index, reference_kind, member.kind, member.class_name, member.name
+            )));
+        }
+        // Only REF_newInvokeSpecial may (and must) refer to <init>; no handle refers to <clinit>.
+        let special_name = member.name == "<init>" || member.name == "<clinit>";
+        let valid_name = match reference_kind {
+            ReferenceKind::NewInvokeSpecial => member.name == "<init>",
+            _ => member.kind == MemberKind::Field || !special_name,
+        };
+        if !valid_name {
+            return Err(ClassFormatError::new(format!(
+                "method handle {} of kind {} refers to method {}",
+                index, reference_kind, member.name
+            )));
+        }
+        Ok(MethodHandle {
+            reference_kind,
+            member,
+        })
+    }
+
+    // Any constant that ldc or a bootstrap method argument can refer to (JVMS 4.4, table
+    // 4.4-C), resolved.
+    pub fn loadable(&self, index: CpIndex) -> Result<LoadableConstant, ClassFormatError> {
+        Ok(match self.get(index)? {
+            Constant::Integer(c) => LoadableConstant::Integer(c.bytes),
+            Constant::Float(c) => LoadableConstant::Float(c.bytes),
+            Constant::Long(c) => LoadableConstant::Long(c.bytes),
+            Constant::Double(c) => LoadableConstant::Double(c.bytes),
+            Constant::Class(c) => LoadableConstant::Class(self.utf8(c.name_index)?),
+            Constant::String(c) => LoadableConstant::String(self.utf8(c.string_index)?),
+            Constant::MethodHandle(_) => LoadableConstant::MethodHandle(self.method_handle(index)?),
+            Constant::MethodType(c) => LoadableConstant::MethodType(self.utf8(c.descriptor_index)?),
+            Constant::Dynamic(c) => {
+                let (name, descriptor) = self.name_and_type(c.name_and_type_index)?;
+                LoadableConstant::Dynamic(DynamicConstant {
+                    bootstrap_method_attr_index: c.bootstrap_method_attr_index,
+                    name,
+                    descriptor,
+                })
+            }
+            c => return Err(ConstantPool::mismatch(index, "loadable", c)),
+        })
+    }
+
+    // The bootstrap method index, name and descriptor of a CONSTANT_InvokeDynamic or
+    // CONSTANT_Dynamic; resolve the bootstrap method with Class::dynamic_call_site.
+    pub fn dynamic(
+        &self,
+        index: CpIndex,
+    ) -> Result<(DynamicKind, DynamicConstant), ClassFormatError> {
+        let (kind, bootstrap_method_attr_index, name_and_type_index) = match self.get(index)? {
+            Constant::InvokeDynamic(c) => (
+                DynamicKind::InvokeDynamic,
+                c.bootstrap_method_attr_index,
+                c.name_and_type_index,
+            ),
+            Constant::Dynamic(c) => (
+                DynamicKind::Dynamic,
+                c.bootstrap_method_attr_index,
+                c.name_and_type_index,
+            ),
+            c => return Err(ConstantPool::mismatch(index, "InvokeDynamic or Dynamic", c)),
+        };
+        let (name, descriptor) = self.name_and_type(name_and_type_index)?;
+        Ok((
+            kind,
+            DynamicConstant {
+                bootstrap_method_attr_index,
+                name,
+                descriptor,
+            },
+        ))
+    }
+
+    pub fn module_name(&self, index: CpIndex) -> Result<String, ClassFormatError> {
+        match self.get(index)? {
+            Constant::Module(c) => self.utf8(c.name_index),
+            c => Err(ConstantPool::mismatch(index, "Module", c)),
+        }
+    }
+
+    pub fn package_name(&self, index: CpIndex) -> Result<String, ClassFormatError> {
+        match self.get(index)? {
+            Constant::Package(c) => self.utf8(c.name_index),
+            c => Err(ConstantPool::mismatch(index, "Package", c)),
+        }
+    }
+
+    // The name of a Module constant with its index.
+    pub fn module_ref(&self, index: CpIndex) -> Result<Indexed<String>, ClassFormatError> {
+        Ok(Indexed::at(self.module_name(index)?, index))
+    }
+
+    // The name of a Package constant with its index.
+    pub fn package_ref(&self, index: CpIndex) -> Result<Indexed<String>, ClassFormatError> {
+        Ok(Indexed::at(self.package_name(index)?, index))
+    }
+
+    // A value resolved by `resolve` with its index.
+    pub fn indexed<T>(
+        &self,
+        index: CpIndex,
+        resolve: impl FnOnce(&ConstantPool, CpIndex) -> Result<T, ClassFormatError>,
+    ) -> Result<Indexed<T>, ClassFormatError> {
+        Ok(Indexed::at(resolve(self, index)?, index))
+    }
+
+    fn mismatch(index: CpIndex, expected: &str, found: &Constant) -> ClassFormatError {
+        ClassFormatError::new(format!(
+            "constant pool index {} is not a {} constant: {:?}",
+            index, expected, found
+        ))
+    }
+
+    // Reads a u2 constant pool index and resolves it to a Utf8 string.
+    pub fn read_utf8(&self, reader: &mut BytecodeReader) -> Result<String, ClassFormatError> {
+        let offset = reader.offset;
+        let index = CpIndex::read(reader)?;
+        self.utf8(index).map_err(|e| e.with_offset(offset))
+    }
+
+    // Like read_utf8, but keeps the index as well.
+    pub fn read_utf8_ref(
+        &self,
+        reader: &mut BytecodeReader,
+    ) -> Result<Indexed<String>, ClassFormatError> {
+        let offset = reader.offset;
+        let index = CpIndex::read(reader)?;
+        self.utf8_ref(index).map_err(|e| e.with_offset(offset))
+    }
+
+    // Reads a u2 constant pool index and resolves it to the name of a Class constant.
+    pub fn read_class(&self, reader: &mut BytecodeReader) -> Result<String, ClassFormatError> {
+        let offset = reader.offset;
+        let index = CpIndex::read(reader)?;
+        self.class_name(index).map_err(|e| e.with_offset(offset))
+    }
+
+    // Like read_class, but keeps the index as well.
+    pub fn read_class_ref(
+        &self,
+        reader: &mut BytecodeReader,
+    ) -> Result<ClassRef, ClassFormatError> {
+        let offset = reader.offset;
+        let index = CpIndex::read(reader)?;
+        self.class_ref(index).map_err(|e| e.with_offset(offset))
+    }
+
+    // Like read_class_ref, but index 0 is allowed and read as None.
+    pub fn read_optional_class(
+        &self,
+        reader: &mut BytecodeReader,
+    ) -> Result<Option<ClassRef>, ClassFormatError> {
+        let offset = reader.offset;
+        let index = CpIndex::read(reader)?;
+        if index.is_none() {
+            return Ok(None);
+        }
+        self.class_ref(index)
+            .map(Some)
+            .map_err(|e| e.with_offset(offset))
+    }
+}
+
+impl Index<CpIndex> for ConstantPool {
+    type Output = Constant;
+
+    fn index(&self, index: CpIndex) -> &Constant {
+        &self.entries[index.0 as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberKind {
+    Field,
+    Method,
+    InterfaceMethod,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberRef {
+    pub kind: MemberKind,
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+impl fmt::Display for MemberRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}:{}", self.class_name, self.name, self.descriptor)
+    }
+}
+
+// reference_kind of a CONSTANT_MethodHandle (JVMS 5.4.3.5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    GetField = 1,
+    GetStatic = 2,
+    PutField = 3,
+    PutStatic = 4,
+    InvokeVirtual = 5,
+    InvokeStatic = 6,
+    InvokeSpecial = 7,
+    NewInvokeSpecial = 8,
+    InvokeInterface = 9,
+}
+
+impl ReferenceKind {
+    pub fn from_u8(reference_kind: u8) -> Option<ReferenceKind> {
+        Some(match reference_kind {
+            1 => ReferenceKind::GetField,
+            2 => ReferenceKind::GetStatic,
+            3 => ReferenceKind::PutField,
+            4 => ReferenceKind::PutStatic,
+            5 => ReferenceKind::InvokeVirtual,
+            6 => ReferenceKind::InvokeStatic,
+            7 => ReferenceKind::InvokeSpecial,
+            8 => ReferenceKind::NewInvokeSpecial,
+            9 => ReferenceKind::InvokeInterface,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReferenceKind::GetField => "REF_getField",
+            ReferenceKind::GetStatic => "REF_getStatic",
+            ReferenceKind::PutField => "REF_putField",
+            ReferenceKind::PutStatic => "REF_putStatic",
+            ReferenceKind::InvokeVirtual => "REF_invokeVirtual",
+            ReferenceKind::InvokeStatic => "REF_invokeStatic",
+            ReferenceKind::InvokeSpecial => "REF_invokeSpecial",
+            ReferenceKind::NewInvokeSpecial => "REF_newInvokeSpecial",
+            ReferenceKind::InvokeInterface => "REF_invokeInterface",
+        }
+    }
+}
+
+impl fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodHandle {
+    pub reference_kind: ReferenceKind,
+    pub member: MemberRef,
+}
+
+impl fmt::Display for MethodHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.reference_kind, self.member)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DynamicKind {
+    InvokeDynamic, // call site of an invokedynamic instruction
+    Dynamic,       // dynamically-computed constant (condy)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicConstant {
+    pub bootstrap_method_attr_index: u16, // index into the BootstrapMethods attribute
+    pub name: String,
+    pub descriptor: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadableConstant {
+    Integer(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    Class(String),
+    String(String),
+    MethodHandle(MethodHandle),
+    MethodType(String),
+    Dynamic(DynamicConstant),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // constant_pool_count followed by the entries, as they appear in a class file.
+    fn pool(count: u16, entries: &[&[u8]]) -> Result<ConstantPool, ClassFormatError> {
+        let mut reader = BytecodeReader::new(entries.concat());
+        ConstantPool::new(&mut reader, count)
+    }
+
+    #[test]
+    fn long_and_double_take_two_slots() {
+        let constant_pool = pool(
+            7,
+            &[
+                &[0x05, 0, 0, 0, 1, 0, 0, 0, 2], // #1 Long, #2 unusable
+                &[0x03, 0xFF, 0xFF, 0xFF, 0xFE], // #3 Integer -2
+                &[0x06, 0x40, 0x09, 0x21, 0xFB, 0x54, 0x44, 0x2D, 0x18], // #4 Double pi, #5
+                &[0x01, 0, 2, b'h', b'i'],       // #6 Utf8 "hi"
+            ],
+        )
+        .unwrap();
+        assert_eq!(constant_pool.count(), 7);
+        assert_eq!(constant_pool.long(CpIndex(1)).unwrap(), 0x1_0000_0002);
+        assert_eq!(constant_pool.integer(CpIndex(3)).unwrap(), -2);
+        assert_eq!(
+            constant_pool.double(CpIndex(4)).unwrap(),
+            std::f64::consts::PI
+        );
+        assert_eq!(constant_pool.utf8(CpIndex(6)).unwrap(), "hi");
+        let indices: Vec<u16> = constant_pool.iter().map(|(index, _)| index.0).collect();
+        assert_eq!(indices, vec![1, 3, 4, 6]);
+        for unusable in [CpIndex(2), CpIndex(5)] {
+            let error = constant_pool.get(unusable).unwrap_err();
+            assert!(error.reason.contains("unusable slot"), "{}", error);
+        }
+        for out_of_range in [CpIndex(0), CpIndex(7)] {
+            let error = constant_pool.get(out_of_range).unwrap_err();
+            assert!(error.reason.contains("out of range"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn last_entry_cannot_be_wide() {
+        let error = pool(2, &[&[0x05, 0, 0, 0, 0, 0, 0, 0, 1]]).unwrap_err();
+        assert!(error.reason.contains("needs two slots"), "{}", error);
+        assert_eq!(error.offset, Some(0));
+    }
+
+    #[test]
+    fn count_must_be_at_least_one() {
+        let mut reader = BytecodeReader::new(vec![0, 0]);
+        reader.u16().unwrap();
+        let error = ConstantPool::new(&mut reader, 0).unwrap_err();
+        assert_eq!(error.offset, Some(0));
+    }
+
+    #[test]
+    fn nan_payloads_are_kept() {
+        // A signalling float NaN and a double NaN with a payload and the sign bit set.
+        let constant_pool = pool(
+            4,
+            &[
+                &[0x04, 0x7F, 0x80, 0x00, 0x01],
+                &[0x06, 0xFF, 0xF0, 0x00, 0x00, 0x12, 0x34, 0x56, 0x78],
+            ],
+        )
+        .unwrap();
+        let float = constant_pool.float(CpIndex(1)).unwrap();
+        assert!(float.is_nan());
+        assert_eq!(float.to_bits(), 0x7F80_0001);
+        let double = constant_pool.double(CpIndex(2)).unwrap();
+        assert!(double.is_nan());
+        assert_eq!(double.to_bits(), 0xFFF0_0000_1234_5678);
+    }
+}
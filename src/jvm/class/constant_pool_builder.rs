@@ -0,0 +1,395 @@
+use std::{borrow::Borrow, collections::HashMap};
+
+use crate::jvm::class::{constant::*, constant_pool::*, error::ClassFormatError};
+
+// Finds or adds constants by value. Seeded from the pool of a parsed class it hands back the
+// original index of every constant already there (the first one if the pool has duplicates,
+// which is why ClassRef and Indexed keep the index they were read from), so writing an
+// unmodified class reproduces its pool exactly; anything new is appended.
+#[derive(Debug, Clone)]
+pub struct ConstantPoolBuilder {
+    constant_pool: ConstantPool,
+    indices: HashMap<ConstantKey, CpIndex>,
+}
+
+// A constant with its references as indices, so equal constants have equal keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Utf8(Vec<u16>), // UTF-16 code units, so unpaired surrogates are kept apart
+    Integer(i32),
+    Float(u32), // bits, so every NaN is kept apart
+    Long(i64),
+    Double(u64),
+    Class(CpIndex),
+    String(CpIndex),
+    Fieldref(CpIndex, CpIndex),
+    Methodref(CpIndex, CpIndex),
+    InterfaceMethodref(CpIndex, CpIndex),
+    NameAndType(CpIndex, CpIndex),
+    MethodHandle(u8, CpIndex),
+    MethodType(CpIndex),
+    Dynamic(u16, CpIndex),
+    InvokeDynamic(u16, CpIndex),
+    Module(CpIndex),
+    Package(CpIndex),
+}
+
+impl ConstantKey {
+    fn new(constant: &Constant) -> Option<ConstantKey> {
+        Some(match constant {
+            Constant::Utf8(c) => ConstantKey::Utf8(c.utf16.clone()),
+            Constant::Integer(c) => ConstantKey::Integer(c.bytes),
+            Constant::Float(c) => ConstantKey::Float(c.bytes.to_bits()),
+            Constant::Long(c) => ConstantKey::Long(c.bytes),
+            Constant::Double(c) => ConstantKey::Double(c.bytes.to_bits()),
+            Constant::Class(c) => ConstantKey::Class(c.name_index),
+            Constant::String(c) => ConstantKey::String(c.string_index),
+            Constant::Fieldref(c) => ConstantKey::Fieldref(c.class_index, c.name_and_type_index),
+            Constant::Methodref(c) => ConstantKey::Methodref(c.class_index, c.name_and_type_index),
+            Constant::InterfaceMethodref(c) => {
+                ConstantKey::InterfaceMethodref(c.class_index, c.name_and_type_index)
+            }
+            Constant::NameAndType(c) => ConstantKey::NameAndType(c.name_index, c.descriptor_index),
+            Constant::MethodHandle(c) => {
+                ConstantKey::MethodHandle(c.reference_kind, c.reference_index)
+            }
+            Constant::MethodType(c) => ConstantKey::MethodType(c.descriptor_index),
+            Constant::Dynamic(c) => {
+                ConstantKey::Dynamic(c.bootstrap_method_attr_index, c.name_and_type_index)
+            }
+            Constant::InvokeDynamic(c) => {
+                ConstantKey::InvokeDynamic(c.bootstrap_method_attr_index, c.name_and_type_index)
+            }
+            Constant::Module(c) => ConstantKey::Module(c.name_index),
+            Constant::Package(c) => ConstantKey::Package(c.name_index),
+            Constant::Unusable => return None,
+        })
+    }
+}
+
+impl Default for ConstantPoolBuilder {
+    fn default() -> Self {
+        ConstantPoolBuilder::new()
+    }
+}
+
+impl ConstantPoolBuilder {
+    pub fn new() -> ConstantPoolBuilder {
+        ConstantPoolBuilder {
+            constant_pool: ConstantPool {
+                entries: vec![Constant::Unusable],
+            },
+            indices: HashMap::new(),
+        }
+    }
+
+    pub fn from_pool(constant_pool: &ConstantPool) -> ConstantPoolBuilder {
+        let mut indices: HashMap<ConstantKey, CpIndex> = HashMap::new();
+        for (index, constant) in constant_pool.iter() {
+            if let Some(key) = ConstantKey::new(constant) {
+                indices.entry(key).or_insert(index);
+            }
+        }
+        ConstantPoolBuilder {
+            constant_pool: constant_pool.clone(),
+            indices,
+        }
+    }
+
+    // `original`, where a constant was read from, while it still resolves to `value`, else
+    // the constant `add` finds or adds. Writers refer to such an index directly, so the uses
+    // of duplicate constants are kept apart.
+    fn reuse<T: PartialEq + ?Sized, R: Borrow<T>>(
+        &mut self,
+        original: Option<CpIndex>,
+        value: &T,
+        resolve: impl FnOnce(&ConstantPool, CpIndex) -> Result<R, ClassFormatError>,
+        add: impl FnOnce(&mut ConstantPoolBuilder) -> Result<CpIndex, ClassFormatError>,
+    ) -> Result<CpIndex, ClassFormatError> {
+        if let Some(index) = original {
+            if resolve(&self.constant_pool, index).is_ok_and(|resolved| resolved.borrow() == value)
+            {
+                return Ok(index);
+            }
+        }
+        add(self)
+    }
+
+    // The index of a value read with ConstantPool::indexed and the same `resolve`.
+    pub fn indexed<T: PartialEq>(
+        &mut self,
+        value: &Indexed<T>,
+        resolve: impl FnOnce(&ConstantPool, CpIndex) -> Result<T, ClassFormatError>,
+        add: impl FnOnce(&mut ConstantPoolBuilder) -> Result<CpIndex, ClassFormatError>,
+    ) -> Result<CpIndex, ClassFormatError> {
+        self.reuse(value.index, &value.value, resolve, add)
+    }
+
+    pub fn build(self) -> ConstantPool {
+        self.constant_pool
+    }
+
+    // The pool built so far, to resolve the indices handed out.
+    pub fn constant_pool(&self) -> &ConstantPool {
+        &self.constant_pool
+    }
+
+    // constant_pool_count of the pool built so far.
+    pub fn count(&self) -> u16 {
+        self.constant_pool.entries.len() as u16
+    }
+
+    fn add(&mut self, constant: Constant) -> Result<CpIndex, ClassFormatError> {
+        let key = ConstantKey::new(&constant).expect("Unusable is never added");
+        if let Some(index) = self.indices.get(&key) {
+            return Ok(*index);
+        }
+        let slots = if constant.is_wide() { 2 } else { 1 };
+        if self.constant_pool.entries.len() + slots > u16::MAX as usize {
+            return Err(ClassFormatError::new(
+                "too many constants, constant_pool_count would exceed 65535",
+            ));
+        }
+        let index = CpIndex(self.constant_pool.entries.len() as u16);
+        let is_wide = constant.is_wide();
+        self.constant_pool.entries.push(constant);
+        if is_wide {
+            self.constant_pool.entries.push(Constant::Unusable);
+        }
+        self.indices.insert(key, index);
+        Ok(index)
+    }
+
+    pub fn utf8(&mut self, s: &str) -> Result<CpIndex, ClassFormatError> {
+        self.utf16(&s.encode_utf16().collect::<Vec<u16>>())
+    }
+
+    // A Utf8 constant of UTF-16 code units, which unlike a str may hold unpaired surrogates.
+    pub fn utf16(&mut self, utf16: &[u16]) -> Result<CpIndex, ClassFormatError> {
+        if let Some(index) = self.indices.get(&ConstantKey::Utf8(utf16.to_vec())) {
+            return Ok(*index);
+        }
+        let utf8 = ConstantUtf8::from_utf16(utf16.to_vec());
+        if utf8.encode().len() > u16::MAX as usize {
+            return Err(ClassFormatError::new(format!(
+                "string of {} UTF-16 code units is too long for a Utf8 constant",
+                utf16.len()
+            )));
+        }
+        self.add(Constant::Utf8(utf8))
+    }
+
+    pub fn utf8_ref(&mut self, s: &Indexed<String>) -> Result<CpIndex, ClassFormatError> {
+        self.indexed(s, ConstantPool::utf8, |builder| builder.utf8(s))
+    }
+
+    pub fn integer(&mut self, value: i32) -> Result<CpIndex, ClassFormatError> {
+        self.add(Constant::Integer(ConstantInteger { bytes: value }))
+    }
+
+    pub fn float(&mut self, value: f32) -> Result<CpIndex, ClassFormatError> {
+        self.add(Constant::Float(ConstantFloat { bytes: value }))
+    }
+
+    pub fn long(&mut self, value: i64) -> Result<CpIndex, ClassFormatError> {
+        self.add(Constant::Long(ConstantLong { bytes: value }))
+    }
+
+    pub fn double(&mut self, value: f64) -> Result<CpIndex, ClassFormatError> {
+        self.add(Constant::Double(ConstantDouble { bytes: value }))
+    }
+
+    pub fn class(&mut self, class_name: &str) -> Result<CpIndex, ClassFormatError> {
+        let name_index = self.utf8(class_name)?;
+        self.add(Constant::Class(ConstantClass { name_index }))
+    }
+
+    pub fn class_ref(&mut self, class: &ClassRef) -> Result<CpIndex, ClassFormatError> {
+        self.reuse(
+            class.index,
+            &class.name,
+            ConstantPool::class_name,
+            |builder| builder.class(&class.name),
+        )
+    }
+
+    pub fn string(&mut self, s: &str) -> Result<CpIndex, ClassFormatError> {
+        let string_index = self.utf8(s)?;
+        self.add(Constant::String(ConstantString { string_index }))
+    }
+
+    // A String constant of UTF-16 code units, see utf16.
+    pub fn string_utf16(&mut self, utf16: &[u16]) -> Result<CpIndex, ClassFormatError> {
+        let string_index = self.utf16(utf16)?;
+        self.add(Constant::String(ConstantString { string_index }))
+    }
+
+    pub fn name_and_type(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<CpIndex, ClassFormatError> {
+        let name_index = self.utf8(name)?;
+        let descriptor_index = self.utf8(descriptor)?;
+        self.add(Constant::NameAndType(ConstantNameAndType {
+            name_index,
+            descriptor_index,
+        }))
+    }
+
+    // A Fieldref, Methodref or InterfaceMethodref depending on `member.kind`.
+    pub fn member_ref(&mut self, member: &MemberRef) -> Result<CpIndex, ClassFormatError> {
+        let class_index = self.class(&member.class_name)?;
+        let name_and_type_index = self.name_and_type(&member.name, &member.descriptor)?;
+        self.add(match member.kind {
+            MemberKind::Field => Constant::Fieldref(ConstantFieldref {
+                class_index,
+                name_and_type_index,
+            }),
+            MemberKind::Method => Constant::Methodref(ConstantMethodref {
+                class_index,
+                name_and_type_index,
+            }),
+            MemberKind::InterfaceMethod => {
+                Constant::InterfaceMethodref(ConstantInterfaceMethodref {
+                    class_index,
+                    name_and_type_index,
+                })
+            }
+        })
+    }
+
+    pub fn method_handle(&mut self, handle: &MethodHandle) -> Result<CpIndex, ClassFormatError> {
+        let reference_index = self.member_ref(&handle.member)?;
+        self.add(Constant::MethodHandle(ConstantMethodHandle {
+            reference_kind: handle.reference_kind as u8,
+            reference_index,
+        }))
+    }
+
+    // Like method_handle, but `original` is used while it holds the handle.
+    pub fn method_handle_at(
+        &mut self,
+        handle: &MethodHandle,
+        original: CpIndex,
+    ) -> Result<CpIndex, ClassFormatError> {
+        self.reuse(
+            Some(original),
+            handle,
+            ConstantPool::method_handle,
+            |builder| builder.method_handle(handle),
+        )
+    }
+
+    pub fn method_type(&mut self, descriptor: &str) -> Result<CpIndex, ClassFormatError> {
+        let descriptor_index = self.utf8(descriptor)?;
+        self.add(Constant::MethodType(ConstantMethodType {
+            descriptor_index,
+        }))
+    }
+
+    pub fn dynamic(
+        &mut self,
+        kind: DynamicKind,
+        constant: &DynamicConstant,
+    ) -> Result<CpIndex, ClassFormatError> {
+        let bootstrap_method_attr_index = constant.bootstrap_method_attr_index;
+        let name_and_type_index = self.name_and_type(&constant.name, &constant.descriptor)?;
+        self.add(match kind {
+            DynamicKind::Dynamic => Constant::Dynamic(ConstantDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }),
+            DynamicKind::InvokeDynamic => Constant::InvokeDynamic(ConstantInvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }),
+        })
+    }
+
+    // The constant an ldc or a bootstrap argument refers to.
+    pub fn loadable(&mut self, constant: &LoadableConstant) -> Result<CpIndex, ClassFormatError> {
+        match constant {
+            LoadableConstant::Integer(value) => self.integer(*value),
+            LoadableConstant::Float(value) => self.float(*value),
+            LoadableConstant::Long(value) => self.long(*value),
+            LoadableConstant::Double(value) => self.double(*value),
+            LoadableConstant::Class(class_name) => self.class(class_name),
+            LoadableConstant::String(s) => self.string(s),
+            LoadableConstant::MethodHandle(handle) => self.method_handle(handle),
+            LoadableConstant::MethodType(descriptor) => self.method_type(descriptor),
+            LoadableConstant::Dynamic(dynamic) => self.dynamic(DynamicKind::Dynamic, dynamic),
+        }
+    }
+
+    // Like loadable, but `original` is used while it holds the constant.
+    pub fn loadable_at(
+        &mut self,
+        constant: &LoadableConstant,
+        original: CpIndex,
+    ) -> Result<CpIndex, ClassFormatError> {
+        self.reuse(
+            Some(original),
+            constant,
+            ConstantPool::loadable,
+            |builder| builder.loadable(constant),
+        )
+    }
+
+    pub fn module(&mut self, module_name: &str) -> Result<CpIndex, ClassFormatError> {
+        let name_index = self.utf8(module_name)?;
+        self.add(Constant::Module(ConstantModule { name_index }))
+    }
+
+    pub fn module_ref(&mut self, module: &Indexed<String>) -> Result<CpIndex, ClassFormatError> {
+        self.indexed(module, ConstantPool::module_name, |builder| {
+            builder.module(module)
+        })
+    }
+
+    pub fn package(&mut self, package_name: &str) -> Result<CpIndex, ClassFormatError> {
+        let name_index = self.utf8(package_name)?;
+        self.add(Constant::Package(ConstantPackage { name_index }))
+    }
+
+    pub fn package_ref(&mut self, package: &Indexed<String>) -> Result<CpIndex, ClassFormatError> {
+        self.indexed(package, ConstantPool::package_name, |builder| {
+            builder.package(package)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class_ref_keeps_its_index_while_it_names_the_class() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let first = constant_pool.class("A").unwrap();
+        let name_index = constant_pool.utf8("A").unwrap();
+        // a duplicate, as a parsed pool may have
+        constant_pool
+            .constant_pool
+            .entries
+            .push(Constant::Class(ConstantClass { name_index }));
+        let second = CpIndex(constant_pool.count() - 1);
+
+        let read = ClassRef {
+            name: String::from("A"),
+            index: Some(second),
+        };
+        assert_eq!(constant_pool.class_ref(&read).unwrap(), second);
+        assert_eq!(constant_pool.class_ref(&ClassRef::new("A")).unwrap(), first);
+        // renamed in the model, so the index no longer applies
+        let renamed = ClassRef {
+            name: String::from("B"),
+            index: Some(second),
+        };
+        let index = constant_pool.class_ref(&renamed).unwrap();
+        assert_eq!(
+            constant_pool.constant_pool().class_name(index).unwrap(),
+            "B"
+        );
+    }
+}
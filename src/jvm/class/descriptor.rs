@@ -0,0 +1,374 @@
+use std::fmt;
+
+use crate::jvm::class::error::ClassFormatError;
+
+// FieldType (JVMS 4.3.2)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Byte,           // B
+    Char,           // C
+    Double,         // D
+    Float,          // F
+    Int,            // I
+    Long,           // J
+    Short,          // S
+    Boolean,        // Z
+    Object(String), // L ClassName ;
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn parse(descriptor: &str) -> Result<FieldType, ClassFormatError> {
+        let mut parser = DescriptorParser::new(descriptor);
+        let field_type = parser.field_type()?;
+        parser.end()?;
+        Ok(field_type)
+    }
+
+    // Number of local variable / operand stack slots a value of this type occupies.
+    pub fn slot_size(&self) -> u16 {
+        if self.is_category2() {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn is_category2(&self) -> bool {
+        matches!(self, FieldType::Long | FieldType::Double)
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(self, FieldType::Object(_) | FieldType::Array(_))
+    }
+
+    // Java source syntax, e.g. "java.lang.String[]".
+    pub fn java_name(&self) -> String {
+        match self {
+            FieldType::Byte => String::from("byte"),
+            FieldType::Char => String::from("char"),
+            FieldType::Double => String::from("double"),
+            FieldType::Float => String::from("float"),
+            FieldType::Int => String::from("int"),
+            FieldType::Long => String::from("long"),
+            FieldType::Short => String::from("short"),
+            FieldType::Boolean => String::from("boolean"),
+            FieldType::Object(class_name) => class_name.replace('/', "."),
+            FieldType::Array(component) => format!("{}[]", component.java_name()),
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldType::Byte => write!(f, "B"),
+            FieldType::Char => write!(f, "C"),
+            FieldType::Double => write!(f, "D"),
+            FieldType::Float => write!(f, "F"),
+            FieldType::Int => write!(f, "I"),
+            FieldType::Long => write!(f, "J"),
+            FieldType::Short => write!(f, "S"),
+            FieldType::Boolean => write!(f, "Z"),
+            FieldType::Object(class_name) => write!(f, "L{};", class_name),
+            FieldType::Array(component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+// MethodDescriptor (JVMS 4.3.3), a `None` return type is void.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    pub return_type: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor: &str) -> Result<MethodDescriptor, ClassFormatError> {
+        let mut parser = DescriptorParser::new(descriptor);
+        parser.expect(b'(')?;
+        let mut parameters: Vec<FieldType> = Vec::new();
+        while parser.peek() != Some(b')') {
+            parameters.push(parser.field_type()?);
+        }
+        parser.expect(b')')?;
+        let return_type = if parser.peek() == Some(b'V') {
+            parser.pos += 1;
+            None
+        } else {
+            Some(parser.field_type()?)
+        };
+        parser.end()?;
+        if parameters.iter().map(|p| p.slot_size()).sum::<u16>() > 255 {
+            return Err(parser.error("parameters take more than 255 slots"));
+        }
+        Ok(MethodDescriptor {
+            parameters,
+            return_type,
+        })
+    }
+
+    // Slots taken by the parameters, not counting `this`.
+    pub fn parameter_slots(&self) -> u16 {
+        self.parameters.iter().map(|p| p.slot_size()).sum()
+    }
+
+    pub fn return_slots(&self) -> u16 {
+        self.return_type.as_ref().map_or(0, |r| r.slot_size())
+    }
+
+    // Java source syntax for a method with the given name, e.g. "int add(int, int, int)".
+    pub fn java_signature(&self, name: &str) -> String {
+        let return_type = match &self.return_type {
+            Some(r) => r.java_name(),
+            None => String::from("void"),
+        };
+        let parameters: Vec<String> = self.parameters.iter().map(|p| p.java_name()).collect();
+        format!("{} {}({})", return_type, name, parameters.join(", "))
+    }
+}
+
+impl fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for p in &self.parameters {
+            write!(f, "{}", p)?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(r) => write!(f, "{}", r),
+            None => write!(f, "V"),
+        }
+    }
+}
+
+struct DescriptorParser<'a> {
+    descriptor: &'a str,
+    pos: usize,
+}
+
+impl<'a> DescriptorParser<'a> {
+    fn new(descriptor: &'a str) -> DescriptorParser<'a> {
+        DescriptorParser { descriptor, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.descriptor.as_bytes().get(self.pos).copied()
+    }
+
+    fn error(&self, reason: &str) -> ClassFormatError {
+        ClassFormatError::new(format!(
+            "invalid descriptor \"{}\" at {}: {}",
+            self.descriptor, self.pos, reason
+        ))
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), ClassFormatError> {
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("expected '{}'", c as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn end(&self) -> Result<(), ClassFormatError> {
+        if self.pos != self.descriptor.len() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(())
+    }
+
+    fn field_type(&mut self) -> Result<FieldType, ClassFormatError> {
+        let mut dimensions = 0;
+        while self.peek() == Some(b'[') {
+            dimensions += 1;
+            self.pos += 1;
+        }
+        if dimensions > 255 {
+            return Err(self.error("array type has more than 255 dimensions"));
+        }
+        let base = match self.peek() {
+            Some(b'B') => FieldType::Byte,
+            Some(b'C') => FieldType::Char,
+            Some(b'D') => FieldType::Double,
+            Some(b'F') => FieldType::Float,
+            Some(b'I') => FieldType::Int,
+            Some(b'J') => FieldType::Long,
+            Some(b'S') => FieldType::Short,
+            Some(b'Z') => FieldType::Boolean,
+            Some(b'L') => {
+                let start = self.pos + 1;
+                let end = match self.descriptor[start..].find(';') {
+                    Some(i) => start + i,
+                    None => return Err(self.error("unterminated class name")),
+                };
+                let class_name = &self.descriptor[start..end];
+                if !is_binary_name(class_name) {
+                    return Err(self.error(&format!("invalid class name \"{}\"", class_name)));
+                }
+                self.pos = end;
+                FieldType::Object(String::from(class_name))
+            }
+            Some(_) => return Err(self.error("unknown type")),
+            None => return Err(self.error("unexpected end")),
+        };
+        self.pos += 1;
+        let mut field_type = base;
+        for _ in 0..dimensions {
+            field_type = FieldType::Array(Box::new(field_type));
+        }
+        Ok(field_type)
+    }
+}
+
+// A binary class name in internal form (JVMS 4.2.1): '/' separated, non-empty unqualified
+// names that contain none of . ; [ /
+pub fn is_binary_name(class_name: &str) -> bool {
+    class_name
+        .split('/')
+        .all(|s| !s.is_empty() && !s.contains(['.', ';', '[']))
+}
+
+// An unqualified name of a field or record component (JVMS 4.2.2).
+pub fn is_unqualified_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['.', ';', '[', '/'])
+}
+
+// A method name is an unqualified name without < and >, or one of the special names.
+pub fn is_method_name(name: &str) -> bool {
+    name == "<init>"
+        || name == "<clinit>"
+        || (is_unqualified_name(name) && !name.contains(['<', '>']))
+}
+
+// The name in a CONSTANT_Class: a binary name, or an array type descriptor.
+pub fn is_class_constant_name(name: &str) -> bool {
+    if name.starts_with('[') {
+        FieldType::parse(name).is_ok()
+    } else {
+        is_binary_name(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array(component: FieldType) -> FieldType {
+        FieldType::Array(Box::new(component))
+    }
+
+    #[test]
+    fn parses_and_prints_field_types() {
+        let string = FieldType::Object(String::from("java/lang/String"));
+        assert_eq!(FieldType::parse("I").unwrap(), FieldType::Int);
+        assert_eq!(
+            FieldType::parse("Ljava/lang/String;").unwrap(),
+            string.clone()
+        );
+        let matrix = FieldType::parse("[[Ljava/lang/String;").unwrap();
+        assert_eq!(matrix, array(array(string)));
+        assert_eq!(matrix.to_string(), "[[Ljava/lang/String;");
+        assert_eq!(matrix.java_name(), "java.lang.String[][]");
+        assert_eq!(FieldType::parse("[Z").unwrap().java_name(), "boolean[]");
+        assert!(matrix.is_reference());
+        assert!(!FieldType::Long.is_reference());
+    }
+
+    #[test]
+    fn long_and_double_take_two_slots() {
+        let descriptor = MethodDescriptor::parse("(IJ[DLjava/lang/Object;D)J").unwrap();
+        let slots: Vec<u16> = descriptor
+            .parameters
+            .iter()
+            .map(|p| p.slot_size())
+            .collect();
+        assert_eq!(slots, vec![1, 2, 1, 1, 2]);
+        assert_eq!(descriptor.parameter_slots(), 7);
+        assert_eq!(descriptor.return_slots(), 2);
+        assert_eq!(MethodDescriptor::parse("()V").unwrap().return_slots(), 0);
+    }
+
+    #[test]
+    fn parses_and_prints_method_descriptors() {
+        let descriptor = MethodDescriptor::parse("(I[Ljava/util/List;)V").unwrap();
+        assert_eq!(descriptor.return_type, None);
+        assert_eq!(descriptor.to_string(), "(I[Ljava/util/List;)V");
+        assert_eq!(
+            descriptor.java_signature("f"),
+            "void f(int, java.util.List[])"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_descriptors() {
+        for (descriptor, reason) in [
+            ("", "unexpected end"),
+            ("V", "unknown type"),
+            ("Q", "unknown type"),
+            ("Ljava/lang/String", "unterminated class name"),
+            ("L;", "invalid class name \"\""),
+            ("Ljava.lang.String;", "invalid class name"),
+            ("La//b;", "invalid class name"),
+            ("II", "trailing characters"),
+            ("[", "unexpected end"),
+        ] {
+            let error = FieldType::parse(descriptor).unwrap_err();
+            assert!(error.reason.contains(reason), "{}: {}", descriptor, error);
+        }
+        for (descriptor, reason) in [
+            ("I)V", "expected '('"),
+            ("(I", "unexpected end"),
+            ("(V)V", "unknown type"),
+            ("()", "unexpected end"),
+            ("()VV", "trailing characters"),
+        ] {
+            let error = MethodDescriptor::parse(descriptor).unwrap_err();
+            assert!(error.reason.contains(reason), "{}: {}", descriptor, error);
+        }
+    }
+
+    #[test]
+    fn array_dimensions_are_limited_to_255() {
+        let descriptor = format!("{}I", "[".repeat(255));
+        assert_eq!(
+            FieldType::parse(&descriptor).unwrap().to_string(),
+            descriptor
+        );
+        let error = FieldType::parse(&format!("[{}", descriptor)).unwrap_err();
+        assert!(
+            error.reason.contains("more than 255 dimensions"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn parameters_are_limited_to_255_slots() {
+        // 127 longs and an int take 255 slots; one more int is too many.
+        let descriptor = format!("({}I)V", "J".repeat(127));
+        assert_eq!(
+            MethodDescriptor::parse(&descriptor)
+                .unwrap()
+                .parameter_slots(),
+            255
+        );
+        let error = MethodDescriptor::parse(&format!("({}II)V", "J".repeat(127))).unwrap_err();
+        assert!(error.reason.contains("more than 255 slots"), "{}", error);
+        let error = MethodDescriptor::parse(&format!("({})V", "D".repeat(128))).unwrap_err();
+        assert!(error.reason.contains("more than 255 slots"), "{}", error);
+    }
+
+    #[test]
+    fn checks_names() {
+        assert!(is_binary_name("java/lang/Object"));
+        assert!(!is_binary_name("java/lang/"));
+        assert!(!is_binary_name("[I"));
+        assert!(is_class_constant_name("[Ljava/lang/Object;"));
+        assert!(!is_class_constant_name("[V"));
+        assert!(is_method_name("<init>"));
+        assert!(!is_method_name("<lambda>"));
+        assert!(is_unqualified_name("lambda$main$0"));
+        assert!(!is_unqualified_name("a/b"));
+    }
+}
@@ -0,0 +1,110 @@
+use std::{error::Error, fmt};
+
+// As in Java, UnsupportedClassVersionError is a kind of ClassFormatError.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassFormatErrorKind {
+    ClassFormat,
+    UnsupportedClassVersion,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassFormatError {
+    pub kind: ClassFormatErrorKind,
+    // Byte offset in the class file where the malformed data starts, if known.
+    pub offset: Option<usize>,
+    // Outermost first, e.g. ["class Add", "method add(III)I", "attribute Code"].
+    pub location: Vec<String>,
+    pub reason: String,
+}
+
+impl ClassFormatError {
+    pub fn new(reason: impl Into<String>) -> ClassFormatError {
+        ClassFormatError {
+            kind: ClassFormatErrorKind::ClassFormat,
+            offset: None,
+            location: Vec::new(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn at(offset: usize, reason: impl Into<String>) -> ClassFormatError {
+        ClassFormatError {
+            kind: ClassFormatErrorKind::ClassFormat,
+            offset: Some(offset),
+            location: Vec::new(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn unsupported_version(reason: impl Into<String>) -> ClassFormatError {
+        ClassFormatError {
+            kind: ClassFormatErrorKind::UnsupportedClassVersion,
+            ..ClassFormatError::new(reason)
+        }
+    }
+
+    pub fn is_unsupported_version(&self) -> bool {
+        self.kind == ClassFormatErrorKind::UnsupportedClassVersion
+    }
+
+    pub fn with_offset(mut self, offset: usize) -> ClassFormatError {
+        if self.offset.is_none() {
+            self.offset = Some(offset);
+        }
+        self
+    }
+
+    pub fn within(mut self, location: impl Into<String>) -> ClassFormatError {
+        self.location.insert(0, location.into());
+        self
+    }
+}
+
+impl fmt::Display for ClassFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_unsupported_version() {
+            write!(f, "UnsupportedClassVersionError: ")?;
+        }
+        if !self.location.is_empty() {
+            write!(f, "{}: ", self.location.join(" > "))?;
+        }
+        write!(f, "{}", self.reason)?;
+        if let Some(offset) = self.offset {
+            write!(f, " (at offset 0x{:04X})", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ClassFormatError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_joins_location_outermost_first_and_keeps_the_first_offset() {
+        let error = ClassFormatError::at(0x1c, "unexpected end of data")
+            .with_offset(0x40)
+            .within("attribute Code")
+            .within("method f()V")
+            .within("class A");
+        assert_eq!(error.offset, Some(0x1c));
+        assert_eq!(
+            error.to_string(),
+            "class A > method f()V > attribute Code: unexpected end of data (at offset 0x001C)"
+        );
+        assert_eq!(ClassFormatError::new("bad").with_offset(3).offset, Some(3));
+    }
+
+    #[test]
+    fn unsupported_version_is_a_kind_of_class_format_error() {
+        let error = ClassFormatError::unsupported_version("major version 99 is too new");
+        assert!(error.is_unsupported_version());
+        assert!(!ClassFormatError::new("bad").is_unsupported_version());
+        assert_eq!(
+            error.within("class A").to_string(),
+            "UnsupportedClassVersionError: class A: major version 99 is too new"
+        );
+    }
+}
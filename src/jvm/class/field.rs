@@ -1,35 +1,77 @@
-use crate::jvm::class::{attribute::*, constant::*};
+use crate::jvm::class::{
+    access_flags::FieldAccessFlags, attribute::*, constant_pool::*, descriptor::FieldType,
+    error::ClassFormatError, signature::TypeSignature,
+};
 use crate::utils::bytecode_reader::BytecodeReader;
 
 #[derive(Debug)]
 pub struct Field {
-    pub access_flags: u16,
-    pub name: String,
-    pub descriptor: String,
+    pub access_flags: FieldAccessFlags,
+    pub name: Indexed<String>,
+    pub descriptor: Indexed<String>,
     pub attributes: Vec<Attribute>,
+    pub attribute_name_indices: Vec<CpIndex>,
 }
 
 impl Field {
-    pub fn new(reader: &mut BytecodeReader, constant_pool: &Vec<Constant>) -> Field {
-        let access_flags = reader.u16();
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<Field, ClassFormatError> {
+        let access_flags = FieldAccessFlags(reader.u16()?);
 
-        let name_index = reader.u16();
-        let name: String = Constant::read_utf8_data(constant_pool, name_index);
+        let name = constant_pool.read_utf8_ref(reader)?;
 
-        let descriptor_index = reader.u16();
-        let descriptor: String = Constant::read_utf8_data(constant_pool, descriptor_index);
+        let descriptor = constant_pool.read_utf8_ref(reader)?;
 
-        let attributes_count = reader.u16();
-        let mut attributes: Vec<Attribute> = Vec::new();
-        for _ in 0..attributes_count {
-            attributes.push(Attribute::new(reader, constant_pool));
-        }
+        let (attributes, attribute_name_indices) = read_attributes(reader, constant_pool)
+            .map_err(|e| e.within(format!("field {}{}", name, descriptor)))?;
 
-        Field {
+        Ok(Field {
             access_flags,
             name,
             descriptor,
             attributes,
+            attribute_name_indices,
+        })
+    }
+
+    pub fn field_type(&self) -> Result<FieldType, ClassFormatError> {
+        FieldType::parse(&self.descriptor)
+    }
+
+    // The generic signature from the Signature attribute, if present.
+    pub fn signature(&self) -> Result<Option<TypeSignature>, ClassFormatError> {
+        for attribute in &self.attributes {
+            if let Attribute::Signature(a) = attribute {
+                return TypeSignature::parse_field(&a.signature).map(Some);
+            }
         }
+        Ok(None)
+    }
+
+    // A declaration annotation (visible or invisible) by its type descriptor, e.g.
+    // "Lorg/junit/Test;".
+    pub fn annotation(&self, type_descriptor: &str) -> Option<&Annotation> {
+        self.annotations()
+            .into_iter()
+            .find(|a| a.type_descriptor == type_descriptor)
+    }
+
+    pub fn annotations(&self) -> Vec<&Annotation> {
+        annotations(&self.attributes)
+    }
+
+    pub fn type_annotations(&self) -> Vec<&TypeAnnotation> {
+        type_annotations(&self.attributes)
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        is_deprecated(&self.attributes)
+    }
+
+    // Either ACC_SYNTHETIC or, in classes older than version 49, the Synthetic attribute.
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags.contains(FieldAccessFlags::SYNTHETIC) || is_synthetic(&self.attributes)
     }
 }
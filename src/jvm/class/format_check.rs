@@ -0,0 +1,483 @@
+use std::collections::HashSet;
+
+use crate::jvm::class::{
+    attribute::*,
+    constant::Constant,
+    constant_pool::*,
+    descriptor::{
+        is_binary_name, is_class_constant_name, is_method_name, is_unqualified_name, FieldType,
+        MethodDescriptor,
+    },
+    error::ClassFormatError,
+    field::Field,
+    instruction::Instruction,
+    method::Method,
+    version::ClassVersion,
+    Class,
+};
+
+// Format checking (JVMS 4.8): the cross references between the parts of a class that parsing
+// alone does not check. Bytecode verification is a separate, later step.
+impl Class {
+    pub fn check_format(&self) -> Result<(), ClassFormatError> {
+        self.check_access_flags()?;
+        self.check_constant_pool()
+            .and_then(|_| self.check_hierarchy())
+            .and_then(|_| self.check_fields())
+            .and_then(|_| self.check_methods())
+            .map_err(|e| e.within(format!("class {}", self.this_class)))
+    }
+
+    fn check_constant_pool(&self) -> Result<(), ClassFormatError> {
+        let constant_pool = &self.constant_pool;
+        let bootstrap_methods_count = self.bootstrap_methods().len();
+        for (index, constant) in constant_pool.iter() {
+            check_constant(
+                constant_pool,
+                self.version(),
+                index,
+                constant,
+                bootstrap_methods_count,
+            )
+            .map_err(|e| e.within(format!("constant {}", index)))?;
+        }
+        Ok(())
+    }
+
+    fn check_hierarchy(&self) -> Result<(), ClassFormatError> {
+        if self.is_module() {
+            if self.this_class != "module-info" {
+                return Err(ClassFormatError::new(format!(
+                    "module class is named {} instead of module-info",
+                    self.this_class
+                )));
+            }
+            if self.super_class.is_some()
+                || !self.interfaces.is_empty()
+                || !self.fields.is_empty()
+                || !self.methods.is_empty()
+            {
+                return Err(ClassFormatError::new(
+                    "module-info has a super class, interfaces, fields or methods",
+                ));
+            }
+            return Ok(());
+        }
+        if !is_binary_name(&self.this_class) {
+            return Err(ClassFormatError::new(format!(
+                "this_class {} is not a valid class name",
+                self.this_class
+            )));
+        }
+        match &self.super_class {
+            None if self.this_class != "java/lang/Object" => {
+                return Err(ClassFormatError::new("super_class is 0"));
+            }
+            Some(super_class) if self.this_class == "java/lang/Object" => {
+                return Err(ClassFormatError::new(format!(
+                    "java/lang/Object has super class {}",
+                    super_class
+                )));
+            }
+            Some(super_class) if super_class.starts_with('[') => {
+                return Err(ClassFormatError::new(format!(
+                    "super class {} is an array type",
+                    super_class
+                )));
+            }
+            Some(super_class)
+                if self.access_flags.is_interface() && super_class != "java/lang/Object" =>
+            {
+                return Err(ClassFormatError::new(format!(
+                    "interface has super class {} instead of java/lang/Object",
+                    super_class
+                )));
+            }
+            _ => {}
+        }
+        for index in &self.interfaces {
+            let interface = self
+                .constant_pool
+                .class_name(*index)
+                .map_err(|e| e.within("interfaces"))?;
+            if interface.starts_with('[') {
+                return Err(ClassFormatError::new(format!(
+                    "super interface {} is an array type",
+                    interface
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_fields(&self) -> Result<(), ClassFormatError> {
+        let mut declared: HashSet<(&str, &str)> = HashSet::new();
+        for field in &self.fields {
+            let location = format!("field {}{}", field.name, field.descriptor);
+            if !declared.insert((&field.name, &field.descriptor)) {
+                return Err(ClassFormatError::new("duplicate field").within(location));
+            }
+            check_field(&self.constant_pool, field).map_err(|e| e.within(location))?;
+        }
+        Ok(())
+    }
+
+    fn check_methods(&self) -> Result<(), ClassFormatError> {
+        let mut declared: HashSet<(&str, &str)> = HashSet::new();
+        for method in &self.methods {
+            let location = format!("method {}{}", method.name, method.descriptor);
+            if !declared.insert((&method.name, &method.descriptor)) {
+                return Err(ClassFormatError::new("duplicate method").within(location));
+            }
+            check_method(&self.constant_pool, self.version(), method)
+                .map_err(|e| e.within(location))?;
+        }
+        Ok(())
+    }
+}
+
+// JVMS 4.4: every constant is allowed in the class file version and every index refers to a
+// constant of the expected kind, with valid names and descriptors.
+fn check_constant(
+    constant_pool: &ConstantPool,
+    version: ClassVersion,
+    index: CpIndex,
+    constant: &Constant,
+    bootstrap_methods_count: usize,
+) -> Result<(), ClassFormatError> {
+    if version.major < constant.min_major_version() {
+        return Err(ClassFormatError::new(format!(
+            "{} constant in a version {} class file, it needs version {} or later",
+            constant.kind(),
+            version,
+            constant.min_major_version()
+        )));
+    }
+    match constant {
+        Constant::Class(c) => {
+            let name = constant_pool.utf8(c.name_index)?;
+            if !is_class_constant_name(&name) {
+                return Err(ClassFormatError::new(format!(
+                    "invalid class name {}",
+                    name
+                )));
+            }
+        }
+        Constant::String(c) => {
+            constant_pool.utf8(c.string_index)?;
+        }
+        Constant::Fieldref(_) | Constant::Methodref(_) | Constant::InterfaceMethodref(_) => {
+            let member = constant_pool.member_ref(index)?;
+            if member.kind == MemberKind::Field {
+                check_field_name(&member.name)?;
+                FieldType::parse(&member.descriptor)?;
+                return Ok(());
+            }
+            if member.kind == MemberKind::InterfaceMethod && member.class_name.starts_with('[') {
+                return Err(ClassFormatError::new(format!(
+                    "interface method of array type {}",
+                    member.class_name
+                )));
+            }
+            let method_descriptor = MethodDescriptor::parse(&member.descriptor)?;
+            if member.name == "<clinit>" {
+                return Err(ClassFormatError::new("method reference to <clinit>"));
+            }
+            check_method_name(&member.name)?;
+            if member.name == "<init>" && method_descriptor.return_type.is_some() {
+                return Err(ClassFormatError::new(format!(
+                    "<init> with non-void descriptor {}",
+                    member.descriptor
+                )));
+            }
+        }
+        Constant::NameAndType(c) => {
+            let name = constant_pool.utf8(c.name_index)?;
+            let descriptor = constant_pool.utf8(c.descriptor_index)?;
+            if descriptor.starts_with('(') {
+                check_method_name(&name)?;
+                MethodDescriptor::parse(&descriptor)?;
+            } else {
+                check_field_name(&name)?;
+                FieldType::parse(&descriptor)?;
+            }
+        }
+        Constant::MethodHandle(_) => {
+            // Also checks the referenced member against reference_kind.
+            constant_pool.method_handle(index)?;
+        }
+        Constant::MethodType(c) => {
+            MethodDescriptor::parse(&constant_pool.utf8(c.descriptor_index)?)?;
+        }
+        Constant::Dynamic(c) => {
+            check_bootstrap_index(c.bootstrap_method_attr_index, bootstrap_methods_count)?;
+            let (name, descriptor) = constant_pool.name_and_type(c.name_and_type_index)?;
+            check_field_name(&name)?;
+            FieldType::parse(&descriptor)?;
+        }
+        Constant::InvokeDynamic(c) => {
+            check_bootstrap_index(c.bootstrap_method_attr_index, bootstrap_methods_count)?;
+            let (name, descriptor) = constant_pool.name_and_type(c.name_and_type_index)?;
+            check_method_name(&name)?;
+            MethodDescriptor::parse(&descriptor)?;
+        }
+        Constant::Module(c) => {
+            constant_pool.utf8(c.name_index)?;
+        }
+        Constant::Package(c) => {
+            let name = constant_pool.utf8(c.name_index)?;
+            if !is_binary_name(&name) {
+                return Err(ClassFormatError::new(format!(
+                    "invalid package name {}",
+                    name
+                )));
+            }
+        }
+        Constant::Utf8(_)
+        | Constant::Integer(_)
+        | Constant::Float(_)
+        | Constant::Long(_)
+        | Constant::Double(_)
+        | Constant::Unusable => {}
+    }
+    Ok(())
+}
+
+fn check_bootstrap_index(
+    index: u16,
+    bootstrap_methods_count: usize,
+) -> Result<(), ClassFormatError> {
+    if index as usize >= bootstrap_methods_count {
+        return Err(ClassFormatError::new(format!(
+            "bootstrap method {} out of range, the class has {}",
+            index, bootstrap_methods_count
+        )));
+    }
+    Ok(())
+}
+
+fn check_field_name(name: &str) -> Result<(), ClassFormatError> {
+    if !is_unqualified_name(name) {
+        return Err(ClassFormatError::new(format!(
+            "invalid field name {}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn check_method_name(name: &str) -> Result<(), ClassFormatError> {
+    if !is_method_name(name) {
+        return Err(ClassFormatError::new(format!(
+            "invalid method name {}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn check_field(constant_pool: &ConstantPool, field: &Field) -> Result<(), ClassFormatError> {
+    check_field_name(&field.name)?;
+    let field_type = field.field_type()?;
+    if !field.access_flags.is_static() {
+        // ConstantValue of a non-static field is silently ignored.
+        return Ok(());
+    }
+    for attribute in &field.attributes {
+        if let Attribute::ConstantValue(a) = attribute {
+            let index = a.constantvalue_index;
+            let matches = match (&field_type, constant_pool.get(index)?) {
+                (FieldType::Long, Constant::Long(_))
+                | (FieldType::Float, Constant::Float(_))
+                | (FieldType::Double, Constant::Double(_))
+                | (
+                    FieldType::Int
+                    | FieldType::Short
+                    | FieldType::Char
+                    | FieldType::Byte
+                    | FieldType::Boolean,
+                    Constant::Integer(_),
+                ) => true,
+                (FieldType::Object(class_name), Constant::String(_)) => {
+                    class_name == "java/lang/String"
+                }
+                _ => false,
+            };
+            if !matches {
+                return Err(ClassFormatError::new(format!(
+                    "ConstantValue {} does not match the field type",
+                    index
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_method(
+    constant_pool: &ConstantPool,
+    version: ClassVersion,
+    method: &Method,
+) -> Result<(), ClassFormatError> {
+    check_method_name(&method.name)?;
+    let method_descriptor = method.method_descriptor()?;
+    if method.name == "<init>" && method_descriptor.return_type.is_some() {
+        return Err(ClassFormatError::new("<init> must return void"));
+    }
+    if method.name == "<clinit>"
+        && (method_descriptor.return_type.is_some() || !method_descriptor.parameters.is_empty())
+    {
+        return Err(ClassFormatError::new("<clinit> must have descriptor ()V"));
+    }
+    if method_descriptor.parameter_slots() + u16::from(!method.access_flags.is_static()) > 255 {
+        return Err(ClassFormatError::new("more than 255 parameter slots"));
+    }
+
+    let codes: Vec<&AttributeCode> = method
+        .attributes
+        .iter()
+        .filter_map(|attribute| match attribute {
+            Attribute::Code(a) => Some(a),
+            _ => None,
+        })
+        .collect();
+    let needs_code = !method.access_flags.is_abstract() && !method.access_flags.is_native();
+    match (needs_code, codes.len()) {
+        (true, 0) => return Err(ClassFormatError::new("missing Code attribute")),
+        (false, 1..) => {
+            return Err(ClassFormatError::new(
+                "abstract or native method has a Code attribute",
+            ))
+        }
+        (_, 2..) => return Err(ClassFormatError::new("more than one Code attribute")),
+        _ => {}
+    }
+    if let Some(code) = codes.first() {
+        check_code(constant_pool, version, code).map_err(|e| e.within("attribute Code"))?;
+    }
+    Ok(())
+}
+
+fn check_code(
+    constant_pool: &ConstantPool,
+    version: ClassVersion,
+    code: &AttributeCode,
+) -> Result<(), ClassFormatError> {
+    let code_length = code.code.len();
+    if code_length == 0 || code_length >= 65536 {
+        return Err(ClassFormatError::new(format!(
+            "code_length {} is not in 1..65535",
+            code_length
+        )));
+    }
+    for exception in &code.exception_table {
+        if exception.start_pc >= exception.end_pc
+            || exception.end_pc as usize > code_length
+            || exception.handler_pc as usize >= code_length
+        {
+            return Err(ClassFormatError::new(format!(
+                "exception handler [{}, {}) -> {} out of range",
+                exception.start_pc, exception.end_pc, exception.handler_pc
+            )));
+        }
+        if !exception.catch_type.is_none() {
+            constant_pool.class_name(exception.catch_type)?;
+        }
+    }
+
+    // Version dependent rules that need a pass over the instructions.
+    let mut branches = !code.exception_table.is_empty();
+    for (pc, instruction) in Instruction::decode_all(&code.code)? {
+        let is_jsr_or_ret = matches!(
+            instruction,
+            Instruction::Jsr(_) | Instruction::JsrW(_) | Instruction::Ret(_)
+        );
+        if is_jsr_or_ret && !version.allows_jsr() {
+            return Err(ClassFormatError::new(format!(
+                "{} at pc {} in a version {} class file",
+                instruction.name(),
+                pc,
+                version
+            )));
+        }
+        branches |= !instruction.branch_targets(pc).is_empty();
+    }
+
+    let stack_map_tables = code
+        .attributes
+        .iter()
+        .filter(|attribute| matches!(attribute, Attribute::StackMapTable(_)))
+        .count();
+    if stack_map_tables > 1 {
+        return Err(ClassFormatError::new(
+            "more than one StackMapTable attribute",
+        ));
+    }
+    if version.requires_stack_map_table() && branches && stack_map_tables == 0 {
+        return Err(ClassFormatError::new(format!(
+            "missing StackMapTable for code with branches or exception handlers in a version {} class file",
+            version
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A class A with no members whose constant pool ends in `extra` constants, numbered from
+    // #5 on.
+    fn class_file(major: u16, extra: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0];
+        bytes.extend(major.to_be_bytes());
+        bytes.extend((5 + extra.len() as u16).to_be_bytes());
+        bytes.extend([1, 0, 1, b'A', 7, 0, 1]);
+        bytes.extend([1, 0, 16]);
+        bytes.extend(b"java/lang/Object");
+        bytes.extend([7, 0, 3]);
+        bytes.extend(extra.concat());
+        bytes.extend([0, 0x21, 0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes
+    }
+
+    fn check(major: u16, extra: &[&[u8]]) -> Result<(), ClassFormatError> {
+        Class::new(&class_file(major, extra)[..])?.check_format()
+    }
+
+    #[test]
+    fn method_type_needs_version_51() {
+        let constants: &[&[u8]] = &[&[1, 0, 3, b'(', b')', b'V'], &[16, 0, 5]];
+        let error = check(50, constants).unwrap_err();
+        assert_eq!(
+            error.reason,
+            "MethodType constant in a version 50.0 class file, it needs version 51 or later"
+        );
+        assert_eq!(error.location, vec!["class A", "constant #6"]);
+        assert_eq!(check(51, constants), Ok(()));
+    }
+
+    #[test]
+    fn package_needs_version_53() {
+        let constants: &[&[u8]] = &[&[1, 0, 1, b'p'], &[20, 0, 5]];
+        let error = check(52, constants).unwrap_err();
+        assert!(error.reason.starts_with("Package constant"), "{}", error);
+        assert_eq!(check(53, constants), Ok(()));
+    }
+
+    #[test]
+    fn dynamic_needs_version_55() {
+        // Dynamic x:I with bootstrap method 0; the class has no BootstrapMethods attribute,
+        // which is only reported once the version allows the constant.
+        let constants: &[&[u8]] = &[
+            &[1, 0, 1, b'x'],
+            &[1, 0, 1, b'I'],
+            &[12, 0, 5, 0, 6],
+            &[17, 0, 0, 0, 7],
+        ];
+        let error = check(54, constants).unwrap_err();
+        assert!(error.reason.starts_with("Dynamic constant"), "{}", error);
+        let error = check(55, constants).unwrap_err();
+        assert!(!error.reason.contains("needs version"), "{}", error);
+    }
+}
@@ -0,0 +1,1755 @@
+use std::fmt;
+
+use crate::jvm::class::{
+    constant::Constant,
+    constant_pool::{ConstantPool, CpIndex},
+    descriptor::{FieldType, MethodDescriptor},
+    error::ClassFormatError,
+};
+use crate::utils::bytecode_writer::BytecodeWriter;
+
+// One decoded JVM instruction (JVMS 6.5). Operands are kept as they are encoded: constant pool
+// indices unresolved and branch offsets relative to the opcode of the instruction. The `wide`
+// prefix is folded into the local variable index of the instruction it modifies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,
+    AconstNull,
+    IconstM1,
+    Iconst0,
+    Iconst1,
+    Iconst2,
+    Iconst3,
+    Iconst4,
+    Iconst5,
+    Lconst0,
+    Lconst1,
+    Fconst0,
+    Fconst1,
+    Fconst2,
+    Dconst0,
+    Dconst1,
+    Bipush(i8),
+    Sipush(i16),
+    Ldc(CpIndex),
+    LdcW(CpIndex),
+    Ldc2W(CpIndex),
+    Iload(u16),
+    Lload(u16),
+    Fload(u16),
+    Dload(u16),
+    Aload(u16),
+    Iload0,
+    Iload1,
+    Iload2,
+    Iload3,
+    Lload0,
+    Lload1,
+    Lload2,
+    Lload3,
+    Fload0,
+    Fload1,
+    Fload2,
+    Fload3,
+    Dload0,
+    Dload1,
+    Dload2,
+    Dload3,
+    Aload0,
+    Aload1,
+    Aload2,
+    Aload3,
+    Iaload,
+    Laload,
+    Faload,
+    Daload,
+    Aaload,
+    Baload,
+    Caload,
+    Saload,
+    Istore(u16),
+    Lstore(u16),
+    Fstore(u16),
+    Dstore(u16),
+    Astore(u16),
+    Istore0,
+    Istore1,
+    Istore2,
+    Istore3,
+    Lstore0,
+    Lstore1,
+    Lstore2,
+    Lstore3,
+    Fstore0,
+    Fstore1,
+    Fstore2,
+    Fstore3,
+    Dstore0,
+    Dstore1,
+    Dstore2,
+    Dstore3,
+    Astore0,
+    Astore1,
+    Astore2,
+    Astore3,
+    Iastore,
+    Lastore,
+    Fastore,
+    Dastore,
+    Aastore,
+    Bastore,
+    Castore,
+    Sastore,
+    Pop,
+    Pop2,
+    Dup,
+    DupX1,
+    DupX2,
+    Dup2,
+    Dup2X1,
+    Dup2X2,
+    Swap,
+    Iadd,
+    Ladd,
+    Fadd,
+    Dadd,
+    Isub,
+    Lsub,
+    Fsub,
+    Dsub,
+    Imul,
+    Lmul,
+    Fmul,
+    Dmul,
+    Idiv,
+    Ldiv,
+    Fdiv,
+    Ddiv,
+    Irem,
+    Lrem,
+    Frem,
+    Drem,
+    Ineg,
+    Lneg,
+    Fneg,
+    Dneg,
+    Ishl,
+    Lshl,
+    Ishr,
+    Lshr,
+    Iushr,
+    Lushr,
+    Iand,
+    Land,
+    Ior,
+    Lor,
+    Ixor,
+    Lxor,
+    Iinc {
+        index: u16,
+        value: i16,
+    },
+    I2l,
+    I2f,
+    I2d,
+    L2i,
+    L2f,
+    L2d,
+    F2i,
+    F2l,
+    F2d,
+    D2i,
+    D2l,
+    D2f,
+    I2b,
+    I2c,
+    I2s,
+    Lcmp,
+    Fcmpl,
+    Fcmpg,
+    Dcmpl,
+    Dcmpg,
+    Ifeq(i16),
+    Ifne(i16),
+    Iflt(i16),
+    Ifge(i16),
+    Ifgt(i16),
+    Ifle(i16),
+    IfIcmpeq(i16),
+    IfIcmpne(i16),
+    IfIcmplt(i16),
+    IfIcmpge(i16),
+    IfIcmpgt(i16),
+    IfIcmple(i16),
+    IfAcmpeq(i16),
+    IfAcmpne(i16),
+    Goto(i16),
+    Jsr(i16),
+    Ret(u16),
+    Tableswitch {
+        default: i32,
+        low: i32,
+        high: i32,
+        offsets: Vec<i32>,
+    },
+    Lookupswitch {
+        default: i32,
+        pairs: Vec<(i32, i32)>,
+    },
+    Ireturn,
+    Lreturn,
+    Freturn,
+    Dreturn,
+    Areturn,
+    Return,
+    Getstatic(CpIndex),
+    Putstatic(CpIndex),
+    Getfield(CpIndex),
+    Putfield(CpIndex),
+    Invokevirtual(CpIndex),
+    Invokespecial(CpIndex),
+    Invokestatic(CpIndex),
+    Invokeinterface {
+        index: CpIndex,
+        count: u8,
+    },
+    Invokedynamic(CpIndex),
+    New(CpIndex),
+    Newarray(ArrayType),
+    Anewarray(CpIndex),
+    Arraylength,
+    Athrow,
+    Checkcast(CpIndex),
+    Instanceof(CpIndex),
+    Monitorenter,
+    Monitorexit,
+    Multianewarray {
+        index: CpIndex,
+        dimensions: u8,
+    },
+    Ifnull(i16),
+    Ifnonnull(i16),
+    GotoW(i32),
+    JsrW(i32),
+    Breakpoint,
+    Impdep1,
+    Impdep2,
+}
+
+// atype of newarray
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayType {
+    Boolean = 4,
+    Char = 5,
+    Float = 6,
+    Double = 7,
+    Byte = 8,
+    Short = 9,
+    Int = 10,
+    Long = 11,
+}
+
+impl ArrayType {
+    pub fn from_u8(atype: u8) -> Option<ArrayType> {
+        Some(match atype {
+            4 => ArrayType::Boolean,
+            5 => ArrayType::Char,
+            6 => ArrayType::Float,
+            7 => ArrayType::Double,
+            8 => ArrayType::Byte,
+            9 => ArrayType::Short,
+            10 => ArrayType::Int,
+            11 => ArrayType::Long,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArrayType::Boolean => "boolean",
+            ArrayType::Char => "char",
+            ArrayType::Float => "float",
+            ArrayType::Double => "double",
+            ArrayType::Byte => "byte",
+            ArrayType::Short => "short",
+            ArrayType::Int => "int",
+            ArrayType::Long => "long",
+        }
+    }
+}
+
+impl fmt::Display for ArrayType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// Mnemonics indexed by opcode; unassigned opcodes are "".
+const OPCODE_NAMES: [&str; 256] = [
+    "nop",
+    "aconst_null",
+    "iconst_m1",
+    "iconst_0",
+    "iconst_1",
+    "iconst_2",
+    "iconst_3",
+    "iconst_4",
+    "iconst_5",
+    "lconst_0",
+    "lconst_1",
+    "fconst_0",
+    "fconst_1",
+    "fconst_2",
+    "dconst_0",
+    "dconst_1",
+    "bipush",
+    "sipush",
+    "ldc",
+    "ldc_w",
+    "ldc2_w",
+    "iload",
+    "lload",
+    "fload",
+    "dload",
+    "aload",
+    "iload_0",
+    "iload_1",
+    "iload_2",
+    "iload_3",
+    "lload_0",
+    "lload_1",
+    "lload_2",
+    "lload_3",
+    "fload_0",
+    "fload_1",
+    "fload_2",
+    "fload_3",
+    "dload_0",
+    "dload_1",
+    "dload_2",
+    "dload_3",
+    "aload_0",
+    "aload_1",
+    "aload_2",
+    "aload_3",
+    "iaload",
+    "laload",
+    "faload",
+    "daload",
+    "aaload",
+    "baload",
+    "caload",
+    "saload",
+    "istore",
+    "lstore",
+    "fstore",
+    "dstore",
+    "astore",
+    "istore_0",
+    "istore_1",
+    "istore_2",
+    "istore_3",
+    "lstore_0",
+    "lstore_1",
+    "lstore_2",
+    "lstore_3",
+    "fstore_0",
+    "fstore_1",
+    "fstore_2",
+    "fstore_3",
+    "dstore_0",
+    "dstore_1",
+    "dstore_2",
+    "dstore_3",
+    "astore_0",
+    "astore_1",
+    "astore_2",
+    "astore_3",
+    "iastore",
+    "lastore",
+    "fastore",
+    "dastore",
+    "aastore",
+    "bastore",
+    "castore",
+    "sastore",
+    "pop",
+    "pop2",
+    "dup",
+    "dup_x1",
+    "dup_x2",
+    "dup2",
+    "dup2_x1",
+    "dup2_x2",
+    "swap",
+    "iadd",
+    "ladd",
+    "fadd",
+    "dadd",
+    "isub",
+    "lsub",
+    "fsub",
+    "dsub",
+    "imul",
+    "lmul",
+    "fmul",
+    "dmul",
+    "idiv",
+    "ldiv",
+    "fdiv",
+    "ddiv",
+    "irem",
+    "lrem",
+    "frem",
+    "drem",
+    "ineg",
+    "lneg",
+    "fneg",
+    "dneg",
+    "ishl",
+    "lshl",
+    "ishr",
+    "lshr",
+    "iushr",
+    "lushr",
+    "iand",
+    "land",
+    "ior",
+    "lor",
+    "ixor",
+    "lxor",
+    "iinc",
+    "i2l",
+    "i2f",
+    "i2d",
+    "l2i",
+    "l2f",
+    "l2d",
+    "f2i",
+    "f2l",
+    "f2d",
+    "d2i",
+    "d2l",
+    "d2f",
+    "i2b",
+    "i2c",
+    "i2s",
+    "lcmp",
+    "fcmpl",
+    "fcmpg",
+    "dcmpl",
+    "dcmpg",
+    "ifeq",
+    "ifne",
+    "iflt",
+    "ifge",
+    "ifgt",
+    "ifle",
+    "if_icmpeq",
+    "if_icmpne",
+    "if_icmplt",
+    "if_icmpge",
+    "if_icmpgt",
+    "if_icmple",
+    "if_acmpeq",
+    "if_acmpne",
+    "goto",
+    "jsr",
+    "ret",
+    "tableswitch",
+    "lookupswitch",
+    "ireturn",
+    "lreturn",
+    "freturn",
+    "dreturn",
+    "areturn",
+    "return",
+    "getstatic",
+    "putstatic",
+    "getfield",
+    "putfield",
+    "invokevirtual",
+    "invokespecial",
+    "invokestatic",
+    "invokeinterface",
+    "invokedynamic",
+    "new",
+    "newarray",
+    "anewarray",
+    "arraylength",
+    "athrow",
+    "checkcast",
+    "instanceof",
+    "monitorenter",
+    "monitorexit",
+    "wide",
+    "multianewarray",
+    "ifnull",
+    "ifnonnull",
+    "goto_w",
+    "jsr_w",
+    "breakpoint",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "impdep1",
+    "impdep2",
+];
+
+pub fn opcode_name(opcode: u8) -> Option<&'static str> {
+    match OPCODE_NAMES[opcode as usize] {
+        "" => None,
+        name => Some(name),
+    }
+}
+
+// The opcode of a mnemonic such as "iload_0".
+pub fn opcode_by_name(name: &str) -> Option<u8> {
+    if name.is_empty() {
+        return None;
+    }
+    OPCODE_NAMES
+        .iter()
+        .position(|n| *n == name)
+        .map(|opcode| opcode as u8)
+}
+
+impl Instruction {
+    // Decodes the whole code array of a Code attribute into (pc, instruction) pairs.
+    pub fn decode_all(code: &[u8]) -> Result<Vec<(usize, Instruction)>, ClassFormatError> {
+        let mut instructions: Vec<(usize, Instruction)> = Vec::new();
+        let mut pc = 0;
+        while pc < code.len() {
+            let (instruction, length) = Instruction::decode(code, pc)?;
+            instructions.push((pc, instruction));
+            pc += length;
+        }
+        Ok(instructions)
+    }
+
+    // Decodes the instruction at pc, returning it with its encoded length.
+    pub fn decode(code: &[u8], pc: usize) -> Result<(Instruction, usize), ClassFormatError> {
+        let mut reader = CodeReader {
+            code,
+            start: pc,
+            pc,
+        };
+        let opcode = reader.u8()?;
+        let instruction = match opcode {
+            0x00 => Instruction::Nop,
+            0x01 => Instruction::AconstNull,
+            0x02 => Instruction::IconstM1,
+            0x03 => Instruction::Iconst0,
+            0x04 => Instruction::Iconst1,
+            0x05 => Instruction::Iconst2,
+            0x06 => Instruction::Iconst3,
+            0x07 => Instruction::Iconst4,
+            0x08 => Instruction::Iconst5,
+            0x09 => Instruction::Lconst0,
+            0x0a => Instruction::Lconst1,
+            0x0b => Instruction::Fconst0,
+            0x0c => Instruction::Fconst1,
+            0x0d => Instruction::Fconst2,
+            0x0e => Instruction::Dconst0,
+            0x0f => Instruction::Dconst1,
+            0x10 => Instruction::Bipush(reader.u8()? as i8),
+            0x11 => Instruction::Sipush(reader.u16()? as i16),
+            0x12 => Instruction::Ldc(CpIndex(reader.u8()? as u16)),
+            0x13 => Instruction::LdcW(CpIndex(reader.u16()?)),
+            0x14 => Instruction::Ldc2W(CpIndex(reader.u16()?)),
+            0x15 => Instruction::Iload(reader.u8()? as u16),
+            0x16 => Instruction::Lload(reader.u8()? as u16),
+            0x17 => Instruction::Fload(reader.u8()? as u16),
+            0x18 => Instruction::Dload(reader.u8()? as u16),
+            0x19 => Instruction::Aload(reader.u8()? as u16),
+            0x1a => Instruction::Iload0,
+            0x1b => Instruction::Iload1,
+            0x1c => Instruction::Iload2,
+            0x1d => Instruction::Iload3,
+            0x1e => Instruction::Lload0,
+            0x1f => Instruction::Lload1,
+            0x20 => Instruction::Lload2,
+            0x21 => Instruction::Lload3,
+            0x22 => Instruction::Fload0,
+            0x23 => Instruction::Fload1,
+            0x24 => Instruction::Fload2,
+            0x25 => Instruction::Fload3,
+            0x26 => Instruction::Dload0,
+            0x27 => Instruction::Dload1,
+            0x28 => Instruction::Dload2,
+            0x29 => Instruction::Dload3,
+            0x2a => Instruction::Aload0,
+            0x2b => Instruction::Aload1,
+            0x2c => Instruction::Aload2,
+            0x2d => Instruction::Aload3,
+            0x2e => Instruction::Iaload,
+            0x2f => Instruction::Laload,
+            0x30 => Instruction::Faload,
+            0x31 => Instruction::Daload,
+            0x32 => Instruction::Aaload,
+            0x33 => Instruction::Baload,
+            0x34 => Instruction::Caload,
+            0x35 => Instruction::Saload,
+            0x36 => Instruction::Istore(reader.u8()? as u16),
+            0x37 => Instruction::Lstore(reader.u8()? as u16),
+            0x38 => Instruction::Fstore(reader.u8()? as u16),
+            0x39 => Instruction::Dstore(reader.u8()? as u16),
+            0x3a => Instruction::Astore(reader.u8()? as u16),
+            0x3b => Instruction::Istore0,
+            0x3c => Instruction::Istore1,
+            0x3d => Instruction::Istore2,
+            0x3e => Instruction::Istore3,
+            0x3f => Instruction::Lstore0,
+            0x40 => Instruction::Lstore1,
+            0x41 => Instruction::Lstore2,
+            0x42 => Instruction::Lstore3,
+            0x43 => Instruction::Fstore0,
+            0x44 => Instruction::Fstore1,
+            0x45 => Instruction::Fstore2,
+            0x46 => Instruction::Fstore3,
+            0x47 => Instruction::Dstore0,
+            0x48 => Instruction::Dstore1,
+            0x49 => Instruction::Dstore2,
+            0x4a => Instruction::Dstore3,
+            0x4b => Instruction::Astore0,
+            0x4c => Instruction::Astore1,
+            0x4d => Instruction::Astore2,
+            0x4e => Instruction::Astore3,
+            0x4f => Instruction::Iastore,
+            0x50 => Instruction::Lastore,
+            0x51 => Instruction::Fastore,
+            0x52 => Instruction::Dastore,
+            0x53 => Instruction::Aastore,
+            0x54 => Instruction::Bastore,
+            0x55 => Instruction::Castore,
+            0x56 => Instruction::Sastore,
+            0x57 => Instruction::Pop,
+            0x58 => Instruction::Pop2,
+            0x59 => Instruction::Dup,
+            0x5a => Instruction::DupX1,
+            0x5b => Instruction::DupX2,
+            0x5c => Instruction::Dup2,
+            0x5d => Instruction::Dup2X1,
+            0x5e => Instruction::Dup2X2,
+            0x5f => Instruction::Swap,
+            0x60 => Instruction::Iadd,
+            0x61 => Instruction::Ladd,
+            0x62 => Instruction::Fadd,
+            0x63 => Instruction::Dadd,
+            0x64 => Instruction::Isub,
+            0x65 => Instruction::Lsub,
+            0x66 => Instruction::Fsub,
+            0x67 => Instruction::Dsub,
+            0x68 => Instruction::Imul,
+            0x69 => Instruction::Lmul,
+            0x6a => Instruction::Fmul,
+            0x6b => Instruction::Dmul,
+            0x6c => Instruction::Idiv,
+            0x6d => Instruction::Ldiv,
+            0x6e => Instruction::Fdiv,
+            0x6f => Instruction::Ddiv,
+            0x70 => Instruction::Irem,
+            0x71 => Instruction::Lrem,
+            0x72 => Instruction::Frem,
+            0x73 => Instruction::Drem,
+            0x74 => Instruction::Ineg,
+            0x75 => Instruction::Lneg,
+            0x76 => Instruction::Fneg,
+            0x77 => Instruction::Dneg,
+            0x78 => Instruction::Ishl,
+            0x79 => Instruction::Lshl,
+            0x7a => Instruction::Ishr,
+            0x7b => Instruction::Lshr,
+            0x7c => Instruction::Iushr,
+            0x7d => Instruction::Lushr,
+            0x7e => Instruction::Iand,
+            0x7f => Instruction::Land,
+            0x80 => Instruction::Ior,
+            0x81 => Instruction::Lor,
+            0x82 => Instruction::Ixor,
+            0x83 => Instruction::Lxor,
+            0x84 => Instruction::Iinc {
+                index: reader.u8()? as u16,
+                value: reader.u8()? as i8 as i16,
+            },
+            0x85 => Instruction::I2l,
+            0x86 => Instruction::I2f,
+            0x87 => Instruction::I2d,
+            0x88 => Instruction::L2i,
+            0x89 => Instruction::L2f,
+            0x8a => Instruction::L2d,
+            0x8b => Instruction::F2i,
+            0x8c => Instruction::F2l,
+            0x8d => Instruction::F2d,
+            0x8e => Instruction::D2i,
+            0x8f => Instruction::D2l,
+            0x90 => Instruction::D2f,
+            0x91 => Instruction::I2b,
+            0x92 => Instruction::I2c,
+            0x93 => Instruction::I2s,
+            0x94 => Instruction::Lcmp,
+            0x95 => Instruction::Fcmpl,
+            0x96 => Instruction::Fcmpg,
+            0x97 => Instruction::Dcmpl,
+            0x98 => Instruction::Dcmpg,
+            0x99 => Instruction::Ifeq(reader.u16()? as i16),
+            0x9a => Instruction::Ifne(reader.u16()? as i16),
+            0x9b => Instruction::Iflt(reader.u16()? as i16),
+            0x9c => Instruction::Ifge(reader.u16()? as i16),
+            0x9d => Instruction::Ifgt(reader.u16()? as i16),
+            0x9e => Instruction::Ifle(reader.u16()? as i16),
+            0x9f => Instruction::IfIcmpeq(reader.u16()? as i16),
+            0xa0 => Instruction::IfIcmpne(reader.u16()? as i16),
+            0xa1 => Instruction::IfIcmplt(reader.u16()? as i16),
+            0xa2 => Instruction::IfIcmpge(reader.u16()? as i16),
+            0xa3 => Instruction::IfIcmpgt(reader.u16()? as i16),
+            0xa4 => Instruction::IfIcmple(reader.u16()? as i16),
+            0xa5 => Instruction::IfAcmpeq(reader.u16()? as i16),
+            0xa6 => Instruction::IfAcmpne(reader.u16()? as i16),
+            0xa7 => Instruction::Goto(reader.u16()? as i16),
+            0xa8 => Instruction::Jsr(reader.u16()? as i16),
+            0xa9 => Instruction::Ret(reader.u8()? as u16),
+            0xaa => {
+                reader.skip_padding()?;
+                let default = reader.i32()?;
+                let low = reader.i32()?;
+                let high = reader.i32()?;
+                if low > high {
+                    return Err(reader.error(format!("tableswitch low {} > high {}", low, high)));
+                }
+                let mut offsets: Vec<i32> = Vec::new();
+                for _ in low..=high {
+                    offsets.push(reader.i32()?);
+                }
+                Instruction::Tableswitch {
+                    default,
+                    low,
+                    high,
+                    offsets,
+                }
+            }
+            0xab => {
+                reader.skip_padding()?;
+                let default = reader.i32()?;
+                let npairs = reader.i32()?;
+                if npairs < 0 {
+                    return Err(reader.error(format!("lookupswitch npairs {} < 0", npairs)));
+                }
+                let mut pairs: Vec<(i32, i32)> = Vec::new();
+                for _ in 0..npairs {
+                    pairs.push((reader.i32()?, reader.i32()?));
+                }
+                if pairs.windows(2).any(|w| w[0].0 >= w[1].0) {
+                    return Err(reader.error("lookupswitch keys are not sorted"));
+                }
+                Instruction::Lookupswitch { default, pairs }
+            }
+            0xac => Instruction::Ireturn,
+            0xad => Instruction::Lreturn,
+            0xae => Instruction::Freturn,
+            0xaf => Instruction::Dreturn,
+            0xb0 => Instruction::Areturn,
+            0xb1 => Instruction::Return,
+            0xb2 => Instruction::Getstatic(CpIndex(reader.u16()?)),
+            0xb3 => Instruction::Putstatic(CpIndex(reader.u16()?)),
+            0xb4 => Instruction::Getfield(CpIndex(reader.u16()?)),
+            0xb5 => Instruction::Putfield(CpIndex(reader.u16()?)),
+            0xb6 => Instruction::Invokevirtual(CpIndex(reader.u16()?)),
+            0xb7 => Instruction::Invokespecial(CpIndex(reader.u16()?)),
+            0xb8 => Instruction::Invokestatic(CpIndex(reader.u16()?)),
+            0xb9 => {
+                let index = CpIndex(reader.u16()?);
+                let count = reader.u8()?;
+                if count == 0 || reader.u8()? != 0 {
+                    return Err(
+                        reader.error("invokeinterface count is 0 or the fourth byte is not 0")
+                    );
+                }
+                Instruction::Invokeinterface { index, count }
+            }
+            0xba => {
+                let index = CpIndex(reader.u16()?);
+                if reader.u16()? != 0 {
+                    return Err(reader.error("invokedynamic operand bytes 3 and 4 are not 0"));
+                }
+                Instruction::Invokedynamic(index)
+            }
+            0xbb => Instruction::New(CpIndex(reader.u16()?)),
+            0xbc => {
+                let atype = reader.u8()?;
+                match ArrayType::from_u8(atype) {
+                    Some(array_type) => Instruction::Newarray(array_type),
+                    None => {
+                        return Err(reader.error(format!("newarray with invalid atype {}", atype)))
+                    }
+                }
+            }
+            0xbd => Instruction::Anewarray(CpIndex(reader.u16()?)),
+            0xbe => Instruction::Arraylength,
+            0xbf => Instruction::Athrow,
+            0xc0 => Instruction::Checkcast(CpIndex(reader.u16()?)),
+            0xc1 => Instruction::Instanceof(CpIndex(reader.u16()?)),
+            0xc2 => Instruction::Monitorenter,
+            0xc3 => Instruction::Monitorexit,
+            0xc4 => {
+                let opcode = reader.u8()?;
+                let index = reader.u16()?;
+                match opcode {
+                    0x15 => Instruction::Iload(index),
+                    0x16 => Instruction::Lload(index),
+                    0x17 => Instruction::Fload(index),
+                    0x18 => Instruction::Dload(index),
+                    0x19 => Instruction::Aload(index),
+                    0x36 => Instruction::Istore(index),
+                    0x37 => Instruction::Lstore(index),
+                    0x38 => Instruction::Fstore(index),
+                    0x39 => Instruction::Dstore(index),
+                    0x3a => Instruction::Astore(index),
+                    0xa9 => Instruction::Ret(index),
+                    0x84 => Instruction::Iinc {
+                        index,
+                        value: reader.u16()? as i16,
+                    },
+                    _ => {
+                        return Err(
+                            reader.error(format!("wide cannot modify opcode 0x{:02x}", opcode))
+                        )
+                    }
+                }
+            }
+            0xc5 => {
+                let index = CpIndex(reader.u16()?);
+                let dimensions = reader.u8()?;
+                if dimensions == 0 {
+                    return Err(reader.error("multianewarray with 0 dimensions"));
+                }
+                Instruction::Multianewarray { index, dimensions }
+            }
+            0xc6 => Instruction::Ifnull(reader.u16()? as i16),
+            0xc7 => Instruction::Ifnonnull(reader.u16()? as i16),
+            0xc8 => Instruction::GotoW(reader.u32()? as i32),
+            0xc9 => Instruction::JsrW(reader.u32()? as i32),
+            0xca => Instruction::Breakpoint,
+            0xfe => Instruction::Impdep1,
+            0xff => Instruction::Impdep2,
+            _ => return Err(reader.error(format!("unknown opcode 0x{:02x}", opcode))),
+        };
+        Ok((instruction, reader.pc - pc))
+    }
+
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Instruction::Nop => 0x00,
+            Instruction::AconstNull => 0x01,
+            Instruction::IconstM1 => 0x02,
+            Instruction::Iconst0 => 0x03,
+            Instruction::Iconst1 => 0x04,
+            Instruction::Iconst2 => 0x05,
+            Instruction::Iconst3 => 0x06,
+            Instruction::Iconst4 => 0x07,
+            Instruction::Iconst5 => 0x08,
+            Instruction::Lconst0 => 0x09,
+            Instruction::Lconst1 => 0x0a,
+            Instruction::Fconst0 => 0x0b,
+            Instruction::Fconst1 => 0x0c,
+            Instruction::Fconst2 => 0x0d,
+            Instruction::Dconst0 => 0x0e,
+            Instruction::Dconst1 => 0x0f,
+            Instruction::Bipush(_) => 0x10,
+            Instruction::Sipush(_) => 0x11,
+            Instruction::Ldc(_) => 0x12,
+            Instruction::LdcW(_) => 0x13,
+            Instruction::Ldc2W(_) => 0x14,
+            Instruction::Iload(_) => 0x15,
+            Instruction::Lload(_) => 0x16,
+            Instruction::Fload(_) => 0x17,
+            Instruction::Dload(_) => 0x18,
+            Instruction::Aload(_) => 0x19,
+            Instruction::Iload0 => 0x1a,
+            Instruction::Iload1 => 0x1b,
+            Instruction::Iload2 => 0x1c,
+            Instruction::Iload3 => 0x1d,
+            Instruction::Lload0 => 0x1e,
+            Instruction::Lload1 => 0x1f,
+            Instruction::Lload2 => 0x20,
+            Instruction::Lload3 => 0x21,
+            Instruction::Fload0 => 0x22,
+            Instruction::Fload1 => 0x23,
+            Instruction::Fload2 => 0x24,
+            Instruction::Fload3 => 0x25,
+            Instruction::Dload0 => 0x26,
+            Instruction::Dload1 => 0x27,
+            Instruction::Dload2 => 0x28,
+            Instruction::Dload3 => 0x29,
+            Instruction::Aload0 => 0x2a,
+            Instruction::Aload1 => 0x2b,
+            Instruction::Aload2 => 0x2c,
+            Instruction::Aload3 => 0x2d,
+            Instruction::Iaload => 0x2e,
+            Instruction::Laload => 0x2f,
+            Instruction::Faload => 0x30,
+            Instruction::Daload => 0x31,
+            Instruction::Aaload => 0x32,
+            Instruction::Baload => 0x33,
+            Instruction::Caload => 0x34,
+            Instruction::Saload => 0x35,
+            Instruction::Istore(_) => 0x36,
+            Instruction::Lstore(_) => 0x37,
+            Instruction::Fstore(_) => 0x38,
+            Instruction::Dstore(_) => 0x39,
+            Instruction::Astore(_) => 0x3a,
+            Instruction::Istore0 => 0x3b,
+            Instruction::Istore1 => 0x3c,
+            Instruction::Istore2 => 0x3d,
+            Instruction::Istore3 => 0x3e,
+            Instruction::Lstore0 => 0x3f,
+            Instruction::Lstore1 => 0x40,
+            Instruction::Lstore2 => 0x41,
+            Instruction::Lstore3 => 0x42,
+            Instruction::Fstore0 => 0x43,
+            Instruction::Fstore1 => 0x44,
+            Instruction::Fstore2 => 0x45,
+            Instruction::Fstore3 => 0x46,
+            Instruction::Dstore0 => 0x47,
+            Instruction::Dstore1 => 0x48,
+            Instruction::Dstore2 => 0x49,
+            Instruction::Dstore3 => 0x4a,
+            Instruction::Astore0 => 0x4b,
+            Instruction::Astore1 => 0x4c,
+            Instruction::Astore2 => 0x4d,
+            Instruction::Astore3 => 0x4e,
+            Instruction::Iastore => 0x4f,
+            Instruction::Lastore => 0x50,
+            Instruction::Fastore => 0x51,
+            Instruction::Dastore => 0x52,
+            Instruction::Aastore => 0x53,
+            Instruction::Bastore => 0x54,
+            Instruction::Castore => 0x55,
+            Instruction::Sastore => 0x56,
+            Instruction::Pop => 0x57,
+            Instruction::Pop2 => 0x58,
+            Instruction::Dup => 0x59,
+            Instruction::DupX1 => 0x5a,
+            Instruction::DupX2 => 0x5b,
+            Instruction::Dup2 => 0x5c,
+            Instruction::Dup2X1 => 0x5d,
+            Instruction::Dup2X2 => 0x5e,
+            Instruction::Swap => 0x5f,
+            Instruction::Iadd => 0x60,
+            Instruction::Ladd => 0x61,
+            Instruction::Fadd => 0x62,
+            Instruction::Dadd => 0x63,
+            Instruction::Isub => 0x64,
+            Instruction::Lsub => 0x65,
+            Instruction::Fsub => 0x66,
+            Instruction::Dsub => 0x67,
+            Instruction::Imul => 0x68,
+            Instruction::Lmul => 0x69,
+            Instruction::Fmul => 0x6a,
+            Instruction::Dmul => 0x6b,
+            Instruction::Idiv => 0x6c,
+            Instruction::Ldiv => 0x6d,
+            Instruction::Fdiv => 0x6e,
+            Instruction::Ddiv => 0x6f,
+            Instruction::Irem => 0x70,
+            Instruction::Lrem => 0x71,
+            Instruction::Frem => 0x72,
+            Instruction::Drem => 0x73,
+            Instruction::Ineg => 0x74,
+            Instruction::Lneg => 0x75,
+            Instruction::Fneg => 0x76,
+            Instruction::Dneg => 0x77,
+            Instruction::Ishl => 0x78,
+            Instruction::Lshl => 0x79,
+            Instruction::Ishr => 0x7a,
+            Instruction::Lshr => 0x7b,
+            Instruction::Iushr => 0x7c,
+            Instruction::Lushr => 0x7d,
+            Instruction::Iand => 0x7e,
+            Instruction::Land => 0x7f,
+            Instruction::Ior => 0x80,
+            Instruction::Lor => 0x81,
+            Instruction::Ixor => 0x82,
+            Instruction::Lxor => 0x83,
+            Instruction::Iinc { .. } => 0x84,
+            Instruction::I2l => 0x85,
+            Instruction::I2f => 0x86,
+            Instruction::I2d => 0x87,
+            Instruction::L2i => 0x88,
+            Instruction::L2f => 0x89,
+            Instruction::L2d => 0x8a,
+            Instruction::F2i => 0x8b,
+            Instruction::F2l => 0x8c,
+            Instruction::F2d => 0x8d,
+            Instruction::D2i => 0x8e,
+            Instruction::D2l => 0x8f,
+            Instruction::D2f => 0x90,
+            Instruction::I2b => 0x91,
+            Instruction::I2c => 0x92,
+            Instruction::I2s => 0x93,
+            Instruction::Lcmp => 0x94,
+            Instruction::Fcmpl => 0x95,
+            Instruction::Fcmpg => 0x96,
+            Instruction::Dcmpl => 0x97,
+            Instruction::Dcmpg => 0x98,
+            Instruction::Ifeq(_) => 0x99,
+            Instruction::Ifne(_) => 0x9a,
+            Instruction::Iflt(_) => 0x9b,
+            Instruction::Ifge(_) => 0x9c,
+            Instruction::Ifgt(_) => 0x9d,
+            Instruction::Ifle(_) => 0x9e,
+            Instruction::IfIcmpeq(_) => 0x9f,
+            Instruction::IfIcmpne(_) => 0xa0,
+            Instruction::IfIcmplt(_) => 0xa1,
+            Instruction::IfIcmpge(_) => 0xa2,
+            Instruction::IfIcmpgt(_) => 0xa3,
+            Instruction::IfIcmple(_) => 0xa4,
+            Instruction::IfAcmpeq(_) => 0xa5,
+            Instruction::IfAcmpne(_) => 0xa6,
+            Instruction::Goto(_) => 0xa7,
+            Instruction::Jsr(_) => 0xa8,
+            Instruction::Ret(_) => 0xa9,
+            Instruction::Tableswitch { .. } => 0xaa,
+            Instruction::Lookupswitch { .. } => 0xab,
+            Instruction::Ireturn => 0xac,
+            Instruction::Lreturn => 0xad,
+            Instruction::Freturn => 0xae,
+            Instruction::Dreturn => 0xaf,
+            Instruction::Areturn => 0xb0,
+            Instruction::Return => 0xb1,
+            Instruction::Getstatic(_) => 0xb2,
+            Instruction::Putstatic(_) => 0xb3,
+            Instruction::Getfield(_) => 0xb4,
+            Instruction::Putfield(_) => 0xb5,
+            Instruction::Invokevirtual(_) => 0xb6,
+            Instruction::Invokespecial(_) => 0xb7,
+            Instruction::Invokestatic(_) => 0xb8,
+            Instruction::Invokeinterface { .. } => 0xb9,
+            Instruction::Invokedynamic(_) => 0xba,
+            Instruction::New(_) => 0xbb,
+            Instruction::Newarray(_) => 0xbc,
+            Instruction::Anewarray(_) => 0xbd,
+            Instruction::Arraylength => 0xbe,
+            Instruction::Athrow => 0xbf,
+            Instruction::Checkcast(_) => 0xc0,
+            Instruction::Instanceof(_) => 0xc1,
+            Instruction::Monitorenter => 0xc2,
+            Instruction::Monitorexit => 0xc3,
+            Instruction::Multianewarray { .. } => 0xc5,
+            Instruction::Ifnull(_) => 0xc6,
+            Instruction::Ifnonnull(_) => 0xc7,
+            Instruction::GotoW(_) => 0xc8,
+            Instruction::JsrW(_) => 0xc9,
+            Instruction::Breakpoint => 0xca,
+            Instruction::Impdep1 => 0xfe,
+            Instruction::Impdep2 => 0xff,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        OPCODE_NAMES[self.opcode() as usize]
+    }
+
+    // Absolute targets of a branch, jsr or switch at pc, default target first for switches.
+    pub fn branch_targets(&self, pc: usize) -> Vec<usize> {
+        let target = |offset: i32| (pc as i64 + offset as i64) as usize;
+        match self {
+            Instruction::Ifeq(offset)
+            | Instruction::Ifne(offset)
+            | Instruction::Iflt(offset)
+            | Instruction::Ifge(offset)
+            | Instruction::Ifgt(offset)
+            | Instruction::Ifle(offset)
+            | Instruction::IfIcmpeq(offset)
+            | Instruction::IfIcmpne(offset)
+            | Instruction::IfIcmplt(offset)
+            | Instruction::IfIcmpge(offset)
+            | Instruction::IfIcmpgt(offset)
+            | Instruction::IfIcmple(offset)
+            | Instruction::IfAcmpeq(offset)
+            | Instruction::IfAcmpne(offset)
+            | Instruction::Goto(offset)
+            | Instruction::Jsr(offset)
+            | Instruction::Ifnull(offset)
+            | Instruction::Ifnonnull(offset) => vec![target(*offset as i32)],
+            Instruction::GotoW(offset) | Instruction::JsrW(offset) => vec![target(*offset)],
+            Instruction::Tableswitch {
+                default, offsets, ..
+            } => std::iter::once(default)
+                .chain(offsets.iter())
+                .map(|offset| target(*offset))
+                .collect(),
+            Instruction::Lookupswitch { default, pairs } => std::iter::once(default)
+                .chain(pairs.iter().map(|(_, offset)| offset))
+                .map(|offset| target(*offset))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Whether execution can continue with the next instruction. A jsr does, once the
+    // subroutine returns.
+    pub fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Instruction::Goto(_)
+                | Instruction::GotoW(_)
+                | Instruction::Ret(_)
+                | Instruction::Tableswitch { .. }
+                | Instruction::Lookupswitch { .. }
+                | Instruction::Ireturn
+                | Instruction::Lreturn
+                | Instruction::Freturn
+                | Instruction::Dreturn
+                | Instruction::Areturn
+                | Instruction::Return
+                | Instruction::Athrow
+        )
+    }
+
+    // The local variable slots up to which the instruction reads or writes, if it does.
+    pub fn local_slots(&self) -> Option<u16> {
+        let end = match self {
+            Instruction::Iload(index)
+            | Instruction::Fload(index)
+            | Instruction::Aload(index)
+            | Instruction::Istore(index)
+            | Instruction::Fstore(index)
+            | Instruction::Astore(index)
+            | Instruction::Ret(index)
+            | Instruction::Iinc { index, .. } => *index as u32 + 1,
+            Instruction::Lload(index)
+            | Instruction::Dload(index)
+            | Instruction::Lstore(index)
+            | Instruction::Dstore(index) => *index as u32 + 2,
+            Instruction::Iload0
+            | Instruction::Fload0
+            | Instruction::Aload0
+            | Instruction::Istore0
+            | Instruction::Fstore0
+            | Instruction::Astore0 => 1,
+            Instruction::Iload1
+            | Instruction::Fload1
+            | Instruction::Aload1
+            | Instruction::Istore1
+            | Instruction::Fstore1
+            | Instruction::Astore1
+            | Instruction::Lload0
+            | Instruction::Dload0
+            | Instruction::Lstore0
+            | Instruction::Dstore0 => 2,
+            Instruction::Iload2
+            | Instruction::Fload2
+            | Instruction::Aload2
+            | Instruction::Istore2
+            | Instruction::Fstore2
+            | Instruction::Astore2
+            | Instruction::Lload1
+            | Instruction::Dload1
+            | Instruction::Lstore1
+            | Instruction::Dstore1 => 3,
+            Instruction::Iload3
+            | Instruction::Fload3
+            | Instruction::Aload3
+            | Instruction::Istore3
+            | Instruction::Fstore3
+            | Instruction::Astore3
+            | Instruction::Lload2
+            | Instruction::Dload2
+            | Instruction::Lstore2
+            | Instruction::Dstore2 => 4,
+            Instruction::Lload3
+            | Instruction::Dload3
+            | Instruction::Lstore3
+            | Instruction::Dstore3 => 5,
+            _ => return None,
+        };
+        // A long or double in slot 65535 would need a slot past the last one.
+        Some(end.min(u16::MAX as u32) as u16)
+    }
+
+    // Operand stack slots the instruction pops and pushes, long and double taking two. Member
+    // references and ldc constants are looked up in the constant pool.
+    pub fn stack_effect(
+        &self,
+        constant_pool: &ConstantPool,
+    ) -> Result<(u16, u16), ClassFormatError> {
+        let field_slots = |index: &CpIndex| -> Result<u16, ClassFormatError> {
+            let member = constant_pool.member_ref(*index)?;
+            Ok(FieldType::parse(&member.descriptor)?.slot_size())
+        };
+        let method = |index: &CpIndex| -> Result<MethodDescriptor, ClassFormatError> {
+            MethodDescriptor::parse(&constant_pool.member_ref(*index)?.descriptor)
+        };
+        Ok(match self {
+            Instruction::Nop
+            | Instruction::Iinc { .. }
+            | Instruction::Goto(_)
+            | Instruction::GotoW(_)
+            | Instruction::Ret(_)
+            | Instruction::Return
+            | Instruction::Breakpoint
+            | Instruction::Impdep1
+            | Instruction::Impdep2 => (0, 0),
+            Instruction::AconstNull
+            | Instruction::IconstM1
+            | Instruction::Iconst0
+            | Instruction::Iconst1
+            | Instruction::Iconst2
+            | Instruction::Iconst3
+            | Instruction::Iconst4
+            | Instruction::Iconst5
+            | Instruction::Fconst0
+            | Instruction::Fconst1
+            | Instruction::Fconst2
+            | Instruction::Bipush(_)
+            | Instruction::Sipush(_)
+            | Instruction::Iload(_)
+            | Instruction::Fload(_)
+            | Instruction::Aload(_)
+            | Instruction::Iload0
+            | Instruction::Iload1
+            | Instruction::Iload2
+            | Instruction::Iload3
+            | Instruction::Fload0
+            | Instruction::Fload1
+            | Instruction::Fload2
+            | Instruction::Fload3
+            | Instruction::Aload0
+            | Instruction::Aload1
+            | Instruction::Aload2
+            | Instruction::Aload3
+            | Instruction::Jsr(_)
+            | Instruction::JsrW(_)
+            | Instruction::New(_) => (0, 1),
+            Instruction::Lconst0
+            | Instruction::Lconst1
+            | Instruction::Dconst0
+            | Instruction::Dconst1
+            | Instruction::Lload(_)
+            | Instruction::Dload(_)
+            | Instruction::Lload0
+            | Instruction::Lload1
+            | Instruction::Lload2
+            | Instruction::Lload3
+            | Instruction::Dload0
+            | Instruction::Dload1
+            | Instruction::Dload2
+            | Instruction::Dload3 => (0, 2),
+            Instruction::Ldc(index) | Instruction::LdcW(index) | Instruction::Ldc2W(index) => {
+                let slots = match constant_pool.get(*index)? {
+                    Constant::Long(_) | Constant::Double(_) => 2,
+                    Constant::Dynamic(_) => {
+                        let (_, dynamic) = constant_pool.dynamic(*index)?;
+                        FieldType::parse(&dynamic.descriptor)?.slot_size()
+                    }
+                    _ => 1,
+                };
+                (0, slots)
+            }
+            Instruction::Iaload
+            | Instruction::Faload
+            | Instruction::Aaload
+            | Instruction::Baload
+            | Instruction::Caload
+            | Instruction::Saload => (2, 1),
+            Instruction::Laload | Instruction::Daload => (2, 2),
+            Instruction::Istore(_)
+            | Instruction::Fstore(_)
+            | Instruction::Astore(_)
+            | Instruction::Istore0
+            | Instruction::Istore1
+            | Instruction::Istore2
+            | Instruction::Istore3
+            | Instruction::Fstore0
+            | Instruction::Fstore1
+            | Instruction::Fstore2
+            | Instruction::Fstore3
+            | Instruction::Astore0
+            | Instruction::Astore1
+            | Instruction::Astore2
+            | Instruction::Astore3
+            | Instruction::Pop
+            | Instruction::Ifeq(_)
+            | Instruction::Ifne(_)
+            | Instruction::Iflt(_)
+            | Instruction::Ifge(_)
+            | Instruction::Ifgt(_)
+            | Instruction::Ifle(_)
+            | Instruction::Ifnull(_)
+            | Instruction::Ifnonnull(_)
+            | Instruction::Tableswitch { .. }
+            | Instruction::Lookupswitch { .. }
+            | Instruction::Ireturn
+            | Instruction::Freturn
+            | Instruction::Areturn
+            | Instruction::Athrow
+            | Instruction::Monitorenter
+            | Instruction::Monitorexit => (1, 0),
+            Instruction::Lstore(_)
+            | Instruction::Dstore(_)
+            | Instruction::Lstore0
+            | Instruction::Lstore1
+            | Instruction::Lstore2
+            | Instruction::Lstore3
+            | Instruction::Dstore0
+            | Instruction::Dstore1
+            | Instruction::Dstore2
+            | Instruction::Dstore3
+            | Instruction::Pop2
+            | Instruction::IfIcmpeq(_)
+            | Instruction::IfIcmpne(_)
+            | Instruction::IfIcmplt(_)
+            | Instruction::IfIcmpge(_)
+            | Instruction::IfIcmpgt(_)
+            | Instruction::IfIcmple(_)
+            | Instruction::IfAcmpeq(_)
+            | Instruction::IfAcmpne(_)
+            | Instruction::Lreturn
+            | Instruction::Dreturn => (2, 0),
+            Instruction::Iastore
+            | Instruction::Fastore
+            | Instruction::Aastore
+            | Instruction::Bastore
+            | Instruction::Castore
+            | Instruction::Sastore => (3, 0),
+            Instruction::Lastore | Instruction::Dastore => (4, 0),
+            Instruction::Dup => (1, 2),
+            Instruction::DupX1 => (2, 3),
+            Instruction::DupX2 => (3, 4),
+            Instruction::Dup2 => (2, 4),
+            Instruction::Dup2X1 => (3, 5),
+            Instruction::Dup2X2 => (4, 6),
+            Instruction::Swap => (2, 2),
+            Instruction::Iadd
+            | Instruction::Fadd
+            | Instruction::Isub
+            | Instruction::Fsub
+            | Instruction::Imul
+            | Instruction::Fmul
+            | Instruction::Idiv
+            | Instruction::Fdiv
+            | Instruction::Irem
+            | Instruction::Frem
+            | Instruction::Ishl
+            | Instruction::Ishr
+            | Instruction::Iushr
+            | Instruction::Iand
+            | Instruction::Ior
+            | Instruction::Ixor
+            | Instruction::Fcmpl
+            | Instruction::Fcmpg => (2, 1),
+            Instruction::Ladd
+            | Instruction::Dadd
+            | Instruction::Lsub
+            | Instruction::Dsub
+            | Instruction::Lmul
+            | Instruction::Dmul
+            | Instruction::Ldiv
+            | Instruction::Ddiv
+            | Instruction::Lrem
+            | Instruction::Drem
+            | Instruction::Land
+            | Instruction::Lor
+            | Instruction::Lxor => (4, 2),
+            Instruction::Lshl | Instruction::Lshr | Instruction::Lushr => (3, 2),
+            Instruction::Ineg
+            | Instruction::Fneg
+            | Instruction::I2f
+            | Instruction::F2i
+            | Instruction::I2b
+            | Instruction::I2c
+            | Instruction::I2s
+            | Instruction::Newarray(_)
+            | Instruction::Anewarray(_)
+            | Instruction::Arraylength
+            | Instruction::Checkcast(_)
+            | Instruction::Instanceof(_) => (1, 1),
+            Instruction::Lneg | Instruction::Dneg | Instruction::L2d | Instruction::D2l => (2, 2),
+            Instruction::I2l | Instruction::I2d | Instruction::F2l | Instruction::F2d => (1, 2),
+            Instruction::L2i | Instruction::L2f | Instruction::D2i | Instruction::D2f => (2, 1),
+            Instruction::Lcmp | Instruction::Dcmpl | Instruction::Dcmpg => (4, 1),
+            Instruction::Getstatic(index) => (0, field_slots(index)?),
+            Instruction::Putstatic(index) => (field_slots(index)?, 0),
+            Instruction::Getfield(index) => (1, field_slots(index)?),
+            Instruction::Putfield(index) => (1 + field_slots(index)?, 0),
+            Instruction::Invokevirtual(index)
+            | Instruction::Invokespecial(index)
+            | Instruction::Invokeinterface { index, .. } => {
+                let descriptor = method(index)?;
+                (1 + descriptor.parameter_slots(), descriptor.return_slots())
+            }
+            Instruction::Invokestatic(index) => {
+                let descriptor = method(index)?;
+                (descriptor.parameter_slots(), descriptor.return_slots())
+            }
+            Instruction::Invokedynamic(index) => {
+                let (_, dynamic) = constant_pool.dynamic(*index)?;
+                let descriptor = MethodDescriptor::parse(&dynamic.descriptor)?;
+                (descriptor.parameter_slots(), descriptor.return_slots())
+            }
+            Instruction::Multianewarray { dimensions, .. } => (*dimensions as u16, 1),
+        })
+    }
+
+    // Appends the encoded instruction; writer.offset() must be its pc, since switch padding
+    // depends on it. Local variable indices above 255 and iinc increments outside -128..=127
+    // get a wide prefix.
+    pub fn encode(&self, writer: &mut BytecodeWriter) -> Result<(), ClassFormatError> {
+        let opcode = self.opcode();
+        match self {
+            Instruction::Iload(index)
+            | Instruction::Lload(index)
+            | Instruction::Fload(index)
+            | Instruction::Dload(index)
+            | Instruction::Aload(index)
+            | Instruction::Istore(index)
+            | Instruction::Lstore(index)
+            | Instruction::Fstore(index)
+            | Instruction::Dstore(index)
+            | Instruction::Astore(index)
+            | Instruction::Ret(index) => {
+                if *index > u8::MAX as u16 {
+                    writer.u8(0xc4);
+                    writer.u8(opcode);
+                    writer.u16(*index);
+                } else {
+                    writer.u8(opcode);
+                    writer.u8(*index as u8);
+                }
+            }
+            Instruction::Iinc { index, value } => {
+                if *index > u8::MAX as u16 || i8::try_from(*value).is_err() {
+                    writer.u8(0xc4);
+                    writer.u8(opcode);
+                    writer.u16(*index);
+                    writer.u16(*value as u16);
+                } else {
+                    writer.u8(opcode);
+                    writer.u8(*index as u8);
+                    writer.u8(*value as u8);
+                }
+            }
+            Instruction::Bipush(value) => {
+                writer.u8(opcode);
+                writer.u8(*value as u8);
+            }
+            Instruction::Sipush(value) => {
+                writer.u8(opcode);
+                writer.u16(*value as u16);
+            }
+            Instruction::Ldc(index) => {
+                if index.0 > u8::MAX as u16 {
+                    return Err(ClassFormatError::new(format!(
+                        "ldc cannot refer to constant #{}, use ldc_w",
+                        index
+                    )));
+                }
+                writer.u8(opcode);
+                writer.u8(index.0 as u8);
+            }
+            Instruction::LdcW(index)
+            | Instruction::Ldc2W(index)
+            | Instruction::Getstatic(index)
+            | Instruction::Putstatic(index)
+            | Instruction::Getfield(index)
+            | Instruction::Putfield(index)
+            | Instruction::Invokevirtual(index)
+            | Instruction::Invokespecial(index)
+            | Instruction::Invokestatic(index)
+            | Instruction::New(index)
+            | Instruction::Anewarray(index)
+            | Instruction::Checkcast(index)
+            | Instruction::Instanceof(index) => {
+                writer.u8(opcode);
+                writer.u16(index.0);
+            }
+            Instruction::Ifeq(offset)
+            | Instruction::Ifne(offset)
+            | Instruction::Iflt(offset)
+            | Instruction::Ifge(offset)
+            | Instruction::Ifgt(offset)
+            | Instruction::Ifle(offset)
+            | Instruction::IfIcmpeq(offset)
+            | Instruction::IfIcmpne(offset)
+            | Instruction::IfIcmplt(offset)
+            | Instruction::IfIcmpge(offset)
+            | Instruction::IfIcmpgt(offset)
+            | Instruction::IfIcmple(offset)
+            | Instruction::IfAcmpeq(offset)
+            | Instruction::IfAcmpne(offset)
+            | Instruction::Goto(offset)
+            | Instruction::Jsr(offset)
+            | Instruction::Ifnull(offset)
+            | Instruction::Ifnonnull(offset) => {
+                writer.u8(opcode);
+                writer.u16(*offset as u16);
+            }
+            Instruction::GotoW(offset) | Instruction::JsrW(offset) => {
+                writer.u8(opcode);
+                writer.u32(*offset as u32);
+            }
+            Instruction::Tableswitch {
+                default,
+                low,
+                high,
+                offsets,
+            } => {
+                writer.u8(opcode);
+                while !writer.offset().is_multiple_of(4) {
+                    writer.u8(0);
+                }
+                writer.u32(*default as u32);
+                writer.u32(*low as u32);
+                writer.u32(*high as u32);
+                for offset in offsets {
+                    writer.u32(*offset as u32);
+                }
+            }
+            Instruction::Lookupswitch { default, pairs } => {
+                writer.u8(opcode);
+                while !writer.offset().is_multiple_of(4) {
+                    writer.u8(0);
+                }
+                writer.u32(*default as u32);
+                writer.u32(pairs.len() as u32);
+                for (key, offset) in pairs {
+                    writer.u32(*key as u32);
+                    writer.u32(*offset as u32);
+                }
+            }
+            Instruction::Invokeinterface { index, count } => {
+                writer.u8(opcode);
+                writer.u16(index.0);
+                writer.u8(*count);
+                writer.u8(0);
+            }
+            Instruction::Invokedynamic(index) => {
+                writer.u8(opcode);
+                writer.u16(index.0);
+                writer.u16(0);
+            }
+            Instruction::Newarray(array_type) => {
+                writer.u8(opcode);
+                writer.u8(*array_type as u8);
+            }
+            Instruction::Multianewarray { index, dimensions } => {
+                writer.u8(opcode);
+                writer.u16(index.0);
+                writer.u8(*dimensions);
+            }
+            _ => writer.u8(opcode),
+        }
+        Ok(())
+    }
+}
+
+// Reads operands of the instruction starting at `start`, reporting truncation against it.
+struct CodeReader<'a> {
+    code: &'a [u8],
+    start: usize,
+    pc: usize,
+}
+
+impl CodeReader<'_> {
+    fn error(&self, reason: impl Into<String>) -> ClassFormatError {
+        ClassFormatError::new(reason).within(format!("instruction at pc {}", self.start))
+    }
+
+    fn read<const N: usize>(&mut self) -> Result<[u8; N], ClassFormatError> {
+        match self.code.get(self.pc..self.pc + N) {
+            Some(bytes) => {
+                self.pc += N;
+                Ok(bytes.try_into().unwrap())
+            }
+            None => {
+                let name = opcode_name(self.code[self.start]).unwrap_or("?");
+                Err(self.error(format!(
+                    "truncated code: {} needs {} more bytes at pc {}, code_length is {}",
+                    name,
+                    self.pc + N - self.code.len(),
+                    self.pc,
+                    self.code.len()
+                )))
+            }
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, ClassFormatError> {
+        Ok(self.read::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ClassFormatError> {
+        Ok(u16::from_be_bytes(self.read::<2>()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, ClassFormatError> {
+        Ok(u32::from_be_bytes(self.read::<4>()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, ClassFormatError> {
+        Ok(i32::from_be_bytes(self.read::<4>()?))
+    }
+
+    // tableswitch and lookupswitch operands start at a multiple of four from the start of the
+    // code; the 0-3 padding bytes are not checked.
+    fn skip_padding(&mut self) -> Result<(), ClassFormatError> {
+        while !self.pc.is_multiple_of(4) {
+            self.u8()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Encodes the instructions back to back from pc 0, as CodeBuilder does.
+    fn encode_all(instructions: &[Instruction]) -> Vec<u8> {
+        let mut writer = BytecodeWriter::new();
+        for instruction in instructions {
+            instruction.encode(&mut writer).unwrap();
+        }
+        writer.data
+    }
+
+    #[test]
+    fn switch_padding_depends_on_pc() {
+        let tableswitch = Instruction::Tableswitch {
+            default: 20,
+            low: -1,
+            high: 0,
+            offsets: vec![24, 28],
+        };
+        let lookupswitch = Instruction::Lookupswitch {
+            default: 20,
+            pairs: vec![(-5, 24), (7, 28)],
+        };
+        for switch in [tableswitch, lookupswitch] {
+            for nops in 0..4 {
+                let mut instructions = vec![Instruction::Nop; nops];
+                instructions.push(switch.clone());
+                instructions.push(Instruction::Return);
+                let code = encode_all(&instructions);
+                // opcode, padding to the next multiple of 4, then the operands
+                let padding = 3 - nops % 4;
+                assert!(code[nops + 1..nops + 1 + padding].iter().all(|b| *b == 0));
+                assert_eq!(
+                    code[nops + 1 + padding..nops + 5 + padding],
+                    20i32.to_be_bytes()
+                );
+                let decoded = Instruction::decode_all(&code).unwrap();
+                assert_eq!(decoded.len(), nops + 2);
+                assert_eq!(decoded[nops], (nops, switch.clone()));
+                assert_eq!(decoded[nops + 1].0, code.len() - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn wide_round_trip() {
+        let code = [0xc4, 0x84, 0x01, 0x00, 0xff, 0x38, 0xc4, 0x15, 0x00, 0x07];
+        let decoded = Instruction::decode_all(&code).unwrap();
+        assert_eq!(
+            decoded,
+            vec![
+                (
+                    0,
+                    Instruction::Iinc {
+                        index: 256,
+                        value: -200
+                    }
+                ),
+                (6, Instruction::Iload(7)),
+            ]
+        );
+        // a wide prefix is only kept where the operands need it
+        let encoded = encode_all(&[decoded[0].1.clone(), decoded[1].1.clone()]);
+        assert_eq!(encoded, [0xc4, 0x84, 0x01, 0x00, 0xff, 0x38, 0x15, 0x07]);
+        let narrow = encode_all(&[Instruction::Iinc {
+            index: 1,
+            value: -128,
+        }]);
+        assert_eq!(narrow, [0x84, 0x01, 0x80]);
+        let wide_value = encode_all(&[Instruction::Iinc {
+            index: 1,
+            value: 128,
+        }]);
+        assert_eq!(wide_value, [0xc4, 0x84, 0x00, 0x01, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn wide_rejects_other_opcodes() {
+        let error = Instruction::decode(&[0xc4, 0x10, 0x00, 0x01], 0).unwrap_err();
+        assert!(error.to_string().contains("wide cannot modify opcode 0x10"));
+    }
+
+    #[test]
+    fn truncated_code() {
+        // sipush missing its second byte
+        let error = Instruction::decode_all(&[0x00, 0x11, 0x01]).unwrap_err();
+        assert!(error.to_string().contains("truncated code: sipush"));
+        assert!(error.to_string().contains("instruction at pc 1"));
+        // tableswitch cut inside its padding and inside its offsets
+        assert!(Instruction::decode_all(&[0xaa, 0x00]).is_err());
+        let mut code = encode_all(&[Instruction::Tableswitch {
+            default: 0,
+            low: 0,
+            high: 1,
+            offsets: vec![0, 0],
+        }]);
+        code.pop();
+        assert!(Instruction::decode_all(&code).is_err());
+        // wide iinc without its increment
+        assert!(Instruction::decode_all(&[0xc4, 0x84, 0x00, 0x01]).is_err());
+    }
+}
@@ -1,33 +1,161 @@
-use crate::jvm::class::{attribute::*, constant::*};
+use crate::jvm::class::{
+    access_flags::MethodAccessFlags, attribute::*, constant_pool::*, descriptor::MethodDescriptor,
+    error::ClassFormatError, signature::MethodSignature,
+};
 use crate::utils::bytecode_reader::BytecodeReader;
 
 #[derive(Debug)]
 pub struct Method {
-    pub access_flags: u16,
-    pub name: String,
-    pub descriptor: String,
+    pub access_flags: MethodAccessFlags,
+    pub name: Indexed<String>,
+    pub descriptor: Indexed<String>,
     pub attributes: Vec<Attribute>,
+    pub attribute_name_indices: Vec<CpIndex>,
 }
 impl Method {
-    pub fn new(reader: &mut BytecodeReader, constant_pool: &Vec<Constant>) -> Method {
-        let access_flags = reader.u16();
-
-        let name_index = reader.u16();
-        let name: String = Constant::read_utf8_data(constant_pool, name_index);
-
-        let descriptor_index = reader.u16();
-        let descriptor: String = Constant::read_utf8_data(constant_pool, descriptor_index);
-        let attributes_count = reader.u16();
-        let mut attributes: Vec<Attribute> = Vec::new();
-        for _ in 0..attributes_count {
-            attributes.push(Attribute::new(reader, constant_pool));
-        }
+    pub fn new(
+        reader: &mut BytecodeReader,
+        constant_pool: &ConstantPool,
+    ) -> Result<Method, ClassFormatError> {
+        let access_flags = MethodAccessFlags(reader.u16()?);
+
+        let name = constant_pool.read_utf8_ref(reader)?;
+
+        let descriptor = constant_pool.read_utf8_ref(reader)?;
+        let (attributes, attribute_name_indices) = read_attributes(reader, constant_pool)
+            .map_err(|e| e.within(format!("method {}{}", name, descriptor)))?;
 
-        Method {
+        Ok(Method {
             access_flags,
             name,
             descriptor,
             attributes,
+            attribute_name_indices,
+        })
+    }
+
+    pub fn method_descriptor(&self) -> Result<MethodDescriptor, ClassFormatError> {
+        MethodDescriptor::parse(&self.descriptor)
+    }
+
+    // The generic signature from the Signature attribute, if present.
+    pub fn signature(&self) -> Result<Option<MethodSignature>, ClassFormatError> {
+        for attribute in &self.attributes {
+            if let Attribute::Signature(a) = attribute {
+                return MethodSignature::parse(&a.signature).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    // A declaration annotation (visible or invisible) by its type descriptor, e.g.
+    // "Lorg/junit/Test;".
+    pub fn annotation(&self, type_descriptor: &str) -> Option<&Annotation> {
+        self.annotations()
+            .into_iter()
+            .find(|a| a.type_descriptor == type_descriptor)
+    }
+
+    pub fn annotations(&self) -> Vec<&Annotation> {
+        annotations(&self.attributes)
+    }
+
+    pub fn type_annotations(&self) -> Vec<&TypeAnnotation> {
+        type_annotations(&self.attributes)
+    }
+
+    // Visible and invisible annotations of each formal parameter, merged.
+    pub fn parameter_annotations(&self) -> Vec<Vec<&Annotation>> {
+        let mut parameters: Vec<Vec<&Annotation>> = Vec::new();
+        for attribute in &self.attributes {
+            match attribute {
+                Attribute::RuntimeVisibleParameterAnnotations(a)
+                | Attribute::RuntimeInvisibleParameterAnnotations(a) => {
+                    for (i, annotations) in a.parameter_annotations.iter().enumerate() {
+                        if parameters.len() <= i {
+                            parameters.resize(i + 1, Vec::new());
+                        }
+                        parameters[i].extend(annotations.iter());
+                    }
+                }
+                _ => {}
+            }
+        }
+        parameters
+    }
+
+    // The default value of an annotation interface element.
+    pub fn annotation_default(&self) -> Option<&ElementValue> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::AnnotationDefault(a) => Some(&a.default_value),
+                _ => None,
+            })
+    }
+
+    pub fn code(&self) -> Option<&AttributeCode> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::Code(a) => Some(a),
+                _ => None,
+            })
+    }
+
+    // The `throws` clause from the Exceptions attribute.
+    pub fn exceptions(&self) -> &[ClassRef] {
+        for attribute in &self.attributes {
+            if let Attribute::Exceptions(a) = attribute {
+                return &a.exception_index_table;
+            }
+        }
+        &[]
+    }
+
+    // Formal parameter names and flags, only present when compiled with `javac -parameters`
+    // (and always for some mandated parameters, e.g. of enum valueOf).
+    pub fn parameters(&self) -> &[MethodParameter] {
+        for attribute in &self.attributes {
+            if let Attribute::MethodParameters(a) = attribute {
+                return &a.parameters;
+            }
+        }
+        &[]
+    }
+
+    pub fn local_variables(&self) -> Vec<&LocalVariableInfo> {
+        let mut local_variables: Vec<&LocalVariableInfo> = Vec::new();
+        if let Some(code) = self.code() {
+            for attribute in &code.attributes {
+                if let Attribute::LocalVariableTable(a) = attribute {
+                    local_variables.extend(a.local_variable_table.iter());
+                }
+            }
+        }
+        local_variables
+    }
+
+    // Generic types of the local variables whose type uses a type variable or parameterized
+    // type; a Code attribute may split them over several LocalVariableTypeTable attributes.
+    pub fn local_variable_types(&self) -> Vec<&LocalVariableTypeInfo> {
+        let mut local_variable_types: Vec<&LocalVariableTypeInfo> = Vec::new();
+        if let Some(code) = self.code() {
+            for attribute in &code.attributes {
+                if let Attribute::LocalVariableTypeTable(a) = attribute {
+                    local_variable_types.extend(a.local_variable_type_table.iter());
+                }
+            }
         }
+        local_variable_types
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        is_deprecated(&self.attributes)
+    }
+
+    // Either ACC_SYNTHETIC or, in classes older than version 49, the Synthetic attribute.
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags.contains(MethodAccessFlags::SYNTHETIC) || is_synthetic(&self.attributes)
     }
 }
@@ -1,90 +1,125 @@
+pub mod access_flags;
 pub mod attribute;
+pub mod class_builder;
+pub mod code_builder;
 pub mod constant;
+pub mod constant_pool;
+pub mod constant_pool_builder;
+pub mod descriptor;
+pub mod error;
 pub mod field;
+pub mod format_check;
+pub mod instruction;
 pub mod method;
+pub mod module;
+pub mod signature;
+pub mod stack_map;
+pub mod version;
+pub mod writer;
 
 use std::io::Read;
 
-use crate::jvm::class::{attribute::*, constant::*, field::*, method::*};
-use crate::utils::{bytecode_reader::BytecodeReader, code2name};
+use crate::jvm::class::{
+    access_flags::ClassAccessFlags, attribute::*, constant_pool::*, error::ClassFormatError,
+    field::*, method::*, module::ModuleDescriptor, signature::ClassSignature,
+    version::ClassVersion,
+};
+use crate::utils::bytecode_reader::BytecodeReader;
 
 #[derive(Debug)]
 pub struct Class {
     pub minor_version: u16,
     pub major_version: u16,
-    pub constant_pool: Vec<Constant>,
-    pub access_flags: u16,
-    pub this_class: String,
-    pub super_class: String,
-    pub interfaces: Vec<u16>,
+    pub constant_pool: ConstantPool,
+    pub access_flags: ClassAccessFlags,
+    pub this_class: ClassRef,
+    pub super_class: Option<ClassRef>, // None for java/lang/Object and module-info
+    pub interfaces: Vec<CpIndex>,
     pub fields: Vec<Field>,
     pub methods: Vec<Method>,
     pub attributes: Vec<Attribute>,
+    pub attribute_name_indices: Vec<CpIndex>,
 }
 
 impl Class {
-    pub fn new<T>(file: T) -> Option<Class>
+    pub fn new<T>(file: T) -> Result<Class, ClassFormatError>
     where
         T: Read,
     {
-        let mut reader = BytecodeReader::read_from_file(file);
+        let mut reader = BytecodeReader::read_from_file(file)?;
 
-        let magic = reader.u32();
+        let magic = reader.u32()?;
         if magic != 0xCAFEBABE_u32 {
-            println!("It is not a classfile.");
-            return Option::None;
+            return Err(ClassFormatError::at(
+                0,
+                format!("bad magic number 0x{:08X}, it is not a classfile", magic),
+            ));
         }
-        let minor_version = reader.u16();
-        let major_version = reader.u16();
+        let minor_version = reader.u16()?;
+        let major_version = reader.u16()?;
 
-        let constant_pool_count = reader.u16();
+        let constant_pool_count = reader.u16()?;
 
-        let mut constant_pool: Vec<Constant> = Vec::new();
-        for _ in 1..constant_pool_count {
-            constant_pool.push(Constant::new(&mut reader));
-        }
+        let constant_pool = ConstantPool::new(&mut reader, constant_pool_count)?;
 
-        let access_flags = reader.u16();
-        let this_class_index = reader.u16();
-        let this_class: String = match &constant_pool[this_class_index as usize - 1] {
-            Constant::Class(constant_class) => {
-                Constant::read_utf8_data(&constant_pool, constant_class.name_index)
-            }
-            _ => panic!("read class: wrong this_class_index."),
-        };
-        let super_class_index = reader.u16();
-        let super_class: String = match &constant_pool[super_class_index as usize - 1] {
-            Constant::Class(constant_class) => {
-                Constant::read_utf8_data(&constant_pool, constant_class.name_index)
-            }
-            _ => panic!("read class: wrong super_class_index."),
-        };
+        let access_flags = ClassAccessFlags(reader.u16()?);
+        let this_class = constant_pool
+            .read_class_ref(&mut reader)
+            .map_err(|e| e.within("this_class"))?;
+        Class::read_body(
+            &mut reader,
+            minor_version,
+            major_version,
+            constant_pool,
+            access_flags,
+            this_class.clone(),
+        )
+        .map_err(|e| e.within(format!("class {}", this_class)))
+    }
 
-        let interfaces_count = reader.u16();
-        let mut interfaces: Vec<u16> = Vec::new();
+    fn read_body(
+        reader: &mut BytecodeReader,
+        minor_version: u16,
+        major_version: u16,
+        constant_pool: ConstantPool,
+        access_flags: ClassAccessFlags,
+        this_class: ClassRef,
+    ) -> Result<Class, ClassFormatError> {
+        let super_class = constant_pool
+            .read_optional_class(reader)
+            .map_err(|e| e.within("super_class"))?;
+
+        let interfaces_count = reader.u16()?;
+        let mut interfaces: Vec<CpIndex> = Vec::new();
         for _ in 0..interfaces_count {
-            interfaces.push(reader.u16());
+            interfaces.push(CpIndex::read(reader)?);
         }
 
-        let fields_count = reader.u16();
+        let fields_count = reader.u16()?;
         let mut fields: Vec<Field> = Vec::new();
         for _ in 0..fields_count {
-            fields.push(Field::new(&mut reader, &constant_pool));
+            fields.push(Field::new(reader, &constant_pool)?);
         }
 
-        let methods_count = reader.u16();
+        let methods_count = reader.u16()?;
         let mut methods: Vec<Method> = Vec::new();
         for _ in 0..methods_count {
-            methods.push(Method::new(&mut reader, &constant_pool));
+            methods.push(Method::new(reader, &constant_pool)?);
         }
 
-        let attributes_count = reader.u16();
-        let mut attributes: Vec<Attribute> = Vec::new();
-        for _ in 0..attributes_count {
-            attributes.push(Attribute::new(&mut reader, &constant_pool));
+        let (attributes, attribute_name_indices) = read_attributes(reader, &constant_pool)?;
+
+        if reader.offset != reader.size {
+            return Err(ClassFormatError::at(
+                reader.offset,
+                format!(
+                    "{} extra bytes after the end of class",
+                    reader.size - reader.offset
+                ),
+            ));
         }
 
-        return Some(Class {
+        Ok(Class {
             minor_version,
             major_version,
             constant_pool,
@@ -95,51 +130,216 @@ impl Class {
             fields,
             methods,
             attributes,
-        });
+            attribute_name_indices,
+        })
+    }
+
+    pub fn version(&self) -> ClassVersion {
+        ClassVersion::new(self.major_version, self.minor_version)
+    }
+
+    // Checks the flags of the class, its fields, methods and inner classes against the
+    // combinations allowed by JVMS 4.1, 4.5, 4.6 and 4.7.6.
+    pub fn check_access_flags(&self) -> Result<(), ClassFormatError> {
+        let location = format!("class {}", self.this_class);
+        self.access_flags
+            .validate()
+            .map_err(|e| e.within(location.clone()))?;
+        let in_interface = self.access_flags.is_interface();
+        for field in &self.fields {
+            field.access_flags.validate(in_interface).map_err(|e| {
+                e.within(format!("field {}{}", field.name, field.descriptor))
+                    .within(location.clone())
+            })?;
+        }
+        for method in &self.methods {
+            method
+                .access_flags
+                .validate(&method.name, in_interface, self.major_version)
+                .map_err(|e| {
+                    e.within(format!("method {}{}", method.name, method.descriptor))
+                        .within(location.clone())
+                })?;
+        }
+        for inner_class in self.inner_classes() {
+            inner_class
+                .inner_class_access_flags
+                .validate()
+                .map_err(|e| {
+                    e.within(format!("inner class {}", inner_class.inner_class))
+                        .within(location.clone())
+                })?;
+        }
+        Ok(())
+    }
+
+    // The generic signature from the Signature attribute, if present.
+    pub fn signature(&self) -> Result<Option<ClassSignature>, ClassFormatError> {
+        for attribute in &self.attributes {
+            if let Attribute::Signature(a) = attribute {
+                return ClassSignature::parse(&a.signature).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    // A declaration annotation (visible or invisible) by its type descriptor, e.g.
+    // "Lorg/junit/Test;".
+    pub fn annotation(&self, type_descriptor: &str) -> Option<&Annotation> {
+        self.annotations()
+            .into_iter()
+            .find(|a| a.type_descriptor == type_descriptor)
+    }
+
+    pub fn annotations(&self) -> Vec<&Annotation> {
+        annotations(&self.attributes)
+    }
+
+    pub fn type_annotations(&self) -> Vec<&TypeAnnotation> {
+        type_annotations(&self.attributes)
+    }
+
+    pub fn inner_classes(&self) -> &[InnerClassInfo] {
+        for attribute in &self.attributes {
+            if let Attribute::InnerClasses(a) = attribute {
+                return &a.classes;
+            }
+        }
+        &[]
+    }
+
+    pub fn enclosing_method(&self) -> Option<&AttributeEnclosingMethod> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::EnclosingMethod(a) => Some(a),
+                _ => None,
+            })
+    }
+
+    // The NestHost attribute; a class without one is the host of its own nest.
+    pub fn nest_host(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::NestHost(a) => Some(&*a.host_class),
+                _ => None,
+            })
+    }
+
+    pub fn nest_members(&self) -> &[ClassRef] {
+        for attribute in &self.attributes {
+            if let Attribute::NestMembers(a) = attribute {
+                return &a.classes;
+            }
+        }
+        &[]
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|attribute| matches!(attribute, Attribute::PermittedSubclasses(_)))
+    }
+
+    pub fn permitted_subclasses(&self) -> &[ClassRef] {
+        for attribute in &self.attributes {
+            if let Attribute::PermittedSubclasses(a) = attribute {
+                return &a.classes;
+            }
+        }
+        &[]
+    }
+
+    // Same rule as java.lang.Class#isRecord: a direct subclass of java/lang/Record with a
+    // Record attribute.
+    pub fn is_record(&self) -> bool {
+        self.super_class.as_deref() == Some("java/lang/Record")
+            && self
+                .attributes
+                .iter()
+                .any(|attribute| matches!(attribute, Attribute::Record(_)))
+    }
+
+    pub fn record_components(&self) -> &[RecordComponentInfo] {
+        for attribute in &self.attributes {
+            if let Attribute::Record(a) = attribute {
+                return &a.components;
+            }
+        }
+        &[]
+    }
+
+    // module-info.class files have ACC_MODULE set and carry a Module attribute.
+    pub fn is_module(&self) -> bool {
+        self.access_flags.contains(ClassAccessFlags::MODULE)
     }
 
-    pub fn show_info(&self) {
-        println!(">>> Constant Pool:");
-        for cpi in 0..self.constant_pool.len() {
-            println!("{:>3}: {:?}", cpi + 1, self.constant_pool[cpi]);
+    pub fn module_descriptor(&self) -> Result<Option<ModuleDescriptor>, ClassFormatError> {
+        if !self.is_module() {
+            return Ok(None);
         }
+        ModuleDescriptor::new(self).map(Some)
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        is_deprecated(&self.attributes)
+    }
 
-        // println!("--------------------------------------------------");
-        // println!("--------------------------------------------------");
-        // println!("{:#?}", self);
-        // println!("--------------------------------------------------");
-        // println!("--------------------------------------------------");
-
-        for mi in 0..self.methods.len() {
-            println!(
-                ">>> Code {}: {}",
-                self.methods[mi].name, self.methods[mi].descriptor,
-            );
-            for ai in 0..self.methods[mi].attributes.len() {
-                match &self.methods[mi].attributes[ai] {
-                    Attribute::Code(attribute_code) => {
-                        for ci in 0..attribute_code.code.len() {
-                            println!(
-                                "    <{0:>3}|0x{0:0>2X}> ==> {1}",
-                                attribute_code.code[ci],
-                                code2name(attribute_code.code[ci]),
-                            );
-                        }
-                        for aci in 0..attribute_code.attributes.len() {
-                            match &attribute_code.attributes[aci] {
-                                Attribute::LocalVariableTable(attr) => {
-                                    for acli in 0..attr.local_variable_table.len() {
-                                        println!("    {:?}", attr.local_variable_table[acli]);
-                                    }
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                    _ => {}
-                }
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags.contains(ClassAccessFlags::SYNTHETIC) || is_synthetic(&self.attributes)
+    }
+
+    pub fn source_file(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::SourceFile(a) => Some(a.sourcefile.as_str()),
+                _ => None,
+            })
+    }
+
+    // The SourceDebugExtension as text, e.g. the SMAP that maps Kotlin inline functions or JSP
+    // pages back to their source lines.
+    pub fn source_debug_extension(&self) -> Option<String> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::SourceDebugExtension(a) => Some(a.text()),
+                _ => None,
+            })
+    }
+
+    pub fn bootstrap_methods(&self) -> &[BootstrapMethod] {
+        for attribute in &self.attributes {
+            if let Attribute::BootstrapMethods(a) = attribute {
+                return &a.bootstrap_methods;
             }
-            println!("########################################")
         }
+        &[]
+    }
+
+    // Resolves a CONSTANT_InvokeDynamic or CONSTANT_Dynamic to its bootstrap method, name,
+    // descriptor and static arguments.
+    pub fn dynamic_call_site(&self, index: CpIndex) -> Result<DynamicCallSite, ClassFormatError> {
+        let (kind, constant) = self.constant_pool.dynamic(index)?;
+        let bootstrap_methods = self.bootstrap_methods();
+        let bootstrap = bootstrap_methods
+            .get(constant.bootstrap_method_attr_index as usize)
+            .ok_or_else(|| {
+                ClassFormatError::new(format!(
+                    "constant {} refers to bootstrap method {} but the class has {}",
+                    index,
+                    constant.bootstrap_method_attr_index,
+                    bootstrap_methods.len()
+                ))
+            })?;
+        Ok(DynamicCallSite {
+            kind,
+            name: constant.name,
+            descriptor: constant.descriptor,
+            bootstrap_method: bootstrap.bootstrap_method.clone(),
+            bootstrap_arguments: bootstrap.bootstrap_arguments.clone(),
+        })
     }
 }
@@ -0,0 +1,78 @@
+use crate::jvm::class::{attribute::*, constant_pool::ClassRef, error::ClassFormatError, Class};
+
+// The module declared by a module-info.class, combining its Module, ModulePackages and
+// ModuleMainClass attributes (JVMS 4.7.25 - 4.7.27).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleDescriptor {
+    pub name: String,
+    pub flags: u16,
+    pub version: Option<String>,
+    pub requires: Vec<ModuleRequires>,
+    pub exports: Vec<ModuleExports>,
+    pub opens: Vec<ModuleExports>,
+    pub uses: Vec<ClassRef>,
+    pub provides: Vec<ModuleProvides>,
+    // Every package of the module, including the ones that are not exported or opened.
+    pub packages: Vec<String>,
+    pub main_class: Option<ClassRef>,
+}
+
+impl ModuleDescriptor {
+    pub fn new(class: &Class) -> Result<ModuleDescriptor, ClassFormatError> {
+        let mut module: Option<&AttributeModule> = None;
+        let mut packages: Vec<String> = Vec::new();
+        let mut main_class: Option<ClassRef> = None;
+        for attribute in &class.attributes {
+            match attribute {
+                Attribute::Module(a) => module = Some(a),
+                Attribute::ModulePackages(a) => {
+                    packages = a.packages.iter().map(|p| p.to_string()).collect()
+                }
+                Attribute::ModuleMainClass(a) => main_class = Some(a.main_class.clone()),
+                _ => {}
+            }
+        }
+        let module = match module {
+            Some(module) => module,
+            None => {
+                return Err(ClassFormatError::new("missing Module attribute")
+                    .within(format!("class {}", class.this_class)))
+            }
+        };
+        Ok(ModuleDescriptor {
+            name: module.module_name.to_string(),
+            flags: module.module_flags,
+            version: module.module_version.as_ref().map(|v| v.to_string()),
+            requires: module.requires.clone(),
+            exports: module.exports.clone(),
+            opens: module.opens.clone(),
+            uses: module.uses.clone(),
+            provides: module.provides.clone(),
+            packages,
+            main_class,
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.flags & ACC_OPEN != 0
+    }
+
+    pub fn requires(&self, module: &str) -> Option<&ModuleRequires> {
+        self.requires.iter().find(|r| r.module == module)
+    }
+
+    // Whether `package` is exported, unqualified or to `to_module`.
+    pub fn exports_to(&self, package: &str, to_module: &str) -> bool {
+        self.exports.iter().any(|e| {
+            e.package == package && (e.to.is_empty() || e.to.iter().any(|m| m == to_module))
+        })
+    }
+
+    // Whether `package` is open for deep reflection to `to_module`.
+    pub fn opens_to(&self, package: &str, to_module: &str) -> bool {
+        self.is_open()
+            || self.opens.iter().any(|o| {
+                o.package == package && (o.to.is_empty() || o.to.iter().any(|m| m == to_module))
+            })
+    }
+}
@@ -0,0 +1,640 @@
+use std::fmt;
+
+use crate::jvm::class::{descriptor::FieldType, error::ClassFormatError};
+
+// Generic signatures (JVMS 4.7.9.1) as found in the Signature attribute.
+
+// JavaTypeSignature / ReferenceTypeSignature
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeSignature {
+    Base(FieldType), // only the primitive FieldType variants
+    Class(ClassTypeSignature),
+    TypeVariable(String),
+    Array(Box<TypeSignature>),
+}
+
+// L [PackageSpecifier] SimpleClassTypeSignature {. SimpleClassTypeSignature} ;
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassTypeSignature {
+    pub package: String, // "java/util", empty for the unnamed package
+    // The top level class followed by one entry per inner class suffix.
+    pub classes: Vec<SimpleClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimpleClassTypeSignature {
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeArgument {
+    Any,                    // *
+    Exact(TypeSignature),   // T
+    Extends(TypeSignature), // + T
+    Super(TypeSignature),   // - T
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeParameter {
+    pub name: String,
+    pub class_bound: Option<TypeSignature>,
+    pub interface_bounds: Vec<TypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub superclass: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<TypeSignature>,
+    pub return_type: Option<TypeSignature>, // None is void
+    pub throws: Vec<TypeSignature>,
+}
+
+impl ClassSignature {
+    pub fn parse(signature: &str) -> Result<ClassSignature, ClassFormatError> {
+        let mut parser = SignatureParser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        let superclass = parser.class_type()?;
+        let mut interfaces: Vec<ClassTypeSignature> = Vec::new();
+        while parser.peek().is_some() {
+            interfaces.push(parser.class_type()?);
+        }
+        Ok(ClassSignature {
+            type_parameters,
+            superclass,
+            interfaces,
+        })
+    }
+}
+
+impl MethodSignature {
+    pub fn parse(signature: &str) -> Result<MethodSignature, ClassFormatError> {
+        let mut parser = SignatureParser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        parser.expect('(')?;
+        let mut parameters: Vec<TypeSignature> = Vec::new();
+        while parser.peek() != Some(')') {
+            parameters.push(parser.java_type()?);
+        }
+        parser.expect(')')?;
+        let return_type = if parser.peek() == Some('V') {
+            parser.pos += 1;
+            None
+        } else {
+            Some(parser.java_type()?)
+        };
+        let mut throws: Vec<TypeSignature> = Vec::new();
+        while parser.peek() == Some('^') {
+            parser.pos += 1;
+            let thrown = match parser.peek() {
+                Some('T') => parser.reference_type()?,
+                _ => TypeSignature::Class(parser.class_type()?),
+            };
+            throws.push(thrown);
+        }
+        parser.end()?;
+        Ok(MethodSignature {
+            type_parameters,
+            parameters,
+            return_type,
+            throws,
+        })
+    }
+}
+
+impl TypeSignature {
+    // FieldSignature: a ReferenceTypeSignature.
+    pub fn parse_field(signature: &str) -> Result<TypeSignature, ClassFormatError> {
+        let mut parser = SignatureParser::new(signature);
+        let field = parser.reference_type()?;
+        parser.end()?;
+        Ok(field)
+    }
+
+    // Java source syntax, e.g. "java.util.List<? extends T>".
+    pub fn java_name(&self) -> String {
+        match self {
+            TypeSignature::Base(base) => base.java_name(),
+            TypeSignature::Class(class) => class.java_name(),
+            TypeSignature::TypeVariable(name) => name.clone(),
+            TypeSignature::Array(component) => format!("{}[]", component.java_name()),
+        }
+    }
+}
+
+impl ClassTypeSignature {
+    // The erased class name in internal form, e.g. "java/util/Map$Entry".
+    pub fn binary_name(&self) -> String {
+        let names: Vec<&str> = self.classes.iter().map(|c| c.name.as_str()).collect();
+        if self.package.is_empty() {
+            names.join("$")
+        } else {
+            format!("{}/{}", self.package, names.join("$"))
+        }
+    }
+
+    pub fn java_name(&self) -> String {
+        let classes: Vec<String> = self.classes.iter().map(|c| c.java_name()).collect();
+        if self.package.is_empty() {
+            classes.join(".")
+        } else {
+            format!("{}.{}", self.package.replace('/', "."), classes.join("."))
+        }
+    }
+}
+
+impl SimpleClassTypeSignature {
+    fn java_name(&self) -> String {
+        if self.type_arguments.is_empty() {
+            return self.name.clone();
+        }
+        let arguments: Vec<String> = self.type_arguments.iter().map(|a| a.java_name()).collect();
+        format!("{}<{}>", self.name, arguments.join(", "))
+    }
+}
+
+impl TypeArgument {
+    pub fn java_name(&self) -> String {
+        match self {
+            TypeArgument::Any => String::from("?"),
+            TypeArgument::Exact(t) => t.java_name(),
+            TypeArgument::Extends(t) => format!("? extends {}", t.java_name()),
+            TypeArgument::Super(t) => format!("? super {}", t.java_name()),
+        }
+    }
+}
+
+impl TypeParameter {
+    pub fn java_name(&self) -> String {
+        let bounds: Vec<String> = self
+            .class_bound
+            .iter()
+            .chain(self.interface_bounds.iter())
+            .map(|b| b.java_name())
+            .filter(|b| b != "java.lang.Object")
+            .collect();
+        if bounds.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} extends {}", self.name, bounds.join(" & "))
+        }
+    }
+}
+
+pub fn type_parameters_java_name(type_parameters: &[TypeParameter]) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
+    }
+    let parameters: Vec<String> = type_parameters.iter().map(|p| p.java_name()).collect();
+    format!("<{}>", parameters.join(", "))
+}
+
+impl fmt::Display for TypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeSignature::Base(base) => write!(f, "{}", base),
+            TypeSignature::Class(class) => write!(f, "{}", class),
+            TypeSignature::TypeVariable(name) => write!(f, "T{};", name),
+            TypeSignature::Array(component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+impl fmt::Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "L")?;
+        if !self.package.is_empty() {
+            write!(f, "{}/", self.package)?;
+        }
+        for (i, class) in self.classes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", class.name)?;
+            if !class.type_arguments.is_empty() {
+                write!(f, "<")?;
+                for argument in &class.type_arguments {
+                    match argument {
+                        TypeArgument::Any => write!(f, "*")?,
+                        TypeArgument::Exact(t) => write!(f, "{}", t)?,
+                        TypeArgument::Extends(t) => write!(f, "+{}", t)?,
+                        TypeArgument::Super(t) => write!(f, "-{}", t)?,
+                    }
+                }
+                write!(f, ">")?;
+            }
+        }
+        write!(f, ";")
+    }
+}
+
+fn write_type_parameters(
+    f: &mut fmt::Formatter<'_>,
+    type_parameters: &[TypeParameter],
+) -> fmt::Result {
+    if type_parameters.is_empty() {
+        return Ok(());
+    }
+    write!(f, "<")?;
+    for p in type_parameters {
+        write!(f, "{}:", p.name)?;
+        if let Some(class_bound) = &p.class_bound {
+            write!(f, "{}", class_bound)?;
+        }
+        for interface_bound in &p.interface_bounds {
+            write!(f, ":{}", interface_bound)?;
+        }
+    }
+    write!(f, ">")
+}
+
+impl fmt::Display for ClassSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "{}", self.superclass)?;
+        for interface in &self.interfaces {
+            write!(f, "{}", interface)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MethodSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "(")?;
+        for p in &self.parameters {
+            write!(f, "{}", p)?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(r) => write!(f, "{}", r)?,
+            None => write!(f, "V")?,
+        }
+        for t in &self.throws {
+            write!(f, "^{}", t)?;
+        }
+        Ok(())
+    }
+}
+
+struct SignatureParser<'a> {
+    signature: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a> SignatureParser<'a> {
+    fn new(signature: &'a str) -> SignatureParser<'a> {
+        SignatureParser {
+            signature,
+            chars: signature.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, reason: &str) -> ClassFormatError {
+        ClassFormatError::new(format!(
+            "invalid signature \"{}\" at {}: {}",
+            self.signature, self.pos, reason
+        ))
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ClassFormatError> {
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("expected '{}'", c)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn end(&self) -> Result<(), ClassFormatError> {
+        if self.pos != self.chars.len() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> Result<String, ClassFormatError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if matches!(c, '.' | ';' | '[' | '/' | '<' | '>' | ':') {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("expected an identifier"));
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>, ClassFormatError> {
+        let mut type_parameters: Vec<TypeParameter> = Vec::new();
+        if self.peek() != Some('<') {
+            return Ok(type_parameters);
+        }
+        self.pos += 1;
+        loop {
+            let name = self.identifier()?;
+            self.expect(':')?;
+            let class_bound = match self.peek() {
+                Some('L') | Some('T') | Some('[') => Some(self.reference_type()?),
+                _ => None,
+            };
+            let mut interface_bounds: Vec<TypeSignature> = Vec::new();
+            while self.peek() == Some(':') {
+                self.pos += 1;
+                interface_bounds.push(self.reference_type()?);
+            }
+            type_parameters.push(TypeParameter {
+                name,
+                class_bound,
+                interface_bounds,
+            });
+            if self.peek() == Some('>') {
+                self.pos += 1;
+                return Ok(type_parameters);
+            }
+        }
+    }
+
+    fn java_type(&mut self) -> Result<TypeSignature, ClassFormatError> {
+        let base = match self.peek() {
+            Some('B') => FieldType::Byte,
+            Some('C') => FieldType::Char,
+            Some('D') => FieldType::Double,
+            Some('F') => FieldType::Float,
+            Some('I') => FieldType::Int,
+            Some('J') => FieldType::Long,
+            Some('S') => FieldType::Short,
+            Some('Z') => FieldType::Boolean,
+            _ => return self.reference_type(),
+        };
+        self.pos += 1;
+        Ok(TypeSignature::Base(base))
+    }
+
+    fn reference_type(&mut self) -> Result<TypeSignature, ClassFormatError> {
+        match self.peek() {
+            Some('L') => Ok(TypeSignature::Class(self.class_type()?)),
+            Some('T') => {
+                self.pos += 1;
+                let name = self.identifier()?;
+                self.expect(';')?;
+                Ok(TypeSignature::TypeVariable(name))
+            }
+            Some('[') => {
+                self.pos += 1;
+                Ok(TypeSignature::Array(Box::new(self.java_type()?)))
+            }
+            Some(_) => Err(self.error("expected a reference type")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature, ClassFormatError> {
+        self.expect('L')?;
+        let mut package_segments: Vec<String> = Vec::new();
+        let mut name = self.identifier()?;
+        while self.peek() == Some('/') {
+            self.pos += 1;
+            package_segments.push(name);
+            name = self.identifier()?;
+        }
+        let mut classes = vec![SimpleClassTypeSignature {
+            name,
+            type_arguments: self.type_arguments()?,
+        }];
+        while self.peek() == Some('.') {
+            self.pos += 1;
+            let name = self.identifier()?;
+            classes.push(SimpleClassTypeSignature {
+                name,
+                type_arguments: self.type_arguments()?,
+            });
+        }
+        self.expect(';')?;
+        Ok(ClassTypeSignature {
+            package: package_segments.join("/"),
+            classes,
+        })
+    }
+
+    fn type_arguments(&mut self) -> Result<Vec<TypeArgument>, ClassFormatError> {
+        let mut type_arguments: Vec<TypeArgument> = Vec::new();
+        if self.peek() != Some('<') {
+            return Ok(type_arguments);
+        }
+        self.pos += 1;
+        loop {
+            let argument = match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    TypeArgument::Any
+                }
+                Some('+') => {
+                    self.pos += 1;
+                    TypeArgument::Extends(self.reference_type()?)
+                }
+                Some('-') => {
+                    self.pos += 1;
+                    TypeArgument::Super(self.reference_type()?)
+                }
+                _ => TypeArgument::Exact(self.reference_type()?),
+            };
+            type_arguments.push(argument);
+            if self.peek() == Some('>') {
+                self.pos += 1;
+                return Ok(type_arguments);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(package: &str, classes: &[(&str, Vec<TypeArgument>)]) -> ClassTypeSignature {
+        ClassTypeSignature {
+            package: String::from(package),
+            classes: classes
+                .iter()
+                .map(|(name, type_arguments)| SimpleClassTypeSignature {
+                    name: String::from(*name),
+                    type_arguments: type_arguments.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    fn variable(name: &str) -> TypeSignature {
+        TypeSignature::TypeVariable(String::from(name))
+    }
+
+    #[test]
+    fn parses_wildcards() {
+        let signature = "Ljava/util/Map<*+Ljava/lang/Number;-TT;>;";
+        let field = TypeSignature::parse_field(signature).unwrap();
+        let number = TypeSignature::Class(class("java/lang", &[("Number", vec![])]));
+        assert_eq!(
+            field,
+            TypeSignature::Class(class(
+                "java/util",
+                &[(
+                    "Map",
+                    vec![
+                        TypeArgument::Any,
+                        TypeArgument::Extends(number),
+                        TypeArgument::Super(variable("T")),
+                    ]
+                )]
+            ))
+        );
+        assert_eq!(field.to_string(), signature);
+        assert_eq!(
+            field.java_name(),
+            "java.util.Map<?, ? extends java.lang.Number, ? super T>"
+        );
+    }
+
+    #[test]
+    fn parses_type_arguments_of_inner_classes() {
+        let signature = "Lp/Outer<TK;>.Inner<[TV;>.Leaf;";
+        let TypeSignature::Class(field) = TypeSignature::parse_field(signature).unwrap() else {
+            panic!("not a class type");
+        };
+        assert_eq!(
+            field,
+            class(
+                "p",
+                &[
+                    ("Outer", vec![TypeArgument::Exact(variable("K"))]),
+                    (
+                        "Inner",
+                        vec![TypeArgument::Exact(TypeSignature::Array(Box::new(
+                            variable("V")
+                        )))]
+                    ),
+                    ("Leaf", vec![]),
+                ]
+            )
+        );
+        assert_eq!(field.binary_name(), "p/Outer$Inner$Leaf");
+        assert_eq!(field.java_name(), "p.Outer<K>.Inner<V[]>.Leaf");
+        assert_eq!(field.to_string(), signature);
+    }
+
+    #[test]
+    fn parses_class_signatures() {
+        // class C<T extends Comparable<T>, U extends Number & Runnable> extends AbstractList<T>
+        // implements RandomAccess
+        let signature = "<T::Ljava/lang/Comparable<TT;>;U:Ljava/lang/Number;:Ljava/lang/Runnable;>\
+            Ljava/util/AbstractList<TT;>;Ljava/util/RandomAccess;";
+        let class_signature = ClassSignature::parse(signature).unwrap();
+        let [t, u] = &class_signature.type_parameters[..] else {
+            panic!("expected two type parameters");
+        };
+        assert_eq!(t.class_bound, None);
+        assert_eq!(t.interface_bounds.len(), 1);
+        assert_eq!(t.java_name(), "T extends java.lang.Comparable<T>");
+        assert_eq!(
+            u.java_name(),
+            "U extends java.lang.Number & java.lang.Runnable"
+        );
+        assert_eq!(
+            class_signature.superclass.binary_name(),
+            "java/util/AbstractList"
+        );
+        assert_eq!(class_signature.interfaces.len(), 1);
+        assert_eq!(class_signature.to_string(), signature);
+        assert_eq!(
+            type_parameters_java_name(&class_signature.type_parameters),
+            "<T extends java.lang.Comparable<T>, U extends java.lang.Number & java.lang.Runnable>"
+        );
+    }
+
+    #[test]
+    fn parses_method_signatures_with_thrown_type_variables() {
+        // <X extends Exception> T[] f(int, List<T>[]) throws X, IOException
+        let signature =
+            "<X:Ljava/lang/Exception;>(I[Ljava/util/List<TT;>;)[TT;^TX;^Ljava/io/IOException;";
+        let method = MethodSignature::parse(signature).unwrap();
+        assert_eq!(
+            method.type_parameters[0].java_name(),
+            "X extends java.lang.Exception"
+        );
+        assert_eq!(method.parameters[0], TypeSignature::Base(FieldType::Int));
+        assert_eq!(method.parameters[1].java_name(), "java.util.List<T>[]");
+        assert_eq!(
+            method.return_type,
+            Some(TypeSignature::Array(Box::new(variable("T"))))
+        );
+        assert_eq!(
+            method.throws,
+            vec![
+                variable("X"),
+                TypeSignature::Class(class("java/io", &[("IOException", vec![])])),
+            ]
+        );
+        assert_eq!(method.to_string(), signature);
+
+        let void = MethodSignature::parse("()V").unwrap();
+        assert_eq!(void.return_type, None);
+        assert_eq!(void.to_string(), "()V");
+    }
+
+    #[test]
+    fn parses_arrays_of_type_variables() {
+        let field = TypeSignature::parse_field("[[TE;").unwrap();
+        assert_eq!(
+            field,
+            TypeSignature::Array(Box::new(TypeSignature::Array(Box::new(variable("E")))))
+        );
+        assert_eq!(field.java_name(), "E[][]");
+        assert_eq!(field.to_string(), "[[TE;");
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        for (signature, reason) in [
+            ("I", "expected a reference type"),
+            ("", "unexpected end"),
+            ("TT", "expected ';'"),
+            ("T;", "expected an identifier"),
+            ("Ljava/util/List<>;", "expected a reference type"),
+            ("Ljava/util/List<TT;", "unexpected end"),
+            ("Ljava//List;", "expected an identifier"),
+            ("Ljava/lang/String;X", "trailing characters"),
+        ] {
+            let error = TypeSignature::parse_field(signature).unwrap_err();
+            assert!(error.reason.contains(reason), "{}: {}", signature, error);
+        }
+        for (signature, reason) in [
+            ("I)V", "expected '('"),
+            ("(I", "unexpected end"),
+            ("()V^I", "expected 'L'"),
+            ("<T>()V", "expected ':'"),
+            ("()VI", "trailing characters"),
+        ] {
+            let error = MethodSignature::parse(signature).unwrap_err();
+            assert!(error.reason.contains(reason), "{}: {}", signature, error);
+        }
+        let error = ClassSignature::parse("<:Ljava/lang/Object;>Ljava/lang/Object;").unwrap_err();
+        assert!(error.reason.contains("expected an identifier"), "{}", error);
+        let error = ClassSignature::parse("Ljava/lang/Object;I").unwrap_err();
+        assert!(error.reason.contains("expected 'L'"), "{}", error);
+    }
+}
@@ -0,0 +1,1120 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::jvm::class::{
+    attribute::{
+        initial_locals, Attribute, AttributeCode, AttributeStackMapTable, ExceptionInfo,
+        StackMapFrame, VerificationTypeInfo as Type,
+    },
+    constant::Constant,
+    constant_pool::{ClassRef, ConstantPool, CpIndex},
+    descriptor::{FieldType, MethodDescriptor},
+    error::ClassFormatError,
+    instruction::{ArrayType, Instruction},
+    method::Method,
+    Class,
+};
+use crate::jvm::class_loader::ClassLoader;
+
+// Answers the closest common superclass of two different classes, which is what the type of a
+// local or stack slot becomes where paths with different class types join. Both names are
+// internal class names, never arrays nor java/lang/Object. A class the resolver does not know
+// is an error: guessing java/lang/Object gives frames the verifier rejects as soon as the
+// joined value is used as either class.
+pub trait SuperclassResolver {
+    fn common_super_class(&self, a: &str, b: &str) -> Result<String, ClassFormatError>;
+}
+
+// Knows no classes, so any join of two different classes fails. Enough for code that never
+// has paths with different class types meet.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoClasses;
+
+impl SuperclassResolver for NoClasses {
+    fn common_super_class(&self, a: &str, b: &str) -> Result<String, ClassFormatError> {
+        Err(unknown_common_super_class(a, b, a))
+    }
+}
+
+// Walks the superclass chains of the loaded classes. Interfaces join to java/lang/Object, as
+// the verifier treats interface types as Object.
+impl SuperclassResolver for ClassLoader {
+    fn common_super_class(&self, a: &str, b: &str) -> Result<String, ClassFormatError> {
+        // None for an interface.
+        let chain = |name: &str| -> Result<Option<Vec<String>>, ClassFormatError> {
+            let mut chain = vec![String::from(name)];
+            let mut class = match self.class_map.get(name) {
+                Some(class) => class,
+                None => return Err(unknown_common_super_class(a, b, name)),
+            };
+            if class.access_flags.is_interface() {
+                return Ok(None);
+            }
+            while let Some(super_class) = &class.super_class {
+                chain.push(super_class.name.clone());
+                class = match self.class_map.get(super_class.name.as_str()) {
+                    Some(class) => class,
+                    None if super_class == OBJECT => break,
+                    None => return Err(unknown_common_super_class(a, b, super_class)),
+                };
+            }
+            Ok(Some(chain))
+        };
+        Ok(match (chain(a)?, chain(b)?) {
+            (Some(a), Some(b)) => a
+                .into_iter()
+                .find(|name| b.contains(name))
+                .unwrap_or_else(|| String::from(OBJECT)),
+            _ => String::from(OBJECT),
+        })
+    }
+}
+
+fn unknown_common_super_class(a: &str, b: &str, unknown: &str) -> ClassFormatError {
+    ClassFormatError::new(format!(
+        "cannot join {} and {} for a stack map frame, class {} is unknown to the resolver",
+        a, b, unknown
+    ))
+}
+
+// The class being analyzed may not be known to the resolver. When the resolver fails on a
+// join with it, the join is retried through its superclass; were it known, the superclass
+// would give the same answer unless the other class extends it, and then the retry fails too.
+struct WithThisClass<'a> {
+    this_class: &'a str,
+    super_class: Option<&'a str>,
+    resolver: &'a dyn SuperclassResolver,
+}
+
+impl SuperclassResolver for WithThisClass<'_> {
+    fn common_super_class(&self, a: &str, b: &str) -> Result<String, ClassFormatError> {
+        let common = self.resolver.common_super_class(a, b);
+        let retry = match self.super_class {
+            _ if common.is_ok() => return common,
+            Some(super_class) if a == self.this_class => (super_class, b),
+            Some(super_class) if b == self.this_class => (a, super_class),
+            _ => return common,
+        };
+        match retry {
+            (OBJECT, _) | (_, OBJECT) => Ok(String::from(OBJECT)),
+            (x, y) if x == y => Ok(String::from(x)),
+            (x, y) => self.resolver.common_super_class(x, y).or(common),
+        }
+    }
+}
+
+const OBJECT: &str = "java/lang/Object";
+
+impl Class {
+    // Replaces the StackMapTable of every method with frames computed from its code, as
+    // needed after generating or rewriting code of a version 50 or later class.
+    pub fn compute_stack_maps(
+        &mut self,
+        resolver: &dyn SuperclassResolver,
+    ) -> Result<(), ClassFormatError> {
+        let resolver = WithThisClass {
+            this_class: &self.this_class,
+            super_class: self.super_class.as_deref(),
+            resolver,
+        };
+        let location = format!("class {}", self.this_class);
+        for method in &mut self.methods {
+            compute_stack_map_table(&self.this_class, method, &self.constant_pool, &resolver)
+                .map_err(|e| {
+                    e.within(format!("method {}{}", method.name, method.descriptor))
+                        .within(location.clone())
+                })?;
+        }
+        Ok(())
+    }
+}
+
+// Replaces the StackMapTable in the code of a method (JVMS 4.7.4) with one computed by a
+// data-flow analysis over the decoded instructions: a frame at every branch target,
+// exception handler and instruction after an unconditional jump. Unreachable code is
+// overwritten, since the verifier checks it too but nothing gives it types.
+pub fn compute_stack_map_table(
+    this_class: &str,
+    method: &mut Method,
+    constant_pool: &ConstantPool,
+    resolver: &dyn SuperclassResolver,
+) -> Result<(), ClassFormatError> {
+    let code = match method.code() {
+        Some(code) => code,
+        None => return Ok(()),
+    };
+    let analyzer = Analyzer {
+        this_class,
+        constant_pool,
+        resolver,
+        code,
+    };
+    let initial = analyzer.initial_frame(method)?;
+    let analysis = analyzer
+        .analyze(initial.clone())
+        .map_err(|e| e.within("attribute Code"))?;
+    let table = if analysis.frames.is_empty() {
+        None
+    } else {
+        Some(encode(&initial, &analysis.frames)?)
+    };
+
+    for attribute in &mut method.attributes {
+        if let Attribute::Code(code) = attribute {
+            remove_dead_code(code, &analysis.dead);
+            let position = code
+                .attributes
+                .iter()
+                .position(|a| matches!(a, Attribute::StackMapTable(_)));
+            match (position, table) {
+                (Some(i), Some(table)) => code.attributes[i] = Attribute::StackMapTable(table),
+                (Some(i), None) => {
+                    code.attributes.remove(i);
+                }
+                (None, Some(table)) => code.attributes.push(Attribute::StackMapTable(table)),
+                (None, None) => {}
+            }
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Turns each run of unreachable code into nops ending in athrow and takes it out of the
+// exception handler ranges, so that only its frame matters to the verifier.
+fn remove_dead_code(code: &mut AttributeCode, dead: &[(usize, usize)]) {
+    if dead.is_empty() {
+        return;
+    }
+    for (start, end) in dead {
+        code.code[*start..*end - 1].fill(0x00); // nop
+        code.code[*end - 1] = 0xbf; // athrow
+    }
+    code.max_stack = code.max_stack.max(1);
+
+    let mut exception_table: Vec<ExceptionInfo> = Vec::new();
+    for exception in &code.exception_table {
+        let mut start = exception.start_pc as usize;
+        let end = exception.end_pc as usize;
+        let mut range = |start: usize, end: usize| {
+            exception_table.push(ExceptionInfo {
+                start_pc: start as u16,
+                end_pc: end as u16,
+                handler_pc: exception.handler_pc,
+                catch_type: exception.catch_type,
+            })
+        };
+        for (dead_start, dead_end) in dead {
+            if *dead_end <= start || *dead_start >= end {
+                continue;
+            }
+            if *dead_start > start {
+                range(start, *dead_start);
+            }
+            start = *dead_end;
+        }
+        if start < end {
+            range(start, end);
+        }
+    }
+    code.exception_table = exception_table;
+}
+
+// Types per local variable and operand stack slot; a long or double is followed by a Top
+// for its second slot.
+#[derive(Debug, Clone, PartialEq)]
+struct Frame {
+    locals: Vec<Type>,
+    stack: Vec<Type>,
+}
+
+impl Frame {
+    fn pop(&mut self, pc: usize) -> Result<Type, ClassFormatError> {
+        self.stack
+            .pop()
+            .ok_or_else(|| ClassFormatError::new(format!("stack underflow at pc {}", pc)))
+    }
+
+    fn pop_n(&mut self, slots: u16, pc: usize) -> Result<(), ClassFormatError> {
+        for _ in 0..slots {
+            self.pop(pc)?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, t: Type) {
+        let category2 = t.is_category2();
+        self.stack.push(t);
+        if category2 {
+            self.stack.push(Type::Top);
+        }
+    }
+
+    fn load(&mut self, index: u16, pc: usize) -> Result<(), ClassFormatError> {
+        let t = self.locals.get(index as usize).cloned().ok_or_else(|| {
+            ClassFormatError::new(format!("local {} out of range at pc {}", index, pc))
+        })?;
+        self.push(t);
+        Ok(())
+    }
+
+    // Stores the value on top of the stack, which also invalidates a long or double whose
+    // second slot it overwrites.
+    fn store(&mut self, index: u16, category2: bool, pc: usize) -> Result<(), ClassFormatError> {
+        let t = if category2 {
+            self.pop(pc)?;
+            self.pop(pc)?
+        } else {
+            self.pop(pc)?
+        };
+        let index = index as usize;
+        let end = index + if category2 { 2 } else { 1 };
+        if end > self.locals.len() {
+            return Err(ClassFormatError::new(format!(
+                "local {} out of range at pc {}",
+                index, pc
+            )));
+        }
+        if index > 0 && self.locals[index - 1].is_category2() {
+            self.locals[index - 1] = Type::Top;
+        }
+        self.locals[index] = t;
+        if category2 {
+            self.locals[index + 1] = Type::Top;
+        }
+        Ok(())
+    }
+}
+
+struct Analysis {
+    frames: Vec<(usize, Frame)>, // at every offset that needs one, in offset order
+    dead: Vec<(usize, usize)>,   // [start, end) of each run of unreachable code
+}
+
+struct Analyzer<'a> {
+    this_class: &'a str,
+    constant_pool: &'a ConstantPool,
+    resolver: &'a dyn SuperclassResolver,
+    code: &'a AttributeCode,
+}
+
+impl Analyzer<'_> {
+    // `this` (uninitialized in a constructor) and the parameters, the remaining locals Top.
+    fn initial_frame(&self, method: &Method) -> Result<Frame, ClassFormatError> {
+        let mut frame = Frame {
+            locals: Vec::new(),
+            stack: Vec::new(),
+        };
+        for t in initial_locals(self.this_class, method)? {
+            let category2 = t.is_category2();
+            frame.locals.push(t);
+            if category2 {
+                frame.locals.push(Type::Top);
+            }
+        }
+        let max_locals = self.code.max_locals as usize;
+        if frame.locals.len() > max_locals {
+            return Err(ClassFormatError::new(format!(
+                "parameters take {} slots but max_locals is {}",
+                frame.locals.len(),
+                max_locals
+            )));
+        }
+        frame.locals.resize(max_locals, Type::Top);
+        Ok(frame)
+    }
+
+    fn analyze(&self, initial: Frame) -> Result<Analysis, ClassFormatError> {
+        let code = &self.code.code;
+        let instructions = Instruction::decode_all(code)?;
+        if instructions.is_empty() {
+            return Err(ClassFormatError::new("code_length 0 is not in 1..=65535"));
+        }
+        let index_of: HashMap<usize, usize> = instructions
+            .iter()
+            .enumerate()
+            .map(|(i, (pc, _))| (*pc, i))
+            .collect();
+        let index = |pc: usize| -> Result<usize, ClassFormatError> {
+            match index_of.get(&pc) {
+                Some(i) => Ok(*i),
+                None if pc == code.len() => Err(ClassFormatError::new(
+                    "execution can fall off the end of the code",
+                )),
+                None => Err(ClassFormatError::new(format!(
+                    "pc {} is not the start of an instruction",
+                    pc
+                ))),
+            }
+        };
+
+        let mut handlers: Vec<(usize, usize, usize, Type)> = Vec::new();
+        for exception in &self.code.exception_table {
+            let catch_type = if exception.catch_type.is_none() {
+                ClassRef::new("java/lang/Throwable")
+            } else {
+                self.constant_pool.class_ref(exception.catch_type)?
+            };
+            handlers.push((
+                exception.start_pc as usize,
+                exception.end_pc as usize,
+                index(exception.handler_pc as usize)?,
+                Type::Object(catch_type),
+            ));
+        }
+
+        // Input frame of each instruction, merged over every path that reaches it.
+        let mut frames: Vec<Option<Frame>> = vec![None; instructions.len()];
+        frames[0] = Some(initial);
+        let mut pending: Vec<usize> = vec![0];
+        while let Some(i) = pending.pop() {
+            let (pc, instruction) = (instructions[i].0, &instructions[i].1);
+            let before = frames[i].clone().unwrap();
+            let mut after = before.clone();
+            self.execute(&mut after, pc, instruction)
+                .map_err(|e| e.within(format!("instruction at pc {}", pc)))?;
+
+            let mut successors: Vec<(usize, Frame)> = Vec::new();
+            for target in instruction.branch_targets(pc) {
+                successors.push((index(target)?, after.clone()));
+            }
+            if instruction.falls_through() {
+                let next = instructions.get(i + 1).map_or(code.len(), |(pc, _)| *pc);
+                successors.push((index(next)?, after.clone()));
+            }
+            // A handler can be entered before or after the instruction changes the locals.
+            for (start, end, handler, catch_type) in &handlers {
+                if (*start..*end).contains(&pc) {
+                    for locals in [&before.locals, &after.locals] {
+                        let frame = Frame {
+                            locals: locals.clone(),
+                            stack: vec![catch_type.clone()],
+                        };
+                        successors.push((*handler, frame));
+                    }
+                }
+            }
+
+            for (successor, frame) in successors {
+                let merged = match &frames[successor] {
+                    None => frame,
+                    Some(old) => self
+                        .merge(old, &frame)
+                        .map_err(|e| e.within(format!("pc {}", instructions[successor].0)))?,
+                };
+                if frames[successor].as_ref() != Some(&merged) {
+                    frames[successor] = Some(merged);
+                    pending.push(successor);
+                }
+            }
+        }
+
+        // Frames go where control does not just fall through. Code that nothing reaches has
+        // no types; each run of it gets a frame with no locals and a Throwable on the stack,
+        // which holds once the run is turned into nop ... athrow.
+        let mut needs_frame: BTreeSet<usize> = BTreeSet::new();
+        let mut dead: Vec<(usize, usize)> = Vec::new();
+        for (i, (pc, instruction)) in instructions.iter().enumerate() {
+            let next = instructions.get(i + 1).map_or(code.len(), |(pc, _)| *pc);
+            if frames[i].is_none() {
+                match dead.last_mut() {
+                    Some((_, end)) if *end == *pc => *end = next,
+                    _ => dead.push((*pc, next)),
+                }
+                continue;
+            }
+            needs_frame.extend(instruction.branch_targets(*pc));
+            if !instruction.falls_through() && next < code.len() {
+                needs_frame.insert(next);
+            }
+        }
+        for (_, _, handler, _) in &handlers {
+            needs_frame.insert(instructions[*handler].0);
+        }
+
+        let mut result: Vec<(usize, Frame)> = Vec::new();
+        for pc in needs_frame {
+            if let Some(frame) = frames[index(pc)?].take() {
+                result.push((pc, frame));
+            }
+        }
+        for (start, _) in &dead {
+            let frame = Frame {
+                locals: vec![Type::Top; self.code.max_locals as usize],
+                stack: vec![Type::Object(ClassRef::new("java/lang/Throwable"))],
+            };
+            result.push((*start, frame));
+        }
+        result.sort_by_key(|(pc, _)| *pc);
+        Ok(Analysis {
+            frames: result,
+            dead,
+        })
+    }
+
+    fn merge(&self, a: &Frame, b: &Frame) -> Result<Frame, ClassFormatError> {
+        if a.stack.len() != b.stack.len() {
+            return Err(ClassFormatError::new(format!(
+                "reached with stack depths {} and {}",
+                a.stack.len(),
+                b.stack.len()
+            )));
+        }
+        let mut locals = Vec::new();
+        for (x, y) in a.locals.iter().zip(&b.locals) {
+            locals.push(self.merge_types(x, y)?.unwrap_or(Type::Top));
+        }
+        let mut stack = Vec::new();
+        for (x, y) in a.stack.iter().zip(&b.stack) {
+            stack.push(self.merge_types(x, y)?.ok_or_else(|| {
+                ClassFormatError::new(format!(
+                    "reached with incompatible stack types {:?} and {:?}",
+                    x, y
+                ))
+            })?);
+        }
+        Ok(Frame { locals, stack })
+    }
+
+    // The join of two types, None if they have none but Top.
+    fn merge_types(&self, a: &Type, b: &Type) -> Result<Option<Type>, ClassFormatError> {
+        Ok(match (a, b) {
+            _ if a == b => Some(a.clone()),
+            (Type::Null, Type::Object(_)) => Some(b.clone()),
+            (Type::Object(_), Type::Null) => Some(a.clone()),
+            (Type::Object(x), Type::Object(y)) => {
+                Some(Type::Object(ClassRef::new(self.merge_classes(x, y)?)))
+            }
+            _ => None,
+        })
+    }
+
+    // Arrays of references join to an array of the join of their components; any other mix
+    // with an array is just an Object.
+    fn merge_classes(&self, a: &str, b: &str) -> Result<String, ClassFormatError> {
+        Ok(match (a.strip_prefix('['), b.strip_prefix('[')) {
+            _ if a == b => String::from(a),
+            (Some(x), Some(y)) => match (component_class(x), component_class(y)) {
+                (Some(x), Some(y)) => {
+                    let merged = self.merge_classes(x, y)?;
+                    if merged.starts_with('[') {
+                        format!("[{}", merged)
+                    } else {
+                        format!("[L{};", merged)
+                    }
+                }
+                _ => String::from(OBJECT),
+            },
+            (None, None) if a == OBJECT || b == OBJECT => String::from(OBJECT),
+            (None, None) => self.resolver.common_super_class(a, b)?,
+            _ => String::from(OBJECT),
+        })
+    }
+
+    fn class_type(&self, index: &CpIndex) -> Result<Type, ClassFormatError> {
+        Ok(Type::Object(self.constant_pool.class_ref(*index)?))
+    }
+
+    // Applies one instruction to the frame (JVMS 6.5, at the level of verification types).
+    fn execute(
+        &self,
+        frame: &mut Frame,
+        pc: usize,
+        instruction: &Instruction,
+    ) -> Result<(), ClassFormatError> {
+        match instruction {
+            Instruction::AconstNull => frame.push(Type::Null),
+            Instruction::Ldc(index) | Instruction::LdcW(index) | Instruction::Ldc2W(index) => {
+                let t = match self.constant_pool.get(*index)? {
+                    Constant::Integer(_) => Type::Integer,
+                    Constant::Float(_) => Type::Float,
+                    Constant::Long(_) => Type::Long,
+                    Constant::Double(_) => Type::Double,
+                    Constant::Class(_) => Type::Object(ClassRef::new("java/lang/Class")),
+                    Constant::String(_) => Type::Object(ClassRef::new("java/lang/String")),
+                    Constant::MethodHandle(_) => {
+                        Type::Object(ClassRef::new("java/lang/invoke/MethodHandle"))
+                    }
+                    Constant::MethodType(_) => {
+                        Type::Object(ClassRef::new("java/lang/invoke/MethodType"))
+                    }
+                    Constant::Dynamic(_) => {
+                        let (_, dynamic) = self.constant_pool.dynamic(*index)?;
+                        Type::of(&FieldType::parse(&dynamic.descriptor)?)
+                    }
+                    c => {
+                        return Err(ClassFormatError::new(format!(
+                            "ldc of a non-loadable constant {:?}",
+                            c
+                        )))
+                    }
+                };
+                frame.push(t);
+            }
+            Instruction::Iload(n)
+            | Instruction::Lload(n)
+            | Instruction::Fload(n)
+            | Instruction::Dload(n)
+            | Instruction::Aload(n) => frame.load(*n, pc)?,
+            Instruction::Iload0
+            | Instruction::Lload0
+            | Instruction::Fload0
+            | Instruction::Dload0
+            | Instruction::Aload0 => frame.load(0, pc)?,
+            Instruction::Iload1
+            | Instruction::Lload1
+            | Instruction::Fload1
+            | Instruction::Dload1
+            | Instruction::Aload1 => frame.load(1, pc)?,
+            Instruction::Iload2
+            | Instruction::Lload2
+            | Instruction::Fload2
+            | Instruction::Dload2
+            | Instruction::Aload2 => frame.load(2, pc)?,
+            Instruction::Iload3
+            | Instruction::Lload3
+            | Instruction::Fload3
+            | Instruction::Dload3
+            | Instruction::Aload3 => frame.load(3, pc)?,
+            Instruction::Istore(n) | Instruction::Fstore(n) | Instruction::Astore(n) => {
+                frame.store(*n, false, pc)?
+            }
+            Instruction::Lstore(n) | Instruction::Dstore(n) => frame.store(*n, true, pc)?,
+            Instruction::Istore0 | Instruction::Fstore0 | Instruction::Astore0 => {
+                frame.store(0, false, pc)?
+            }
+            Instruction::Istore1 | Instruction::Fstore1 | Instruction::Astore1 => {
+                frame.store(1, false, pc)?
+            }
+            Instruction::Istore2 | Instruction::Fstore2 | Instruction::Astore2 => {
+                frame.store(2, false, pc)?
+            }
+            Instruction::Istore3 | Instruction::Fstore3 | Instruction::Astore3 => {
+                frame.store(3, false, pc)?
+            }
+            Instruction::Lstore0 | Instruction::Dstore0 => frame.store(0, true, pc)?,
+            Instruction::Lstore1 | Instruction::Dstore1 => frame.store(1, true, pc)?,
+            Instruction::Lstore2 | Instruction::Dstore2 => frame.store(2, true, pc)?,
+            Instruction::Lstore3 | Instruction::Dstore3 => frame.store(3, true, pc)?,
+            Instruction::Iinc { index, .. } => {
+                frame.push(Type::Integer);
+                frame.store(*index, false, pc)?;
+            }
+            Instruction::Aaload => {
+                frame.pop(pc)?;
+                let t = match frame.pop(pc)? {
+                    Type::Null => Type::Null,
+                    Type::Object(array) if array.starts_with('[') => {
+                        Type::of(&FieldType::parse(&array[1..])?)
+                    }
+                    t => {
+                        return Err(ClassFormatError::new(format!(
+                            "aaload from {:?}, which is not an array of references",
+                            t
+                        )))
+                    }
+                };
+                frame.push(t);
+            }
+            Instruction::Pop => {
+                frame.pop(pc)?;
+            }
+            Instruction::Pop2 => frame.pop_n(2, pc)?,
+            Instruction::Dup => {
+                let v1 = frame.pop(pc)?;
+                frame.stack.extend([v1.clone(), v1]);
+            }
+            Instruction::DupX1 => {
+                let (v1, v2) = (frame.pop(pc)?, frame.pop(pc)?);
+                frame.stack.extend([v1.clone(), v2, v1]);
+            }
+            Instruction::DupX2 => {
+                let (v1, v2, v3) = (frame.pop(pc)?, frame.pop(pc)?, frame.pop(pc)?);
+                frame.stack.extend([v1.clone(), v3, v2, v1]);
+            }
+            Instruction::Dup2 => {
+                let (v1, v2) = (frame.pop(pc)?, frame.pop(pc)?);
+                frame.stack.extend([v2.clone(), v1.clone(), v2, v1]);
+            }
+            Instruction::Dup2X1 => {
+                let (v1, v2, v3) = (frame.pop(pc)?, frame.pop(pc)?, frame.pop(pc)?);
+                frame.stack.extend([v2.clone(), v1.clone(), v3, v2, v1]);
+            }
+            Instruction::Dup2X2 => {
+                let (v1, v2) = (frame.pop(pc)?, frame.pop(pc)?);
+                let (v3, v4) = (frame.pop(pc)?, frame.pop(pc)?);
+                frame.stack.extend([v2.clone(), v1.clone(), v4, v3, v2, v1]);
+            }
+            Instruction::Swap => {
+                let (v1, v2) = (frame.pop(pc)?, frame.pop(pc)?);
+                frame.stack.extend([v1, v2]);
+            }
+            Instruction::Getstatic(index) | Instruction::Getfield(index) => {
+                if matches!(instruction, Instruction::Getfield(_)) {
+                    frame.pop(pc)?;
+                }
+                let member = self.constant_pool.member_ref(*index)?;
+                frame.push(Type::of(&FieldType::parse(&member.descriptor)?));
+            }
+            Instruction::Invokevirtual(index)
+            | Instruction::Invokespecial(index)
+            | Instruction::Invokestatic(index)
+            | Instruction::Invokeinterface { index, .. } => {
+                let member = self.constant_pool.member_ref(*index)?;
+                let descriptor = MethodDescriptor::parse(&member.descriptor)?;
+                frame.pop_n(descriptor.parameter_slots(), pc)?;
+                if !matches!(instruction, Instruction::Invokestatic(_)) {
+                    let receiver = frame.pop(pc)?;
+                    if member.name == "<init>" {
+                        self.initialize(frame, &receiver)?;
+                    }
+                }
+                if let Some(return_type) = &descriptor.return_type {
+                    frame.push(Type::of(return_type));
+                }
+            }
+            Instruction::Invokedynamic(index) => {
+                let (_, dynamic) = self.constant_pool.dynamic(*index)?;
+                let descriptor = MethodDescriptor::parse(&dynamic.descriptor)?;
+                frame.pop_n(descriptor.parameter_slots(), pc)?;
+                if let Some(return_type) = &descriptor.return_type {
+                    frame.push(Type::of(return_type));
+                }
+            }
+            Instruction::New(_) => frame.push(Type::Uninitialized(pc as u16)),
+            Instruction::Newarray(atype) => {
+                frame.pop(pc)?;
+                frame.push(Type::Object(ClassRef::new(format!(
+                    "[{}",
+                    array_type_descriptor(*atype)
+                ))));
+            }
+            Instruction::Anewarray(index) => {
+                frame.pop(pc)?;
+                let component = self.constant_pool.class_name(*index)?;
+                frame.push(Type::Object(ClassRef::new(array_of(&component))));
+            }
+            Instruction::Checkcast(index) => {
+                frame.pop(pc)?;
+                frame.push(self.class_type(index)?);
+            }
+            Instruction::Multianewarray { index, dimensions } => {
+                frame.pop_n(*dimensions as u16, pc)?;
+                frame.push(self.class_type(index)?);
+            }
+            Instruction::Jsr(_) | Instruction::JsrW(_) | Instruction::Ret(_) => {
+                return Err(ClassFormatError::new(format!(
+                    "{} has no stack map frames",
+                    instruction.name()
+                )))
+            }
+            _ => {
+                // Everything else pushes at most one value of a type fixed by the opcode.
+                let (pops, pushes) = instruction.stack_effect(self.constant_pool)?;
+                frame.pop_n(pops, pc)?;
+                if pushes > 0 {
+                    frame.push(result_type(instruction).ok_or_else(|| {
+                        ClassFormatError::new(format!("no result type for {}", instruction.name()))
+                    })?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // A constructor call initializes every copy of the object it is called on.
+    fn initialize(&self, frame: &mut Frame, receiver: &Type) -> Result<(), ClassFormatError> {
+        let initialized = match receiver {
+            Type::UninitializedThis => Type::Object(ClassRef::new(self.this_class)),
+            Type::Uninitialized(new_pc) => {
+                match Instruction::decode(&self.code.code, *new_pc as usize) {
+                    Ok((Instruction::New(index), _)) => self.class_type(&index)?,
+                    _ => {
+                        return Err(ClassFormatError::new(format!(
+                            "uninitialized object from pc {}, which is not a new",
+                            new_pc
+                        )))
+                    }
+                }
+            }
+            _ => return Ok(()),
+        };
+        for t in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+            if t == receiver {
+                *t = initialized.clone();
+            }
+        }
+        Ok(())
+    }
+}
+
+// Result of the instructions whose type does not depend on their operands.
+fn result_type(instruction: &Instruction) -> Option<Type> {
+    Some(match instruction {
+        Instruction::IconstM1
+        | Instruction::Iconst0
+        | Instruction::Iconst1
+        | Instruction::Iconst2
+        | Instruction::Iconst3
+        | Instruction::Iconst4
+        | Instruction::Iconst5
+        | Instruction::Bipush(_)
+        | Instruction::Sipush(_)
+        | Instruction::Iaload
+        | Instruction::Baload
+        | Instruction::Caload
+        | Instruction::Saload
+        | Instruction::Iadd
+        | Instruction::Isub
+        | Instruction::Imul
+        | Instruction::Idiv
+        | Instruction::Irem
+        | Instruction::Ineg
+        | Instruction::Ishl
+        | Instruction::Ishr
+        | Instruction::Iushr
+        | Instruction::Iand
+        | Instruction::Ior
+        | Instruction::Ixor
+        | Instruction::L2i
+        | Instruction::F2i
+        | Instruction::D2i
+        | Instruction::I2b
+        | Instruction::I2c
+        | Instruction::I2s
+        | Instruction::Lcmp
+        | Instruction::Fcmpl
+        | Instruction::Fcmpg
+        | Instruction::Dcmpl
+        | Instruction::Dcmpg
+        | Instruction::Arraylength
+        | Instruction::Instanceof(_) => Type::Integer,
+        Instruction::Lconst0
+        | Instruction::Lconst1
+        | Instruction::Laload
+        | Instruction::Ladd
+        | Instruction::Lsub
+        | Instruction::Lmul
+        | Instruction::Ldiv
+        | Instruction::Lrem
+        | Instruction::Lneg
+        | Instruction::Lshl
+        | Instruction::Lshr
+        | Instruction::Lushr
+        | Instruction::Land
+        | Instruction::Lor
+        | Instruction::Lxor
+        | Instruction::I2l
+        | Instruction::F2l
+        | Instruction::D2l => Type::Long,
+        Instruction::Fconst0
+        | Instruction::Fconst1
+        | Instruction::Fconst2
+        | Instruction::Faload
+        | Instruction::Fadd
+        | Instruction::Fsub
+        | Instruction::Fmul
+        | Instruction::Fdiv
+        | Instruction::Frem
+        | Instruction::Fneg
+        | Instruction::I2f
+        | Instruction::L2f
+        | Instruction::D2f => Type::Float,
+        Instruction::Dconst0
+        | Instruction::Dconst1
+        | Instruction::Daload
+        | Instruction::Dadd
+        | Instruction::Dsub
+        | Instruction::Dmul
+        | Instruction::Ddiv
+        | Instruction::Drem
+        | Instruction::Dneg
+        | Instruction::I2d
+        | Instruction::L2d
+        | Instruction::F2d => Type::Double,
+        _ => return None,
+    })
+}
+
+// The class name of an array type from the class name of its component; arrays are named
+// by their descriptor.
+fn array_of(component: &str) -> String {
+    if component.starts_with('[') {
+        format!("[{}", component)
+    } else {
+        format!("[L{};", component)
+    }
+}
+
+// The class name of a reference component descriptor, None for primitives.
+fn component_class(descriptor: &str) -> Option<&str> {
+    if descriptor.starts_with('[') {
+        Some(descriptor)
+    } else {
+        descriptor.strip_prefix('L')?.strip_suffix(';')
+    }
+}
+
+fn array_type_descriptor(atype: ArrayType) -> char {
+    match atype {
+        ArrayType::Boolean => 'Z',
+        ArrayType::Char => 'C',
+        ArrayType::Float => 'F',
+        ArrayType::Double => 'D',
+        ArrayType::Byte => 'B',
+        ArrayType::Short => 'S',
+        ArrayType::Int => 'I',
+        ArrayType::Long => 'J',
+    }
+}
+
+// One entry per value as frames list them: the Top of a second slot dropped, and for
+// locals trailing Tops as well.
+fn entries(slots: &[Type], trim: bool) -> Vec<Type> {
+    let mut entries = Vec::new();
+    let mut i = 0;
+    while i < slots.len() {
+        entries.push(slots[i].clone());
+        i += if slots[i].is_category2() { 2 } else { 1 };
+    }
+    if trim {
+        while entries.last() == Some(&Type::Top) {
+            entries.pop();
+        }
+    }
+    entries
+}
+
+// Each frame in the smallest form relative to the one before it, the first relative to the
+// initial frame of the method.
+fn encode(
+    initial: &Frame,
+    frames: &[(usize, Frame)],
+) -> Result<AttributeStackMapTable, ClassFormatError> {
+    let mut entries_out: Vec<StackMapFrame> = Vec::new();
+    let mut previous_locals = entries(&initial.locals, true);
+    let mut previous_pc: Option<usize> = None;
+    for (pc, frame) in frames {
+        let offset_delta = match previous_pc {
+            None => *pc,
+            Some(previous) => pc - previous - 1,
+        };
+        let offset_delta = u16::try_from(offset_delta)
+            .map_err(|_| ClassFormatError::new(format!("frame at pc {} out of range", pc)))?;
+        let locals = entries(&frame.locals, true);
+        let stack = entries(&frame.stack, false);
+        let same_locals = locals == previous_locals;
+        let encoded = if same_locals && stack.is_empty() {
+            if offset_delta < 64 {
+                StackMapFrame::Same {
+                    offset_delta: offset_delta as u8,
+                }
+            } else {
+                StackMapFrame::SameExtended { offset_delta }
+            }
+        } else if same_locals && stack.len() == 1 {
+            let stack = stack[0].clone();
+            if offset_delta < 64 {
+                StackMapFrame::SameLocals1StackItem {
+                    offset_delta: offset_delta as u8,
+                    stack,
+                }
+            } else {
+                StackMapFrame::SameLocals1StackItemExtended {
+                    offset_delta,
+                    stack,
+                }
+            }
+        } else if stack.is_empty()
+            && locals.len() < previous_locals.len()
+            && previous_locals.len() - locals.len() <= 3
+            && previous_locals.starts_with(&locals)
+        {
+            StackMapFrame::Chop {
+                k: (previous_locals.len() - locals.len()) as u8,
+                offset_delta,
+            }
+        } else if stack.is_empty()
+            && locals.len() > previous_locals.len()
+            && locals.len() - previous_locals.len() <= 3
+            && locals.starts_with(&previous_locals)
+        {
+            StackMapFrame::Append {
+                offset_delta,
+                locals: locals[previous_locals.len()..].to_vec(),
+            }
+        } else {
+            StackMapFrame::Full {
+                offset_delta,
+                locals: locals.clone(),
+                stack,
+            }
+        };
+        entries_out.push(encoded);
+        previous_locals = locals;
+        previous_pc = Some(*pc);
+    }
+    Ok(AttributeStackMapTable {
+        entries: entries_out,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        access_flags::{ClassAccessFlags, MethodAccessFlags},
+        attribute::ExpandedFrame,
+        class_builder::ClassBuilder,
+        version::ClassVersion,
+    };
+
+    // A ClassLoader holding java/lang/Throwable and the exceptions used below, without
+    // java/lang/Object itself.
+    fn exceptions() -> ClassLoader {
+        let mut class_loader = ClassLoader::new();
+        for (name, super_class) in [
+            ("java/lang/Throwable", "java/lang/Object"),
+            ("java/lang/Exception", "java/lang/Throwable"),
+            ("java/io/IOException", "java/lang/Exception"),
+            ("java/lang/RuntimeException", "java/lang/Exception"),
+            (
+                "java/lang/IllegalStateException",
+                "java/lang/RuntimeException",
+            ),
+        ] {
+            let class = ClassBuilder::new(ClassAccessFlags::PUBLIC, name, Some(super_class))
+                .unwrap()
+                .build()
+                .unwrap();
+            class_loader.class_map.insert(String::from(name), class);
+        }
+        class_loader
+    }
+
+    // static Exception pick(boolean b) { return b ? new IOException() : new IllegalStateException(); }
+    fn pick() -> ClassBuilder {
+        let mut builder =
+            ClassBuilder::new(ClassAccessFlags::PUBLIC, "A", Some("java/lang/Object")).unwrap();
+        builder.version(ClassVersion::new(50, 0));
+        builder
+            .method_with_code(
+                MethodAccessFlags::STATIC,
+                "pick",
+                "(Z)Ljava/lang/Exception;",
+                |code| {
+                    let (other, join) = (code.new_label(), code.new_label());
+                    code.emit(Instruction::Iload0)?;
+                    code.jump(Instruction::Ifeq, other)?;
+                    for (label, class_name) in [
+                        (None, "java/io/IOException"),
+                        (Some(other), "java/lang/IllegalStateException"),
+                    ] {
+                        if let Some(label) = label {
+                            code.bind(label)?;
+                        }
+                        code.new_object(class_name)?;
+                        code.emit(Instruction::Dup)?;
+                        code.invokespecial(class_name, "<init>", "()V")?;
+                        if label.is_none() {
+                            code.jump(Instruction::Goto, join)?;
+                        }
+                    }
+                    code.bind(join)?;
+                    code.emit(Instruction::Areturn)
+                },
+            )
+            .unwrap();
+        builder
+    }
+
+    fn frames(class: &Class) -> Vec<ExpandedFrame> {
+        let method = &class.methods[0];
+        let table = method
+            .code()
+            .and_then(|code| {
+                code.attributes.iter().find_map(|a| match a {
+                    Attribute::StackMapTable(table) => Some(table),
+                    _ => None,
+                })
+            })
+            .expect("no StackMapTable");
+        table.expand(&class.this_class, method).unwrap()
+    }
+
+    #[test]
+    fn joins_classes_at_their_common_superclass() {
+        let class = pick().build_with(&exceptions()).unwrap();
+        let frames = frames(&class);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].stack, vec![]);
+        assert_eq!(
+            frames[1].stack,
+            vec![Type::Object(ClassRef::new("java/lang/Exception"))]
+        );
+    }
+
+    #[test]
+    fn joining_unknown_classes_is_an_error() {
+        let error = pick().build().unwrap_err().to_string();
+        assert!(
+            error.contains("cannot join java/io/IOException and java/lang/IllegalStateException"),
+            "{}",
+            error
+        );
+
+        let mut class_loader = exceptions();
+        class_loader.class_map.remove("java/lang/RuntimeException");
+        let error = pick().build_with(&class_loader).unwrap_err().to_string();
+        assert!(
+            error.contains("class java/lang/RuntimeException is unknown"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn joins_interfaces_and_this_class_without_the_resolver_knowing_them() {
+        let mut class_loader = exceptions();
+        let runnable = ClassBuilder::new(
+            ClassAccessFlags::PUBLIC | ClassAccessFlags::INTERFACE | ClassAccessFlags::ABSTRACT,
+            "java/lang/Runnable",
+            Some("java/lang/Object"),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        class_loader
+            .class_map
+            .insert(String::from("java/lang/Runnable"), runnable);
+        let resolver = WithThisClass {
+            this_class: "A",
+            super_class: Some("java/lang/Exception"),
+            resolver: &class_loader,
+        };
+        let join = |a, b| resolver.common_super_class(a, b).unwrap();
+        assert_eq!(join("java/lang/Runnable", "java/io/IOException"), OBJECT);
+        assert_eq!(join("A", "java/io/IOException"), "java/lang/Exception");
+        assert_eq!(join("java/lang/Exception", "A"), "java/lang/Exception");
+        assert!(resolver.common_super_class("A", "B").is_err());
+    }
+
+    #[test]
+    fn empty_code_is_an_error() {
+        let mut builder =
+            ClassBuilder::new(ClassAccessFlags::PUBLIC, "A", Some("java/lang/Object")).unwrap();
+        builder
+            .method_with_code(MethodAccessFlags::STATIC, "f", "()V", |code| {
+                code.emit(Instruction::Return)
+            })
+            .unwrap();
+        let mut class = builder.build().unwrap();
+        if let Some(Attribute::Code(code)) = class.methods[0].attributes.first_mut() {
+            code.code.clear();
+        }
+        let error = class
+            .compute_stack_maps(&NoClasses)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("code_length 0"), "{}", error);
+    }
+}
@@ -0,0 +1,200 @@
+use std::fmt;
+
+use crate::jvm::class::error::ClassFormatError;
+
+pub const MIN_MAJOR_VERSION: u16 = 45;
+// The newest class file version this VM understands (Java SE 17).
+pub const MAX_MAJOR_VERSION: u16 = 61;
+// Marks a class file that depends on the preview features of its major version.
+pub const PREVIEW_MINOR_VERSION: u16 = 0xFFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClassVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ClassVersion {
+    pub fn new(major: u16, minor: u16) -> ClassVersion {
+        ClassVersion { major, minor }
+    }
+
+    pub fn is_preview(&self) -> bool {
+        self.major >= 56 && self.minor == PREVIEW_MINOR_VERSION
+    }
+
+    // The Java SE release that introduced this major version, e.g. "1.4" for 48 or "17" for 61.
+    pub fn java_release(&self) -> Option<String> {
+        match self.major {
+            45 => Some(String::from("1.1")),
+            46..=48 => Some(format!("1.{}", self.major - 44)),
+            49.. => Some(format!("{}", self.major - 44)),
+            _ => None,
+        }
+    }
+
+    // jsr and ret may not appear in class files from Java 7 on (JVMS 4.9.1).
+    pub fn allows_jsr(&self) -> bool {
+        self.major < 51
+    }
+
+    // From Java 6 methods are verified by type checking and need a StackMapTable wherever
+    // the code branches (JVMS 4.10.1).
+    pub fn requires_stack_map_table(&self) -> bool {
+        self.major >= 50
+    }
+}
+
+impl fmt::Display for ClassVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+// Which class file versions the loader accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionSupport {
+    pub max_major_version: u16,
+    // Preview class files are only accepted for max_major_version and only when enabled,
+    // like `java --enable-preview`.
+    pub enable_preview: bool,
+}
+
+impl Default for VersionSupport {
+    fn default() -> Self {
+        VersionSupport {
+            max_major_version: MAX_MAJOR_VERSION,
+            enable_preview: false,
+        }
+    }
+}
+
+impl VersionSupport {
+    // JVMS 4.1: the supported range of major versions, and the minor versions allowed for each.
+    pub fn check(&self, version: ClassVersion) -> Result<(), ClassFormatError> {
+        if version.major < MIN_MAJOR_VERSION || version.major > self.max_major_version {
+            return Err(ClassFormatError::unsupported_version(format!(
+                "class file version {} is not supported, this VM supports versions {}.0 to {}.0",
+                version, MIN_MAJOR_VERSION, self.max_major_version
+            )));
+        }
+        if version.major < 56 || version.minor == 0 {
+            return Ok(());
+        }
+        if version.minor != PREVIEW_MINOR_VERSION {
+            return Err(ClassFormatError::unsupported_version(format!(
+                "class file version {} has a minor version other than 0 or 65535",
+                version
+            )));
+        }
+        if version.major != self.max_major_version {
+            return Err(ClassFormatError::unsupported_version(format!(
+                "class file version {} uses preview features of an unsupported release, \
+                 only {}.65535 can be loaded",
+                version, self.max_major_version
+            )));
+        }
+        if !self.enable_preview {
+            return Err(ClassFormatError::unsupported_version(format!(
+                "preview features are not enabled for class file version {}, \
+                 try running with '--enable-preview'",
+                version
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(support: VersionSupport, major: u16, minor: u16) -> Result<(), String> {
+        support.check(ClassVersion::new(major, minor)).map_err(|e| {
+            assert!(e.is_unsupported_version());
+            e.reason
+        })
+    }
+
+    #[test]
+    fn supported_major_versions() {
+        let support = VersionSupport::default();
+        assert_eq!(check(support, MIN_MAJOR_VERSION, 0), Ok(()));
+        assert_eq!(check(support, 45, 3), Ok(()));
+        assert_eq!(check(support, MAX_MAJOR_VERSION, 0), Ok(()));
+        assert_eq!(
+            check(support, 44, 0),
+            Err(String::from(
+                "class file version 44.0 is not supported, this VM supports versions 45.0 to 61.0"
+            ))
+        );
+        assert_eq!(
+            check(support, MAX_MAJOR_VERSION + 1, 0),
+            Err(String::from(
+                "class file version 62.0 is not supported, this VM supports versions 45.0 to 61.0"
+            ))
+        );
+        let older = VersionSupport {
+            max_major_version: 52,
+            enable_preview: false,
+        };
+        assert_eq!(check(older, 52, 0), Ok(()));
+        assert!(check(older, 53, 0).is_err());
+    }
+
+    #[test]
+    fn minor_versions_from_56_on_are_0_or_preview() {
+        let support = VersionSupport::default();
+        // Before Java 12 any minor version goes.
+        assert_eq!(check(support, 55, 1), Ok(()));
+        assert_eq!(check(support, 55, PREVIEW_MINOR_VERSION), Ok(()));
+        assert!(!ClassVersion::new(55, PREVIEW_MINOR_VERSION).is_preview());
+        assert_eq!(
+            check(support, 56, 1),
+            Err(String::from(
+                "class file version 56.1 has a minor version other than 0 or 65535"
+            ))
+        );
+        assert_eq!(
+            check(support, 61, 3),
+            Err(String::from(
+                "class file version 61.3 has a minor version other than 0 or 65535"
+            ))
+        );
+    }
+
+    #[test]
+    fn preview_class_files_need_the_current_release_and_enable_preview() {
+        let disabled = VersionSupport::default();
+        let enabled = VersionSupport {
+            enable_preview: true,
+            ..VersionSupport::default()
+        };
+        assert!(ClassVersion::new(61, PREVIEW_MINOR_VERSION).is_preview());
+        assert_eq!(
+            check(disabled, 61, PREVIEW_MINOR_VERSION),
+            Err(String::from(
+                "preview features are not enabled for class file version 61.65535, \
+                 try running with '--enable-preview'"
+            ))
+        );
+        assert_eq!(check(enabled, 61, PREVIEW_MINOR_VERSION), Ok(()));
+        let older_preview = Err(String::from(
+            "class file version 60.65535 uses preview features of an unsupported release, \
+             only 61.65535 can be loaded",
+        ));
+        assert_eq!(check(disabled, 60, PREVIEW_MINOR_VERSION), older_preview);
+        assert_eq!(check(enabled, 60, PREVIEW_MINOR_VERSION), older_preview);
+    }
+
+    #[test]
+    fn java_release_names() {
+        let release = |major| ClassVersion::new(major, 0).java_release();
+        assert_eq!(release(44), None);
+        assert_eq!(release(45), Some(String::from("1.1")));
+        assert_eq!(release(48), Some(String::from("1.4")));
+        assert_eq!(release(49), Some(String::from("5")));
+        assert_eq!(release(61), Some(String::from("17")));
+        assert_eq!(ClassVersion::new(61, 65535).to_string(), "61.65535");
+    }
+}
@@ -0,0 +1,816 @@
+use crate::jvm::class::{
+    attribute::*,
+    constant::Constant,
+    constant_pool::{ClassRef, ConstantPool, CpIndex, Indexed},
+    constant_pool_builder::ConstantPoolBuilder,
+    error::ClassFormatError,
+    field::Field,
+    method::Method,
+    Class,
+};
+use crate::utils::bytecode_writer::BytecodeWriter;
+
+// Serialization back to the class file format (JVMS 4). The parsed model keeps constant pool
+// references resolved, so they are looked up again through a ConstantPoolBuilder seeded with
+// the original pool. A pool may hold duplicates, as compilers leave for Class and MethodType
+// constants and obfuscators for anything, so the model also keeps the index it read next to
+// each resolved value (ClassRef, Indexed and the attribute_name_indices) and that index is
+// written back while it still holds the value. An unmodified class is written byte for byte
+// as it was read, and constants introduced by changes to the model are appended to the pool.
+impl Class {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ClassFormatError> {
+        let mut constant_pool = ConstantPoolBuilder::from_pool(&self.constant_pool);
+        let mut body = BytecodeWriter::new();
+        self.write_body(&mut body, &mut constant_pool)
+            .map_err(|e| e.within(format!("class {}", self.this_class)))?;
+
+        let mut writer = BytecodeWriter::new();
+        writer.u32(0xCAFEBABE);
+        writer.u16(self.minor_version);
+        writer.u16(self.major_version);
+        let constant_pool = constant_pool.build();
+        writer.u16(constant_pool.count());
+        for (_, constant) in constant_pool.iter() {
+            write_constant(&mut writer, constant);
+        }
+        writer.bytes(&body.data);
+        Ok(writer.data)
+    }
+
+    fn write_body(
+        &self,
+        writer: &mut BytecodeWriter,
+        constant_pool: &mut ConstantPoolBuilder,
+    ) -> Result<(), ClassFormatError> {
+        writer.u16(self.access_flags.0);
+        writer.u16(constant_pool.class_ref(&self.this_class)?.0);
+        match &self.super_class {
+            Some(super_class) => writer.u16(constant_pool.class_ref(super_class)?.0),
+            None => writer.u16(0),
+        }
+        writer.count(self.interfaces.len(), "interfaces")?;
+        for interface in &self.interfaces {
+            writer.u16(interface.0);
+        }
+        writer.count(self.fields.len(), "fields")?;
+        for field in &self.fields {
+            field.write(writer, constant_pool)?;
+        }
+        writer.count(self.methods.len(), "methods")?;
+        for method in &self.methods {
+            method.write(writer, constant_pool)?;
+        }
+        write_attributes(
+            writer,
+            constant_pool,
+            &self.attributes,
+            &self.attribute_name_indices,
+        )
+    }
+}
+
+fn write_constant(writer: &mut BytecodeWriter, constant: &Constant) {
+    writer.u8(constant.tag());
+    match constant {
+        Constant::Utf8(c) => {
+            // ConstantPoolBuilder::utf8 and the reader both keep this within a u2.
+            let bytes = c.encode();
+            writer.u16(bytes.len() as u16);
+            writer.bytes(&bytes);
+        }
+        Constant::Integer(c) => writer.u32(c.bytes as u32),
+        Constant::Float(c) => writer.u32(c.bytes.to_bits()),
+        Constant::Long(c) => writer.u64(c.bytes as u64),
+        Constant::Double(c) => writer.u64(c.bytes.to_bits()),
+        Constant::Class(c) => writer.u16(c.name_index.0),
+        Constant::String(c) => writer.u16(c.string_index.0),
+        Constant::Fieldref(c) => {
+            writer.u16(c.class_index.0);
+            writer.u16(c.name_and_type_index.0);
+        }
+        Constant::Methodref(c) => {
+            writer.u16(c.class_index.0);
+            writer.u16(c.name_and_type_index.0);
+        }
+        Constant::InterfaceMethodref(c) => {
+            writer.u16(c.class_index.0);
+            writer.u16(c.name_and_type_index.0);
+        }
+        Constant::NameAndType(c) => {
+            writer.u16(c.name_index.0);
+            writer.u16(c.descriptor_index.0);
+        }
+        Constant::MethodHandle(c) => {
+            writer.u8(c.reference_kind);
+            writer.u16(c.reference_index.0);
+        }
+        Constant::MethodType(c) => writer.u16(c.descriptor_index.0),
+        Constant::Dynamic(c) => {
+            writer.u16(c.bootstrap_method_attr_index);
+            writer.u16(c.name_and_type_index.0);
+        }
+        Constant::InvokeDynamic(c) => {
+            writer.u16(c.bootstrap_method_attr_index);
+            writer.u16(c.name_and_type_index.0);
+        }
+        Constant::Module(c) => writer.u16(c.name_index.0),
+        Constant::Package(c) => writer.u16(c.name_index.0),
+        Constant::Unusable => unreachable!("ConstantPool::iter skips unusable slots"),
+    }
+}
+
+impl Field {
+    pub fn write(
+        &self,
+        writer: &mut BytecodeWriter,
+        constant_pool: &mut ConstantPoolBuilder,
+    ) -> Result<(), ClassFormatError> {
+        writer.u16(self.access_flags.0);
+        writer.u16(constant_pool.utf8_ref(&self.name)?.0);
+        writer.u16(constant_pool.utf8_ref(&self.descriptor)?.0);
+        write_attributes(
+            writer,
+            constant_pool,
+            &self.attributes,
+            &self.attribute_name_indices,
+        )
+        .map_err(|e| e.within(format!("field {}{}", self.name, self.descriptor)))
+    }
+}
+
+impl Method {
+    pub fn write(
+        &self,
+        writer: &mut BytecodeWriter,
+        constant_pool: &mut ConstantPoolBuilder,
+    ) -> Result<(), ClassFormatError> {
+        writer.u16(self.access_flags.0);
+        writer.u16(constant_pool.utf8_ref(&self.name)?.0);
+        writer.u16(constant_pool.utf8_ref(&self.descriptor)?.0);
+        write_attributes(
+            writer,
+            constant_pool,
+            &self.attributes,
+            &self.attribute_name_indices,
+        )
+        .map_err(|e| e.within(format!("method {}{}", self.name, self.descriptor)))
+    }
+}
+
+// attributes_count followed by the attributes; `name_indices` are the attribute_name_index
+// hints read_attributes returned, empty for attributes built in memory.
+pub fn write_attributes(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    attributes: &[Attribute],
+    name_indices: &[CpIndex],
+) -> Result<(), ClassFormatError> {
+    writer.count(attributes.len(), "attributes")?;
+    for (i, attribute) in attributes.iter().enumerate() {
+        attribute.write(writer, constant_pool, name_indices.get(i).copied())?;
+    }
+    Ok(())
+}
+
+impl Attribute {
+    // `name_index` is the attribute_name_index it was read from, if any.
+    pub fn write(
+        &self,
+        writer: &mut BytecodeWriter,
+        constant_pool: &mut ConstantPoolBuilder,
+        name_index: Option<CpIndex>,
+    ) -> Result<(), ClassFormatError> {
+        let name = Indexed {
+            value: String::from(self.name()),
+            index: name_index,
+        };
+        writer.u16(constant_pool.utf8_ref(&name)?.0);
+        let length_offset = writer.offset();
+        writer.u32(0);
+        self.write_body(writer, constant_pool)
+            .map_err(|e| e.within(format!("attribute {}", self.name())))?;
+        let attribute_length = writer.offset() - length_offset - 4;
+        let attribute_length = u32::try_from(attribute_length).map_err(|_| {
+            ClassFormatError::new(format!(
+                "attribute {} of {} bytes is too long",
+                self.name(),
+                attribute_length
+            ))
+        })?;
+        writer.patch_u32(length_offset, attribute_length);
+        Ok(())
+    }
+
+    fn write_body(
+        &self,
+        writer: &mut BytecodeWriter,
+        constant_pool: &mut ConstantPoolBuilder,
+    ) -> Result<(), ClassFormatError> {
+        match self {
+            Attribute::ConstantValue(a) => writer.u16(a.constantvalue_index.0),
+            Attribute::Code(a) => {
+                writer.u16(a.max_stack);
+                writer.u16(a.max_locals);
+                let code_length = u32::try_from(a.code.len()).map_err(|_| {
+                    ClassFormatError::new(format!("code of {} bytes is too long", a.code.len()))
+                })?;
+                writer.u32(code_length);
+                writer.bytes(&a.code);
+                writer.count(a.exception_table.len(), "exception table entries")?;
+                for entry in &a.exception_table {
+                    writer.u16(entry.start_pc);
+                    writer.u16(entry.end_pc);
+                    writer.u16(entry.handler_pc);
+                    writer.u16(entry.catch_type.0);
+                }
+                write_attributes(
+                    writer,
+                    constant_pool,
+                    &a.attributes,
+                    &a.attribute_name_indices,
+                )?;
+            }
+            Attribute::LineNumberTable(a) => {
+                writer.count(a.line_number_table.len(), "line numbers")?;
+                for entry in &a.line_number_table {
+                    writer.u16(entry[0]);
+                    writer.u16(entry[1]);
+                }
+            }
+            Attribute::SourceFile(a) => writer.u16(constant_pool.utf8_ref(&a.sourcefile)?.0),
+            Attribute::LocalVariableTable(a) => {
+                writer.count(a.local_variable_table.len(), "local variables")?;
+                for v in &a.local_variable_table {
+                    writer.u16(v.start_pc);
+                    writer.u16(v.length);
+                    writer.u16(constant_pool.utf8_ref(&v.name)?.0);
+                    writer.u16(constant_pool.utf8_ref(&v.descriptor)?.0);
+                    writer.u16(v.index);
+                }
+            }
+            Attribute::StackMapTable(a) => {
+                writer.count(a.entries.len(), "stack map frames")?;
+                for frame in &a.entries {
+                    write_frame(writer, constant_pool, frame)?;
+                }
+            }
+            Attribute::Signature(a) => writer.u16(constant_pool.utf8_ref(&a.signature)?.0),
+            Attribute::RuntimeVisibleAnnotations(a) | Attribute::RuntimeInvisibleAnnotations(a) => {
+                write_annotations(writer, constant_pool, &a.annotations)?;
+            }
+            Attribute::RuntimeVisibleParameterAnnotations(a)
+            | Attribute::RuntimeInvisibleParameterAnnotations(a) => {
+                writer.count_u8(a.parameter_annotations.len(), "annotated parameters")?;
+                for annotations in &a.parameter_annotations {
+                    write_annotations(writer, constant_pool, annotations)?;
+                }
+            }
+            Attribute::RuntimeVisibleTypeAnnotations(a)
+            | Attribute::RuntimeInvisibleTypeAnnotations(a) => {
+                writer.count(a.annotations.len(), "type annotations")?;
+                for annotation in &a.annotations {
+                    write_type_annotation(writer, constant_pool, annotation)?;
+                }
+            }
+            Attribute::AnnotationDefault(a) => {
+                write_element_value(writer, constant_pool, &a.default_value)?;
+            }
+            Attribute::InnerClasses(a) => {
+                writer.count(a.classes.len(), "inner classes")?;
+                for inner in &a.classes {
+                    writer.u16(constant_pool.class_ref(&inner.inner_class)?.0);
+                    match &inner.outer_class {
+                        Some(outer_class) => writer.u16(constant_pool.class_ref(outer_class)?.0),
+                        None => writer.u16(0),
+                    }
+                    match &inner.inner_name {
+                        Some(inner_name) => writer.u16(constant_pool.utf8_ref(inner_name)?.0),
+                        None => writer.u16(0),
+                    }
+                    writer.u16(inner.inner_class_access_flags.0);
+                }
+            }
+            Attribute::EnclosingMethod(a) => {
+                writer.u16(constant_pool.class_ref(&a.class)?.0);
+                match (&a.method_name, &a.method_descriptor) {
+                    (Some(name), Some(descriptor)) => {
+                        let method = Indexed {
+                            value: (name.clone(), descriptor.clone()),
+                            index: a.method_index,
+                        };
+                        let index =
+                            constant_pool.indexed(&method, ConstantPool::name_and_type, |cp| {
+                                cp.name_and_type(name, descriptor)
+                            })?;
+                        writer.u16(index.0)
+                    }
+                    _ => writer.u16(0),
+                }
+            }
+            Attribute::NestHost(a) => writer.u16(constant_pool.class_ref(&a.host_class)?.0),
+            Attribute::NestMembers(a) | Attribute::PermittedSubclasses(a) => {
+                write_classes(writer, constant_pool, &a.classes)?;
+            }
+            Attribute::Record(a) => {
+                writer.count(a.components.len(), "record components")?;
+                for component in &a.components {
+                    writer.u16(constant_pool.utf8_ref(&component.name)?.0);
+                    writer.u16(constant_pool.utf8_ref(&component.descriptor)?.0);
+                    write_attributes(
+                        writer,
+                        constant_pool,
+                        &component.attributes,
+                        &component.attribute_name_indices,
+                    )
+                    .map_err(|e| e.within(format!("record component {}", component.name)))?;
+                }
+            }
+            Attribute::Module(a) => write_module(writer, constant_pool, a)?,
+            Attribute::ModulePackages(a) => {
+                writer.count(a.packages.len(), "packages")?;
+                for package in &a.packages {
+                    writer.u16(constant_pool.package_ref(package)?.0);
+                }
+            }
+            Attribute::ModuleMainClass(a) => writer.u16(constant_pool.class_ref(&a.main_class)?.0),
+            Attribute::Exceptions(a) => {
+                write_classes(writer, constant_pool, &a.exception_index_table)?;
+            }
+            Attribute::MethodParameters(a) => {
+                writer.count_u8(a.parameters.len(), "method parameters")?;
+                for parameter in &a.parameters {
+                    match &parameter.name {
+                        Some(name) => writer.u16(constant_pool.utf8_ref(name)?.0),
+                        None => writer.u16(0),
+                    }
+                    writer.u16(parameter.access_flags);
+                }
+            }
+            Attribute::LocalVariableTypeTable(a) => {
+                writer.count(a.local_variable_type_table.len(), "local variables")?;
+                for v in &a.local_variable_type_table {
+                    writer.u16(v.start_pc);
+                    writer.u16(v.length);
+                    writer.u16(constant_pool.utf8_ref(&v.name)?.0);
+                    writer.u16(constant_pool.utf8_ref(&v.signature)?.0);
+                    writer.u16(v.index);
+                }
+            }
+            Attribute::Deprecated | Attribute::Synthetic => {}
+            Attribute::SourceDebugExtension(a) => writer.bytes(&a.debug_extension),
+            Attribute::BootstrapMethods(a) => {
+                writer.count(a.bootstrap_methods.len(), "bootstrap methods")?;
+                for bootstrap in &a.bootstrap_methods {
+                    let bootstrap_method = constant_pool.method_handle_at(
+                        &bootstrap.bootstrap_method,
+                        bootstrap.bootstrap_method_ref,
+                    )?;
+                    writer.u16(bootstrap_method.0);
+                    writer.count(bootstrap.bootstrap_arguments.len(), "bootstrap arguments")?;
+                    for (i, argument) in bootstrap.bootstrap_arguments.iter().enumerate() {
+                        let index = match bootstrap.bootstrap_argument_indices.get(i) {
+                            Some(original) => constant_pool.loadable_at(argument, *original)?,
+                            None => constant_pool.loadable(argument)?,
+                        };
+                        writer.u16(index.0);
+                    }
+                }
+            }
+            Attribute::Unknown { bytes, .. } => writer.bytes(bytes),
+        }
+        Ok(())
+    }
+}
+
+fn write_classes(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    classes: &[ClassRef],
+) -> Result<(), ClassFormatError> {
+    writer.count(classes.len(), "classes")?;
+    for class in classes {
+        writer.u16(constant_pool.class_ref(class)?.0);
+    }
+    Ok(())
+}
+
+fn write_frame(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    frame: &StackMapFrame,
+) -> Result<(), ClassFormatError> {
+    writer.u8(frame.frame_type());
+    match frame {
+        StackMapFrame::Same { .. } => {}
+        StackMapFrame::SameLocals1StackItem { stack, .. } => {
+            write_verification_type(writer, constant_pool, stack)?;
+        }
+        StackMapFrame::SameLocals1StackItemExtended {
+            offset_delta,
+            stack,
+        } => {
+            writer.u16(*offset_delta);
+            write_verification_type(writer, constant_pool, stack)?;
+        }
+        StackMapFrame::Chop { offset_delta, .. } | StackMapFrame::SameExtended { offset_delta } => {
+            writer.u16(*offset_delta);
+        }
+        StackMapFrame::Append {
+            offset_delta,
+            locals,
+        } => {
+            writer.u16(*offset_delta);
+            for local in locals {
+                write_verification_type(writer, constant_pool, local)?;
+            }
+        }
+        StackMapFrame::Full {
+            offset_delta,
+            locals,
+            stack,
+        } => {
+            writer.u16(*offset_delta);
+            writer.count(locals.len(), "locals")?;
+            for local in locals {
+                write_verification_type(writer, constant_pool, local)?;
+            }
+            writer.count(stack.len(), "stack items")?;
+            for item in stack {
+                write_verification_type(writer, constant_pool, item)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_verification_type(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    info: &VerificationTypeInfo,
+) -> Result<(), ClassFormatError> {
+    writer.u8(info.tag());
+    match info {
+        VerificationTypeInfo::Object(class) => writer.u16(constant_pool.class_ref(class)?.0),
+        VerificationTypeInfo::Uninitialized(offset) => writer.u16(*offset),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn write_annotations(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    annotations: &[Annotation],
+) -> Result<(), ClassFormatError> {
+    writer.count(annotations.len(), "annotations")?;
+    for annotation in annotations {
+        write_annotation(writer, constant_pool, annotation)?;
+    }
+    Ok(())
+}
+
+fn write_annotation(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    annotation: &Annotation,
+) -> Result<(), ClassFormatError> {
+    writer.u16(constant_pool.utf8_ref(&annotation.type_descriptor)?.0);
+    writer.count(annotation.element_value_pairs.len(), "element value pairs")?;
+    for pair in &annotation.element_value_pairs {
+        writer.u16(constant_pool.utf8_ref(&pair.name)?.0);
+        write_element_value(writer, constant_pool, &pair.value)?;
+    }
+    Ok(())
+}
+
+fn write_element_value(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    value: &ElementValue,
+) -> Result<(), ClassFormatError> {
+    writer.u8(value.tag());
+    match value {
+        ElementValue::Const(constant) => {
+            let tag = constant.tag();
+            let resolve = |cp: &ConstantPool, index| ConstValue::resolve(cp, tag, index);
+            let index = constant_pool.indexed(constant, resolve, |cp| match &constant.value {
+                ConstValue::Byte(v) => cp.integer(*v as i32),
+                ConstValue::Char(v) => cp.integer(*v as i32),
+                ConstValue::Double(v) => cp.double(*v),
+                ConstValue::Float(v) => cp.float(*v),
+                ConstValue::Int(v) => cp.integer(*v),
+                ConstValue::Long(v) => cp.long(*v),
+                ConstValue::Short(v) => cp.integer(*v as i32),
+                ConstValue::Boolean(v) => cp.integer(*v as i32),
+                ConstValue::String(v) => cp.utf8(v),
+            })?;
+            writer.u16(index.0);
+        }
+        ElementValue::Enum {
+            type_name,
+            const_name,
+        } => {
+            writer.u16(constant_pool.utf8_ref(type_name)?.0);
+            writer.u16(constant_pool.utf8_ref(const_name)?.0);
+        }
+        ElementValue::Class(descriptor) => writer.u16(constant_pool.utf8_ref(descriptor)?.0),
+        ElementValue::Annotation(annotation) => {
+            write_annotation(writer, constant_pool, annotation)?
+        }
+        ElementValue::Array(values) => {
+            writer.count(values.len(), "array elements")?;
+            for value in values {
+                write_element_value(writer, constant_pool, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_type_annotation(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    annotation: &TypeAnnotation,
+) -> Result<(), ClassFormatError> {
+    writer.u8(annotation.target_type);
+    match &annotation.target_info {
+        TargetInfo::TypeParameter {
+            type_parameter_index,
+        } => writer.u8(*type_parameter_index),
+        TargetInfo::Supertype { supertype_index } => writer.u16(*supertype_index),
+        TargetInfo::TypeParameterBound {
+            type_parameter_index,
+            bound_index,
+        } => {
+            writer.u8(*type_parameter_index);
+            writer.u8(*bound_index);
+        }
+        TargetInfo::Empty => {}
+        TargetInfo::FormalParameter {
+            formal_parameter_index,
+        } => writer.u8(*formal_parameter_index),
+        TargetInfo::Throws { throws_type_index } => writer.u16(*throws_type_index),
+        TargetInfo::Localvar(table) => {
+            writer.count(table.len(), "local variable targets")?;
+            for target in table {
+                writer.u16(target.start_pc);
+                writer.u16(target.length);
+                writer.u16(target.index);
+            }
+        }
+        TargetInfo::Catch {
+            exception_table_index,
+        } => writer.u16(*exception_table_index),
+        TargetInfo::Offset { offset } => writer.u16(*offset),
+        TargetInfo::TypeArgument {
+            offset,
+            type_argument_index,
+        } => {
+            writer.u16(*offset);
+            writer.u8(*type_argument_index);
+        }
+    }
+    writer.count_u8(annotation.type_path.len(), "type path entries")?;
+    for entry in &annotation.type_path {
+        writer.u8(entry.type_path_kind);
+        writer.u8(entry.type_argument_index);
+    }
+    write_annotation(writer, constant_pool, &annotation.annotation)
+}
+
+fn write_module(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    module: &AttributeModule,
+) -> Result<(), ClassFormatError> {
+    writer.u16(constant_pool.module_ref(&module.module_name)?.0);
+    writer.u16(module.module_flags);
+    write_optional_utf8(writer, constant_pool, &module.module_version)?;
+
+    writer.count(module.requires.len(), "requires")?;
+    for requires in &module.requires {
+        writer.u16(constant_pool.module_ref(&requires.module)?.0);
+        writer.u16(requires.flags);
+        write_optional_utf8(writer, constant_pool, &requires.version)?;
+    }
+    for table in [&module.exports, &module.opens] {
+        writer.count(table.len(), "exports")?;
+        for exports in table {
+            writer.u16(constant_pool.package_ref(&exports.package)?.0);
+            writer.u16(exports.flags);
+            writer.count(exports.to.len(), "modules")?;
+            for to in &exports.to {
+                writer.u16(constant_pool.module_ref(to)?.0);
+            }
+        }
+    }
+    write_classes(writer, constant_pool, &module.uses)?;
+    writer.count(module.provides.len(), "provides")?;
+    for provides in &module.provides {
+        writer.u16(constant_pool.class_ref(&provides.service)?.0);
+        write_classes(writer, constant_pool, &provides.with)?;
+    }
+    Ok(())
+}
+
+fn write_optional_utf8(
+    writer: &mut BytecodeWriter,
+    constant_pool: &mut ConstantPoolBuilder,
+    value: &Option<Indexed<String>>,
+) -> Result<(), ClassFormatError> {
+    match value {
+        Some(value) => writer.u16(constant_pool.utf8_ref(value)?.0),
+        None => writer.u16(0),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        access_flags::{ClassAccessFlags, MethodAccessFlags},
+        class_builder::ClassBuilder,
+        constant::ConstantFloat,
+        constant_pool::*,
+        instruction::Instruction,
+        version::ClassVersion,
+    };
+
+    // Appends a copy of the constant at `index`, returning the index of the copy.
+    fn duplicate(class: &mut Class, index: CpIndex) -> CpIndex {
+        let copy = class.constant_pool[index].clone();
+        class.constant_pool.entries.push(copy);
+        CpIndex(class.constant_pool.count() - 1)
+    }
+
+    #[test]
+    fn duplicate_constants_round_trip() {
+        let handle = MethodHandle {
+            reference_kind: ReferenceKind::InvokeStatic,
+            member: MemberRef {
+                kind: MemberKind::Method,
+                class_name: String::from("A"),
+                name: String::from("f"),
+                descriptor: String::from("()V"),
+            },
+        };
+        let mut builder =
+            ClassBuilder::new(ClassAccessFlags::PUBLIC, "A", Some("java/lang/Object")).unwrap();
+        builder.version(ClassVersion::new(51, 0));
+        builder
+            .method_with_code(MethodAccessFlags::STATIC, "f", "()V", |code| {
+                code.emit(Instruction::Return)
+            })
+            .unwrap();
+        let exception = builder
+            .constant_pool()
+            .class("java/lang/Exception")
+            .unwrap();
+        let method_type = builder.constant_pool().method_type("()V").unwrap();
+        let method_handle = builder.constant_pool().method_handle(&handle).unwrap();
+        let mut class = builder.build().unwrap();
+
+        let exception = duplicate(&mut class, exception);
+        let method_type = duplicate(&mut class, method_type);
+        let method_handle = duplicate(&mut class, method_handle);
+        for bits in [0x7fc0_0001, 0x7fc0_0001, 0xffc0_0002] {
+            let nan = f32::from_bits(bits);
+            class
+                .constant_pool
+                .entries
+                .push(Constant::Float(ConstantFloat { bytes: nan }));
+        }
+        let exception_ref = ClassRef {
+            name: String::from("java/lang/Exception"),
+            index: Some(exception),
+        };
+        let method = &mut class.methods[0];
+        method
+            .attributes
+            .push(Attribute::Exceptions(AttributeExceptions {
+                exception_index_table: vec![exception_ref.clone()],
+            }));
+        let Some(Attribute::Code(code)) = method.attributes.first_mut() else {
+            panic!("f has no code");
+        };
+        code.attributes
+            .push(Attribute::StackMapTable(AttributeStackMapTable {
+                entries: vec![StackMapFrame::SameLocals1StackItem {
+                    offset_delta: 0,
+                    stack: VerificationTypeInfo::Object(exception_ref),
+                }],
+            }));
+        class
+            .attributes
+            .push(Attribute::BootstrapMethods(AttributeBootstrapMethods {
+                bootstrap_methods: vec![BootstrapMethod {
+                    bootstrap_method: handle,
+                    bootstrap_arguments: vec![LoadableConstant::MethodType(String::from("()V"))],
+                    bootstrap_method_ref: method_handle,
+                    bootstrap_argument_indices: vec![method_type],
+                }],
+            }));
+
+        let bytes = class.to_bytes().unwrap();
+        let read = Class::new(&bytes[..]).unwrap();
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+
+        let method = &read.methods[0];
+        assert_eq!(method.exceptions()[0].index, Some(exception));
+        let frames = match &method.code().unwrap().attributes[..] {
+            [Attribute::StackMapTable(a)] => &a.entries,
+            attributes => panic!("unexpected code attributes {:?}", attributes),
+        };
+        match &frames[..] {
+            [StackMapFrame::SameLocals1StackItem {
+                stack: VerificationTypeInfo::Object(class),
+                ..
+            }] => assert_eq!(class.index, Some(exception)),
+            frames => panic!("unexpected frames {:?}", frames),
+        }
+        let bootstrap = read
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::BootstrapMethods(a) => Some(&a.bootstrap_methods[0]),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(bootstrap.bootstrap_method_ref, method_handle);
+        assert_eq!(bootstrap.bootstrap_argument_indices, vec![method_type]);
+        let nans: Vec<u32> = read
+            .constant_pool
+            .iter()
+            .filter_map(|(_, constant)| match constant {
+                Constant::Float(c) => Some(c.bytes.to_bits()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nans, vec![0x7fc0_0001, 0x7fc0_0001, 0xffc0_0002]);
+    }
+
+    #[test]
+    fn duplicate_utf8_constants_round_trip() {
+        let mut builder =
+            ClassBuilder::new(ClassAccessFlags::PUBLIC, "Add", Some("java/lang/Object")).unwrap();
+        builder
+            .method_with_code(MethodAccessFlags::STATIC, "add", "(II)I", |code| {
+                code.emit(Instruction::Iload0)?;
+                code.emit(Instruction::Iload1)?;
+                code.emit(Instruction::Iadd)?;
+                code.emit(Instruction::Ireturn)
+            })
+            .unwrap();
+        builder.attribute(Attribute::SourceFile(AttributeSourceFile {
+            sourcefile: "Add.java".into(),
+        }));
+        let mut class = builder.build().unwrap();
+
+        // javac never writes these, but nothing forbids them and obfuscators do.
+        let utf8 = |class: &Class, s: &str| {
+            class
+                .constant_pool
+                .iter()
+                .find(|(_, constant)| matches!(constant, Constant::Utf8(c) if c.bytes == s))
+                .unwrap()
+                .0
+        };
+        let originals = [
+            utf8(&class, "Add.java"),
+            utf8(&class, "Code"),
+            utf8(&class, "add"),
+        ];
+        let [sourcefile, code_name, method_name] =
+            originals.map(|index| duplicate(&mut class, index));
+        let Attribute::SourceFile(a) = &mut class.attributes[0] else {
+            panic!("unexpected attributes {:?}", class.attributes);
+        };
+        a.sourcefile.index = Some(sourcefile);
+        class.methods[0].name.index = Some(method_name);
+        class.methods[0].attribute_name_indices = vec![code_name];
+
+        let bytes = class.to_bytes().unwrap();
+        let read = Class::new(&bytes[..]).unwrap();
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+        let Attribute::SourceFile(a) = &read.attributes[0] else {
+            panic!("unexpected attributes {:?}", read.attributes);
+        };
+        assert_eq!(a.sourcefile.index, Some(sourcefile));
+        assert_eq!(read.methods[0].name.index, Some(method_name));
+        assert_eq!(read.methods[0].attribute_name_indices, vec![code_name]);
+
+        // An index that no longer holds its value is not written; the value is looked up.
+        let mut renamed = read;
+        let Attribute::SourceFile(a) = &mut renamed.attributes[0] else {
+            unreachable!();
+        };
+        a.sourcefile.value = String::from("Sum.java");
+        let renamed = Class::new(&renamed.to_bytes().unwrap()[..]).unwrap();
+        let Attribute::SourceFile(a) = &renamed.attributes[0] else {
+            panic!("unexpected attributes {:?}", renamed.attributes);
+        };
+        assert_eq!(a.sourcefile, "Sum.java");
+        assert_ne!(a.sourcefile.index, Some(sourcefile));
+    }
+}
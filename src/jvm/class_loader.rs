@@ -1,9 +1,64 @@
-use crate::jvm::class::Class;
-use std::{collections::HashMap, fs, io::BufReader};
+use crate::jvm::class::{
+    error::ClassFormatError, module::ModuleDescriptor, version::VersionSupport, Class,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs,
+    io::{self, BufReader},
+    path::Path,
+};
+
+// Why an entry of the class path was skipped.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Zip(zip::result::ZipError),
+    ClassFormat(ClassFormatError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Zip(e) => write!(f, "{}", e),
+            LoadError::ClassFormat(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for LoadError {
+    fn from(e: zip::result::ZipError) -> LoadError {
+        LoadError::Zip(e)
+    }
+}
+
+impl From<ClassFormatError> for LoadError {
+    fn from(e: ClassFormatError) -> LoadError {
+        LoadError::ClassFormat(e)
+    }
+}
 
 pub struct ClassLoader {
     // pub manifest: HashMap<String, String>,
     pub class_map: HashMap<String, Class>,
+    // module-info classes all share the same class name, so they are kept by module name.
+    pub module_map: HashMap<String, ModuleDescriptor>,
+    pub version_support: VersionSupport,
+}
+
+impl Default for ClassLoader {
+    fn default() -> Self {
+        ClassLoader::new()
+    }
 }
 
 impl ClassLoader {
@@ -20,7 +75,7 @@ impl ClassLoader {
         //     if ziped_file.is_file() {
         //         if ziped_file.name().ends_with(".class") {
         //             let class = Class::new(ziped_file).unwrap();
-        //             let class_name = String::from(&class.this_class);
+        //             let class_name = class.this_class.name.clone();
 
         //             class_map.insert(class_name, class);
         //         }
@@ -32,44 +87,207 @@ impl ClassLoader {
         ClassLoader {
             // manifest,
             class_map,
+            module_map: HashMap::new(),
+            version_support: VersionSupport::default(),
+        }
+    }
+
+    // Loads every .class file and every class inside a .jar under the given paths; a path may
+    // also be a jar itself. Entries that cannot be read or fail to parse are skipped and
+    // returned together with their location.
+    pub fn load_from_path(&mut self, class_path: String) -> Vec<(String, LoadError)> {
+        let mut skipped: Vec<(String, LoadError)> = Vec::new();
+        for path in class_path.split(';') {
+            let path = Path::new(path);
+            if path.is_file() && path.to_string_lossy().ends_with(".jar") {
+                self.load_jar(path, &mut skipped);
+            } else {
+                self.load_directory(path, &mut skipped);
+            }
         }
+        skipped
     }
 
-    pub fn load_from_path(&mut self, class_path: String) {
-        // BAD CODE
-        for path in class_path.split(";") {
-            for entry in fs::read_dir(path).unwrap() {
-                let entry_path = fs::canonicalize(entry.unwrap().path()).unwrap();
-                let entry_path_string = entry_path.as_path().display().to_string();
-
-                if entry_path.is_dir() {
-                    self.load_from_path(entry_path_string)
-                } else if entry_path.is_file() || entry_path.is_relative() {
-                    if entry_path_string.ends_with(".class") {
-                        let class = Class::new(fs::File::open(entry_path_string).unwrap()).unwrap();
-                        let class_name = String::from(&class.this_class);
-                        self.class_map.insert(class_name, class);
-                    } else if entry_path_string.ends_with(".jar") {
-                        let jar_file = fs::File::open(entry_path_string).unwrap();
-                        let mut jar_archive =
-                            zip::ZipArchive::new(BufReader::new(jar_file)).unwrap();
-                        for i in 0..jar_archive.len() {
-                            let ziped_file = jar_archive.by_index(i).unwrap();
-                            if ziped_file.is_file() {
-                                if ziped_file.name().ends_with(".class") {
-                                    let class = Class::new(ziped_file).unwrap();
-                                    let class_name = String::from(&class.this_class);
-                                    self.class_map.insert(class_name, class);
-                                }
-                            }
-                        }
-                    }
+    fn load_directory(&mut self, directory: &Path, skipped: &mut Vec<(String, LoadError)>) {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) => return skipped.push((directory.display().to_string(), e.into())),
+        };
+        for entry in entries {
+            let entry_path = match entry.and_then(|entry| fs::canonicalize(entry.path())) {
+                Ok(entry_path) => entry_path,
+                Err(e) => {
+                    skipped.push((directory.display().to_string(), e.into()));
+                    continue;
                 }
+            };
+            let entry_path_string = entry_path.display().to_string();
+            if entry_path.is_dir() {
+                self.load_directory(&entry_path, skipped);
+            } else if entry_path_string.ends_with(".class") {
+                let loaded = fs::File::open(&entry_path)
+                    .map_err(LoadError::from)
+                    .and_then(|file| Ok(Class::new(file)?))
+                    .and_then(|class| Ok(self.insert(class)?));
+                if let Err(e) = loaded {
+                    skipped.push((entry_path_string, e));
+                }
+            } else if entry_path_string.ends_with(".jar") {
+                self.load_jar(&entry_path, skipped);
             }
         }
     }
 
-    pub fn find_class(&self, class_name: &String) -> &Class {
+    fn load_jar(&mut self, jar_path: &Path, skipped: &mut Vec<(String, LoadError)>) {
+        let jar_path_string = jar_path.display().to_string();
+        let jar_archive = fs::File::open(jar_path)
+            .map_err(LoadError::from)
+            .and_then(|jar_file| Ok(zip::ZipArchive::new(BufReader::new(jar_file))?));
+        let mut jar_archive = match jar_archive {
+            Ok(jar_archive) => jar_archive,
+            Err(e) => return skipped.push((jar_path_string, e)),
+        };
+        for i in 0..jar_archive.len() {
+            let ziped_file = match jar_archive.by_index(i) {
+                Ok(ziped_file) => ziped_file,
+                Err(e) => {
+                    skipped.push((format!("{}!/#{}", jar_path_string, i), e.into()));
+                    continue;
+                }
+            };
+            if ziped_file.is_file() && ziped_file.name().ends_with(".class") {
+                let entry_name = format!("{}!/{}", jar_path_string, ziped_file.name());
+                let loaded = Class::new(ziped_file).and_then(|class| self.insert(class));
+                if let Err(e) = loaded {
+                    skipped.push((entry_name, e.into()));
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, class: Class) -> Result<(), ClassFormatError> {
+        self.version_support
+            .check(class.version())
+            .map_err(|e| e.within(format!("class {}", class.this_class)))?;
+        class.check_format()?;
+        if let Some(module) = class.module_descriptor()? {
+            self.module_map.insert(module.name.clone(), module);
+            return Ok(());
+        }
+        let class_name = class.this_class.name.clone();
+        self.class_map.insert(class_name, class);
+        Ok(())
+    }
+
+    pub fn find_class(&self, class_name: &str) -> &Class {
         self.class_map.get(class_name).unwrap()
     }
+
+    pub fn find_module(&self, module_name: &str) -> Option<&ModuleDescriptor> {
+        self.module_map.get(module_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        access_flags::ClassAccessFlags, class_builder::ClassBuilder, error::ClassFormatErrorKind,
+    };
+    use std::{io::Write, path::PathBuf};
+
+    fn class_bytes(name: &str) -> Vec<u8> {
+        ClassBuilder::new(ClassAccessFlags::PUBLIC, name, Some("java/lang/Object"))
+            .unwrap()
+            .build()
+            .unwrap()
+            .to_bytes()
+            .unwrap()
+    }
+
+    // A fresh directory under the system temporary directory, unique to the test.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sth_like_jvm-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn class_format_error<'a>(
+        skipped: &'a [(String, LoadError)],
+        suffix: &str,
+    ) -> &'a ClassFormatError {
+        match skipped
+            .iter()
+            .find(|(location, _)| location.ends_with(suffix))
+        {
+            Some((_, LoadError::ClassFormat(e))) => e,
+            other => panic!("{} not skipped as a ClassFormatError: {:?}", suffix, other),
+        }
+    }
+
+    #[test]
+    fn truncated_class_is_skipped_with_its_offset() {
+        let bytes = class_bytes("A");
+        let error = Class::new(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(error.kind, ClassFormatErrorKind::ClassFormat);
+        assert_eq!(error.offset, Some(bytes.len() - 2));
+        assert_eq!(error.location, vec![String::from("class A")]);
+
+        let error = Class::new(&bytes[..9]).unwrap_err();
+        assert_eq!(error.offset, Some(8));
+    }
+
+    #[test]
+    fn corrupt_class_files_do_not_abort_loading_a_directory() {
+        let dir = temp_dir("directory");
+        fs::write(dir.join("A.class"), class_bytes("A")).unwrap();
+        let truncated = class_bytes("B");
+        fs::write(dir.join("B.class"), &truncated[..truncated.len() - 1]).unwrap();
+        fs::create_dir(dir.join("c")).unwrap();
+        fs::write(dir.join("c/C.class"), b"\xca\xfe\xba\xbf").unwrap();
+
+        let mut class_loader = ClassLoader::new();
+        let skipped = class_loader.load_from_path(dir.display().to_string());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(class_loader.class_map.keys().collect::<Vec<_>>(), vec!["A"]);
+        assert_eq!(skipped.len(), 2);
+        let error = class_format_error(&skipped, "B.class");
+        assert_eq!(error.location, vec![String::from("class B")]);
+        assert_eq!(error.offset, Some(truncated.len() - 2));
+        let error = class_format_error(&skipped, "C.class");
+        assert_eq!(error.kind, ClassFormatErrorKind::ClassFormat);
+        assert_eq!(error.offset, Some(0));
+        assert!(error.reason.contains("bad magic number 0xCAFEBABF"));
+    }
+
+    #[test]
+    fn corrupt_jar_entries_and_jars_do_not_abort_loading() {
+        let dir = temp_dir("jar");
+        let mut jar = zip::ZipWriter::new(fs::File::create(dir.join("a.jar")).unwrap());
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        jar.start_file("A.class", options).unwrap();
+        jar.write_all(&class_bytes("A")).unwrap();
+        jar.start_file("p/B.class", options).unwrap();
+        jar.write_all(&class_bytes("p/B")[..20]).unwrap();
+        jar.finish().unwrap();
+        fs::write(dir.join("broken.jar"), b"not a zip").unwrap();
+
+        let mut class_loader = ClassLoader::new();
+        let skipped = class_loader.load_from_path(dir.display().to_string());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(class_loader.class_map.keys().collect::<Vec<_>>(), vec!["A"]);
+        assert_eq!(skipped.len(), 2);
+        let error = class_format_error(&skipped, "a.jar!/p/B.class");
+        assert_eq!(error.kind, ClassFormatErrorKind::ClassFormat);
+        assert_eq!(error.location, vec![String::from("constant pool entry #3")]);
+        assert_eq!(error.offset, Some(20));
+        assert!(skipped.iter().any(
+            |(location, e)| location.ends_with("broken.jar") && matches!(e, LoadError::Zip(_))
+        ));
+    }
 }
@@ -1,9 +1,6 @@
-use std::{collections::HashMap, hash::Hash};
+use std::collections::HashMap;
 
-use crate::{
-    jvm::class::{attribute::*, constant::*, Class},
-    utils::code2name,
-};
+use crate::jvm::class::{attribute::*, constant::*, instruction::Instruction, Class};
 
 pub struct Thread {
     pub pc: usize,
@@ -13,43 +10,59 @@ pub struct Thread {
 
 impl Thread {
     pub fn new(class_map: HashMap<String, Class>) -> Thread {
-        return Thread {
+        Thread {
             pc: 0,
             class_map,
             stacks: Vec::new(),
-        };
+        }
     }
 
     pub fn invoke_from_method_name(&mut self, class_name: String, method_name: String) {
+        self.invoke_method(class_name, method_name, None)
+    }
+
+    // Pushes a frame for the method, passing as many operand stack slots of the caller as the
+    // descriptor (plus `this` for instance methods) requires.
+    pub fn invoke_method(
+        &mut self,
+        class_name: String,
+        method_name: String,
+        descriptor: Option<String>,
+    ) {
         println!(">>> Load {}.{}", class_name, method_name);
         let class_map = &self.class_map;
-        let class = class_map.get(&class_name.replace(".", "/")).unwrap();
+        let class = class_map.get(&class_name.replace('.', "/")).unwrap();
 
-        for mi in 0..class.methods.len() {
-            if method_name == class.methods[mi].name {
-                let method = &class.methods[mi];
-                for ai in 0..method.attributes.len() {
-                    match &method.attributes[ai] {
-                        Attribute::Code(a) => {
-                            let mut f = Frame {
-                                class_name: String::from(&class.this_class),
-                                pc: 0,
-                                code: a.code.clone(),
-                                local_variables: Vec::new(),
-                                operand_stacks: Vec::new(),
-                            };
-                            match self.stacks.last_mut() {
-                                Some(frame) => {
-                                    f.local_variables.append(&mut frame.operand_stacks);
-                                }
-                                None => {}
-                            }
-
-                            self.stacks.push(f);
-                            return;
-                        }
-                        _ => continue,
+        for method in &class.methods {
+            if method_name != method.name
+                || descriptor.as_ref().is_some_and(|d| *d != method.descriptor)
+            {
+                continue;
+            }
+            let method_descriptor = method
+                .method_descriptor()
+                .unwrap_or_else(|e| panic!("{}.{}: {}", class_name, method_name, e));
+            let mut argument_slots = method_descriptor.parameter_slots() as usize;
+            if !method.access_flags.is_static() {
+                // this
+                argument_slots += 1;
+            }
+            for attribute in &method.attributes {
+                if let Attribute::Code(a) = attribute {
+                    let mut f = Frame {
+                        class_name: class.this_class.name.clone(),
+                        pc: 0,
+                        code: a.code.clone(),
+                        local_variables: Vec::new(),
+                        operand_stacks: Vec::new(),
+                    };
+                    if let Some(frame) = self.stacks.last_mut() {
+                        let at = frame.operand_stacks.len().saturating_sub(argument_slots);
+                        f.local_variables = frame.operand_stacks.split_off(at);
                     }
+
+                    self.stacks.push(f);
+                    return;
                 }
             }
         }
@@ -58,14 +71,14 @@ impl Thread {
 
     pub fn pop_stacks(&mut self) -> Frame {
         match self.stacks.pop() {
-            Some(it) => return it,
+            Some(it) => it,
             None => panic!("jvm stack is empty"),
-        };
+        }
     }
 
-    pub fn run(&mut self, args: Vec<String>) {
+    pub fn run(&mut self, _args: Vec<String>) {
         println!(">>> BEGIN <<<");
-        while self.stacks.len() != 0 {
+        while !self.stacks.is_empty() {
             let top_frame = self.stacks.last_mut().unwrap();
             if top_frame.pc == top_frame.code.len() {
                 self.pop_stacks();
@@ -78,73 +91,64 @@ impl Thread {
 
     pub fn exec(&mut self) {
         let top_frame = self.stacks.last_mut().unwrap();
-        let code = top_frame.read_code();
+        let (instruction, length) = Instruction::decode(&top_frame.code, top_frame.pc)
+            .unwrap_or_else(|e| panic!("{}: {}", top_frame.class_name, e));
+        top_frame.pc += length;
         println!(
-            ">>> [{code:0>3}]@{} {}",
+            ">>> [{:0>3}]@{} {}",
+            instruction.opcode(),
             top_frame.class_name,
-            code2name(code)
+            instruction.name()
         );
-        match code {
-            000_u8 => { /* do nothing */ }
-            001_u8 => {
+        match instruction {
+            Instruction::Nop => { /* do nothing */ }
+            Instruction::AconstNull => {
                 // push null
                 top_frame.operand_stacks.push(Option::None);
             }
-            002_u8 => {
-                // iconst_m1
+            Instruction::IconstM1 => {
                 let value = -1_i32 as u32;
                 top_frame.operand_stacks.push(Some(value));
             }
-            003_u8 => {
-                // iconst_0
+            Instruction::Iconst0 => {
                 let value = 0_i32 as u32;
                 top_frame.operand_stacks.push(Some(value));
             }
-            004_u8 => {
-                // iconst_1
+            Instruction::Iconst1 => {
                 let value = 1_i32 as u32;
                 top_frame.operand_stacks.push(Some(value));
             }
-            005_u8 => {
-                // iconst_2
+            Instruction::Iconst2 => {
                 let value = 2_i32 as u32;
                 top_frame.operand_stacks.push(Some(value));
             }
-            006_u8 => {
-                // iconst_3
+            Instruction::Iconst3 => {
                 let value = 3_i32 as u32;
                 top_frame.operand_stacks.push(Some(value));
             }
-            007_u8 => {
-                // iconst_4
+            Instruction::Iconst4 => {
                 let value = 4_i32 as u32;
                 top_frame.operand_stacks.push(Some(value));
             }
-            008_u8 => {
-                // iconst_5
+            Instruction::Iconst5 => {
                 let value = 5_i32 as u32;
                 top_frame.operand_stacks.push(Some(value));
             }
-            016_u8 => {
-                // bipush
-                let value: u32 = top_frame.read_code() as i8 as u32;
-                top_frame.operand_stacks.push(Some(value));
+            Instruction::Bipush(value) => {
+                top_frame.operand_stacks.push(Some(value as i32 as u32));
                 println!("self.operand_stacks: {:?}", top_frame.operand_stacks);
             }
-            017_u8 => {
-                // sipush
-                let value: u32 =
-                    ((top_frame.read_code() as u32) << 8) | top_frame.read_code() as u32;
-                top_frame.operand_stacks.push(Some(value));
+            Instruction::Sipush(value) => {
+                top_frame.operand_stacks.push(Some(value as i32 as u32));
             }
-            018_u8 => {
-                // ldc
-                let const_pool_index = top_frame.read_code() as usize;
-                let constant = &self
+            Instruction::Ldc(const_pool_index) => {
+                let constant = self
                     .class_map
                     .get(&top_frame.class_name)
                     .unwrap()
-                    .constant_pool[const_pool_index - 1];
+                    .constant_pool
+                    .get(const_pool_index)
+                    .unwrap_or_else(|e| panic!("ldc: {}", e));
                 match constant {
                     Constant::Integer(i) => {
                         top_frame.operand_stacks.push(Some(i.bytes as u32));
@@ -152,35 +156,34 @@ impl Thread {
                     _ => {
                         panic!(
                             "unsupported constant, const_pool_index:{}, {:?}",
-                            const_pool_index - 1,
-                            constant
+                            const_pool_index, constant
                         )
                     }
                 }
             }
-            026_u8 => {
+            Instruction::Iload0 => {
                 top_frame.operand_stacks.push(top_frame.local_variables[0]);
                 println!("self.operand_stacks: {:?}", top_frame.operand_stacks);
             }
-            027_u8 => {
+            Instruction::Iload1 => {
                 top_frame.operand_stacks.push(top_frame.local_variables[1]);
                 println!("self.operand_stacks: {:?}", top_frame.operand_stacks);
             }
-            028_u8 => {
+            Instruction::Iload2 => {
                 top_frame.operand_stacks.push(top_frame.local_variables[2]);
                 println!("self.operand_stacks: {:?}", top_frame.operand_stacks);
             }
-            087_u8 => {
+            Instruction::Pop => {
                 top_frame.operand_stacks.pop();
             }
-            096_u8 => {
+            Instruction::Iadd => {
                 let value1 = top_frame.operand_stacks.pop().unwrap().unwrap();
                 let value2 = top_frame.operand_stacks.pop().unwrap().unwrap();
-                let result = value1 + value2;
+                let result = value1.wrapping_add(value2);
                 top_frame.operand_stacks.push(Some(result));
                 println!("self.operand_stacks: {:?}", top_frame.operand_stacks);
             }
-            172_u8 => {
+            Instruction::Ireturn => {
                 let return_value = top_frame.operand_stacks.pop().unwrap();
                 self.stacks.pop();
                 self.stacks
@@ -189,88 +192,24 @@ impl Thread {
                     .operand_stacks
                     .push(return_value);
             }
-            177_u8 => {
+            Instruction::Return => {
                 // return void
             }
-            184_u8 => {
-                let static_method_index =
-                    (((top_frame.read_code() as u32) << 8) | top_frame.read_code() as u32) as usize;
-                let constant = &self
+            Instruction::Invokestatic(static_method_index) => {
+                let method_ref = self
                     .class_map
                     .get(&top_frame.class_name)
                     .unwrap()
-                    .constant_pool[static_method_index - 1];
-                match constant {
-                    Constant::Methodref(m) => {
-                        let mut class_name: String;
-                        let mut name: String;
-                        let mut descriptor: String;
-                        match &self
-                            .class_map
-                            .get(&top_frame.class_name)
-                            .unwrap()
-                            .constant_pool[m.class_index as usize - 1]
-                        {
-                            Constant::Class(c) => {
-                                match &self
-                                    .class_map
-                                    .get(&top_frame.class_name)
-                                    .unwrap()
-                                    .constant_pool[c.name_index as usize - 1]
-                                {
-                                    Constant::Utf8(u) => {
-                                        class_name = String::from(&u.bytes);
-                                    }
-                                    _ => panic!("Wrong index."),
-                                }
-                            }
-                            _ => panic!("Wrong index."),
-                        }
-                        match &self
-                            .class_map
-                            .get(&top_frame.class_name)
-                            .unwrap()
-                            .constant_pool[m.name_and_type_index as usize - 1]
-                        {
-                            Constant::NameAndType(nt) => {
-                                match &self
-                                    .class_map
-                                    .get(&top_frame.class_name)
-                                    .unwrap()
-                                    .constant_pool[nt.name_index as usize - 1]
-                                {
-                                    Constant::Utf8(u) => {
-                                        name = String::from(&u.bytes);
-                                    }
-                                    _ => panic!("Wrong index."),
-                                }
-                                match &self
-                                    .class_map
-                                    .get(&top_frame.class_name)
-                                    .unwrap()
-                                    .constant_pool
-                                    [nt.descriptor_index as usize - 1]
-                                {
-                                    Constant::Utf8(u) => {
-                                        descriptor = String::from(&u.bytes);
-                                    }
-                                    _ => panic!("Wrong index."),
-                                }
-                            }
-                            _ => panic!("Wrong index."),
-                        }
-                        self.invoke_from_method_name(class_name, name);
-                    }
-                    _ => {
-                        panic!(
-                            "unsupported static method, const_pool_index:{}, {:?}",
-                            static_method_index - 1,
-                            constant
-                        )
-                    }
-                }
-            }
-            _ => panic!("unsupported code: {}", code),
+                    .constant_pool
+                    .member_ref(static_method_index)
+                    .unwrap_or_else(|e| panic!("unsupported static method: {}", e));
+                self.invoke_method(
+                    method_ref.class_name,
+                    method_ref.name,
+                    Some(method_ref.descriptor),
+                );
+            }
+            _ => panic!("unsupported instruction: {:?}", instruction),
         }
     }
 }
@@ -282,13 +221,3 @@ pub struct Frame {
     pub local_variables: Vec<Option<u32>>,
     pub operand_stacks: Vec<Option<u32>>,
 }
-
-impl Frame {
-    fn read_code(&mut self) -> u8 {
-        if self.pc >= self.code.len() {
-            panic!("end of code, read code failed.")
-        }
-        self.pc += 1;
-        self.code[self.pc - 1]
-    }
-}
@@ -0,0 +1,3 @@
+pub mod jvm;
+pub mod tools;
+pub mod utils;
@@ -1,19 +1,29 @@
-mod jvm;
-mod utils;
-
-use clap::{Parser, Subcommand};
-use jvm::run_time::*;
+use clap::{Parser, Subcommand, ValueEnum};
 use std::{collections::HashMap, fs, io::BufReader};
 
-use crate::jvm::class_loader::ClassLoader;
-use crate::utils::parse_manifest;
+use sth_like_jvm::jvm::class::version::VersionSupport;
+use sth_like_jvm::jvm::class_loader::ClassLoader;
+use sth_like_jvm::jvm::run_time::*;
+use sth_like_jvm::tools::{
+    asm::assemble_file,
+    compat::{check_targets, Severity},
+    diff::diff_targets,
+    disasm::Disassembly,
+    dump::class_to_json,
+    read_class,
+};
+use sth_like_jvm::utils::parse_manifest;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
+    /// Directories and jars to load classes from, separated by ';'.
     #[arg(long, default_value = ".")]
     classpath: String,
+    /// Allow loading class files that depend on preview features.
+    #[arg(long)]
+    enable_preview: bool,
     // debug: bool,
     #[command(subcommand)]
     command: Option<Commands>,
@@ -21,25 +31,84 @@ struct Cli {
 
 #[derive(Debug, Subcommand)]
 enum Commands {
-    Jar {
-        path: String,
-        args: Vec<String>,
-    },
+    /// Run the Main-Class of a jar.
+    Jar { path: String, args: Vec<String> },
+    /// Run the main method of a class on the classpath.
     Run {
         class_name: String,
         args: Vec<String>,
     },
+    /// Print a javap -c -v like listing of a .class file, a jar entry ("app.jar!/a/B.class")
+    /// or a class on the classpath.
+    Disasm { target: String },
+    /// Print a class, like disasm takes it, as a javap style listing or as JSON.
+    Dump {
+        target: String,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Compare two classes or two jars: added, removed and changed classes and members, and an
+    /// instruction level diff of changed method bodies. Like diff(1) it exits with 1 when they
+    /// differ and 2 on errors.
+    Diff { old: String, new: String },
+    /// Check a new version of a jar against the binary compatibility rules of JLS chapter 13.
+    /// Exits with 1 when a problem of at least the --fail-on severity is found, to gate
+    /// releases.
+    Compat {
+        old: String,
+        new: String,
+        #[arg(long, value_enum, default_value_t = FailOn::Error)]
+        fail_on: FailOn,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Assemble a Jasmin-like source file into <output-dir>/<class name>.class.
+    Asm {
+        source: String,
+        #[arg(short = 'd', long, default_value = ".")]
+        output_dir: String,
+    },
 }
 
-fn main() {
-    let cli = Cli::parse();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FailOn {
+    Error,
+    Warning,
+}
+
+fn version_support(cli: &Cli) -> VersionSupport {
+    VersionSupport {
+        enable_preview: cli.enable_preview,
+        ..VersionSupport::default()
+    }
+}
+
+fn start(cli: &Cli) -> ClassLoader {
     println!("########## start ##########");
+    load_class_path(cli)
+}
 
+fn load_class_path(cli: &Cli) -> ClassLoader {
     let mut class_loader = ClassLoader::new();
-    class_loader.load_from_path(cli.classpath);
+    class_loader.version_support = version_support(cli);
+    for (path, error) in class_loader.load_from_path(cli.classpath.clone()) {
+        eprintln!("skip {}: {}", path, error);
+    }
+    class_loader
+}
+
+fn main() {
+    let cli = Cli::parse();
 
     match &cli.command {
         Some(Commands::Jar { path, args }) => {
+            let class_loader = start(&cli);
             // class_loader.load_from_path(path.to_string());
             let jar_file = fs::File::open(path).unwrap();
             let mut jar_archive = zip::ZipArchive::new(BufReader::new(jar_file)).unwrap();
@@ -48,22 +117,96 @@ fn main() {
             let main_class_name = manifest.get("Main-Class").unwrap();
             println!("Main-Class: {}", main_class_name);
 
-            class_loader
-                .class_map
-                .get(&main_class_name.replace(".", "/"))
-                .unwrap()
-                .show_info();
             let mut thread = Thread::new(class_loader.class_map);
 
             thread.invoke_from_method_name(String::from(main_class_name), String::from("main"));
             thread.run(args.to_vec());
         }
         Some(Commands::Run { class_name, args }) => {
+            let class_loader = start(&cli);
             let mut thread = Thread::new(class_loader.class_map);
 
             thread.invoke_from_method_name(String::from(class_name), String::from("main"));
             thread.run(args.to_vec());
         }
-        None => {}
+        Some(Commands::Disasm { target }) => {
+            match read_class(target, &cli.classpath, version_support(&cli)) {
+                Ok(class) => print!("{}", Disassembly::new(&class)),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Dump { target, format }) => {
+            match read_class(target, &cli.classpath, version_support(&cli)) {
+                Ok(class) => match format {
+                    OutputFormat::Text => print!("{}", Disassembly::new(&class)),
+                    OutputFormat::Json => match class_to_json(&class) {
+                        Ok(json) => println!("{}", json.pretty()),
+                        Err(e) => {
+                            eprintln!("error: {}", e);
+                            std::process::exit(1);
+                        }
+                    },
+                },
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Diff { old, new }) => {
+            match diff_targets(old, new, &cli.classpath, version_support(&cli)) {
+                Ok(diff) => {
+                    print!("{}", diff);
+                    if !diff.is_empty() {
+                        std::process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Some(Commands::Compat {
+            old,
+            new,
+            fail_on,
+            format,
+        }) => match check_targets(old, new) {
+            Ok(report) => {
+                match format {
+                    OutputFormat::Text => print!("{}", report),
+                    OutputFormat::Json => println!("{}", report.to_json().pretty()),
+                }
+                let threshold = match fail_on {
+                    FailOn::Error => Severity::Error,
+                    FailOn::Warning => Severity::Warning,
+                };
+                if report.worst() >= Some(threshold) {
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(2);
+            }
+        },
+        Some(Commands::Asm { source, output_dir }) => {
+            // The classpath answers the common superclasses of the stack map frames.
+            let class_loader = load_class_path(&cli);
+            match assemble_file(source, output_dir, &class_loader) {
+                Ok(path) => println!("{}", path.display()),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => {
+            start(&cli);
+        }
     }
 }
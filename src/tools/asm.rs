@@ -0,0 +1,1145 @@
+use std::{
+    collections::HashMap,
+    fs,
+    ops::BitOr,
+    path::{Path, PathBuf},
+};
+
+use crate::jvm::class::{
+    access_flags::*,
+    attribute::*,
+    class_builder::ClassBuilder,
+    code_builder::{CodeBuilder, Label},
+    constant_pool::{ClassRef, LoadableConstant},
+    descriptor::is_class_constant_name,
+    error::ClassFormatError,
+    instruction::{opcode_by_name, ArrayType, Instruction},
+    stack_map::SuperclassResolver,
+    version::ClassVersion,
+    Class,
+};
+
+// Assembles a Jasmin-like text description of a class:
+//
+//     .class public Add
+//     .super java/lang/Object
+//
+//     .method public static add(II)I
+//         iload_0
+//         iload_1
+//         iadd
+//         ireturn
+//     .end method
+//
+// Class directives are .class or .interface <flags> <name>, .super <name>, .implements <name>,
+// .version <major> [minor], .source <file>, .field <flags> <name> <descriptor> [= <value>] and
+// .method <flags> <name><descriptor> ... .end method. A method body holds `Label:`s,
+// instructions, .limit stack|locals <n>, .catch <class>|all from <label> to <label> using
+// <label>, .line <n> and .throws <class>. Fields are referred to as `class/name descriptor`,
+// methods as `class/name(parameters)return`; strings are double quoted, with \uXXXX escaping
+// one UTF-16 code unit so unpaired surrogates can be written, and a ';' at the start
+// of a token begins a comment. max_stack and max_locals are computed unless .limit sets them.
+// From version 50 on, `resolver` answers the common superclasses for the stack map frames.
+pub fn assemble(
+    source: &str,
+    resolver: &dyn SuperclassResolver,
+) -> Result<Class, ClassFormatError> {
+    let mut lines: Vec<Line> = Vec::new();
+    for (i, text) in source.lines().enumerate() {
+        let tokens = tokenize(text).map_err(|e| e.within(format!("line {}", i + 1)))?;
+        if !tokens.is_empty() {
+            lines.push(Line {
+                number: i + 1,
+                tokens,
+            });
+        }
+    }
+
+    let mut declarations = lines
+        .iter()
+        .filter(|line| matches!(line.first(), ".class" | ".interface"));
+    let declaration = declarations
+        .next()
+        .ok_or_else(|| ClassFormatError::new("missing .class or .interface directive"))?;
+    if let Some(line) = declarations.next() {
+        return Err(line.error("a second class declaration"));
+    }
+    let mut supers = lines.iter().filter(|line| line.first() == ".super");
+    let super_line = supers.next();
+    if let Some(line) = supers.next() {
+        return Err(line.error("a second .super directive"));
+    }
+
+    let words = declaration.words()?;
+    let this_class = match words.last() {
+        Some(name) if words.len() > 1 => *name,
+        _ => return Err(declaration.error("expected a class name")),
+    };
+    let mut access_flags = parse_flags(declaration, &words[1..words.len() - 1], CLASS_FLAGS)?;
+    if declaration.first() == ".interface" {
+        access_flags = access_flags | ClassAccessFlags::INTERFACE | ClassAccessFlags::ABSTRACT;
+    } else {
+        // As javac sets it on every class.
+        access_flags = access_flags | ClassAccessFlags::SUPER;
+    }
+    let super_class = match super_line {
+        Some(line) => Some(line.operands(1)?[0]),
+        None if this_class == "java/lang/Object" => None,
+        None => Some("java/lang/Object"),
+    };
+    let mut builder = ClassBuilder::new(access_flags, this_class, super_class)
+        .map_err(|e| e.within(declaration.location()))?;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        i += 1;
+        match line.first() {
+            ".class" | ".interface" | ".super" => {}
+            ".version" => {
+                let words = line.words()?;
+                let (major, minor) = match words[1..] {
+                    [major] => (major, "0"),
+                    [major, minor] => (major, minor),
+                    _ => return Err(line.error("expected .version <major> [minor]")),
+                };
+                builder.version(ClassVersion::new(
+                    parse_number(line, major)?,
+                    parse_number(line, minor)?,
+                ));
+            }
+            ".source" => {
+                let sourcefile = match &line.tokens[1..] {
+                    [Token::Word(s)] => s.clone(),
+                    [Token::Str(s)] => String::from_utf16(s)
+                        .map_err(|_| line.error("unpaired surrogate in the .source file name"))?,
+                    _ => return Err(line.error("expected .source <file>")),
+                };
+                builder.attribute(Attribute::SourceFile(AttributeSourceFile {
+                    sourcefile: sourcefile.into(),
+                }));
+            }
+            ".implements" => builder
+                .interface(line.operands(1)?[0])
+                .map_err(|e| e.within(line.location()))?,
+            ".field" => assemble_field(&mut builder, line)?,
+            ".method" => {
+                let end = lines[i..]
+                    .iter()
+                    .position(|l| l.first() == ".end")
+                    .ok_or_else(|| line.error("missing .end method"))?;
+                let body: Vec<&Line> = lines[i..i + end].iter().collect();
+                let end_line = &lines[i + end];
+                if end_line.words()? != [".end", "method"] {
+                    return Err(end_line.error("expected .end method"));
+                }
+                assemble_method(&mut builder, line, &body)?;
+                i += end + 1;
+            }
+            _ => return Err(line.error(format!("unexpected {} outside a method", line.first()))),
+        }
+    }
+    builder.build_with(resolver)
+}
+
+// Assembles the file at `source_path` into <output_dir>/<this_class>.class, creating the
+// package directories, and returns the path written.
+pub fn assemble_file(
+    source_path: &str,
+    output_dir: &str,
+    resolver: &dyn SuperclassResolver,
+) -> Result<PathBuf, String> {
+    let source = fs::read_to_string(source_path).map_err(|e| format!("{}: {}", source_path, e))?;
+    let class = assemble(&source, resolver).map_err(|e| format!("{}: {}", source_path, e))?;
+    let bytes = class
+        .to_bytes()
+        .map_err(|e| format!("{}: {}", source_path, e))?;
+    let path = Path::new(output_dir).join(format!("{}.class", class.this_class));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    fs::write(&path, bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+const CLASS_FLAGS: &[(&str, ClassAccessFlags)] = &[
+    ("public", ClassAccessFlags::PUBLIC),
+    ("final", ClassAccessFlags::FINAL),
+    ("super", ClassAccessFlags::SUPER),
+    ("interface", ClassAccessFlags::INTERFACE),
+    ("abstract", ClassAccessFlags::ABSTRACT),
+    ("synthetic", ClassAccessFlags::SYNTHETIC),
+    ("annotation", ClassAccessFlags::ANNOTATION),
+    ("enum", ClassAccessFlags::ENUM),
+];
+
+const FIELD_FLAGS: &[(&str, FieldAccessFlags)] = &[
+    ("public", FieldAccessFlags::PUBLIC),
+    ("private", FieldAccessFlags::PRIVATE),
+    ("protected", FieldAccessFlags::PROTECTED),
+    ("static", FieldAccessFlags::STATIC),
+    ("final", FieldAccessFlags::FINAL),
+    ("volatile", FieldAccessFlags::VOLATILE),
+    ("transient", FieldAccessFlags::TRANSIENT),
+    ("synthetic", FieldAccessFlags::SYNTHETIC),
+    ("enum", FieldAccessFlags::ENUM),
+];
+
+const METHOD_FLAGS: &[(&str, MethodAccessFlags)] = &[
+    ("public", MethodAccessFlags::PUBLIC),
+    ("private", MethodAccessFlags::PRIVATE),
+    ("protected", MethodAccessFlags::PROTECTED),
+    ("static", MethodAccessFlags::STATIC),
+    ("final", MethodAccessFlags::FINAL),
+    ("synchronized", MethodAccessFlags::SYNCHRONIZED),
+    ("bridge", MethodAccessFlags::BRIDGE),
+    ("varargs", MethodAccessFlags::VARARGS),
+    ("native", MethodAccessFlags::NATIVE),
+    ("abstract", MethodAccessFlags::ABSTRACT),
+    ("strict", MethodAccessFlags::STRICT),
+    ("synthetic", MethodAccessFlags::SYNTHETIC),
+];
+
+fn parse_flags<T>(line: &Line, words: &[&str], table: &[(&str, T)]) -> Result<T, ClassFormatError>
+where
+    T: Copy + Default + BitOr<Output = T>,
+{
+    let mut flags = T::default();
+    for word in words {
+        match table.iter().find(|(name, _)| name == word) {
+            Some((_, flag)) => flags = flags | *flag,
+            None => return Err(line.error(format!("unknown access flag {}", word))),
+        }
+    }
+    Ok(flags)
+}
+
+// .field <flags> <name> <descriptor> [= <value>]
+fn assemble_field(builder: &mut ClassBuilder, line: &Line) -> Result<(), ClassFormatError> {
+    let (declaration, value) = match line.tokens.iter().position(|t| t.is_word("=")) {
+        Some(i) => match &line.tokens[i + 1..] {
+            [value] => (&line.tokens[..i], Some(value)),
+            _ => return Err(line.error("expected one value after =")),
+        },
+        None => (&line.tokens[..], None),
+    };
+    let words = words(line, declaration)?;
+    if words.len() < 3 {
+        return Err(line.error("expected .field <flags> <name> <descriptor>"));
+    }
+    let (name, descriptor) = (words[words.len() - 2], words[words.len() - 1]);
+    let access_flags = parse_flags(line, &words[1..words.len() - 2], FIELD_FLAGS)?;
+    let constantvalue_index = value
+        .map(|value| match value {
+            Token::Str(s) if descriptor == "Ljava/lang/String;" => {
+                builder.constant_pool().string_utf16(s)
+            }
+            _ => {
+                let constant = field_constant(line, descriptor, value)?;
+                builder.constant_pool().loadable(&constant)
+            }
+        })
+        .transpose()
+        .map_err(|e| line.locate(e))?;
+    let field = builder
+        .field(access_flags, name, descriptor)
+        .map_err(|e| e.within(line.location()))?;
+    if let Some(constantvalue_index) = constantvalue_index {
+        field
+            .attributes
+            .push(Attribute::ConstantValue(AttributeConstantValue {
+                constantvalue_index,
+            }));
+    }
+    Ok(())
+}
+
+// The ConstantValue of a field other than a string, typed by its descriptor (JVMS 4.7.2).
+fn field_constant(
+    line: &Line,
+    descriptor: &str,
+    value: &Token,
+) -> Result<LoadableConstant, ClassFormatError> {
+    let word = match value {
+        Token::Word(word) => word.as_str(),
+        Token::Str(_) => return Err(line.error(format!("a string is not a {} value", descriptor))),
+    };
+    Ok(match descriptor {
+        "I" | "S" | "C" | "B" | "Z" => LoadableConstant::Integer(parse_number(line, word)?),
+        "J" => LoadableConstant::Long(parse_number(line, word.trim_end_matches(['L', 'l']))?),
+        "F" => LoadableConstant::Float(parse_float(line, word.trim_end_matches(['F', 'f']))?),
+        "D" => LoadableConstant::Double(parse_float(line, word.trim_end_matches(['D', 'd']))?),
+        _ => {
+            return Err(line.error(format!(
+                "a field of type {} cannot have a constant value",
+                descriptor
+            )))
+        }
+    })
+}
+
+// .method <flags> <name><descriptor>, the body up to .end method
+fn assemble_method(
+    builder: &mut ClassBuilder,
+    header: &Line,
+    body: &[&Line],
+) -> Result<(), ClassFormatError> {
+    let words = header.words()?;
+    let (name, descriptor) = match words.last() {
+        Some(word) if words.len() > 1 => match word.find('(') {
+            Some(i) => word.split_at(i),
+            None => return Err(header.error("expected <name>(<parameters>)<return type>")),
+        },
+        _ => return Err(header.error("expected .method <flags> <name><descriptor>")),
+    };
+    let access_flags = parse_flags(header, &words[1..words.len() - 1], METHOD_FLAGS)?;
+
+    let mut throws: Vec<String> = Vec::new();
+    let mut max_stack: Option<u16> = None;
+    let mut max_locals: Option<u16> = None;
+    let mut code_lines: Vec<&Line> = Vec::new();
+    for line in body {
+        match line.first() {
+            ".throws" => throws.push(String::from(line.operands(1)?[0])),
+            ".limit" => match line.operands(2)?[..] {
+                ["stack", n] => max_stack = Some(parse_number(line, n)?),
+                ["locals", n] => max_locals = Some(parse_number(line, n)?),
+                _ => return Err(line.error("expected .limit stack|locals <n>")),
+            },
+            _ => code_lines.push(line),
+        }
+    }
+
+    let method = if code_lines.is_empty() {
+        builder.method(access_flags, name, descriptor)
+    } else {
+        builder.method_with_code(access_flags, name, descriptor, |code| {
+            MethodAssembler::new(code).assemble(&code_lines)
+        })
+    }
+    .map_err(|e| header.locate(e))?;
+
+    let code = method.attributes.iter_mut().find_map(|a| match a {
+        Attribute::Code(code) => Some(code),
+        _ => None,
+    });
+    match code {
+        Some(code) => {
+            code.max_stack = max_stack.unwrap_or(code.max_stack);
+            code.max_locals = max_locals.unwrap_or(code.max_locals);
+        }
+        None if max_stack.is_some() || max_locals.is_some() => {
+            return Err(header.error(".limit in a method without code"))
+        }
+        None => {}
+    }
+    if !throws.is_empty() {
+        method
+            .attributes
+            .push(Attribute::Exceptions(AttributeExceptions {
+                exception_index_table: throws.into_iter().map(ClassRef::new).collect(),
+            }));
+    }
+    Ok(())
+}
+
+struct MethodAssembler<'a, 'b> {
+    code: &'a mut CodeBuilder<'b>,
+    // The label of each name, with the line that first mentions it and whether it is bound.
+    labels: HashMap<String, (Label, usize, bool)>,
+    line_numbers: Vec<Vec<u16>>,
+}
+
+impl<'a, 'b> MethodAssembler<'a, 'b> {
+    fn new(code: &'a mut CodeBuilder<'b>) -> MethodAssembler<'a, 'b> {
+        MethodAssembler {
+            code,
+            labels: HashMap::new(),
+            line_numbers: Vec::new(),
+        }
+    }
+
+    fn label(&mut self, line: &Line, name: &str) -> Label {
+        let code = &mut self.code;
+        self.labels
+            .entry(String::from(name))
+            .or_insert_with(|| (code.new_label(), line.number, false))
+            .0
+    }
+
+    fn bind(&mut self, line: &Line, name: &str) -> Result<(), ClassFormatError> {
+        let label = self.label(line, name);
+        let entry = self.labels.get_mut(name).unwrap();
+        if entry.2 {
+            return Err(line.error(format!("label {} is defined twice", name)));
+        }
+        entry.2 = true;
+        self.code.bind(label)
+    }
+
+    fn assemble(mut self, lines: &[&Line]) -> Result<(), ClassFormatError> {
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            i += 1;
+            let mut tokens = &line.tokens[..];
+            if let Some(Token::Word(word)) = tokens.first() {
+                if word.len() > 1 && word.ends_with(':') {
+                    self.bind(line, &word[..word.len() - 1])?;
+                    tokens = &tokens[1..];
+                }
+            }
+            let Some(first) = tokens.first() else {
+                continue;
+            };
+            let mnemonic = match first {
+                Token::Word(word) => word.as_str(),
+                Token::Str(_) => return Err(line.error("expected an instruction")),
+            };
+            let operands = &tokens[1..];
+            match mnemonic {
+                ".catch" => self.catch(line, operands)?,
+                ".line" => {
+                    let number = parse_number(line, words(line, operands)?.first().unwrap_or(&""))?;
+                    self.line_numbers.push(vec![self.code.pc() as u16, number]);
+                }
+                "tableswitch" | "lookupswitch" => {
+                    // The cases follow on their own lines, up to and including the default.
+                    let end = lines[i..]
+                        .iter()
+                        .position(|l| l.first() == "default" || l.first().starts_with("default:"))
+                        .ok_or_else(|| line.error(format!("{} without a default", mnemonic)))?;
+                    self.switch(line, mnemonic, operands, &lines[i..=i + end])?;
+                    i += end + 1;
+                }
+                _ => self
+                    .instruction(line, mnemonic, operands)
+                    .map_err(|e| line.locate(e))?,
+            }
+        }
+
+        let mut unbound: Vec<(&String, usize)> = self
+            .labels
+            .iter()
+            .filter(|(_, (_, _, bound))| !bound)
+            .map(|(name, (_, first_line, _))| (name, *first_line))
+            .collect();
+        unbound.sort_by_key(|(_, first_line)| *first_line);
+        if let Some((name, first_line)) = unbound.first() {
+            return Err(ClassFormatError::new(format!("undefined label {}", name))
+                .within(format!("line {}", first_line)));
+        }
+        if !self.line_numbers.is_empty() {
+            self.code
+                .attribute(Attribute::LineNumberTable(AttributeLineNumberTable {
+                    line_number_table: self.line_numbers,
+                }));
+        }
+        Ok(())
+    }
+
+    // .catch <class>|all from <label> to <label> using <label>
+    fn catch(&mut self, line: &Line, operands: &[Token]) -> Result<(), ClassFormatError> {
+        let (catch_type, start, end, handler) = match words(line, operands)?[..] {
+            [catch_type, "from", start, "to", end, "using", handler] => {
+                (catch_type, start, end, handler)
+            }
+            _ => {
+                return Err(
+                    line.error("expected .catch <class>|all from <label> to <label> using <label>")
+                )
+            }
+        };
+        let catch_type = if catch_type == "all" {
+            None
+        } else {
+            Some(catch_type)
+        };
+        let (start, end, handler) = (
+            self.label(line, start),
+            self.label(line, end),
+            self.label(line, handler),
+        );
+        self.code
+            .try_catch(start, end, handler, catch_type)
+            .map_err(|e| line.locate(e))
+    }
+
+    // tableswitch <low> [high] followed by a target label per line, or lookupswitch followed by
+    // `<key> : <label>` lines; both end with `default : <label>`.
+    fn switch(
+        &mut self,
+        line: &Line,
+        mnemonic: &str,
+        operands: &[Token],
+        cases: &[&Line],
+    ) -> Result<(), ClassFormatError> {
+        let mut targets: Vec<Label> = Vec::new();
+        let mut pairs: Vec<(i32, Label)> = Vec::new();
+        let mut default: Option<Label> = None;
+        for case in cases {
+            let text = case.words()?.join(" ");
+            match text.split_once(':') {
+                Some((key, target)) => {
+                    let target = self.label(case, target.trim());
+                    if key.trim() == "default" {
+                        default = Some(target);
+                    } else if mnemonic == "lookupswitch" {
+                        pairs.push((parse_number(case, key.trim())?, target));
+                    } else {
+                        return Err(case.error("expected a label or default : <label>"));
+                    }
+                }
+                None if mnemonic == "tableswitch" && !text.contains(' ') => {
+                    targets.push(self.label(case, &text))
+                }
+                None => return Err(case.error("expected <key> : <label> or default : <label>")),
+            }
+        }
+        let default = default.ok_or_else(|| line.error("missing default"))?;
+        let result = if mnemonic == "tableswitch" {
+            let (low, high) = match words(line, operands)?[..] {
+                [low] => (parse_number::<i32>(line, low)?, None),
+                [low, high] => (
+                    parse_number::<i32>(line, low)?,
+                    Some(parse_number::<i32>(line, high)?),
+                ),
+                _ => return Err(line.error("expected tableswitch <low> [high]")),
+            };
+            if let Some(high) = high {
+                if high as i64 - low as i64 + 1 != targets.len() as i64 {
+                    return Err(line.error(format!(
+                        "{} targets for keys {} to {}",
+                        targets.len(),
+                        low,
+                        high
+                    )));
+                }
+            }
+            self.code.tableswitch(low, default, &targets)
+        } else {
+            if !operands.is_empty() {
+                return Err(line.error("lookupswitch takes no operands on its line"));
+            }
+            self.code.lookupswitch(default, &pairs)
+        };
+        result.map_err(|e| line.locate(e))
+    }
+
+    fn instruction(
+        &mut self,
+        line: &Line,
+        mnemonic: &str,
+        operands: &[Token],
+    ) -> Result<(), ClassFormatError> {
+        let opcode = opcode_by_name(mnemonic)
+            .ok_or_else(|| ClassFormatError::new(format!("unknown instruction {}", mnemonic)))?;
+        // Instructions without operands decode from their opcode alone.
+        if let Ok((instruction, 1)) = Instruction::decode(&[opcode], 0) {
+            line.operands_of(operands, 0)?;
+            return self.code.emit(instruction);
+        }
+        match opcode {
+            0x10 => {
+                let value = parse_number(line, line.operands_of(operands, 1)?[0])?;
+                self.code.emit(Instruction::Bipush(value))
+            }
+            0x11 => {
+                let value = parse_number(line, line.operands_of(operands, 1)?[0])?;
+                self.code.emit(Instruction::Sipush(value))
+            }
+            0x12..=0x14 => {
+                let [operand] = operands else {
+                    return Err(ClassFormatError::new(format!(
+                        "{} takes 1 operand",
+                        mnemonic
+                    )));
+                };
+                let index = match operand {
+                    Token::Str(_) if opcode == 0x14 => {
+                        return Err(ClassFormatError::new("ldc2_w cannot load a string"))
+                    }
+                    Token::Str(s) => self.code.constant_pool().string_utf16(s)?,
+                    Token::Word(word) => {
+                        let constant = ldc_constant(line, word, opcode == 0x14)?;
+                        self.code.constant_pool().loadable(&constant)?
+                    }
+                };
+                self.code.emit(match opcode {
+                    0x12 => Instruction::Ldc(index),
+                    0x13 => Instruction::LdcW(index),
+                    _ => Instruction::Ldc2W(index),
+                })
+            }
+            0x15..=0x19 | 0x36..=0x3a | 0xa9 => {
+                let index = parse_number(line, line.operands_of(operands, 1)?[0])?;
+                self.code.emit(local_instruction(opcode, index))
+            }
+            0x84 => {
+                let words = line.operands_of(operands, 2)?;
+                self.code.emit(Instruction::Iinc {
+                    index: parse_number(line, words[0])?,
+                    value: parse_number(line, words[1])?,
+                })
+            }
+            0x99..=0xa8 | 0xc6 | 0xc7 => {
+                let label = self.label(line, line.operands_of(operands, 1)?[0]);
+                self.code.jump(branch_instruction(opcode), label)
+            }
+            0xc8 | 0xc9 => {
+                let label = self.label(line, line.operands_of(operands, 1)?[0]);
+                let branch: fn(i32) -> Instruction = if opcode == 0xc8 {
+                    Instruction::GotoW
+                } else {
+                    Instruction::JsrW
+                };
+                self.code.jump_wide(branch, label)
+            }
+            0xb2..=0xb5 => {
+                let words = line.operands_of(operands, 2)?;
+                let (class_name, name) = member_name(words[0])?;
+                let descriptor = words[1];
+                match opcode {
+                    0xb2 => self.code.getstatic(class_name, name, descriptor),
+                    0xb3 => self.code.putstatic(class_name, name, descriptor),
+                    0xb4 => self.code.getfield(class_name, name, descriptor),
+                    _ => self.code.putfield(class_name, name, descriptor),
+                }
+            }
+            0xb6..=0xb9 => {
+                // The count of invokeinterface is computed, so writing it is optional.
+                let words = words(line, operands)?;
+                let valid = words.len() == 1 || (opcode == 0xb9 && words.len() == 2);
+                if !valid {
+                    return Err(ClassFormatError::new(format!(
+                        "expected {} <class>/<name><descriptor>",
+                        mnemonic
+                    )));
+                }
+                let (member, descriptor) = match words[0].find('(') {
+                    Some(i) => words[0].split_at(i),
+                    None => return Err(ClassFormatError::new("missing method descriptor")),
+                };
+                let (class_name, name) = member_name(member)?;
+                match opcode {
+                    0xb6 => self.code.invokevirtual(class_name, name, descriptor),
+                    0xb7 => self.code.invokespecial(class_name, name, descriptor),
+                    0xb8 => self.code.invokestatic(class_name, name, descriptor),
+                    _ => self.code.invokeinterface(class_name, name, descriptor),
+                }
+            }
+            0xbb | 0xbd | 0xc0 | 0xc1 => {
+                let class_name = line.operands_of(operands, 1)?[0];
+                match opcode {
+                    0xbb => self.code.new_object(class_name),
+                    0xbd => self.code.anewarray(class_name),
+                    0xc0 => self.code.checkcast(class_name),
+                    _ => self.code.instanceof(class_name),
+                }
+            }
+            0xbc => {
+                let name = line.operands_of(operands, 1)?[0];
+                let array_type = (4..=11)
+                    .filter_map(ArrayType::from_u8)
+                    .find(|t| t.name() == name)
+                    .ok_or_else(|| {
+                        ClassFormatError::new(format!("unknown newarray type {}", name))
+                    })?;
+                self.code.emit(Instruction::Newarray(array_type))
+            }
+            0xc5 => {
+                let words = line.operands_of(operands, 2)?;
+                self.code
+                    .multianewarray(words[0], parse_number(line, words[1])?)
+            }
+            0xc4 => Err(ClassFormatError::new(
+                "wide is added by the assembler where an operand needs it",
+            )),
+            _ => Err(ClassFormatError::new(format!(
+                "{} is not supported by the assembler",
+                mnemonic
+            ))),
+        }
+    }
+}
+
+// The constant of an ldc, ldc_w (`wide` false) or ldc2_w written as a word: a number or a
+// class name.
+fn ldc_constant(line: &Line, word: &str, wide: bool) -> Result<LoadableConstant, ClassFormatError> {
+    let numeric = word.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c))
+        || matches!(word, "NaN" | "Infinity");
+    if wide {
+        return Ok(
+            match parse_number(line, word.trim_end_matches(['L', 'l'])) {
+                Ok(value) => LoadableConstant::Long(value),
+                Err(_) => {
+                    LoadableConstant::Double(parse_float(line, word.trim_end_matches(['D', 'd']))?)
+                }
+            },
+        );
+    }
+    if numeric {
+        return Ok(match parse_number(line, word) {
+            Ok(value) => LoadableConstant::Integer(value),
+            Err(_) => {
+                LoadableConstant::Float(parse_float(line, word.trim_end_matches(['F', 'f']))?)
+            }
+        });
+    }
+    if !is_class_constant_name(word) {
+        return Err(ClassFormatError::new(format!("invalid constant {}", word)));
+    }
+    Ok(LoadableConstant::Class(String::from(word)))
+}
+
+// Splits "java/lang/System/out" into the class and the member name.
+fn member_name(word: &str) -> Result<(&str, &str), ClassFormatError> {
+    word.rsplit_once('/')
+        .ok_or_else(|| ClassFormatError::new(format!("expected <class>/<name>, found {}", word)))
+}
+
+fn local_instruction(opcode: u8, index: u16) -> Instruction {
+    match opcode {
+        0x15 => Instruction::Iload(index),
+        0x16 => Instruction::Lload(index),
+        0x17 => Instruction::Fload(index),
+        0x18 => Instruction::Dload(index),
+        0x19 => Instruction::Aload(index),
+        0x36 => Instruction::Istore(index),
+        0x37 => Instruction::Lstore(index),
+        0x38 => Instruction::Fstore(index),
+        0x39 => Instruction::Dstore(index),
+        0x3a => Instruction::Astore(index),
+        0xa9 => Instruction::Ret(index),
+        _ => unreachable!("opcode 0x{:02x} takes no local variable index", opcode),
+    }
+}
+
+fn branch_instruction(opcode: u8) -> fn(i16) -> Instruction {
+    match opcode {
+        0x99 => Instruction::Ifeq,
+        0x9a => Instruction::Ifne,
+        0x9b => Instruction::Iflt,
+        0x9c => Instruction::Ifge,
+        0x9d => Instruction::Ifgt,
+        0x9e => Instruction::Ifle,
+        0x9f => Instruction::IfIcmpeq,
+        0xa0 => Instruction::IfIcmpne,
+        0xa1 => Instruction::IfIcmplt,
+        0xa2 => Instruction::IfIcmpge,
+        0xa3 => Instruction::IfIcmpgt,
+        0xa4 => Instruction::IfIcmple,
+        0xa5 => Instruction::IfAcmpeq,
+        0xa6 => Instruction::IfAcmpne,
+        0xa7 => Instruction::Goto,
+        0xa8 => Instruction::Jsr,
+        0xc6 => Instruction::Ifnull,
+        0xc7 => Instruction::Ifnonnull,
+        _ => unreachable!("opcode 0x{:02x} is not a branch", opcode),
+    }
+}
+
+// A decimal or 0x hexadecimal integer of type T.
+fn parse_number<T: TryFrom<i128>>(line: &Line, word: &str) -> Result<T, ClassFormatError> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => digits.parse::<i128>(),
+    };
+    value
+        .ok()
+        .map(|value| if negative { -value } else { value })
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| line.error(format!("invalid or out of range number {}", word)))
+}
+
+fn parse_float<T: std::str::FromStr>(line: &Line, word: &str) -> Result<T, ClassFormatError> {
+    word.parse::<T>()
+        .map_err(|_| line.error(format!("invalid number {}", word)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(Vec<u16>), // a double quoted string, unescaped into UTF-16 as \u may be a lone surrogate
+}
+
+impl Token {
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self, Token::Word(w) if w == word)
+    }
+}
+
+#[derive(Debug)]
+struct Line {
+    number: usize,
+    tokens: Vec<Token>,
+}
+
+impl Line {
+    fn location(&self) -> String {
+        format!("line {}", self.number)
+    }
+
+    fn error(&self, reason: impl Into<String>) -> ClassFormatError {
+        ClassFormatError::new(reason).within(self.location())
+    }
+
+    // Adds the line to an error unless it already names one, as errors from a method body do.
+    fn locate(&self, e: ClassFormatError) -> ClassFormatError {
+        if e.location.iter().any(|l| l.starts_with("line ")) {
+            e
+        } else {
+            e.within(self.location())
+        }
+    }
+
+    // The directive or mnemonic the line starts with, "" for a string.
+    fn first(&self) -> &str {
+        match &self.tokens[0] {
+            Token::Word(word) => word,
+            Token::Str(_) => "",
+        }
+    }
+
+    fn words(&self) -> Result<Vec<&str>, ClassFormatError> {
+        words(self, &self.tokens)
+    }
+
+    // The n words after the directive.
+    fn operands(&self, n: usize) -> Result<Vec<&str>, ClassFormatError> {
+        self.operands_of(&self.tokens[1..], n)
+            .map_err(|e| self.locate(e))
+    }
+
+    fn operands_of<'t>(
+        &self,
+        operands: &'t [Token],
+        n: usize,
+    ) -> Result<Vec<&'t str>, ClassFormatError> {
+        let words = words(self, operands)?;
+        if words.len() != n {
+            return Err(ClassFormatError::new(format!(
+                "{} takes {} operand{}, found {}",
+                self.first(),
+                n,
+                if n == 1 { "" } else { "s" },
+                words.len()
+            )));
+        }
+        Ok(words)
+    }
+}
+
+fn words<'t>(line: &Line, tokens: &'t [Token]) -> Result<Vec<&'t str>, ClassFormatError> {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Word(word) => Ok(word.as_str()),
+            Token::Str(_) => Err(line.error("unexpected string")),
+        })
+        .collect()
+}
+
+// Splits a line into words and strings. A ';' starts a comment only at the start of a token,
+// since descriptors contain it.
+fn tokenize(text: &str) -> Result<Vec<Token>, ClassFormatError> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek() {
+            None | Some(';') => return Ok(tokens),
+            Some('"') => {
+                chars.next();
+                let mut s: Vec<u16> = Vec::new();
+                loop {
+                    let c = match chars.next() {
+                        None => return Err(ClassFormatError::new("unterminated string")),
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('r') => '\r',
+                            Some('0') => '\0',
+                            Some('"') => '"',
+                            Some('\\') => '\\',
+                            // One UTF-16 code unit as in Java, so a surrogate pair is two escapes
+                            // and an unpaired surrogate is kept as it is.
+                            Some('u') => {
+                                let hex: String = chars.by_ref().take(4).collect();
+                                let unit = u16::from_str_radix(&hex, 16)
+                                    .ok()
+                                    .filter(|_| hex.len() == 4 && !hex.starts_with('+'))
+                                    .ok_or_else(|| {
+                                        ClassFormatError::new(format!("invalid escape \\u{}", hex))
+                                    })?;
+                                s.push(unit);
+                                continue;
+                            }
+                            c => {
+                                return Err(ClassFormatError::new(format!(
+                                    "invalid escape \\{}",
+                                    c.map(String::from).unwrap_or_default()
+                                )))
+                            }
+                        },
+                        Some(c) => c,
+                    };
+                    s.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+                }
+                tokens.push(Token::Str(s));
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        attribute::AttributeCode, constant::Constant, constant_pool::CpIndex, stack_map::NoClasses,
+    };
+
+    // Assembles `body` as the code of `static void m()` in class T.
+    fn assemble_method_body(body: &str) -> Result<Class, ClassFormatError> {
+        assemble(
+            &format!(
+                ".class public T\n.method public static m()V\n{}\n.end method\n",
+                body
+            ),
+            &NoClasses,
+        )
+    }
+
+    fn code(class: &Class) -> &AttributeCode {
+        class.methods[0].code().unwrap()
+    }
+
+    fn instructions(class: &Class) -> Vec<(usize, Instruction)> {
+        Instruction::decode_all(&code(class).code).unwrap()
+    }
+
+    // The UTF-16 code units of the String constant at `index`.
+    fn loaded_string(class: &Class, index: CpIndex) -> Vec<u16> {
+        let pool = &class.constant_pool;
+        match pool.get(index).unwrap() {
+            Constant::String(s) => match pool.get(s.string_index).unwrap() {
+                Constant::Utf8(utf8) => utf8.utf16.clone(),
+                other => panic!("{:?}", other),
+            },
+            other => panic!("{:?}", other),
+        }
+    }
+
+    fn error(source: &str) -> String {
+        assemble(source, &NoClasses).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn unicode_escapes_are_utf16_code_units() {
+        assert_eq!(
+            tokenize(r#"ldc "a\t\u00e9\uD83D\uDE00\uD800" ; comment"#).unwrap(),
+            vec![
+                Token::Word(String::from("ldc")),
+                Token::Str(vec![0x61, 0x09, 0xe9, 0xd83d, 0xde00, 0xd800]),
+            ]
+        );
+        assert_eq!(
+            tokenize(r#""\u00""#).unwrap_err().to_string(),
+            "invalid escape \\u00\""
+        );
+        assert!(tokenize(r#""\u+abc""#).is_err());
+        assert!(tokenize(r#""\q""#).is_err());
+        assert!(tokenize(r#""open"#).is_err());
+
+        let class = assemble_method_body(
+            "    ldc \"\\uD800\"\n    pop\n    ldc \"\\uFFFD\"\n    pop\n    ldc \"\\uD83D\\uDE00\"\n    pop\n    return",
+        )
+        .unwrap();
+        let loaded: Vec<Vec<u16>> = instructions(&class)
+            .into_iter()
+            .filter_map(|(_, instruction)| match instruction {
+                Instruction::Ldc(index) => Some(loaded_string(&class, index)),
+                _ => None,
+            })
+            .collect();
+        // The lone surrogate is not confused with the replacement character it displays as.
+        assert_eq!(
+            loaded,
+            vec![vec![0xd800], vec![0xfffd], vec![0xd83d, 0xde00]]
+        );
+        let bytes = class.to_bytes().unwrap();
+        assert!(bytes.windows(5).any(|w| w == [1, 0, 3, 0xed, 0xa0]));
+    }
+
+    #[test]
+    fn ldc_forms_and_their_constants() {
+        let class = assemble_method_body(
+            "    ldc 1000000
+    ldc_w 2.5
+    ldc2_w 10
+    ldc2_w 1.5
+    ldc java/lang/String
+    ldc \"s\"
+    return",
+        )
+        .unwrap();
+        let loaded: Vec<(usize, &str, LoadableConstant)> = instructions(&class)
+            .into_iter()
+            .filter_map(|(pc, instruction)| {
+                let (name, index) = match instruction {
+                    Instruction::Ldc(index) => ("ldc", index),
+                    Instruction::LdcW(index) => ("ldc_w", index),
+                    Instruction::Ldc2W(index) => ("ldc2_w", index),
+                    _ => return None,
+                };
+                Some((pc, name, class.constant_pool.loadable(index).unwrap()))
+            })
+            .collect();
+        assert_eq!(
+            loaded,
+            vec![
+                (0, "ldc", LoadableConstant::Integer(1000000)),
+                (2, "ldc_w", LoadableConstant::Float(2.5)),
+                (5, "ldc2_w", LoadableConstant::Long(10)),
+                (8, "ldc2_w", LoadableConstant::Double(1.5)),
+                (
+                    11,
+                    "ldc",
+                    LoadableConstant::Class(String::from("java/lang/String"))
+                ),
+                (13, "ldc", LoadableConstant::String(String::from("s"))),
+            ]
+        );
+        assert_eq!(code(&class).max_stack, 8);
+
+        assert_eq!(
+            error(".class T\n.method static m()V\n    ldc2_w \"s\"\n    return\n.end method"),
+            "method m()V > line 3: ldc2_w cannot load a string"
+        );
+    }
+
+    #[test]
+    fn switches_catch_and_wide_locals() {
+        let class = assemble_method_body(
+            "    iconst_1
+    istore 300
+    iinc 300 -1000
+    iload 300
+    tableswitch 0 1
+        zero
+        one
+        default : done
+zero:
+    iconst_2
+    lookupswitch
+        10 : one
+        -1 : done
+        default : done
+one:
+start:
+    aconst_null
+    athrow
+end:
+handler:
+    astore_0
+done:
+    return
+    .catch java/lang/Exception from start to end using handler",
+        )
+        .unwrap();
+        assert_eq!(
+            instructions(&class),
+            vec![
+                (0, Instruction::Iconst1),
+                (1, Instruction::Istore(300)),
+                (
+                    5,
+                    Instruction::Iinc {
+                        index: 300,
+                        value: -1000
+                    }
+                ),
+                (11, Instruction::Iload(300)),
+                (
+                    15,
+                    Instruction::Tableswitch {
+                        default: 52,
+                        low: 0,
+                        high: 1,
+                        offsets: vec![21, 49],
+                    }
+                ),
+                (36, Instruction::Iconst2),
+                (
+                    37,
+                    Instruction::Lookupswitch {
+                        default: 30,
+                        pairs: vec![(-1, 30), (10, 27)],
+                    }
+                ),
+                (64, Instruction::AconstNull),
+                (65, Instruction::Athrow),
+                (66, Instruction::Astore0),
+                (67, Instruction::Return),
+            ]
+        );
+        let code = code(&class);
+        let handler = &code.exception_table[0];
+        assert_eq!(
+            (handler.start_pc, handler.end_pc, handler.handler_pc),
+            (64, 66, 66)
+        );
+        assert_eq!(
+            class.constant_pool.class_name(handler.catch_type).unwrap(),
+            "java/lang/Exception"
+        );
+        assert_eq!((code.max_stack, code.max_locals), (1, 301));
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        let cases = [
+            (".method static m()V\n.end method", "missing .class or .interface directive"),
+            (".class T\n.class U", "line 2: a second class declaration"),
+            (".class T\n\n.field public x I = \"s\"", "line 3: a string is not a I value"),
+            (".class T\n.source \"\\uDC00\"", "line 2: unpaired surrogate in the .source file name"),
+            (".class T\n.method static m()V\n    return", "line 2: missing .end method"),
+            (
+                ".class T\n.method static m()V\n    nop\n    bogus\n.end method",
+                "method m()V > line 4: unknown instruction bogus",
+            ),
+            (
+                ".class T\n.method static m()V\n    goto nowhere\n    return\n.end method",
+                "method m()V > line 3: undefined label nowhere",
+            ),
+            (
+                ".class T\n.method static m()V\n    bipush 200\n    return\n.end method",
+                "method m()V > line 3: invalid or out of range number 200",
+            ),
+            (
+                ".class T\n.method static m()V\nl:\n    nop\nl:\n    return\n.end method",
+                "method m()V > line 5: label l is defined twice",
+            ),
+            (
+                ".class T\n.method static m()V\n    iload 0\n    tableswitch 0 2\n        a\n        default : a\na:\n    return\n.end method",
+                "method m()V > line 4: 1 targets for keys 0 to 2",
+            ),
+            ("\n.class T\n.method static m()V\n    \"s\"\n.end method", "method m()V > line 4: expected an instruction"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(error(source), expected, "{}", source);
+        }
+    }
+}
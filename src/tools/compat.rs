@@ -0,0 +1,934 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
+};
+
+use crate::jvm::class::{
+    access_flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+    Class,
+};
+use crate::tools::{diff::constant_value_text, read_jar};
+use crate::utils::json::Json;
+
+// Checks a new version of a jar against the binary compatibility rules of JLS chapter 13:
+// would binaries compiled against the old version still link and behave the same? Only the
+// API of the old version is checked, i.e. public classes and their public and protected
+// members. Supertypes that are not in the jar are not read, so members inherited from them
+// are only known for java/lang/Object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    // Existing binaries still link but may misbehave, e.g. they keep an inlined constant or
+    // fail with AbstractMethodError when they do not implement a new interface method.
+    Warning,
+    // Existing binaries may fail to link with a LinkageError such as NoSuchMethodError or
+    // IncompatibleClassChangeError.
+    Error,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub severity: Severity,
+    pub rule: &'static str, // e.g. "member-removed"
+    pub jls: Option<&'static str>,
+    pub class: String,
+    pub member: Option<String>, // e.g. "LIMIT:I" for a field, "add(I)J" for a method
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.problems
+            .iter()
+            .filter(|problem| problem.severity == severity)
+            .count()
+    }
+
+    pub fn worst(&self) -> Option<Severity> {
+        self.problems.iter().map(|problem| problem.severity).max()
+    }
+
+    pub fn to_json(&self) -> Json {
+        let problems = self.problems.iter().map(|problem| {
+            Json::object(vec![
+                ("severity", problem.severity.name().into()),
+                ("rule", problem.rule.into()),
+                ("jls", problem.jls.into()),
+                ("class", (&problem.class).into()),
+                ("member", problem.member.clone().into()),
+                ("message", (&problem.message).into()),
+            ])
+        });
+        Json::object(vec![
+            ("errors", self.count(Severity::Error).into()),
+            ("warnings", self.count(Severity::Warning).into()),
+            ("problems", Json::array(problems)),
+        ])
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for problem in &self.problems {
+            write!(f, "{:<8}{}", problem.severity.name(), problem.class)?;
+            if let Some(member) = &problem.member {
+                write!(f, ".{}", member)?;
+            }
+            write!(f, ": {} [{}", problem.message, problem.rule)?;
+            if let Some(jls) = problem.jls {
+                write!(f, ", JLS {}", jls)?;
+            }
+            writeln!(f, "]")?;
+        }
+        writeln!(
+            f,
+            "{} errors, {} warnings",
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        )
+    }
+}
+
+pub fn check_targets(old: &str, new: &str) -> Result<Report, String> {
+    Ok(check_jars(&read_jar(old)?, &read_jar(new)?))
+}
+
+pub fn check_jars(old: &BTreeMap<String, Class>, new: &BTreeMap<String, Class>) -> Report {
+    let mut checker = Checker {
+        old,
+        new,
+        problems: Vec::new(),
+    };
+    for class in old.values() {
+        checker.check_class(class);
+    }
+    Report {
+        problems: checker.problems,
+    }
+}
+
+// The public and protected methods of java/lang/Object, which every class inherits.
+const OBJECT_METHODS: &[(&str, &str)] = &[
+    ("clone", "()Ljava/lang/Object;"),
+    ("equals", "(Ljava/lang/Object;)Z"),
+    ("finalize", "()V"),
+    ("getClass", "()Ljava/lang/Class;"),
+    ("hashCode", "()I"),
+    ("notify", "()V"),
+    ("notifyAll", "()V"),
+    ("toString", "()Ljava/lang/String;"),
+    ("wait", "()V"),
+    ("wait", "(J)V"),
+    ("wait", "(JI)V"),
+];
+
+const PRIVATE: u8 = 0;
+const PACKAGE: u8 = 1;
+const PROTECTED: u8 = 2;
+const PUBLIC: u8 = 3;
+
+fn access_name(access: u8) -> &'static str {
+    match access {
+        PRIVATE => "private",
+        PACKAGE => "package-private",
+        PROTECTED => "protected",
+        _ => "public",
+    }
+}
+
+fn access_level(public: bool, protected: bool, private: bool) -> u8 {
+    if public {
+        PUBLIC
+    } else if protected {
+        PROTECTED
+    } else if private {
+        PRIVATE
+    } else {
+        PACKAGE
+    }
+}
+
+// A field or method with the properties the rules look at.
+struct Member<'a> {
+    is_field: bool,
+    name: &'a str,
+    descriptor: &'a str,
+    access: u8,
+    is_static: bool,
+    is_final: bool,
+    is_abstract: bool,
+    is_synthetic: bool,
+    constant: Option<String>, // the inlined value of a static constant variable
+}
+
+impl Member<'_> {
+    fn kind(&self) -> &'static str {
+        if self.is_field {
+            "field"
+        } else {
+            "method"
+        }
+    }
+
+    fn text(&self) -> String {
+        if self.is_field {
+            format!("{}:{}", self.name, self.descriptor)
+        } else {
+            format!("{}{}", self.name, self.descriptor)
+        }
+    }
+
+    fn is_api(&self) -> bool {
+        self.access >= PROTECTED && !self.is_synthetic && self.name != "<clinit>"
+    }
+}
+
+fn members(class: &Class) -> Vec<Member<'_>> {
+    let fields = class.fields.iter().map(|field| {
+        let flags = field.access_flags;
+        let is_final = flags.contains(FieldAccessFlags::FINAL);
+        let constant = Some(constant_value_text(class, &field.attributes))
+            .filter(|value| flags.is_static() && is_final && !value.is_empty());
+        Member {
+            is_field: true,
+            name: &field.name,
+            descriptor: &field.descriptor,
+            access: access_level(
+                flags.contains(FieldAccessFlags::PUBLIC),
+                flags.contains(FieldAccessFlags::PROTECTED),
+                flags.contains(FieldAccessFlags::PRIVATE),
+            ),
+            is_static: flags.is_static(),
+            is_final,
+            is_abstract: false,
+            is_synthetic: flags.contains(FieldAccessFlags::SYNTHETIC),
+            constant,
+        }
+    });
+    let methods = class.methods.iter().map(|method| {
+        let flags = method.access_flags;
+        Member {
+            is_field: false,
+            name: &method.name,
+            descriptor: &method.descriptor,
+            access: access_level(
+                flags.contains(MethodAccessFlags::PUBLIC),
+                flags.contains(MethodAccessFlags::PROTECTED),
+                flags.contains(MethodAccessFlags::PRIVATE),
+            ),
+            is_static: flags.is_static(),
+            is_final: flags.contains(MethodAccessFlags::FINAL),
+            is_abstract: flags.is_abstract(),
+            is_synthetic: flags.contains(MethodAccessFlags::SYNTHETIC)
+                || flags.contains(MethodAccessFlags::BRIDGE),
+            constant: None,
+        }
+    });
+    fields.chain(methods).collect()
+}
+
+struct Checker<'a> {
+    old: &'a BTreeMap<String, Class>,
+    new: &'a BTreeMap<String, Class>,
+    problems: Vec<Problem>,
+}
+
+impl Checker<'_> {
+    fn problem(
+        &mut self,
+        severity: Severity,
+        rule: &'static str,
+        jls: Option<&'static str>,
+        class: &Class,
+        member: Option<&Member>,
+        message: String,
+    ) {
+        self.problems.push(Problem {
+            severity,
+            rule,
+            jls,
+            class: class.this_class.to_string(),
+            member: member.map(Member::text),
+            message,
+        });
+    }
+
+    fn check_class(&mut self, old: &Class) {
+        let old_flags = old.access_flags;
+        if !old_flags.contains(ClassAccessFlags::PUBLIC)
+            || old_flags.contains(ClassAccessFlags::SYNTHETIC)
+            || old_flags.contains(ClassAccessFlags::MODULE)
+        {
+            return;
+        }
+        let kind = |flags: ClassAccessFlags| {
+            if flags.is_interface() {
+                "interface"
+            } else {
+                "class"
+            }
+        };
+        let Some(new) = self.new.get(old.this_class.name.as_str()) else {
+            let message = format!("{} removed", kind(old_flags));
+            self.problem(Severity::Error, "class-removed", None, old, None, message);
+            return;
+        };
+        let new_flags = new.access_flags;
+        if !new_flags.contains(ClassAccessFlags::PUBLIC) {
+            let message = format!("{} is no longer public", kind(old_flags));
+            self.problem(
+                Severity::Error,
+                "class-not-public",
+                Some("13.4.3"),
+                old,
+                None,
+                message,
+            );
+            return;
+        }
+        if old_flags.is_interface() != new_flags.is_interface() {
+            let message = format!("{} became {}", kind(old_flags), kind(new_flags));
+            self.problem(
+                Severity::Error,
+                "class-kind-changed",
+                None,
+                old,
+                None,
+                message,
+            );
+            return;
+        }
+        if !old_flags.is_interface() {
+            if !old_flags.contains(ClassAccessFlags::ABSTRACT)
+                && new_flags.contains(ClassAccessFlags::ABSTRACT)
+            {
+                let message = String::from("class made abstract");
+                self.problem(
+                    Severity::Error,
+                    "class-made-abstract",
+                    Some("13.4.1"),
+                    old,
+                    None,
+                    message,
+                );
+            }
+            if !old_flags.contains(ClassAccessFlags::FINAL)
+                && new_flags.contains(ClassAccessFlags::FINAL)
+            {
+                let message = String::from("class made final");
+                self.problem(
+                    Severity::Error,
+                    "class-made-final",
+                    Some("13.4.2"),
+                    old,
+                    None,
+                    message,
+                );
+            }
+        }
+        let new_supertypes = supertypes(self.new, new);
+        for supertype in supertypes(self.old, old).difference(&new_supertypes) {
+            let message = format!("no longer a subtype of {}", supertype);
+            let jls = if old_flags.is_interface() {
+                "13.5.2"
+            } else {
+                "13.4.4"
+            };
+            self.problem(
+                Severity::Error,
+                "supertype-removed",
+                Some(jls),
+                old,
+                None,
+                message,
+            );
+        }
+        self.check_members(old, new);
+    }
+
+    fn check_members(&mut self, old: &Class, new: &Class) {
+        let is_interface = old.access_flags.is_interface();
+        let old_members = members(old);
+        let new_members = members(new);
+        let find = |members: &'_ [Member<'_>], m: &Member| {
+            members.iter().position(|other| {
+                other.is_field == m.is_field
+                    && other.name == m.name
+                    && other.descriptor == m.descriptor
+            })
+        };
+        // Members of the new version whose descriptor is the only new one for their name.
+        let mut retyped: BTreeSet<usize> = BTreeSet::new();
+
+        for m in old_members.iter().filter(|m| m.is_api()) {
+            let Some(index) = find(&new_members, m) else {
+                if inherits(self.new, new, m) {
+                    continue;
+                }
+                let candidates: Vec<usize> = (0..new_members.len())
+                    .filter(|i| {
+                        let n = &new_members[*i];
+                        n.is_field == m.is_field
+                            && n.name == m.name
+                            && n.is_api()
+                            && find(&old_members, n).is_none()
+                    })
+                    .collect();
+                if let [candidate] = candidates[..] {
+                    retyped.insert(candidate);
+                    let n = &new_members[candidate];
+                    let jls = if m.is_field {
+                        "13.4.8"
+                    } else if m.descriptor.split(')').next() == n.descriptor.split(')').next() {
+                        "13.4.15"
+                    } else {
+                        "13.4.14"
+                    };
+                    let message = format!(
+                        "descriptor changed from {} to {}",
+                        m.descriptor, n.descriptor
+                    );
+                    self.problem(
+                        Severity::Error,
+                        "descriptor-changed",
+                        Some(jls),
+                        old,
+                        Some(m),
+                        message,
+                    );
+                } else {
+                    let jls = if m.is_field { "13.4.8" } else { "13.4.12" };
+                    let message = format!("{} removed", m.kind());
+                    self.problem(
+                        Severity::Error,
+                        "member-removed",
+                        Some(jls),
+                        old,
+                        Some(m),
+                        message,
+                    );
+                }
+                continue;
+            };
+            let n = &new_members[index];
+            if n.access < m.access {
+                let message = format!(
+                    "access narrowed from {} to {}",
+                    access_name(m.access),
+                    access_name(n.access)
+                );
+                self.problem(
+                    Severity::Error,
+                    "access-narrowed",
+                    Some("13.4.7"),
+                    old,
+                    Some(m),
+                    message,
+                );
+            }
+            if m.is_static != n.is_static {
+                let (from, to) = if m.is_static {
+                    ("static", "instance")
+                } else {
+                    ("instance", "static")
+                };
+                let jls = if m.is_field { "13.4.10" } else { "13.4.19" };
+                let message = format!("changed from {} to {} {}", from, to, m.kind());
+                self.problem(
+                    Severity::Error,
+                    "static-changed",
+                    Some(jls),
+                    old,
+                    Some(m),
+                    message,
+                );
+            }
+            // A final method of a final class cannot have been overridden, and a class made
+            // final is reported once as class-made-final rather than once per method.
+            let class_final = |class: &Class| class.access_flags.contains(ClassAccessFlags::FINAL);
+            let overridable = m.is_field || !(class_final(old) || class_final(new));
+            if !m.is_final && n.is_final && overridable && m.name != "<init>" {
+                let jls = if m.is_field { "13.4.9" } else { "13.4.17" };
+                let message = format!("{} made final", m.kind());
+                self.problem(
+                    Severity::Error,
+                    "final-added",
+                    Some(jls),
+                    old,
+                    Some(m),
+                    message,
+                );
+            }
+            if !m.is_field && !m.is_abstract && n.is_abstract {
+                let jls = if is_interface { "13.5.6" } else { "13.4.16" };
+                let message = String::from("method made abstract");
+                self.problem(
+                    Severity::Error,
+                    "method-made-abstract",
+                    Some(jls),
+                    old,
+                    Some(m),
+                    message,
+                );
+            }
+            if let Some(value) = &m.constant {
+                if n.constant.as_ref() != Some(value) {
+                    let message = match &n.constant {
+                        Some(new_value) => format!(
+                            "constant value changed from {} to {}; callers compiled against the \
+                             old version still use {}",
+                            value, new_value, value
+                        ),
+                        None => format!(
+                            "no longer a constant; callers compiled against the old version \
+                             still use {}",
+                            value
+                        ),
+                    };
+                    self.problem(
+                        Severity::Warning,
+                        "constant-changed",
+                        Some("13.4.9"),
+                        old,
+                        Some(m),
+                        message,
+                    );
+                }
+            }
+        }
+
+        // Existing implementations of an interface or subclasses of an abstract class do not
+        // implement a new abstract method, so calling it on them throws AbstractMethodError.
+        let extendable = is_interface || old.access_flags.contains(ClassAccessFlags::ABSTRACT);
+        for (index, n) in new_members.iter().enumerate() {
+            if !extendable
+                || n.is_field
+                || !n.is_abstract
+                || !n.is_api()
+                || retyped.contains(&index)
+                || find(&old_members, n).is_some()
+                || inherits(self.old, old, n)
+            {
+                continue;
+            }
+            let (jls, message) = if is_interface {
+                (
+                    "13.5.3",
+                    "abstract method added to interface without a default",
+                )
+            } else {
+                ("13.4.16", "abstract method added to class")
+            };
+            self.problem(
+                Severity::Warning,
+                "abstract-method-added",
+                Some(jls),
+                old,
+                Some(n),
+                String::from(message),
+            );
+        }
+    }
+}
+
+// The superclasses and superinterfaces of a class, followed through the classes in `jar`.
+fn supertypes(jar: &BTreeMap<String, Class>, class: &Class) -> BTreeSet<String> {
+    let mut supertypes = BTreeSet::new();
+    let mut queue: VecDeque<&Class> = VecDeque::from([class]);
+    while let Some(class) = queue.pop_front() {
+        let interfaces = class
+            .interfaces
+            .iter()
+            .filter_map(|index| class.constant_pool.class_name(*index).ok());
+        for name in class
+            .super_class
+            .iter()
+            .map(|name| name.to_string())
+            .chain(interfaces)
+        {
+            if supertypes.insert(name.clone()) {
+                if let Some(supertype) = jar.get(&name) {
+                    queue.push_back(supertype);
+                }
+            }
+        }
+    }
+    supertypes
+}
+
+// Whether `class` inherits a public or protected member with the same name, descriptor and kind from
+// one of its supertypes, which keeps links to it working after it was removed from `class`.
+fn inherits(jar: &BTreeMap<String, Class>, class: &Class, member: &Member) -> bool {
+    if member.name.starts_with('<') {
+        return false; // constructors are not inherited
+    }
+    if !member.is_field
+        && !member.is_static
+        && OBJECT_METHODS.contains(&(member.name, member.descriptor))
+    {
+        return true;
+    }
+    supertypes(jar, class)
+        .iter()
+        .filter_map(|name| jar.get(name))
+        .any(|supertype| {
+            members(supertype).iter().any(|other| {
+                other.is_field == member.is_field
+                    && other.name == member.name
+                    && other.descriptor == member.descriptor
+                    && other.is_static == member.is_static
+                    && other.access >= PROTECTED
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        attribute::{Attribute, AttributeConstantValue},
+        class_builder::ClassBuilder,
+        instruction::Instruction,
+        version::ClassVersion,
+    };
+    use Severity::{Error, Warning};
+
+    const PUBLIC_CLASS: ClassAccessFlags = ClassAccessFlags::PUBLIC;
+    const PUBLIC_INTERFACE: ClassAccessFlags = ClassAccessFlags(
+        ClassAccessFlags::PUBLIC.0 | ClassAccessFlags::INTERFACE.0 | ClassAccessFlags::ABSTRACT.0,
+    );
+    const PUBLIC_ABSTRACT: ClassAccessFlags =
+        ClassAccessFlags(ClassAccessFlags::PUBLIC.0 | ClassAccessFlags::ABSTRACT.0);
+
+    fn class(flags: ClassAccessFlags, members: impl FnOnce(&mut ClassBuilder)) -> Class {
+        let mut builder = ClassBuilder::new(flags, "p/A", Some("java/lang/Object")).unwrap();
+        builder.version(ClassVersion::new(52, 0)); // for default methods
+        members(&mut builder);
+        builder.build().unwrap()
+    }
+
+    fn field(builder: &mut ClassBuilder, flags: FieldAccessFlags, descriptor: &str) {
+        builder.field(flags, "f", descriptor).unwrap();
+    }
+
+    // Concrete methods throw, which suits any descriptor.
+    fn method(builder: &mut ClassBuilder, flags: MethodAccessFlags, descriptor: &str) {
+        if flags.is_abstract() {
+            builder.method(flags, "m", descriptor).unwrap();
+        } else {
+            builder
+                .method_with_code(flags, "m", descriptor, |code| {
+                    code.emit(Instruction::AconstNull)?;
+                    code.emit(Instruction::Athrow)
+                })
+                .unwrap();
+        }
+    }
+
+    fn constant(builder: &mut ClassBuilder, value: i32) {
+        let constantvalue_index = builder.constant_pool().integer(value).unwrap();
+        let flags = FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC | FieldAccessFlags::FINAL;
+        builder
+            .field(flags, "LIMIT", "I")
+            .unwrap()
+            .attributes
+            .push(Attribute::ConstantValue(AttributeConstantValue {
+                constantvalue_index,
+            }));
+    }
+
+    // The severity, rule and JLS section of each problem found in the new version of p/A.
+    fn problems(
+        old: Class,
+        new: Option<Class>,
+    ) -> Vec<(Severity, &'static str, Option<&'static str>)> {
+        let jar = |class: Option<Class>| -> BTreeMap<String, Class> {
+            class
+                .into_iter()
+                .map(|class| (class.this_class.to_string(), class))
+                .collect()
+        };
+        check_jars(&jar(Some(old)), &jar(new))
+            .problems
+            .into_iter()
+            .map(|problem| (problem.severity, problem.rule, problem.jls))
+            .collect()
+    }
+
+    fn changed(
+        old: impl FnOnce(&mut ClassBuilder),
+        new: impl FnOnce(&mut ClassBuilder),
+    ) -> Vec<(Severity, &'static str, Option<&'static str>)> {
+        problems(class(PUBLIC_CLASS, old), Some(class(PUBLIC_CLASS, new)))
+    }
+
+    #[test]
+    fn unchanged_and_non_public_classes_have_no_problems() {
+        let old = || {
+            class(PUBLIC_CLASS, |b| {
+                method(b, MethodAccessFlags::PUBLIC, "()V")
+            })
+        };
+        assert_eq!(problems(old(), Some(old())), []);
+        assert_eq!(problems(class(ClassAccessFlags::SUPER, |_| {}), None), []);
+    }
+
+    #[test]
+    fn class_removed() {
+        assert_eq!(
+            problems(class(PUBLIC_CLASS, |_| {}), None),
+            [(Error, "class-removed", None)]
+        );
+    }
+
+    #[test]
+    fn class_not_public() {
+        assert_eq!(
+            problems(
+                class(PUBLIC_CLASS, |_| {}),
+                Some(class(ClassAccessFlags::SUPER, |_| {}))
+            ),
+            [(Error, "class-not-public", Some("13.4.3"))]
+        );
+    }
+
+    #[test]
+    fn class_kind_changed() {
+        assert_eq!(
+            problems(
+                class(PUBLIC_CLASS, |_| {}),
+                Some(class(PUBLIC_INTERFACE, |_| {}))
+            ),
+            [(Error, "class-kind-changed", None)]
+        );
+    }
+
+    #[test]
+    fn class_made_abstract() {
+        assert_eq!(
+            problems(
+                class(PUBLIC_CLASS, |_| {}),
+                Some(class(PUBLIC_ABSTRACT, |_| {}))
+            ),
+            [(Error, "class-made-abstract", Some("13.4.1"))]
+        );
+    }
+
+    #[test]
+    fn class_made_final() {
+        let final_class = PUBLIC_CLASS | ClassAccessFlags::FINAL;
+        // Its final methods are covered by the class being made final.
+        let methods = |b: &mut ClassBuilder, flags| method(b, flags, "()V");
+        assert_eq!(
+            problems(
+                class(PUBLIC_CLASS, |b| methods(b, MethodAccessFlags::PUBLIC)),
+                Some(class(final_class, |b| methods(
+                    b,
+                    MethodAccessFlags::PUBLIC | MethodAccessFlags::FINAL
+                )))
+            ),
+            [(Error, "class-made-final", Some("13.4.2"))]
+        );
+    }
+
+    #[test]
+    fn supertype_removed() {
+        let serializable = |b: &mut ClassBuilder| b.interface("java/io/Serializable").unwrap();
+        assert_eq!(
+            changed(serializable, |_| {}),
+            [(Error, "supertype-removed", Some("13.4.4"))]
+        );
+        assert_eq!(
+            problems(
+                class(PUBLIC_INTERFACE, serializable),
+                Some(class(PUBLIC_INTERFACE, |_| {}))
+            ),
+            [(Error, "supertype-removed", Some("13.5.2"))]
+        );
+    }
+
+    #[test]
+    fn descriptor_changed() {
+        let public = MethodAccessFlags::PUBLIC;
+        assert_eq!(
+            changed(
+                |b| field(b, FieldAccessFlags::PUBLIC, "I"),
+                |b| field(b, FieldAccessFlags::PUBLIC, "J")
+            ),
+            [(Error, "descriptor-changed", Some("13.4.8"))]
+        );
+        assert_eq!(
+            changed(|b| method(b, public, "(I)V"), |b| method(b, public, "(J)V")),
+            [(Error, "descriptor-changed", Some("13.4.14"))]
+        );
+        assert_eq!(
+            changed(|b| method(b, public, "(I)V"), |b| method(b, public, "(I)I")),
+            [(Error, "descriptor-changed", Some("13.4.15"))]
+        );
+    }
+
+    #[test]
+    fn member_removed() {
+        assert_eq!(
+            changed(|b| field(b, FieldAccessFlags::PROTECTED, "I"), |_| {}),
+            [(Error, "member-removed", Some("13.4.8"))]
+        );
+        assert_eq!(
+            changed(|b| method(b, MethodAccessFlags::PUBLIC, "()V"), |_| {}),
+            [(Error, "member-removed", Some("13.4.12"))]
+        );
+        // Private members are not API, and methods of java/lang/Object are still inherited.
+        let old = |b: &mut ClassBuilder| {
+            field(b, FieldAccessFlags::PRIVATE, "I");
+            b.method_with_code(
+                MethodAccessFlags::PUBLIC,
+                "toString",
+                "()Ljava/lang/String;",
+                |code| {
+                    code.emit(Instruction::AconstNull)?;
+                    code.emit(Instruction::Areturn)
+                },
+            )
+            .unwrap();
+        };
+        assert_eq!(changed(old, |_| {}), []);
+    }
+
+    #[test]
+    fn access_narrowed() {
+        assert_eq!(
+            changed(
+                |b| method(b, MethodAccessFlags::PUBLIC, "()V"),
+                |b| method(b, MethodAccessFlags::PROTECTED, "()V")
+            ),
+            [(Error, "access-narrowed", Some("13.4.7"))]
+        );
+    }
+
+    #[test]
+    fn static_changed() {
+        let public = FieldAccessFlags::PUBLIC;
+        assert_eq!(
+            changed(
+                |b| field(b, public, "I"),
+                |b| field(b, public | FieldAccessFlags::STATIC, "I")
+            ),
+            [(Error, "static-changed", Some("13.4.10"))]
+        );
+        let public = MethodAccessFlags::PUBLIC;
+        assert_eq!(
+            changed(
+                |b| method(b, public | MethodAccessFlags::STATIC, "()V"),
+                |b| method(b, public, "()V")
+            ),
+            [(Error, "static-changed", Some("13.4.19"))]
+        );
+    }
+
+    #[test]
+    fn final_added() {
+        let public = FieldAccessFlags::PUBLIC;
+        assert_eq!(
+            changed(
+                |b| field(b, public, "I"),
+                |b| field(b, public | FieldAccessFlags::FINAL, "I")
+            ),
+            [(Error, "final-added", Some("13.4.9"))]
+        );
+        let public = MethodAccessFlags::PUBLIC;
+        assert_eq!(
+            changed(
+                |b| method(b, public, "()V"),
+                |b| method(b, public | MethodAccessFlags::FINAL, "()V")
+            ),
+            [(Error, "final-added", Some("13.4.17"))]
+        );
+    }
+
+    #[test]
+    fn method_made_abstract() {
+        let public = MethodAccessFlags::PUBLIC;
+        let abstract_method = |b: &mut ClassBuilder| {
+            method(b, public | MethodAccessFlags::ABSTRACT, "()V");
+        };
+        assert_eq!(
+            problems(
+                class(PUBLIC_ABSTRACT, |b| method(b, public, "()V")),
+                Some(class(PUBLIC_ABSTRACT, abstract_method))
+            ),
+            [(Error, "method-made-abstract", Some("13.4.16"))]
+        );
+        assert_eq!(
+            problems(
+                class(PUBLIC_INTERFACE, |b| method(b, public, "()V")),
+                Some(class(PUBLIC_INTERFACE, abstract_method))
+            ),
+            [(Error, "method-made-abstract", Some("13.5.6"))]
+        );
+    }
+
+    #[test]
+    fn constant_changed() {
+        assert_eq!(
+            changed(|b| constant(b, 1), |b| constant(b, 2)),
+            [(Warning, "constant-changed", Some("13.4.9"))]
+        );
+    }
+
+    #[test]
+    fn abstract_method_added() {
+        let abstract_method = |b: &mut ClassBuilder| {
+            method(
+                b,
+                MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT,
+                "()V",
+            );
+        };
+        assert_eq!(
+            problems(
+                class(PUBLIC_INTERFACE, |_| {}),
+                Some(class(PUBLIC_INTERFACE, abstract_method))
+            ),
+            [(Warning, "abstract-method-added", Some("13.5.3"))]
+        );
+        assert_eq!(
+            problems(
+                class(PUBLIC_ABSTRACT, |_| {}),
+                Some(class(PUBLIC_ABSTRACT, abstract_method))
+            ),
+            [(Warning, "abstract-method-added", Some("13.4.16"))]
+        );
+        // Concrete classes cannot have been extended without implementing it.
+        assert_eq!(changed(|_| {}, abstract_method), []);
+    }
+
+    #[test]
+    fn report_text_cites_the_jls() {
+        let report = check_jars(
+            &BTreeMap::from([(
+                String::from("p/A"),
+                class(PUBLIC_CLASS, |b| {
+                    method(b, MethodAccessFlags::PUBLIC, "()V")
+                }),
+            )]),
+            &BTreeMap::from([(String::from("p/A"), class(PUBLIC_CLASS, |_| {}))]),
+        );
+        assert_eq!(
+            report.to_string(),
+            "error   p/A.m()V: method removed [member-removed, JLS 13.4.12]\n1 errors, 0 warnings\n"
+        );
+    }
+}
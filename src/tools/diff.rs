@@ -0,0 +1,933 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::jvm::class::{
+    attribute::*, constant_pool::*, error::ClassFormatError, instruction::Instruction,
+    method::Method, version::VersionSupport, Class,
+};
+use crate::tools::{read_class, read_jar};
+
+// A structural comparison of two versions of a class or of a jar. Members are matched by name
+// and descriptor. Method bodies are compared instruction by instruction with every constant
+// pool operand resolved and branch targets replaced by labels, so recompiling a class that
+// only reorders its constant pool or moves code around does not show up as a change.
+#[derive(Debug, Default)]
+pub struct Diff {
+    pub classes: Vec<ClassDiff>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Status {
+    fn marker(self) -> char {
+        match self {
+            Status::Added => '+',
+            Status::Removed => '-',
+            Status::Changed => '~',
+        }
+    }
+}
+
+// A property of a class or member present in both versions, e.g. its access flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub what: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug)]
+pub struct ClassDiff {
+    pub name: String,
+    pub status: Status,
+    pub changes: Vec<Change>,
+    pub members: Vec<MemberDiff>,
+}
+
+#[derive(Debug)]
+pub struct MemberDiff {
+    pub kind: &'static str, // "field" or "method"
+    pub name: String,
+    pub descriptor: String, // of the new version when the descriptor changed
+    pub status: Status,
+    pub changes: Vec<Change>,
+    pub code: Vec<Hunk>,
+}
+
+// A run of removed and added lines of a method body. `old_start` and `new_start` count
+// instructions and exception handlers from 0; the text form prints them as unified diff does.
+#[derive(Debug)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub new_start: usize,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+}
+
+// Compares two jars, or two classes as read_class reads them.
+pub fn diff_targets(
+    old: &str,
+    new: &str,
+    class_path: &str,
+    version_support: VersionSupport,
+) -> Result<Diff, String> {
+    match (old.ends_with(".jar"), new.ends_with(".jar")) {
+        (true, true) => Ok(diff_jars(&read_jar(old)?, &read_jar(new)?)),
+        (false, false) => {
+            let old = read_class(old, class_path, version_support)?;
+            let new = read_class(new, class_path, version_support)?;
+            Ok(Diff {
+                classes: diff_classes(&old, &new).into_iter().collect(),
+            })
+        }
+        _ => Err(String::from("cannot compare a jar with a class")),
+    }
+}
+
+pub fn diff_jars(old: &BTreeMap<String, Class>, new: &BTreeMap<String, Class>) -> Diff {
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut classes = Vec::new();
+    for name in names {
+        match (old.get(name), new.get(name)) {
+            (Some(old), Some(new)) => classes.extend(diff_classes(old, new)),
+            (old, _) => classes.push(ClassDiff {
+                name: name.clone(),
+                status: if old.is_some() {
+                    Status::Removed
+                } else {
+                    Status::Added
+                },
+                changes: Vec::new(),
+                members: Vec::new(),
+            }),
+        }
+    }
+    Diff { classes }
+}
+
+// None when the two versions do not differ in anything the diff looks at.
+pub fn diff_classes(old: &Class, new: &Class) -> Option<ClassDiff> {
+    let mut changes = Vec::new();
+    let mut change = |what, old: String, new: String| {
+        if old != new {
+            changes.push(Change { what, old, new });
+        }
+    };
+    change(
+        "this class",
+        old.this_class.to_string(),
+        new.this_class.to_string(),
+    );
+    change("version", version_text(old), version_text(new));
+    change(
+        "access flags",
+        old.access_flags.names().join(" "),
+        new.access_flags.names().join(" "),
+    );
+    change("super class", super_text(old), super_text(new));
+    change("interfaces", interfaces_text(old), interfaces_text(new));
+    change("signature", signature_text(old), signature_text(new));
+
+    let old_fields = old
+        .fields
+        .iter()
+        .map(|f| (&f.name.value, &f.descriptor.value, f));
+    let new_fields = new
+        .fields
+        .iter()
+        .map(|f| (&f.name.value, &f.descriptor.value, f));
+    let mut members = diff_members("field", old_fields, new_fields, |old_field, new_field| {
+        let mut changes = Vec::new();
+        let mut change = |what, old: String, new: String| {
+            if old != new {
+                changes.push(Change { what, old, new });
+            }
+        };
+        change(
+            "access flags",
+            old_field.access_flags.names().join(" "),
+            new_field.access_flags.names().join(" "),
+        );
+        change(
+            "signature",
+            attribute_signature(&old_field.attributes),
+            attribute_signature(&new_field.attributes),
+        );
+        change(
+            "constant value",
+            constant_value_text(old, &old_field.attributes),
+            constant_value_text(new, &new_field.attributes),
+        );
+        (changes, Vec::new())
+    });
+    let old_methods = old
+        .methods
+        .iter()
+        .map(|m| (&m.name.value, &m.descriptor.value, m));
+    let new_methods = new
+        .methods
+        .iter()
+        .map(|m| (&m.name.value, &m.descriptor.value, m));
+    members.extend(diff_members(
+        "method",
+        old_methods,
+        new_methods,
+        |old_method, new_method| {
+            let mut changes = Vec::new();
+            let mut change = |what, old: String, new: String| {
+                if old != new {
+                    changes.push(Change { what, old, new });
+                }
+            };
+            change(
+                "access flags",
+                old_method.access_flags.names().join(" "),
+                new_method.access_flags.names().join(" "),
+            );
+            change(
+                "signature",
+                attribute_signature(&old_method.attributes),
+                attribute_signature(&new_method.attributes),
+            );
+            change(
+                "exceptions",
+                class_names(old_method.exceptions()),
+                class_names(new_method.exceptions()),
+            );
+            let code = diff_lines(&code_lines(old, old_method), &code_lines(new, new_method));
+            (changes, code)
+        },
+    ));
+
+    if changes.is_empty() && members.is_empty() {
+        return None;
+    }
+    Some(ClassDiff {
+        name: new.this_class.to_string(),
+        status: Status::Changed,
+        changes,
+        members,
+    })
+}
+
+// Members present in both versions are compared with `compare`. A member whose descriptor
+// changed is reported as changed rather than removed and added when it is the only member of
+// that name that disappeared and the only one that appeared.
+fn diff_members<'a, T: 'a>(
+    kind: &'static str,
+    old: impl Iterator<Item = (&'a String, &'a String, &'a T)>,
+    new: impl Iterator<Item = (&'a String, &'a String, &'a T)>,
+    mut compare: impl FnMut(&T, &T) -> (Vec<Change>, Vec<Hunk>),
+) -> Vec<MemberDiff> {
+    let old: BTreeMap<(&String, &String), &T> = old.map(|(n, d, m)| ((n, d), m)).collect();
+    let new: BTreeMap<(&String, &String), &T> = new.map(|(n, d, m)| ((n, d), m)).collect();
+    let removed: Vec<(&String, &String)> = old
+        .keys()
+        .filter(|k| !new.contains_key(*k))
+        .copied()
+        .collect();
+    let added: Vec<(&String, &String)> = new
+        .keys()
+        .filter(|k| !old.contains_key(*k))
+        .copied()
+        .collect();
+
+    let mut members = Vec::new();
+    let mut retyped: BTreeSet<(&String, &String)> = BTreeSet::new();
+    for (name, descriptor) in removed.iter().chain(&added) {
+        let in_old = old.contains_key(&(*name, *descriptor));
+        let (Some(old_key), Some(new_key)) = (only_named(&removed, name), only_named(&added, name))
+        else {
+            members.push(MemberDiff {
+                kind,
+                name: (*name).clone(),
+                descriptor: (*descriptor).clone(),
+                status: if in_old {
+                    Status::Removed
+                } else {
+                    Status::Added
+                },
+                changes: Vec::new(),
+                code: Vec::new(),
+            });
+            continue;
+        };
+        if !retyped.insert(new_key) {
+            continue;
+        }
+        let (mut changes, code) = compare(old[&old_key], new[&new_key]);
+        changes.insert(
+            0,
+            Change {
+                what: "descriptor",
+                old: old_key.1.clone(),
+                new: new_key.1.clone(),
+            },
+        );
+        members.push(MemberDiff {
+            kind,
+            name: (*name).clone(),
+            descriptor: new_key.1.clone(),
+            status: Status::Changed,
+            changes,
+            code,
+        });
+    }
+    for (key, old_member) in &old {
+        if let Some(new_member) = new.get(key) {
+            let (changes, code) = compare(old_member, new_member);
+            if !changes.is_empty() || !code.is_empty() {
+                members.push(MemberDiff {
+                    kind,
+                    name: key.0.clone(),
+                    descriptor: key.1.clone(),
+                    status: Status::Changed,
+                    changes,
+                    code,
+                });
+            }
+        }
+    }
+    members.sort_by(|a, b| (&a.name, &a.descriptor).cmp(&(&b.name, &b.descriptor)));
+    members
+}
+
+fn only_named<'a>(
+    keys: &[(&'a String, &'a String)],
+    name: &str,
+) -> Option<(&'a String, &'a String)> {
+    let mut matching = keys.iter().filter(|(n, _)| *n == name);
+    match (matching.next(), matching.next()) {
+        (Some(key), None) => Some(*key),
+        _ => None,
+    }
+}
+
+fn version_text(class: &Class) -> String {
+    format!("{}.{}", class.major_version, class.minor_version)
+}
+
+fn super_text(class: &Class) -> String {
+    class
+        .super_class
+        .as_ref()
+        .map(ClassRef::to_string)
+        .unwrap_or_default()
+}
+
+fn interfaces_text(class: &Class) -> String {
+    let names: Vec<String> = class
+        .interfaces
+        .iter()
+        .map(|index| {
+            class
+                .constant_pool
+                .class_name(*index)
+                .unwrap_or_else(|e| format!("<{}>", e.reason))
+        })
+        .collect();
+    names.join(" ")
+}
+
+fn signature_text(class: &Class) -> String {
+    attribute_signature(&class.attributes)
+}
+
+fn class_names(classes: &[ClassRef]) -> String {
+    let names: Vec<&str> = classes.iter().map(|class| class.name.as_str()).collect();
+    names.join(" ")
+}
+
+// The raw Signature attribute; generic types are compared as written.
+fn attribute_signature(attributes: &[Attribute]) -> String {
+    attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            Attribute::Signature(a) => Some(a.signature.to_string()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+// The value of a field's ConstantValue attribute as shown in a diff, empty when it has none.
+pub fn constant_value_text(class: &Class, attributes: &[Attribute]) -> String {
+    attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            Attribute::ConstantValue(a) => {
+                Some(match class.constant_pool.loadable(a.constantvalue_index) {
+                    Ok(constant) => loadable_text(class, &constant, 0),
+                    Err(e) => format!("<{}>", e.reason),
+                })
+            }
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+// A constant as it appears in a method body, e.g. `"text"`, `1.5f` or `class java/util/List`.
+fn loadable_text(class: &Class, constant: &LoadableConstant, depth: usize) -> String {
+    match constant {
+        LoadableConstant::Integer(v) => v.to_string(),
+        LoadableConstant::Float(v) => format!("{:?}f", v),
+        LoadableConstant::Long(v) => format!("{}l", v),
+        LoadableConstant::Double(v) => format!("{:?}d", v),
+        LoadableConstant::Class(name) => format!("class {}", name),
+        LoadableConstant::String(s) => format!("{:?}", s),
+        LoadableConstant::MethodHandle(handle) => handle.to_string(),
+        LoadableConstant::MethodType(descriptor) => format!("methodtype {}", descriptor),
+        LoadableConstant::Dynamic(dynamic) => {
+            let bootstrap = class
+                .bootstrap_methods()
+                .get(dynamic.bootstrap_method_attr_index as usize);
+            format!(
+                "dynamic {}:{} {}",
+                dynamic.name,
+                dynamic.descriptor,
+                bootstrap_text(class, bootstrap, depth)
+            )
+        }
+    }
+}
+
+// Bootstrap methods are shown by value since their order in the BootstrapMethods attribute
+// is as arbitrary as the constant pool's. A malformed class can make a dynamic constant its
+// own argument, hence the depth limit.
+fn bootstrap_text(class: &Class, bootstrap: Option<&BootstrapMethod>, depth: usize) -> String {
+    match bootstrap {
+        Some(_) if depth > 8 => String::from("..."),
+        Some(bootstrap) => {
+            let arguments: Vec<String> = bootstrap
+                .bootstrap_arguments
+                .iter()
+                .map(|argument| loadable_text(class, argument, depth + 1))
+                .collect();
+            format!("{} [{}]", bootstrap.bootstrap_method, arguments.join(", "))
+        }
+        None => String::from("<missing bootstrap method>"),
+    }
+}
+
+// The body of a method as one line per instruction followed by one per exception handler.
+// Branch targets become labels numbered in code order, so inserting an instruction changes
+// only the lines around it.
+fn code_lines(class: &Class, method: &Method) -> Vec<String> {
+    let Some(code) = method.code() else {
+        return Vec::new();
+    };
+    let instructions = match Instruction::decode_all(&code.code) {
+        Ok(instructions) => instructions,
+        Err(e) => return vec![format!("<{}>", e)],
+    };
+    let mut targets: BTreeSet<usize> = BTreeSet::new();
+    for (pc, instruction) in &instructions {
+        targets.extend(instruction.branch_targets(*pc));
+    }
+    for entry in &code.exception_table {
+        targets.extend([entry.start_pc, entry.end_pc, entry.handler_pc].map(usize::from));
+    }
+    let labels: BTreeMap<usize, String> = targets
+        .into_iter()
+        .enumerate()
+        .map(|(i, pc)| (pc, format!("L{}", i)))
+        .collect();
+    let label = |pc: usize| labels[&pc].clone();
+
+    let mut lines = Vec::new();
+    for (pc, instruction) in &instructions {
+        let mut line = String::new();
+        if let Some(label) = labels.get(pc) {
+            line.push_str(label);
+            line.push_str(": ");
+        }
+        line.push_str(&instruction_text(class, *pc, instruction, &label));
+        lines.push(line);
+    }
+    for entry in &code.exception_table {
+        let catch_type = if entry.catch_type.is_none() {
+            String::from("any")
+        } else {
+            class
+                .constant_pool
+                .class_name(entry.catch_type)
+                .unwrap_or_else(|e| format!("<{}>", e.reason))
+        };
+        lines.push(format!(
+            "catch {} {} {} -> {}",
+            catch_type,
+            label(entry.start_pc.into()),
+            label(entry.end_pc.into()),
+            label(entry.handler_pc.into())
+        ));
+    }
+    lines
+}
+
+fn instruction_text(
+    class: &Class,
+    pc: usize,
+    instruction: &Instruction,
+    label: &dyn Fn(usize) -> String,
+) -> String {
+    let constant_pool = &class.constant_pool;
+    let resolved = |result: Result<String, _>| {
+        result.unwrap_or_else(|e: ClassFormatError| format!("<{}>", e.reason))
+    };
+    // ldc_w and goto_w only differ from ldc and goto in the width of their operand, which
+    // depends on the constant pool and code layout.
+    let name = match instruction {
+        Instruction::LdcW(_) => "ldc",
+        Instruction::GotoW(_) => "goto",
+        Instruction::JsrW(_) => "jsr",
+        _ => instruction.name(),
+    };
+    let operands = match instruction {
+        Instruction::Ldc(index) | Instruction::LdcW(index) | Instruction::Ldc2W(index) => resolved(
+            constant_pool
+                .loadable(*index)
+                .map(|constant| loadable_text(class, &constant, 0)),
+        ),
+        Instruction::Getstatic(index)
+        | Instruction::Putstatic(index)
+        | Instruction::Getfield(index)
+        | Instruction::Putfield(index)
+        | Instruction::Invokevirtual(index)
+        | Instruction::Invokespecial(index)
+        | Instruction::Invokestatic(index)
+        | Instruction::Invokeinterface { index, .. } => {
+            resolved(constant_pool.member_ref(*index).map(|m| m.to_string()))
+        }
+        Instruction::Invokedynamic(index) => {
+            resolved(class.dynamic_call_site(*index).map(|site| {
+                format!(
+                    "{}:{} {} [{}]",
+                    site.name,
+                    site.descriptor,
+                    site.bootstrap_method,
+                    site.bootstrap_arguments
+                        .iter()
+                        .map(|argument| loadable_text(class, argument, 1))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            }))
+        }
+        Instruction::New(index)
+        | Instruction::Anewarray(index)
+        | Instruction::Checkcast(index)
+        | Instruction::Instanceof(index) => resolved(constant_pool.class_name(*index)),
+        Instruction::Multianewarray { index, dimensions } => format!(
+            "{} {}",
+            resolved(constant_pool.class_name(*index)),
+            dimensions
+        ),
+        Instruction::Bipush(value) => value.to_string(),
+        Instruction::Sipush(value) => value.to_string(),
+        Instruction::Iload(index)
+        | Instruction::Lload(index)
+        | Instruction::Fload(index)
+        | Instruction::Dload(index)
+        | Instruction::Aload(index)
+        | Instruction::Istore(index)
+        | Instruction::Lstore(index)
+        | Instruction::Fstore(index)
+        | Instruction::Dstore(index)
+        | Instruction::Astore(index)
+        | Instruction::Ret(index) => index.to_string(),
+        Instruction::Iinc { index, value } => format!("{} {}", index, value),
+        Instruction::Newarray(atype) => atype.to_string(),
+        Instruction::Tableswitch { low, high, .. } => {
+            let targets = instruction.branch_targets(pc);
+            let cases: Vec<String> = (*low..=*high)
+                .zip(targets.iter().skip(1))
+                .map(|(key, target)| format!("{}: {}", key, label(*target)))
+                .collect();
+            format!("{{{}}} default: {}", cases.join(", "), label(targets[0]))
+        }
+        Instruction::Lookupswitch { pairs, .. } => {
+            let targets = instruction.branch_targets(pc);
+            let cases: Vec<String> = pairs
+                .iter()
+                .zip(targets.iter().skip(1))
+                .map(|((key, _), target)| format!("{}: {}", key, label(*target)))
+                .collect();
+            format!("{{{}}} default: {}", cases.join(", "), label(targets[0]))
+        }
+        _ => match instruction.branch_targets(pc).first() {
+            Some(target) => label(*target),
+            None => String::new(),
+        },
+    };
+    if operands.is_empty() {
+        String::from(name)
+    } else {
+        format!("{} {}", name, operands)
+    }
+}
+
+// Edit scripts longer than this are reported as replacing the whole body; the diff's memory
+// grows with the square of the script length.
+const MAX_EDITS: usize = 2000;
+
+// The hunks of a shortest edit script from `old` to `new` (Myers, "An O(ND) Difference
+// Algorithm and Its Variations").
+fn diff_lines(old: &[String], new: &[String]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    if a.is_empty() && b.is_empty() {
+        return Vec::new();
+    }
+
+    // keep[i] is the index in b that a[i] is matched with.
+    let mut keep: Vec<Option<usize>> = vec![None; a.len()];
+    if let Some(matches) = shortest_edit_script(a, b) {
+        for (i, j) in matches {
+            keep[i] = Some(j);
+        }
+    }
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && keep[i] == Some(j) {
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let mut hunk = Hunk {
+            old_start: prefix + i,
+            new_start: prefix + j,
+            removed: Vec::new(),
+            added: Vec::new(),
+        };
+        while i < a.len() && keep[i].is_none() {
+            hunk.removed.push(a[i].clone());
+            i += 1;
+        }
+        let next = if i < a.len() {
+            keep[i].unwrap()
+        } else {
+            b.len()
+        };
+        while j < next {
+            hunk.added.push(b[j].clone());
+            j += 1;
+        }
+        hunks.push(hunk);
+    }
+    hunks
+}
+
+// The matched (old, new) index pairs, or None when the script would exceed MAX_EDITS.
+fn shortest_edit_script(a: &[String], b: &[String]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m).min(MAX_EDITS as isize);
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut found = None;
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let down =
+                k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]);
+            let mut x = if down {
+                v[(k + 1 + offset) as usize]
+            } else {
+                v[(k - 1 + offset) as usize] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(k + offset) as usize] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                break 'search;
+            }
+        }
+    }
+    let d_end = found?;
+
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..=d_end).rev() {
+        let v = &trace[d as usize];
+        let k = x - y;
+        let down =
+            k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]);
+        let prev_k = if down { k + 1 } else { k - 1 };
+        let prev_x = if d == 0 {
+            0
+        } else {
+            v[(prev_k + offset) as usize]
+        };
+        let prev_y = if d == 0 { 0 } else { prev_x - prev_k };
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    matches.reverse();
+    Some(matches)
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for class in &self.classes {
+            writeln!(f, "{} class {}", class.status.marker(), class.name)?;
+            write_changes(f, &class.changes, "    ")?;
+            for member in &class.members {
+                let separator = if member.kind == "field" { ":" } else { "" };
+                writeln!(
+                    f,
+                    "    {} {} {}{}{}",
+                    member.status.marker(),
+                    member.kind,
+                    member.name,
+                    separator,
+                    member.descriptor
+                )?;
+                write_changes(f, &member.changes, "        ")?;
+                if !member.code.is_empty() {
+                    writeln!(f, "        code:")?;
+                }
+                for hunk in &member.code {
+                    writeln!(
+                        f,
+                        "        @@ -{},{} +{},{} @@",
+                        unified_start(hunk.old_start, &hunk.removed),
+                        hunk.removed.len(),
+                        unified_start(hunk.new_start, &hunk.added),
+                        hunk.added.len()
+                    )?;
+                    for line in &hunk.removed {
+                        writeln!(f, "        - {}", line)?;
+                    }
+                    for line in &hunk.added {
+                        writeln!(f, "        + {}", line)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// The start of a hunk range in a unified diff header: the 1-based number of its first line,
+// or of the line it follows when it is empty.
+fn unified_start(start: usize, lines: &[String]) -> usize {
+    if lines.is_empty() {
+        start
+    } else {
+        start + 1
+    }
+}
+
+fn write_changes(f: &mut fmt::Formatter<'_>, changes: &[Change], indent: &str) -> fmt::Result {
+    for change in changes {
+        let text = |s: &str| {
+            if s.is_empty() {
+                String::from("(none)")
+            } else {
+                s.to_string()
+            }
+        };
+        writeln!(
+            f,
+            "{}{}: {} -> {}",
+            indent,
+            change.what,
+            text(&change.old),
+            text(&change.new)
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        access_flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+        class_builder::ClassBuilder,
+    };
+
+    // Version 1 and 2 of a class: `count` turns public, `gone` is removed, `fresh` is added,
+    // `size` changes its descriptor, `log` gains two instructions in front and `run` returns
+    // another constant.
+    fn class(version: i8) -> Class {
+        let mut builder =
+            ClassBuilder::new(ClassAccessFlags::PUBLIC, "C", Some("java/lang/Object")).unwrap();
+        let (count, fields) = if version == 1 {
+            (FieldAccessFlags::PRIVATE, [("gone", "J")])
+        } else {
+            (FieldAccessFlags::PUBLIC, [("fresh", "Z")])
+        };
+        builder.field(count, "count", "I").unwrap();
+        for (name, descriptor) in fields {
+            builder
+                .field(FieldAccessFlags::PRIVATE, name, descriptor)
+                .unwrap();
+        }
+        let size = if version == 1 { "()I" } else { "()J" };
+        builder
+            .method(MethodAccessFlags::ABSTRACT, "size", size)
+            .unwrap();
+        builder
+            .method_with_code(MethodAccessFlags::STATIC, "log", "()V", |code| {
+                if version == 2 {
+                    code.emit(Instruction::Iconst0)?;
+                    code.emit(Instruction::Pop)?;
+                }
+                code.emit(Instruction::Return)
+            })
+            .unwrap();
+        builder
+            .method_with_code(MethodAccessFlags::STATIC, "run", "(I)I", |code| {
+                let zero = code.new_label();
+                code.emit(Instruction::Iload0)?;
+                code.jump(Instruction::Ifeq, zero)?;
+                code.iconst(version.into())?;
+                code.emit(Instruction::Ireturn)?;
+                code.bind(zero)?;
+                code.emit(Instruction::Iconst0)?;
+                code.emit(Instruction::Ireturn)
+            })
+            .unwrap();
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn identical_classes_do_not_differ() {
+        assert!(diff_classes(&class(1), &class(1)).is_none());
+    }
+
+    #[test]
+    fn added_removed_and_changed_members() {
+        let diff = diff_classes(&class(1), &class(2)).unwrap();
+        let members: Vec<(&str, &str, Status)> = diff
+            .members
+            .iter()
+            .map(|m| (m.name.as_str(), m.descriptor.as_str(), m.status))
+            .collect();
+        assert_eq!(
+            members,
+            [
+                ("count", "I", Status::Changed),
+                ("fresh", "Z", Status::Added),
+                ("gone", "J", Status::Removed),
+                ("log", "()V", Status::Changed),
+                ("run", "(I)I", Status::Changed),
+                ("size", "()J", Status::Changed),
+            ]
+        );
+        assert_eq!(
+            diff.members[0].changes,
+            [Change {
+                what: "access flags",
+                old: String::from("ACC_PRIVATE"),
+                new: String::from("ACC_PUBLIC"),
+            }]
+        );
+        assert_eq!(
+            diff.members[5].changes,
+            [Change {
+                what: "descriptor",
+                old: String::from("()I"),
+                new: String::from("()J"),
+            }]
+        );
+    }
+
+    #[test]
+    fn instruction_hunks_with_unified_diff_headers() {
+        let diff = diff_classes(&class(1), &class(2)).unwrap();
+        let run = &diff.members[4];
+        assert_eq!(run.code.len(), 1);
+        assert_eq!((run.code[0].old_start, run.code[0].new_start), (2, 2));
+        assert_eq!(
+            Diff {
+                classes: vec![diff],
+            }
+            .to_string(),
+            "~ class C
+    ~ field count:I
+        access flags: ACC_PRIVATE -> ACC_PUBLIC
+    + field fresh:Z
+    - field gone:J
+    ~ method log()V
+        code:
+        @@ -0,0 +1,2 @@
+        + iconst_0
+        + pop
+    ~ method run(I)I
+        code:
+        @@ -3,1 +3,1 @@
+        - iconst_1
+        + iconst_2
+    ~ method size()J
+        descriptor: ()I -> ()J
+"
+        );
+    }
+
+    #[test]
+    fn hunks_of_an_edit_script() {
+        let lines = |text: &str| -> Vec<String> { text.split(' ').map(String::from).collect() };
+        let hunks = diff_lines(&lines("a b c d e"), &lines("a x c e f"));
+        let hunks: Vec<(usize, usize, Vec<String>, Vec<String>)> = hunks
+            .into_iter()
+            .map(|h| (h.old_start, h.new_start, h.removed, h.added))
+            .collect();
+        assert_eq!(
+            hunks,
+            [
+                (1, 1, lines("b"), lines("x")),
+                (3, 3, lines("d"), Vec::new()),
+                (5, 4, Vec::new(), lines("f")),
+            ]
+        );
+    }
+
+    #[test]
+    fn jars_with_added_and_removed_classes() {
+        let jar = |names: &[&str]| -> BTreeMap<String, Class> {
+            names
+                .iter()
+                .map(|name| {
+                    let builder =
+                        ClassBuilder::new(ClassAccessFlags::PUBLIC, name, Some("java/lang/Object"))
+                            .unwrap();
+                    (name.to_string(), builder.build().unwrap())
+                })
+                .collect()
+        };
+        let diff = diff_jars(&jar(&["p/A", "p/B"]), &jar(&["p/B", "p/C"]));
+        assert_eq!(diff.to_string(), "- class p/A\n+ class p/C\n");
+    }
+}
@@ -0,0 +1,1400 @@
+use std::fmt;
+
+use crate::jvm::class::{
+    attribute::*,
+    constant::Constant,
+    constant_pool::*,
+    descriptor::{FieldType, MethodDescriptor},
+    error::ClassFormatError,
+    field::Field,
+    instruction::Instruction,
+    method::Method,
+    signature::type_parameters_java_name,
+    Class,
+};
+
+// A `javap -c -v -p` style listing of a class: the resolved constant pool, flags, signatures,
+// the instructions of every method with symbolic operands and the attributes of the class and
+// its members. Constants that do not resolve are shown as `<reason>` instead of failing, so
+// classes that do not pass check_format can still be inspected.
+pub struct Disassembly<'a> {
+    class: &'a Class,
+}
+
+impl<'a> Disassembly<'a> {
+    pub fn new(class: &'a Class) -> Disassembly<'a> {
+        Disassembly { class }
+    }
+
+    fn write_header(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let class = self.class;
+        if let Some(source_file) = class.source_file() {
+            writeln!(f, "  Compiled from \"{}\"", source_file)?;
+        }
+        writeln!(f, "{}", self.class_declaration())?;
+        writeln!(f, "  minor version: {}", class.minor_version)?;
+        writeln!(f, "  major version: {}", class.major_version)?;
+        writeln!(f, "  flags: {:?}", class.access_flags)?;
+        write_commented(
+            f,
+            &format!("  this_class: {}", self.class_index(&class.this_class)),
+            &class.this_class,
+        )?;
+        match &class.super_class {
+            Some(super_class) => write_commented(
+                f,
+                &format!("  super_class: {}", self.class_index(super_class)),
+                super_class,
+            )?,
+            None => writeln!(f, "  super_class: #0")?,
+        }
+        writeln!(
+            f,
+            "  interfaces: {}, fields: {}, methods: {}, attributes: {}",
+            class.interfaces.len(),
+            class.fields.len(),
+            class.methods.len(),
+            class.attributes.len()
+        )
+    }
+
+    // e.g. "public class Gen<T> extends java.util.AbstractList<T> implements java.io.Serializable"
+    fn class_declaration(&self) -> String {
+        let class = self.class;
+        if class.is_module() {
+            return match class.module_descriptor() {
+                Ok(Some(module)) => {
+                    let open = if module.is_open() { "open " } else { "" };
+                    match &module.version {
+                        Some(version) => format!("{}module {}@{}", open, module.name, version),
+                        None => format!("{}module {}", open, module.name),
+                    }
+                }
+                _ => String::from("module-info"),
+            };
+        }
+        let mut declaration = modifiers(&class.access_flags.to_string());
+        declaration.push_str(class.access_flags.kind());
+        declaration.push(' ');
+        declaration.push_str(&class.this_class.replace('/', "."));
+
+        let interfaces: Vec<String> = class
+            .interfaces
+            .iter()
+            .map(|index| self.class_constant(*index).replace('/', "."))
+            .collect();
+        let signature = class.signature();
+        let has_signature = matches!(signature, Ok(Some(_)));
+        let (type_parameters, super_class, interfaces) = match signature {
+            Ok(Some(signature)) => (
+                type_parameters_java_name(&signature.type_parameters),
+                Some(signature.superclass.java_name()),
+                signature.interfaces.iter().map(|i| i.java_name()).collect(),
+            ),
+            _ => (
+                String::new(),
+                class.super_class.as_ref().map(|s| s.replace('/', ".")),
+                interfaces,
+            ),
+        };
+        declaration.push_str(&type_parameters);
+        if class.access_flags.is_interface() {
+            if !interfaces.is_empty() {
+                declaration.push_str(&format!(" extends {}", interfaces.join(", ")));
+            }
+            return declaration;
+        }
+        // Like javap, a generic class shows the superclass of its Signature even when that is
+        // java.lang.Object.
+        if let Some(super_class) = super_class.filter(|s| has_signature || s != "java.lang.Object")
+        {
+            declaration.push_str(&format!(" extends {}", super_class));
+        }
+        if !interfaces.is_empty() {
+            declaration.push_str(&format!(" implements {}", interfaces.join(", ")));
+        }
+        declaration
+    }
+
+    // The index of the Class constant naming `class_name`, the classic layout has it right
+    // after the pool so it is looked up rather than kept around while parsing.
+    fn class_index(&self, class_name: &str) -> String {
+        self.class
+            .constant_pool
+            .iter()
+            .find(|(index, constant)| {
+                matches!(constant, Constant::Class(_))
+                    && self.class.constant_pool.class_name(*index).as_deref() == Ok(class_name)
+            })
+            .map_or(String::from("#?"), |(index, _)| index.to_string())
+    }
+
+    fn write_constant_pool(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Constant pool:")?;
+        let width = format!("#{}", self.class.constant_pool.count() - 1).len() + 2;
+        for (index, constant) in self.class.constant_pool.iter() {
+            let line = format!(
+                "{:>width$} = {:<18} {}",
+                index.to_string(),
+                constant.kind(),
+                constant_operands(constant),
+                width = width
+            );
+            match constant {
+                Constant::Utf8(_)
+                | Constant::Integer(_)
+                | Constant::Float(_)
+                | Constant::Long(_)
+                | Constant::Double(_) => writeln!(f, "{}", line)?,
+                _ => writeln!(f, "{} // {}", line, self.constant(index, false))?,
+            }
+        }
+        Ok(())
+    }
+
+    // The resolved value of a constant as shown after `//`. Inside code javap drops the class
+    // name from references to members of the class itself; `in_code` does the same.
+    fn constant(&self, index: CpIndex, in_code: bool) -> String {
+        self.try_constant(index, in_code)
+            .unwrap_or_else(|e| format!("<{}>", e.reason))
+    }
+
+    fn try_constant(&self, index: CpIndex, in_code: bool) -> Result<String, ClassFormatError> {
+        let constant_pool = &self.class.constant_pool;
+        let text = match constant_pool.get(index)? {
+            Constant::Utf8(c) => escape(&c.bytes),
+            Constant::Integer(c) => c.bytes.to_string(),
+            Constant::Float(c) => format!("{}f", float_literal(c.bytes)),
+            Constant::Long(c) => format!("{}l", c.bytes),
+            Constant::Double(c) => format!("{}d", float_literal(c.bytes)),
+            Constant::Class(_) => quote_array(&constant_pool.class_name(index)?),
+            Constant::String(_) => escape(&constant_pool.string(index)?),
+            Constant::Fieldref(_) | Constant::Methodref(_) | Constant::InterfaceMethodref(_) => {
+                self.member(&constant_pool.member_ref(index)?, in_code)
+            }
+            Constant::NameAndType(_) => {
+                let (name, descriptor) = constant_pool.name_and_type(index)?;
+                format!("{}:{}", quote_special(&name), descriptor)
+            }
+            Constant::MethodHandle(_) => {
+                let handle = constant_pool.method_handle(index)?;
+                format!(
+                    "{} {}",
+                    handle.reference_kind,
+                    self.member(&handle.member, in_code)
+                )
+            }
+            Constant::MethodType(_) => constant_pool.method_type(index)?,
+            Constant::Dynamic(_) | Constant::InvokeDynamic(_) => {
+                let (_, dynamic) = constant_pool.dynamic(index)?;
+                format!(
+                    "#{}:{}:{}",
+                    dynamic.bootstrap_method_attr_index,
+                    quote_special(&dynamic.name),
+                    dynamic.descriptor
+                )
+            }
+            Constant::Module(_) => constant_pool.module_name(index)?,
+            Constant::Package(_) => constant_pool.package_name(index)?,
+            Constant::Unusable => unreachable!("ConstantPool::get never returns Unusable"),
+        };
+        Ok(text)
+    }
+
+    fn member(&self, member: &MemberRef, in_code: bool) -> String {
+        if in_code && member.class_name == self.class.this_class {
+            format!("{}:{}", quote_special(&member.name), member.descriptor)
+        } else {
+            format!(
+                "{}.{}:{}",
+                quote_array(&member.class_name),
+                quote_special(&member.name),
+                member.descriptor
+            )
+        }
+    }
+
+    fn class_constant(&self, index: CpIndex) -> String {
+        self.class
+            .constant_pool
+            .class_name(index)
+            .unwrap_or_else(|e| format!("<{}>", e.reason))
+    }
+
+    // The comment of an instruction operand or ConstantValue, e.g. "Method java/io/PrintStream.println:(I)V".
+    fn typed_constant(&self, index: CpIndex) -> String {
+        let kind = match self.class.constant_pool.get(index) {
+            Ok(Constant::Integer(_)) => "int",
+            Ok(Constant::Float(_)) => "float",
+            Ok(Constant::Long(_)) => "long",
+            Ok(Constant::Double(_)) => "double",
+            Ok(Constant::Class(_)) => "class",
+            Ok(Constant::String(_)) => "String",
+            Ok(Constant::Fieldref(_)) => "Field",
+            Ok(Constant::Methodref(_)) => "Method",
+            Ok(Constant::InterfaceMethodref(_)) => "InterfaceMethod",
+            Ok(Constant::MethodHandle(_)) => "MethodHandle",
+            Ok(Constant::MethodType(_)) => "MethodType",
+            Ok(Constant::Dynamic(_)) => "Dynamic",
+            Ok(Constant::InvokeDynamic(_)) => "InvokeDynamic",
+            _ => return self.constant(index, true),
+        };
+        format!("{} {}", kind, self.constant(index, true))
+    }
+
+    fn write_field(&self, f: &mut fmt::Formatter<'_>, field: &Field) -> fmt::Result {
+        let field_type = match field.signature() {
+            Ok(Some(signature)) => signature.java_name(),
+            _ => FieldType::parse(&field.descriptor)
+                .map_or_else(|_| field.descriptor.to_string(), |t| t.java_name()),
+        };
+        writeln!(
+            f,
+            "  {}{} {};",
+            modifiers(&field.access_flags.to_string()),
+            field_type,
+            field.name
+        )?;
+        writeln!(f, "    descriptor: {}", field.descriptor)?;
+        writeln!(f, "    flags: {:?}", field.access_flags)?;
+        for attribute in &field.attributes {
+            self.write_attribute(f, attribute, "    ")?;
+        }
+        Ok(())
+    }
+
+    // e.g. "public <T> java.util.List<T> sort(java.util.List<T>, int) throws java.io.IOException"
+    fn method_declaration(&self, method: &Method) -> String {
+        let mut declaration = modifiers(&method.access_flags.to_string());
+        if method.name == "<clinit>" {
+            declaration.push_str("{}");
+            return declaration;
+        }
+        let name = if method.name == "<init>" {
+            self.class.this_class.replace('/', ".")
+        } else {
+            method.name.to_string()
+        };
+        let exceptions: Vec<String> = method
+            .exceptions()
+            .iter()
+            .map(|e| e.replace('/', "."))
+            .collect();
+        let (type_parameters, return_type, parameters, throws): (
+            String,
+            String,
+            Vec<String>,
+            Vec<String>,
+        ) = match method.signature() {
+            Ok(Some(signature)) => {
+                let throws: Vec<String> = signature.throws.iter().map(|t| t.java_name()).collect();
+                (
+                    type_parameters_java_name(&signature.type_parameters),
+                    signature
+                        .return_type
+                        .as_ref()
+                        .map_or(String::from("void"), |r| r.java_name()),
+                    signature.parameters.iter().map(|p| p.java_name()).collect(),
+                    if throws.is_empty() {
+                        exceptions
+                    } else {
+                        throws
+                    },
+                )
+            }
+            _ => match method.method_descriptor() {
+                Ok(descriptor) => (
+                    String::new(),
+                    descriptor
+                        .return_type
+                        .as_ref()
+                        .map_or(String::from("void"), |r| r.java_name()),
+                    descriptor
+                        .parameters
+                        .iter()
+                        .map(|p| p.java_name())
+                        .collect(),
+                    exceptions,
+                ),
+                Err(_) => return format!("{}{}{}", declaration, name, method.descriptor),
+            },
+        };
+        if !type_parameters.is_empty() {
+            declaration.push_str(&type_parameters);
+            declaration.push(' ');
+        }
+        if method.name != "<init>" {
+            declaration.push_str(&return_type);
+            declaration.push(' ');
+        }
+        declaration.push_str(&format!("{}({})", name, parameters.join(", ")));
+        if !throws.is_empty() {
+            declaration.push_str(&format!(" throws {}", throws.join(", ")));
+        }
+        declaration
+    }
+
+    fn write_method(&self, f: &mut fmt::Formatter<'_>, method: &Method) -> fmt::Result {
+        writeln!(f, "  {};", self.method_declaration(method))?;
+        writeln!(f, "    descriptor: {}", method.descriptor)?;
+        writeln!(f, "    flags: {:?}", method.access_flags)?;
+        for attribute in &method.attributes {
+            match attribute {
+                Attribute::Code(code) => self.write_code(f, method, code)?,
+                _ => self.write_attribute(f, attribute, "    ")?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_code(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        method: &Method,
+        code: &AttributeCode,
+    ) -> fmt::Result {
+        writeln!(f, "    Code:")?;
+        // javap counts parameters, not the local variable slots they take.
+        let args_size = MethodDescriptor::parse(&method.descriptor).map(|descriptor| {
+            let this = !method.access_flags.is_static() as usize;
+            descriptor.parameters.len() + this
+        });
+        match args_size {
+            Ok(args_size) => writeln!(
+                f,
+                "      stack={}, locals={}, args_size={}",
+                code.max_stack, code.max_locals, args_size
+            )?,
+            Err(_) => writeln!(
+                f,
+                "      stack={}, locals={}",
+                code.max_stack, code.max_locals
+            )?,
+        }
+        match Instruction::decode_all(&code.code) {
+            Ok(instructions) => {
+                for (pc, instruction) in &instructions {
+                    self.write_instruction(f, *pc, instruction)?;
+                }
+            }
+            Err(e) => writeln!(f, "      <{}>", e)?,
+        }
+        if !code.exception_table.is_empty() {
+            writeln!(f, "      Exception table:")?;
+            writeln!(f, "         from    to  target type")?;
+            for entry in &code.exception_table {
+                let catch_type = if entry.catch_type.is_none() {
+                    String::from("any")
+                } else {
+                    format!("Class {}", self.class_constant(entry.catch_type))
+                };
+                writeln!(
+                    f,
+                    "{:>14}{:>6}{:>6}   {}",
+                    entry.start_pc, entry.end_pc, entry.handler_pc, catch_type
+                )?;
+            }
+        }
+        for attribute in &code.attributes {
+            self.write_attribute(f, attribute, "      ")?;
+        }
+        Ok(())
+    }
+
+    fn write_instruction(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        pc: usize,
+        instruction: &Instruction,
+    ) -> fmt::Result {
+        let name = instruction.name();
+        let (operands, index) = match instruction {
+            Instruction::Ldc(index)
+            | Instruction::LdcW(index)
+            | Instruction::Ldc2W(index)
+            | Instruction::Getstatic(index)
+            | Instruction::Putstatic(index)
+            | Instruction::Getfield(index)
+            | Instruction::Putfield(index)
+            | Instruction::Invokevirtual(index)
+            | Instruction::Invokespecial(index)
+            | Instruction::Invokestatic(index)
+            | Instruction::New(index)
+            | Instruction::Anewarray(index)
+            | Instruction::Checkcast(index)
+            | Instruction::Instanceof(index) => (index.to_string(), Some(*index)),
+            Instruction::Invokeinterface { index, count } => {
+                (format!("{},  {}", index, count), Some(*index))
+            }
+            Instruction::Invokedynamic(index) => (format!("{},  0", index), Some(*index)),
+            Instruction::Multianewarray { index, dimensions } => {
+                (format!("{},  {}", index, dimensions), Some(*index))
+            }
+            Instruction::Bipush(value) => (value.to_string(), None),
+            Instruction::Sipush(value) => (value.to_string(), None),
+            Instruction::Iload(index)
+            | Instruction::Lload(index)
+            | Instruction::Fload(index)
+            | Instruction::Dload(index)
+            | Instruction::Aload(index)
+            | Instruction::Istore(index)
+            | Instruction::Lstore(index)
+            | Instruction::Fstore(index)
+            | Instruction::Dstore(index)
+            | Instruction::Astore(index)
+            | Instruction::Ret(index) => (index.to_string(), None),
+            Instruction::Iinc { index, value } => (format!("{}, {}", index, value), None),
+            Instruction::Newarray(atype) => (atype.to_string(), None),
+            Instruction::Tableswitch { low, high, .. } => {
+                writeln!(f, "{:>10}: {:<13} {{ // {} to {}", pc, name, low, high)?;
+                let targets = instruction.branch_targets(pc);
+                for (key, target) in (*low..=*high).zip(targets.iter().skip(1)) {
+                    writeln!(f, "{:>24}: {}", key, target)?;
+                }
+                writeln!(f, "{:>24}: {}", "default", targets[0])?;
+                return writeln!(f, "            }}");
+            }
+            Instruction::Lookupswitch { pairs, .. } => {
+                writeln!(f, "{:>10}: {:<13} {{ // {}", pc, name, pairs.len())?;
+                let targets = instruction.branch_targets(pc);
+                for ((key, _), target) in pairs.iter().zip(targets.iter().skip(1)) {
+                    writeln!(f, "{:>24}: {}", key, target)?;
+                }
+                writeln!(f, "{:>24}: {}", "default", targets[0])?;
+                return writeln!(f, "            }}");
+            }
+            _ => match instruction.branch_targets(pc).first() {
+                Some(target) => (target.to_string(), None),
+                None => (String::new(), None),
+            },
+        };
+        match index {
+            Some(index) => writeln!(
+                f,
+                "{:>10}: {:<33} // {}",
+                pc,
+                format!("{:<13} {}", name, operands),
+                self.typed_constant(index)
+            ),
+            None if operands.is_empty() => writeln!(f, "{:>10}: {}", pc, name),
+            None => writeln!(f, "{:>10}: {:<13} {}", pc, name, operands),
+        }
+    }
+
+    fn write_attribute(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        attribute: &Attribute,
+        indent: &str,
+    ) -> fmt::Result {
+        match attribute {
+            Attribute::ConstantValue(a) => writeln!(
+                f,
+                "{}ConstantValue: {}",
+                indent,
+                self.typed_constant(a.constantvalue_index)
+            ),
+            Attribute::Code(a) => writeln!(
+                f,
+                "{}Code: {} bytes outside of a method",
+                indent,
+                a.code.len()
+            ),
+            Attribute::LineNumberTable(a) => {
+                writeln!(f, "{}LineNumberTable:", indent)?;
+                for entry in &a.line_number_table {
+                    writeln!(f, "{}  line {}: {}", indent, entry[1], entry[0])?;
+                }
+                Ok(())
+            }
+            Attribute::SourceFile(a) => writeln!(f, "{}SourceFile: \"{}\"", indent, a.sourcefile),
+            Attribute::LocalVariableTable(a) => {
+                writeln!(f, "{}LocalVariableTable:", indent)?;
+                writeln!(f, "{}  Start  Length  Slot  Name   Signature", indent)?;
+                for v in &a.local_variable_table {
+                    writeln!(
+                        f,
+                        "{}{:>7} {:>7} {:>5} {:>5}   {}",
+                        indent, v.start_pc, v.length, v.index, v.name, v.descriptor
+                    )?;
+                }
+                Ok(())
+            }
+            Attribute::LocalVariableTypeTable(a) => {
+                writeln!(f, "{}LocalVariableTypeTable:", indent)?;
+                writeln!(f, "{}  Start  Length  Slot  Name   Signature", indent)?;
+                for v in &a.local_variable_type_table {
+                    writeln!(
+                        f,
+                        "{}{:>7} {:>7} {:>5} {:>5}   {}",
+                        indent, v.start_pc, v.length, v.index, v.name, v.signature
+                    )?;
+                }
+                Ok(())
+            }
+            Attribute::StackMapTable(a) => {
+                writeln!(
+                    f,
+                    "{}StackMapTable: number_of_entries = {}",
+                    indent,
+                    a.entries.len()
+                )?;
+                for frame in &a.entries {
+                    write_frame(f, frame, indent)?;
+                }
+                Ok(())
+            }
+            Attribute::Signature(a) => writeln!(f, "{}Signature: {}", indent, a.signature),
+            Attribute::RuntimeVisibleAnnotations(a) | Attribute::RuntimeInvisibleAnnotations(a) => {
+                writeln!(f, "{}{}:", indent, attribute.name())?;
+                for (i, annotation) in a.annotations.iter().enumerate() {
+                    writeln!(f, "{}  {}: {}", indent, i, annotation_text(annotation))?;
+                }
+                Ok(())
+            }
+            Attribute::RuntimeVisibleParameterAnnotations(a)
+            | Attribute::RuntimeInvisibleParameterAnnotations(a) => {
+                writeln!(f, "{}{}:", indent, attribute.name())?;
+                for (parameter, annotations) in a.parameter_annotations.iter().enumerate() {
+                    writeln!(f, "{}  parameter {}:", indent, parameter)?;
+                    for (i, annotation) in annotations.iter().enumerate() {
+                        writeln!(f, "{}    {}: {}", indent, i, annotation_text(annotation))?;
+                    }
+                }
+                Ok(())
+            }
+            Attribute::RuntimeVisibleTypeAnnotations(a)
+            | Attribute::RuntimeInvisibleTypeAnnotations(a) => {
+                writeln!(f, "{}{}:", indent, attribute.name())?;
+                for (i, annotation) in a.annotations.iter().enumerate() {
+                    writeln!(
+                        f,
+                        "{}  {}: {}: {}",
+                        indent,
+                        i,
+                        annotation_text(&annotation.annotation),
+                        type_annotation_target(annotation)
+                    )?;
+                }
+                Ok(())
+            }
+            Attribute::AnnotationDefault(a) => {
+                writeln!(f, "{}AnnotationDefault:", indent)?;
+                writeln!(
+                    f,
+                    "{}  default_value: {}",
+                    indent,
+                    element_value_text(&a.default_value)
+                )
+            }
+            Attribute::InnerClasses(a) => {
+                writeln!(f, "{}InnerClasses:", indent)?;
+                for inner in &a.classes {
+                    let mut line = modifiers(&inner.inner_class_access_flags.to_string());
+                    if let Some(inner_name) = &inner.inner_name {
+                        line.push_str(&format!("{}=", inner_name));
+                    }
+                    line.push_str(&format!("class {}", inner.inner_class));
+                    if let Some(outer_class) = &inner.outer_class {
+                        line.push_str(&format!(" of class {}", outer_class));
+                    }
+                    writeln!(f, "{}  {}", indent, line)?;
+                }
+                Ok(())
+            }
+            Attribute::EnclosingMethod(a) => match &a.method_name {
+                Some(method_name) => writeln!(
+                    f,
+                    "{}EnclosingMethod: {}.{}{}",
+                    indent,
+                    a.class,
+                    method_name,
+                    a.method_descriptor.as_deref().unwrap_or("")
+                ),
+                None => writeln!(f, "{}EnclosingMethod: {}", indent, a.class),
+            },
+            Attribute::NestHost(a) => writeln!(f, "{}NestHost: class {}", indent, a.host_class),
+            Attribute::NestMembers(a) | Attribute::PermittedSubclasses(a) => {
+                writeln!(f, "{}{}:", indent, attribute.name())?;
+                for class in &a.classes {
+                    writeln!(f, "{}  {}", indent, class)?;
+                }
+                Ok(())
+            }
+            Attribute::Record(a) => {
+                writeln!(f, "{}Record:", indent)?;
+                for component in &a.components {
+                    let component_type = match component.signature() {
+                        Ok(Some(signature)) => signature.java_name(),
+                        _ => FieldType::parse(&component.descriptor)
+                            .map_or_else(|_| component.descriptor.to_string(), |t| t.java_name()),
+                    };
+                    writeln!(f, "{}  {} {};", indent, component_type, component.name)?;
+                    writeln!(f, "{}    descriptor: {}", indent, component.descriptor)?;
+                    let component_indent = format!("{}    ", indent);
+                    for attribute in &component.attributes {
+                        self.write_attribute(f, attribute, &component_indent)?;
+                    }
+                }
+                Ok(())
+            }
+            Attribute::Module(a) => write_module(f, a, indent),
+            Attribute::ModulePackages(a) => {
+                writeln!(f, "{}ModulePackages:", indent)?;
+                for package in &a.packages {
+                    writeln!(f, "{}  {}", indent, package)?;
+                }
+                Ok(())
+            }
+            Attribute::ModuleMainClass(a) => {
+                writeln!(f, "{}ModuleMainClass: {}", indent, a.main_class)
+            }
+            Attribute::Exceptions(a) => {
+                writeln!(f, "{}Exceptions:", indent)?;
+                let exceptions: Vec<String> = a
+                    .exception_index_table
+                    .iter()
+                    .map(|e| e.replace('/', "."))
+                    .collect();
+                writeln!(f, "{}  throws {}", indent, exceptions.join(", "))
+            }
+            Attribute::MethodParameters(a) => {
+                writeln!(f, "{}MethodParameters:", indent)?;
+                writeln!(f, "{}  {:<30} Flags", indent, "Name")?;
+                for parameter in &a.parameters {
+                    let mut flags: Vec<&str> = Vec::new();
+                    if parameter.is_final() {
+                        flags.push("final");
+                    }
+                    if parameter.is_synthetic() {
+                        flags.push("synthetic");
+                    }
+                    if parameter.is_mandated() {
+                        flags.push("mandated");
+                    }
+                    let name = parameter
+                        .name
+                        .as_ref()
+                        .map_or("<no name>", |name| name.as_str());
+                    let line = format!("{:<30} {}", name, flags.join(" "));
+                    writeln!(f, "{}  {}", indent, line.trim_end())?;
+                }
+                Ok(())
+            }
+            Attribute::Deprecated => writeln!(f, "{}Deprecated: true", indent),
+            Attribute::Synthetic => writeln!(f, "{}Synthetic: true", indent),
+            Attribute::SourceDebugExtension(a) => {
+                writeln!(f, "{}SourceDebugExtension:", indent)?;
+                for line in a.text().lines() {
+                    writeln!(f, "{}  {}", indent, line)?;
+                }
+                Ok(())
+            }
+            Attribute::BootstrapMethods(a) => {
+                writeln!(f, "{}BootstrapMethods:", indent)?;
+                for (i, bootstrap) in a.bootstrap_methods.iter().enumerate() {
+                    writeln!(
+                        f,
+                        "{}  {}: {} {}",
+                        indent,
+                        i,
+                        bootstrap.bootstrap_method.reference_kind,
+                        self.member(&bootstrap.bootstrap_method.member, false)
+                    )?;
+                    writeln!(f, "{}    Method arguments:", indent)?;
+                    for argument in &bootstrap.bootstrap_arguments {
+                        writeln!(f, "{}      {}", indent, self.loadable(argument))?;
+                    }
+                }
+                Ok(())
+            }
+            Attribute::Unknown { name, bytes } => {
+                writeln!(f, "{}{}: length = 0x{:x}", indent, name, bytes.len())?;
+                for chunk in bytes.chunks(16) {
+                    let hex: Vec<String> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+                    writeln!(f, "{}   {}", indent, hex.join(" "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn loadable(&self, constant: &LoadableConstant) -> String {
+        match constant {
+            LoadableConstant::Integer(v) => v.to_string(),
+            LoadableConstant::Float(v) => format!("{}f", float_literal(*v)),
+            LoadableConstant::Long(v) => format!("{}l", v),
+            LoadableConstant::Double(v) => format!("{}d", float_literal(*v)),
+            LoadableConstant::Class(name) => format!("class {}", quote_array(name)),
+            LoadableConstant::String(s) => escape(s),
+            LoadableConstant::MethodHandle(handle) => format!(
+                "{} {}",
+                handle.reference_kind,
+                self.member(&handle.member, false)
+            ),
+            LoadableConstant::MethodType(descriptor) => descriptor.clone(),
+            LoadableConstant::Dynamic(dynamic) => format!(
+                "#{}:{}:{}",
+                dynamic.bootstrap_method_attr_index, dynamic.name, dynamic.descriptor
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Disassembly<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_header(f)?;
+        self.write_constant_pool(f)?;
+        writeln!(f, "{{")?;
+        let mut first = true;
+        for field in &self.class.fields {
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            self.write_field(f, field)?;
+        }
+        for method in &self.class.methods {
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            self.write_method(f, method)?;
+        }
+        writeln!(f, "}}")?;
+        for attribute in &self.class.attributes {
+            self.write_attribute(f, attribute, "")?;
+        }
+        Ok(())
+    }
+}
+
+// Writes `line // comment` with the comments lined up in one column.
+fn write_commented(f: &mut fmt::Formatter<'_>, line: &str, comment: &str) -> fmt::Result {
+    writeln!(f, "{:<41} // {}", line, comment)
+}
+
+// Java modifiers followed by a space, or nothing.
+fn modifiers(modifiers: &str) -> String {
+    if modifiers.is_empty() {
+        String::new()
+    } else {
+        format!("{} ", modifiers)
+    }
+}
+
+// The raw operands of a constant pool entry, padded so the comments line up.
+fn constant_operands(constant: &Constant) -> String {
+    let operands = match constant {
+        Constant::Utf8(c) => return escape(&c.bytes),
+        Constant::Integer(c) => return c.bytes.to_string(),
+        Constant::Float(c) => return format!("{}f", float_literal(c.bytes)),
+        Constant::Long(c) => return format!("{}l", c.bytes),
+        Constant::Double(c) => return format!("{}d", float_literal(c.bytes)),
+        Constant::Class(c) => c.name_index.to_string(),
+        Constant::String(c) => c.string_index.to_string(),
+        Constant::Fieldref(c) => format!("{}.{}", c.class_index, c.name_and_type_index),
+        Constant::Methodref(c) => format!("{}.{}", c.class_index, c.name_and_type_index),
+        Constant::InterfaceMethodref(c) => {
+            format!("{}.{}", c.class_index, c.name_and_type_index)
+        }
+        Constant::NameAndType(c) => format!("{}:{}", c.name_index, c.descriptor_index),
+        Constant::MethodHandle(c) => format!("{}:{}", c.reference_kind, c.reference_index),
+        Constant::MethodType(c) => c.descriptor_index.to_string(),
+        Constant::Dynamic(c) => format!(
+            "#{}:{}",
+            c.bootstrap_method_attr_index, c.name_and_type_index
+        ),
+        Constant::InvokeDynamic(c) => format!(
+            "#{}:{}",
+            c.bootstrap_method_attr_index, c.name_and_type_index
+        ),
+        Constant::Module(c) => c.name_index.to_string(),
+        Constant::Package(c) => c.name_index.to_string(),
+        Constant::Unusable => String::new(),
+    };
+    format!("{:<14}", operands)
+}
+
+fn write_frame(f: &mut fmt::Formatter<'_>, frame: &StackMapFrame, indent: &str) -> fmt::Result {
+    let kind = match frame {
+        StackMapFrame::Same { .. } => "same",
+        StackMapFrame::SameLocals1StackItem { .. } => "same_locals_1_stack_item",
+        StackMapFrame::SameLocals1StackItemExtended { .. } => {
+            "same_locals_1_stack_item_frame_extended"
+        }
+        StackMapFrame::Chop { .. } => "chop",
+        StackMapFrame::SameExtended { .. } => "same_frame_extended",
+        StackMapFrame::Append { .. } => "append",
+        StackMapFrame::Full { .. } => "full_frame",
+    };
+    writeln!(
+        f,
+        "{}  frame_type = {} /* {} */",
+        indent,
+        frame.frame_type(),
+        kind
+    )?;
+    match frame {
+        StackMapFrame::Same { .. } => Ok(()),
+        StackMapFrame::SameLocals1StackItem { stack, .. } => {
+            writeln!(f, "{}    stack = [ {} ]", indent, verification_type(stack))
+        }
+        StackMapFrame::SameLocals1StackItemExtended {
+            offset_delta,
+            stack,
+        } => {
+            writeln!(f, "{}    offset_delta = {}", indent, offset_delta)?;
+            writeln!(f, "{}    stack = [ {} ]", indent, verification_type(stack))
+        }
+        StackMapFrame::Chop { offset_delta, .. } | StackMapFrame::SameExtended { offset_delta } => {
+            writeln!(f, "{}    offset_delta = {}", indent, offset_delta)
+        }
+        StackMapFrame::Append {
+            offset_delta,
+            locals,
+        } => {
+            writeln!(f, "{}    offset_delta = {}", indent, offset_delta)?;
+            writeln!(
+                f,
+                "{}    locals = [ {} ]",
+                indent,
+                verification_types(locals)
+            )
+        }
+        StackMapFrame::Full {
+            offset_delta,
+            locals,
+            stack,
+        } => {
+            writeln!(f, "{}    offset_delta = {}", indent, offset_delta)?;
+            writeln!(
+                f,
+                "{}    locals = [ {} ]",
+                indent,
+                verification_types(locals)
+            )?;
+            writeln!(f, "{}    stack = [ {} ]", indent, verification_types(stack))
+        }
+    }
+}
+
+fn verification_types(types: &[VerificationTypeInfo]) -> String {
+    let types: Vec<String> = types.iter().map(verification_type).collect();
+    types.join(", ")
+}
+
+fn verification_type(info: &VerificationTypeInfo) -> String {
+    match info {
+        VerificationTypeInfo::Top => String::from("top"),
+        VerificationTypeInfo::Integer => String::from("int"),
+        VerificationTypeInfo::Float => String::from("float"),
+        VerificationTypeInfo::Double => String::from("double"),
+        VerificationTypeInfo::Long => String::from("long"),
+        VerificationTypeInfo::Null => String::from("null"),
+        VerificationTypeInfo::UninitializedThis => String::from("this"),
+        VerificationTypeInfo::Object(class_name) => format!("class {}", quote_array(class_name)),
+        VerificationTypeInfo::Uninitialized(offset) => format!("uninitialized {}", offset),
+    }
+}
+
+fn write_module(f: &mut fmt::Formatter<'_>, module: &AttributeModule, indent: &str) -> fmt::Result {
+    writeln!(f, "{}Module:", indent)?;
+    let open = if module.module_flags & ACC_OPEN != 0 {
+        "open "
+    } else {
+        ""
+    };
+    match &module.module_version {
+        Some(version) => writeln!(
+            f,
+            "{}  {}module {}@{} {{",
+            indent, open, module.module_name, version
+        )?,
+        None => writeln!(f, "{}  {}module {} {{", indent, open, module.module_name)?,
+    }
+    for requires in &module.requires {
+        let mut line = String::from("requires ");
+        if requires.is_transitive() {
+            line.push_str("transitive ");
+        }
+        if requires.is_static() {
+            line.push_str("static ");
+        }
+        if requires.is_mandated() {
+            line.push_str("/* mandated */ ");
+        }
+        line.push_str(&requires.module);
+        if let Some(version) = &requires.version {
+            line.push_str(&format!("@{}", version));
+        }
+        writeln!(f, "{}    {};", indent, line)?;
+    }
+    for (directive, packages) in [("exports", &module.exports), ("opens", &module.opens)] {
+        for exports in packages {
+            let package = exports.package.replace('/', ".");
+            if exports.is_qualified() {
+                writeln!(
+                    f,
+                    "{}    {} {} to {};",
+                    indent,
+                    directive,
+                    package,
+                    exports.to.join(", ")
+                )?;
+            } else {
+                writeln!(f, "{}    {} {};", indent, directive, package)?;
+            }
+        }
+    }
+    for uses in &module.uses {
+        writeln!(f, "{}    uses {};", indent, uses.replace('/', "."))?;
+    }
+    for provides in &module.provides {
+        let with: Vec<String> = provides.with.iter().map(|w| w.replace('/', ".")).collect();
+        writeln!(
+            f,
+            "{}    provides {} with {};",
+            indent,
+            provides.service.replace('/', "."),
+            with.join(", ")
+        )?;
+    }
+    writeln!(f, "{}  }}", indent)
+}
+
+// e.g. "java.lang.annotation.Retention(value=java.lang.annotation.RetentionPolicy.RUNTIME)"
+fn annotation_text(annotation: &Annotation) -> String {
+    let type_name = descriptor_java_name(&annotation.type_descriptor);
+    if annotation.element_value_pairs.is_empty() {
+        return type_name;
+    }
+    let pairs: Vec<String> = annotation
+        .element_value_pairs
+        .iter()
+        .map(|pair| format!("{}={}", pair.name, element_value_text(&pair.value)))
+        .collect();
+    format!("{}({})", type_name, pairs.join(","))
+}
+
+// Where a type annotation applies, as javap prints it, e.g. "CAST, offset=3, type_index=0" or
+// "FIELD, location=[ARRAY, TYPE_ARGUMENT(0)]".
+fn type_annotation_target(annotation: &TypeAnnotation) -> String {
+    let mut target = match annotation.target_type {
+        0x00 => "CLASS_TYPE_PARAMETER",
+        0x01 => "METHOD_TYPE_PARAMETER",
+        0x10 => "CLASS_EXTENDS",
+        0x11 => "CLASS_TYPE_PARAMETER_BOUND",
+        0x12 => "METHOD_TYPE_PARAMETER_BOUND",
+        0x13 => "FIELD",
+        0x14 => "METHOD_RETURN",
+        0x15 => "METHOD_RECEIVER",
+        0x16 => "METHOD_FORMAL_PARAMETER",
+        0x17 => "THROWS",
+        0x40 => "LOCAL_VARIABLE",
+        0x41 => "RESOURCE_VARIABLE",
+        0x42 => "EXCEPTION_PARAMETER",
+        0x43 => "INSTANCEOF",
+        0x44 => "NEW",
+        0x45 => "CONSTRUCTOR_REFERENCE",
+        0x46 => "METHOD_REFERENCE",
+        0x47 => "CAST",
+        0x48 => "CONSTRUCTOR_INVOCATION_TYPE_ARGUMENT",
+        0x49 => "METHOD_INVOCATION_TYPE_ARGUMENT",
+        0x4A => "CONSTRUCTOR_REFERENCE_TYPE_ARGUMENT",
+        0x4B => "METHOD_REFERENCE_TYPE_ARGUMENT",
+        _ => "UNKNOWN",
+    }
+    .to_string();
+    match &annotation.target_info {
+        TargetInfo::TypeParameter {
+            type_parameter_index,
+        }
+        | TargetInfo::FormalParameter {
+            formal_parameter_index: type_parameter_index,
+        } => target.push_str(&format!(", param_index={}", type_parameter_index)),
+        TargetInfo::Supertype { supertype_index } => {
+            target.push_str(&format!(", type_index={}", supertype_index))
+        }
+        TargetInfo::TypeParameterBound {
+            type_parameter_index,
+            bound_index,
+        } => target.push_str(&format!(
+            ", param_index={}, bound_index={}",
+            type_parameter_index, bound_index
+        )),
+        TargetInfo::Empty => {}
+        TargetInfo::Throws { throws_type_index } => {
+            target.push_str(&format!(", type_index={}", throws_type_index))
+        }
+        TargetInfo::Localvar(table) => {
+            let ranges: Vec<String> = table
+                .iter()
+                .map(|t| {
+                    format!(
+                        "start_pc={}, length={}, index={}",
+                        t.start_pc, t.length, t.index
+                    )
+                })
+                .collect();
+            target.push_str(&format!(", {{{}}}", ranges.join("; ")));
+        }
+        TargetInfo::Catch {
+            exception_table_index,
+        } => target.push_str(&format!(", exception_index={}", exception_table_index)),
+        TargetInfo::Offset { offset } => target.push_str(&format!(", offset={}", offset)),
+        TargetInfo::TypeArgument {
+            offset,
+            type_argument_index,
+        } => target.push_str(&format!(
+            ", offset={}, type_index={}",
+            offset, type_argument_index
+        )),
+    }
+    if !annotation.type_path.is_empty() {
+        let location: Vec<String> = annotation
+            .type_path
+            .iter()
+            .map(|entry| match entry.type_path_kind {
+                0 => String::from("ARRAY"),
+                1 => String::from("INNER_TYPE"),
+                2 => String::from("WILDCARD"),
+                _ => format!("TYPE_ARGUMENT({})", entry.type_argument_index),
+            })
+            .collect();
+        target.push_str(&format!(", location=[{}]", location.join(", ")));
+    }
+    target
+}
+
+fn element_value_text(value: &ElementValue) -> String {
+    match value {
+        ElementValue::Const(constant) => match &constant.value {
+            ConstValue::Byte(v) => format!("(byte){}", v),
+            ConstValue::Char(v) => match char::from_u32(*v as u32) {
+                Some(c) => format!("'{}'", escape(&c.to_string())),
+                None => format!("'\\u{:04x}'", v),
+            },
+            ConstValue::Double(v) => format!("{}d", float_literal(*v)),
+            ConstValue::Float(v) => format!("{}f", float_literal(*v)),
+            ConstValue::Int(v) => v.to_string(),
+            ConstValue::Long(v) => format!("{}l", v),
+            ConstValue::Short(v) => format!("(short){}", v),
+            ConstValue::Boolean(v) => v.to_string(),
+            ConstValue::String(v) => format!("\"{}\"", escape(v)),
+        },
+        ElementValue::Enum {
+            type_name,
+            const_name,
+        } => format!("{}.{}", descriptor_java_name(type_name), const_name),
+        ElementValue::Class(descriptor) => {
+            format!("class {}", descriptor_java_name(descriptor))
+        }
+        ElementValue::Annotation(annotation) => format!("@{}", annotation_text(annotation)),
+        ElementValue::Array(values) => {
+            let values: Vec<String> = values.iter().map(element_value_text).collect();
+            format!("[{}]", values.join(","))
+        }
+    }
+}
+
+// "Ljava/lang/String;" as "java.lang.String", the return descriptor "V" as "void".
+fn descriptor_java_name(descriptor: &str) -> String {
+    if descriptor == "V" {
+        return String::from("void");
+    }
+    match FieldType::parse(descriptor) {
+        Ok(field_type) => field_type.java_name(),
+        Err(_) => descriptor.to_string(),
+    }
+}
+
+// Array classes are quoted by javap, e.g. `class "[Ljava/lang/String;"`.
+fn quote_array(class_name: &str) -> String {
+    if class_name.starts_with('[') {
+        format!("\"{}\"", class_name)
+    } else {
+        class_name.to_string()
+    }
+}
+
+// "<init>" and "<clinit>" are quoted by javap.
+fn quote_special(name: &str) -> String {
+    if name.starts_with('<') {
+        format!("\"{}\"", name)
+    } else {
+        name.to_string()
+    }
+}
+
+// Control characters are shown as Java escapes so every constant stays on one line.
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Float and double values the way Java prints them, e.g. "1.0", "NaN" or "-Infinity".
+fn float_literal<T: fmt::Debug + Into<f64> + Copy>(value: T) -> String {
+    let wide: f64 = value.into();
+    if wide.is_nan() {
+        String::from("NaN")
+    } else if wide.is_infinite() {
+        String::from(if wide > 0.0 { "Infinity" } else { "-Infinity" })
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        access_flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+        class_builder::ClassBuilder,
+        version::ClassVersion,
+    };
+
+    fn generic_class(signature: Option<&str>) -> Class {
+        let mut builder = ClassBuilder::new(
+            ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+            "Gen",
+            Some("java/lang/Object"),
+        )
+        .unwrap();
+        builder.version(ClassVersion::new(52, 0));
+        builder
+            .method_with_code(MethodAccessFlags::PUBLIC, "<init>", "()V", |code| {
+                code.emit(Instruction::Aload0)?;
+                code.invokespecial("java/lang/Object", "<init>", "()V")?;
+                code.emit(Instruction::Return)
+            })
+            .unwrap();
+        builder.attribute(Attribute::SourceFile(AttributeSourceFile {
+            sourcefile: "Gen.java".into(),
+        }));
+        if let Some(signature) = signature {
+            builder.attribute(Attribute::Signature(AttributeSignature {
+                signature: signature.into(),
+            }));
+        }
+        builder.build().unwrap()
+    }
+
+    fn marker(target_type: u8, target_info: TargetInfo, path: &[(u8, u8)]) -> TypeAnnotation {
+        TypeAnnotation {
+            target_type,
+            target_info,
+            type_path: path
+                .iter()
+                .map(|&(type_path_kind, type_argument_index)| TypePathEntry {
+                    type_path_kind,
+                    type_argument_index,
+                })
+                .collect(),
+            annotation: Annotation {
+                type_descriptor: "LA;".into(),
+                element_value_pairs: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn generic_class_listing() {
+        let class = generic_class(Some("<T:Ljava/lang/Object;>Ljava/lang/Object;"));
+        assert_eq!(
+            Disassembly::new(&class).to_string(),
+            r#"  Compiled from "Gen.java"
+public class Gen<T> extends java.lang.Object
+  minor version: 0
+  major version: 52
+  flags: (0x0021) ACC_PUBLIC, ACC_SUPER
+  this_class: #2                          // Gen
+  super_class: #4                         // java/lang/Object
+  interfaces: 0, fields: 0, methods: 1, attributes: 2
+Constant pool:
+   #1 = Utf8               Gen
+   #2 = Class              #1             // Gen
+   #3 = Utf8               java/lang/Object
+   #4 = Class              #3             // java/lang/Object
+   #5 = Utf8               <init>
+   #6 = Utf8               ()V
+   #7 = NameAndType        #5:#6          // "<init>":()V
+   #8 = Methodref          #4.#7          // java/lang/Object."<init>":()V
+   #9 = Utf8               Code
+  #10 = Utf8               SourceFile
+  #11 = Utf8               Gen.java
+  #12 = Utf8               Signature
+  #13 = Utf8               <T:Ljava/lang/Object;>Ljava/lang/Object;
+{
+  public Gen();
+    descriptor: ()V
+    flags: (0x0001) ACC_PUBLIC
+    Code:
+      stack=1, locals=1, args_size=1
+         0: aload_0
+         1: invokespecial #8                  // Method java/lang/Object."<init>":()V
+         4: return
+}
+SourceFile: "Gen.java"
+Signature: <T:Ljava/lang/Object;>Ljava/lang/Object;
+"#
+        );
+    }
+
+    #[test]
+    fn object_superclass_is_only_shown_for_a_signature() {
+        let declaration = |class: &Class| Disassembly::new(class).class_declaration();
+        assert_eq!(declaration(&generic_class(None)), "public class Gen");
+        assert_eq!(
+            declaration(&generic_class(Some("Ljava/lang/Object;"))),
+            "public class Gen extends java.lang.Object"
+        );
+        assert_eq!(
+            declaration(&generic_class(Some(
+                "<K:Ljava/lang/Object;>Ljava/util/AbstractMap<TK;Ljava/lang/String;>;\
+                 Ljava/io/Serializable;"
+            ))),
+            "public class Gen<K> extends java.util.AbstractMap<K, java.lang.String> \
+             implements java.io.Serializable"
+        );
+    }
+
+    #[test]
+    fn type_annotation_targets_as_javap_shows_them() {
+        let mut builder =
+            ClassBuilder::new(ClassAccessFlags::PUBLIC, "T", Some("java/lang/Object")).unwrap();
+        let annotations = vec![
+            marker(
+                0x00,
+                TargetInfo::TypeParameter {
+                    type_parameter_index: 0,
+                },
+                &[],
+            ),
+            marker(
+                0x01,
+                TargetInfo::TypeParameter {
+                    type_parameter_index: 1,
+                },
+                &[],
+            ),
+            marker(
+                0x10,
+                TargetInfo::Supertype {
+                    supertype_index: 65535,
+                },
+                &[(3, 0)],
+            ),
+            marker(
+                0x12,
+                TargetInfo::TypeParameterBound {
+                    type_parameter_index: 0,
+                    bound_index: 1,
+                },
+                &[],
+            ),
+            marker(0x13, TargetInfo::Empty, &[(0, 0), (3, 1)]),
+            marker(0x14, TargetInfo::Empty, &[(1, 0), (2, 0)]),
+            marker(0x15, TargetInfo::Empty, &[]),
+            marker(
+                0x16,
+                TargetInfo::FormalParameter {
+                    formal_parameter_index: 0,
+                },
+                &[],
+            ),
+            marker(
+                0x17,
+                TargetInfo::Throws {
+                    throws_type_index: 0,
+                },
+                &[],
+            ),
+            marker(
+                0x40,
+                TargetInfo::Localvar(vec![
+                    LocalvarTarget {
+                        start_pc: 2,
+                        length: 20,
+                        index: 3,
+                    },
+                    LocalvarTarget {
+                        start_pc: 30,
+                        length: 4,
+                        index: 3,
+                    },
+                ]),
+                &[],
+            ),
+            marker(
+                0x42,
+                TargetInfo::Catch {
+                    exception_table_index: 1,
+                },
+                &[],
+            ),
+            marker(0x43, TargetInfo::Offset { offset: 13 }, &[]),
+            marker(
+                0x47,
+                TargetInfo::TypeArgument {
+                    offset: 3,
+                    type_argument_index: 0,
+                },
+                &[],
+            ),
+            marker(
+                0x4B,
+                TargetInfo::TypeArgument {
+                    offset: 9,
+                    type_argument_index: 1,
+                },
+                &[],
+            ),
+        ];
+        builder
+            .field(FieldAccessFlags::PRIVATE, "f", "Ljava/lang/Object;")
+            .unwrap()
+            .attributes
+            .push(Attribute::RuntimeVisibleTypeAnnotations(
+                AttributeTypeAnnotations { annotations },
+            ));
+        let class = builder.build().unwrap();
+        let listing = Disassembly::new(&class).to_string();
+        let lines: Vec<&str> = listing
+            .lines()
+            .skip_while(|line| !line.ends_with("RuntimeVisibleTypeAnnotations:"))
+            .skip(1)
+            .take_while(|line| line.starts_with("      "))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "      0: A: CLASS_TYPE_PARAMETER, param_index=0",
+                "      1: A: METHOD_TYPE_PARAMETER, param_index=1",
+                "      2: A: CLASS_EXTENDS, type_index=65535, location=[TYPE_ARGUMENT(0)]",
+                "      3: A: METHOD_TYPE_PARAMETER_BOUND, param_index=0, bound_index=1",
+                "      4: A: FIELD, location=[ARRAY, TYPE_ARGUMENT(1)]",
+                "      5: A: METHOD_RETURN, location=[INNER_TYPE, WILDCARD]",
+                "      6: A: METHOD_RECEIVER",
+                "      7: A: METHOD_FORMAL_PARAMETER, param_index=0",
+                "      8: A: THROWS, type_index=0",
+                "      9: A: LOCAL_VARIABLE, {start_pc=2, length=20, index=3; start_pc=30, length=4, index=3}",
+                "      10: A: EXCEPTION_PARAMETER, exception_index=1",
+                "      11: A: INSTANCEOF, offset=13",
+                "      12: A: CAST, offset=3, type_index=0",
+                "      13: A: METHOD_REFERENCE_TYPE_ARGUMENT, offset=9, type_index=1",
+            ]
+        );
+    }
+}
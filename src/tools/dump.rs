@@ -0,0 +1,898 @@
+use crate::jvm::class::{
+    attribute::*, constant::*, constant_pool::*, error::ClassFormatError, field::Field,
+    instruction::Instruction, method::Method, Class,
+};
+use crate::utils::json::Json;
+
+// The whole class as JSON, for tooling that diffs or queries class structure: the constant
+// pool with every entry resolved, the fields and methods, decoded instructions and every
+// attribute. Keys follow the JVMS names of the class file items (chapter 4); constant pool
+// references are given both as their index and resolved.
+pub fn class_to_json(class: &Class) -> Result<Json, ClassFormatError> {
+    let dumper = Dumper {
+        constant_pool: &class.constant_pool,
+    };
+    dumper
+        .class(class)
+        .map_err(|e| e.within(format!("class {}", class.this_class)))
+}
+
+macro_rules! object {
+    ($($key:literal => $value:expr),* $(,)?) => {
+        Json::object(vec![$(($key, Json::from($value))),*])
+    };
+}
+
+impl From<&ClassRef> for Json {
+    fn from(class: &ClassRef) -> Json {
+        Json::String(class.name.clone())
+    }
+}
+
+impl From<&Indexed<String>> for Json {
+    fn from(s: &Indexed<String>) -> Json {
+        Json::String(s.value.clone())
+    }
+}
+
+struct Dumper<'a> {
+    constant_pool: &'a ConstantPool,
+}
+
+impl Dumper<'_> {
+    fn class(&self, class: &Class) -> Result<Json, ClassFormatError> {
+        let mut constant_pool: Vec<Json> = Vec::new();
+        for (index, _) in self.constant_pool.iter() {
+            constant_pool.push(self.constant(index)?);
+        }
+        let mut interfaces: Vec<Json> = Vec::new();
+        for interface in &class.interfaces {
+            interfaces.push(self.constant_pool.class_name(*interface)?.into());
+        }
+        Ok(object! {
+            "minor_version" => class.minor_version,
+            "major_version" => class.major_version,
+            "constant_pool" => constant_pool,
+            "access_flags" => flags(class.access_flags.0, class.access_flags.names()),
+            "this_class" => &class.this_class,
+            "super_class" => class.super_class.as_ref(),
+            "interfaces" => interfaces,
+            "fields" => self.list(&class.fields, |f| self.field(f))?,
+            "methods" => self.list(&class.methods, |m| self.method(m))?,
+            "attributes" => self.attributes(&class.attributes)?,
+        })
+    }
+
+    fn list<T>(
+        &self,
+        items: &[T],
+        to_json: impl Fn(&T) -> Result<Json, ClassFormatError>,
+    ) -> Result<Json, ClassFormatError> {
+        Ok(Json::Array(
+            items.iter().map(to_json).collect::<Result<_, _>>()?,
+        ))
+    }
+
+    fn field(&self, field: &Field) -> Result<Json, ClassFormatError> {
+        let attributes = self
+            .attributes(&field.attributes)
+            .map_err(|e| e.within(format!("field {}{}", field.name, field.descriptor)))?;
+        Ok(object! {
+            "access_flags" => flags(field.access_flags.0, field.access_flags.names()),
+            "name" => &field.name,
+            "descriptor" => &field.descriptor,
+            "attributes" => attributes,
+        })
+    }
+
+    fn method(&self, method: &Method) -> Result<Json, ClassFormatError> {
+        let attributes = self
+            .attributes(&method.attributes)
+            .map_err(|e| e.within(format!("method {}{}", method.name, method.descriptor)))?;
+        Ok(object! {
+            "access_flags" => flags(method.access_flags.0, method.access_flags.names()),
+            "name" => &method.name,
+            "descriptor" => &method.descriptor,
+            "attributes" => attributes,
+        })
+    }
+
+    // A constant pool entry with its raw operands and what they resolve to.
+    fn constant(&self, index: CpIndex) -> Result<Json, ClassFormatError> {
+        let pool = self.constant_pool;
+        let constant = pool.get(index)?;
+        let mut entries: Vec<(&str, Json)> = vec![
+            ("index", index.0.into()),
+            ("tag", constant_tag(constant).into()),
+        ];
+        match constant {
+            Constant::Utf8(c) => entries.push(("value", Json::utf16(&c.utf16))),
+            Constant::Integer(c) => entries.push(("value", c.bytes.into())),
+            Constant::Float(c) => entries.push(("value", c.bytes.into())),
+            Constant::Long(c) => entries.push(("value", c.bytes.into())),
+            Constant::Double(c) => entries.push(("value", c.bytes.into())),
+            Constant::Class(c) => entries.extend([
+                ("name_index", c.name_index.0.into()),
+                ("name", pool.utf8(c.name_index)?.into()),
+            ]),
+            Constant::String(c) => entries.extend([
+                ("string_index", c.string_index.0.into()),
+                ("value", self.utf16(c.string_index)?),
+            ]),
+            Constant::Fieldref(ConstantFieldref {
+                class_index,
+                name_and_type_index,
+            })
+            | Constant::Methodref(ConstantMethodref {
+                class_index,
+                name_and_type_index,
+            })
+            | Constant::InterfaceMethodref(ConstantInterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            }) => {
+                let member = pool.member_ref(index)?;
+                entries.extend([
+                    ("class_index", class_index.0.into()),
+                    ("name_and_type_index", name_and_type_index.0.into()),
+                    ("class", member.class_name.into()),
+                    ("name", member.name.into()),
+                    ("descriptor", member.descriptor.into()),
+                ]);
+            }
+            Constant::NameAndType(c) => entries.extend([
+                ("name_index", c.name_index.0.into()),
+                ("descriptor_index", c.descriptor_index.0.into()),
+                ("name", pool.utf8(c.name_index)?.into()),
+                ("descriptor", pool.utf8(c.descriptor_index)?.into()),
+            ]),
+            Constant::MethodHandle(c) => {
+                let handle = pool.method_handle(index)?;
+                entries.extend([
+                    ("reference_kind", c.reference_kind.into()),
+                    ("reference_index", c.reference_index.0.into()),
+                    ("reference_kind_name", handle.reference_kind.name().into()),
+                    ("class", handle.member.class_name.into()),
+                    ("name", handle.member.name.into()),
+                    ("descriptor", handle.member.descriptor.into()),
+                ]);
+            }
+            Constant::MethodType(c) => entries.extend([
+                ("descriptor_index", c.descriptor_index.0.into()),
+                ("descriptor", pool.utf8(c.descriptor_index)?.into()),
+            ]),
+            Constant::Dynamic(ConstantDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            })
+            | Constant::InvokeDynamic(ConstantInvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }) => {
+                let (name, descriptor) = pool.name_and_type(*name_and_type_index)?;
+                entries.extend([
+                    (
+                        "bootstrap_method_attr_index",
+                        (*bootstrap_method_attr_index).into(),
+                    ),
+                    ("name_and_type_index", name_and_type_index.0.into()),
+                    ("name", name.into()),
+                    ("descriptor", descriptor.into()),
+                ]);
+            }
+            Constant::Module(c) => entries.extend([
+                ("name_index", c.name_index.0.into()),
+                ("name", pool.module_name(index)?.into()),
+            ]),
+            Constant::Package(c) => entries.extend([
+                ("name_index", c.name_index.0.into()),
+                ("name", pool.package_name(index)?.into()),
+            ]),
+            Constant::Unusable => {}
+        }
+        Ok(Json::object(entries))
+    }
+
+    // The Utf8 constant at `index`, unpaired surrogates included.
+    fn utf16(&self, index: CpIndex) -> Result<Json, ClassFormatError> {
+        match self.constant_pool.get(index)? {
+            Constant::Utf8(c) => Ok(Json::utf16(&c.utf16)),
+            _ => self.constant_pool.utf8(index).map(Json::from),
+        }
+    }
+
+    fn attributes(&self, attributes: &[Attribute]) -> Result<Json, ClassFormatError> {
+        self.list(attributes, |a| {
+            self.attribute(a)
+                .map_err(|e| e.within(format!("attribute {}", a.name())))
+        })
+    }
+
+    fn attribute(&self, attribute: &Attribute) -> Result<Json, ClassFormatError> {
+        let mut entries: Vec<(&str, Json)> = vec![("name", attribute.name().into())];
+        match attribute {
+            Attribute::ConstantValue(a) => entries.extend([
+                ("constantvalue_index", a.constantvalue_index.0.into()),
+                ("value", self.constant(a.constantvalue_index)?),
+            ]),
+            Attribute::Code(a) => entries.extend(self.code(a)?),
+            Attribute::LineNumberTable(a) => {
+                let table = a.line_number_table.iter().map(|entry| {
+                    object! {
+                        "start_pc" => entry[0],
+                        "line_number" => entry[1],
+                    }
+                });
+                entries.push(("line_number_table", Json::array(table)));
+            }
+            Attribute::SourceFile(a) => entries.push(("sourcefile", (&a.sourcefile).into())),
+            Attribute::LocalVariableTable(a) => {
+                let table = a.local_variable_table.iter().map(|v| {
+                    object! {
+                        "start_pc" => v.start_pc,
+                        "length" => v.length,
+                        "name" => &v.name,
+                        "descriptor" => &v.descriptor,
+                        "index" => v.index,
+                    }
+                });
+                entries.push(("local_variable_table", Json::array(table)));
+            }
+            Attribute::StackMapTable(a) => {
+                let frames = a.offsets().into_iter().map(|(offset, f)| frame(offset, f));
+                entries.push(("entries", Json::array(frames)));
+            }
+            Attribute::Signature(a) => entries.push(("signature", (&a.signature).into())),
+            Attribute::RuntimeVisibleAnnotations(a) | Attribute::RuntimeInvisibleAnnotations(a) => {
+                entries.push((
+                    "annotations",
+                    Json::array(a.annotations.iter().map(annotation)),
+                ))
+            }
+            Attribute::RuntimeVisibleParameterAnnotations(a)
+            | Attribute::RuntimeInvisibleParameterAnnotations(a) => {
+                let parameters = a
+                    .parameter_annotations
+                    .iter()
+                    .map(|p| Json::array(p.iter().map(annotation)));
+                entries.push(("parameter_annotations", Json::array(parameters)));
+            }
+            Attribute::RuntimeVisibleTypeAnnotations(a)
+            | Attribute::RuntimeInvisibleTypeAnnotations(a) => {
+                let annotations = a.annotations.iter().map(type_annotation);
+                entries.push(("annotations", Json::array(annotations)));
+            }
+            Attribute::AnnotationDefault(a) => {
+                entries.push(("default_value", element_value(&a.default_value)))
+            }
+            Attribute::InnerClasses(a) => {
+                let classes = a.classes.iter().map(|c| {
+                    object! {
+                        "inner_class" => &c.inner_class,
+                        "outer_class" => c.outer_class.as_ref(),
+                        "inner_name" => c.inner_name.as_ref(),
+                        "inner_class_access_flags" => flags(
+                            c.inner_class_access_flags.0,
+                            c.inner_class_access_flags.names(),
+                        ),
+                    }
+                });
+                entries.push(("classes", Json::array(classes)));
+            }
+            Attribute::EnclosingMethod(a) => entries.extend([
+                ("class", (&a.class).into()),
+                ("method_name", a.method_name.as_ref().into()),
+                ("method_descriptor", a.method_descriptor.as_ref().into()),
+            ]),
+            Attribute::NestHost(a) => entries.push(("host_class", (&a.host_class).into())),
+            Attribute::NestMembers(a) | Attribute::PermittedSubclasses(a) => {
+                entries.push(("classes", Json::array(&a.classes)))
+            }
+            Attribute::Record(a) => {
+                let components = self.list(&a.components, |c| {
+                    Ok(object! {
+                        "name" => &c.name,
+                        "descriptor" => &c.descriptor,
+                        "attributes" => self.attributes(&c.attributes)?,
+                    })
+                })?;
+                entries.push(("components", components));
+            }
+            Attribute::Module(a) => entries.extend(module(a)),
+            Attribute::ModulePackages(a) => entries.push(("packages", Json::array(&a.packages))),
+            Attribute::ModuleMainClass(a) => entries.push(("main_class", (&a.main_class).into())),
+            Attribute::Exceptions(a) => {
+                entries.push(("exceptions", Json::array(&a.exception_index_table)))
+            }
+            Attribute::MethodParameters(a) => {
+                let parameters = a.parameters.iter().map(|p| {
+                    object! {
+                        "name" => p.name.as_ref(),
+                        "access_flags" => p.access_flags,
+                    }
+                });
+                entries.push(("parameters", Json::array(parameters)));
+            }
+            Attribute::LocalVariableTypeTable(a) => {
+                let table = a.local_variable_type_table.iter().map(|v| {
+                    object! {
+                        "start_pc" => v.start_pc,
+                        "length" => v.length,
+                        "name" => &v.name,
+                        "signature" => &v.signature,
+                        "index" => v.index,
+                    }
+                });
+                entries.push(("local_variable_type_table", Json::array(table)));
+            }
+            Attribute::Deprecated | Attribute::Synthetic => {}
+            Attribute::SourceDebugExtension(a) => {
+                entries.push(("debug_extension", a.text().into()))
+            }
+            Attribute::BootstrapMethods(a) => {
+                let methods = a.bootstrap_methods.iter().map(|m| {
+                    object! {
+                        "bootstrap_method" => method_handle(&m.bootstrap_method),
+                        "bootstrap_arguments" => Json::array(m.bootstrap_arguments.iter().map(loadable)),
+                    }
+                });
+                entries.push(("bootstrap_methods", Json::array(methods)));
+            }
+            Attribute::Unknown { bytes, .. } => {
+                let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                entries.push(("info", hex.into()));
+            }
+        }
+        Ok(Json::object(entries))
+    }
+
+    fn code<'k>(&self, code: &AttributeCode) -> Result<Vec<(&'k str, Json)>, ClassFormatError> {
+        let mut instructions: Vec<Json> = Vec::new();
+        for (pc, instruction) in Instruction::decode_all(&code.code)? {
+            instructions.push(
+                self.instruction(pc, &instruction)
+                    .map_err(|e| e.within(format!("instruction at pc {}", pc)))?,
+            );
+        }
+        let mut exception_table: Vec<Json> = Vec::new();
+        for e in &code.exception_table {
+            let catch_type = if e.catch_type.is_none() {
+                None
+            } else {
+                Some(self.constant_pool.class_name(e.catch_type)?)
+            };
+            exception_table.push(object! {
+                "start_pc" => e.start_pc,
+                "end_pc" => e.end_pc,
+                "handler_pc" => e.handler_pc,
+                "catch_type" => catch_type,
+            });
+        }
+        Ok(vec![
+            ("max_stack", code.max_stack.into()),
+            ("max_locals", code.max_locals.into()),
+            ("code_length", code.code.len().into()),
+            ("instructions", instructions.into()),
+            ("exception_table", exception_table.into()),
+            ("attributes", self.attributes(&code.attributes)?),
+        ])
+    }
+
+    // The pc and mnemonic of an instruction with its operands; constants are resolved and
+    // branch offsets turned into absolute targets.
+    fn instruction(&self, pc: usize, instruction: &Instruction) -> Result<Json, ClassFormatError> {
+        let mut entries: Vec<(&str, Json)> =
+            vec![("pc", pc.into()), ("opcode", instruction.name().into())];
+        match instruction {
+            Instruction::Ldc(index)
+            | Instruction::LdcW(index)
+            | Instruction::Ldc2W(index)
+            | Instruction::Getstatic(index)
+            | Instruction::Putstatic(index)
+            | Instruction::Getfield(index)
+            | Instruction::Putfield(index)
+            | Instruction::Invokevirtual(index)
+            | Instruction::Invokespecial(index)
+            | Instruction::Invokestatic(index)
+            | Instruction::Invokedynamic(index)
+            | Instruction::New(index)
+            | Instruction::Anewarray(index)
+            | Instruction::Checkcast(index)
+            | Instruction::Instanceof(index) => entries.push(("constant", self.constant(*index)?)),
+            Instruction::Invokeinterface { index, count } => entries.extend([
+                ("constant", self.constant(*index)?),
+                ("count", (*count).into()),
+            ]),
+            Instruction::Multianewarray { index, dimensions } => entries.extend([
+                ("constant", self.constant(*index)?),
+                ("dimensions", (*dimensions).into()),
+            ]),
+            Instruction::Bipush(value) => entries.push(("value", (*value).into())),
+            Instruction::Sipush(value) => entries.push(("value", (*value).into())),
+            Instruction::Iload(index)
+            | Instruction::Lload(index)
+            | Instruction::Fload(index)
+            | Instruction::Dload(index)
+            | Instruction::Aload(index)
+            | Instruction::Istore(index)
+            | Instruction::Lstore(index)
+            | Instruction::Fstore(index)
+            | Instruction::Dstore(index)
+            | Instruction::Astore(index)
+            | Instruction::Ret(index) => entries.push(("index", (*index).into())),
+            Instruction::Iinc { index, value } => {
+                entries.extend([("index", (*index).into()), ("value", (*value).into())])
+            }
+            Instruction::Newarray(atype) => entries.push(("atype", atype.name().into())),
+            Instruction::Tableswitch { low, high, .. } => {
+                let targets = instruction.branch_targets(pc);
+                entries.extend([
+                    ("low", (*low).into()),
+                    ("high", (*high).into()),
+                    ("default", targets[0].into()),
+                    ("targets", Json::array(targets[1..].to_vec())),
+                ]);
+            }
+            Instruction::Lookupswitch { pairs, .. } => {
+                let targets = instruction.branch_targets(pc);
+                let pairs = pairs.iter().zip(&targets[1..]).map(|((key, _), target)| {
+                    object! {
+                        "match" => *key,
+                        "target" => *target,
+                    }
+                });
+                entries.extend([
+                    ("default", targets[0].into()),
+                    ("pairs", Json::array(pairs)),
+                ]);
+            }
+            _ => {
+                if let Some(target) = instruction.branch_targets(pc).first() {
+                    entries.push(("target", (*target).into()));
+                }
+            }
+        }
+        Ok(Json::object(entries))
+    }
+}
+
+fn flags(value: u16, names: Vec<&str>) -> Json {
+    object! {
+        "value" => value,
+        "names" => names,
+    }
+}
+
+fn constant_tag(constant: &Constant) -> &'static str {
+    match constant {
+        Constant::Utf8(_) => "Utf8",
+        Constant::Integer(_) => "Integer",
+        Constant::Float(_) => "Float",
+        Constant::Long(_) => "Long",
+        Constant::Double(_) => "Double",
+        Constant::Class(_) => "Class",
+        Constant::String(_) => "String",
+        Constant::Fieldref(_) => "Fieldref",
+        Constant::Methodref(_) => "Methodref",
+        Constant::InterfaceMethodref(_) => "InterfaceMethodref",
+        Constant::NameAndType(_) => "NameAndType",
+        Constant::MethodHandle(_) => "MethodHandle",
+        Constant::MethodType(_) => "MethodType",
+        Constant::Dynamic(_) => "Dynamic",
+        Constant::InvokeDynamic(_) => "InvokeDynamic",
+        Constant::Module(_) => "Module",
+        Constant::Package(_) => "Package",
+        Constant::Unusable => "Unusable",
+    }
+}
+
+fn method_handle(handle: &MethodHandle) -> Json {
+    object! {
+        "reference_kind" => handle.reference_kind.name(),
+        "class" => &handle.member.class_name,
+        "name" => &handle.member.name,
+        "descriptor" => &handle.member.descriptor,
+    }
+}
+
+fn loadable(constant: &LoadableConstant) -> Json {
+    match constant {
+        LoadableConstant::Integer(v) => object! { "tag" => "Integer", "value" => *v },
+        LoadableConstant::Float(v) => object! { "tag" => "Float", "value" => *v },
+        LoadableConstant::Long(v) => object! { "tag" => "Long", "value" => *v },
+        LoadableConstant::Double(v) => object! { "tag" => "Double", "value" => *v },
+        LoadableConstant::Class(name) => object! { "tag" => "Class", "name" => name },
+        LoadableConstant::String(value) => object! { "tag" => "String", "value" => value },
+        LoadableConstant::MethodHandle(handle) => {
+            let mut json = method_handle(handle);
+            if let Json::Object(entries) = &mut json {
+                entries.insert(0, (String::from("tag"), Json::from("MethodHandle")));
+            }
+            json
+        }
+        LoadableConstant::MethodType(descriptor) => {
+            object! { "tag" => "MethodType", "descriptor" => descriptor }
+        }
+        LoadableConstant::Dynamic(dynamic) => object! {
+            "tag" => "Dynamic",
+            "bootstrap_method_attr_index" => dynamic.bootstrap_method_attr_index,
+            "name" => &dynamic.name,
+            "descriptor" => &dynamic.descriptor,
+        },
+    }
+}
+
+// Each frame also gets the offset it applies to, which the class file only has as a delta.
+fn frame(offset: u32, frame: &StackMapFrame) -> Json {
+    let types = |types: &[VerificationTypeInfo]| Json::array(types.iter().map(verification_type));
+    let (kind, locals, stack) = match frame {
+        StackMapFrame::Same { .. } => ("same", None, None),
+        StackMapFrame::SameExtended { .. } => ("same_frame_extended", None, None),
+        StackMapFrame::SameLocals1StackItem { stack, .. } => (
+            "same_locals_1_stack_item",
+            None,
+            Some(types(std::slice::from_ref(stack))),
+        ),
+        StackMapFrame::SameLocals1StackItemExtended { stack, .. } => (
+            "same_locals_1_stack_item_extended",
+            None,
+            Some(types(std::slice::from_ref(stack))),
+        ),
+        StackMapFrame::Chop { .. } => ("chop", None, None),
+        StackMapFrame::Append { locals, .. } => ("append", Some(types(locals)), None),
+        StackMapFrame::Full { locals, stack, .. } => {
+            ("full_frame", Some(types(locals)), Some(types(stack)))
+        }
+    };
+    let mut entries: Vec<(&str, Json)> = vec![
+        ("frame_type", frame.frame_type().into()),
+        ("kind", kind.into()),
+        ("offset_delta", frame.offset_delta().into()),
+        ("offset", offset.into()),
+    ];
+    if let Some(locals) = locals {
+        entries.push(("locals", locals));
+    }
+    if let Some(stack) = stack {
+        entries.push(("stack", stack));
+    }
+    Json::object(entries)
+}
+
+fn verification_type(info: &VerificationTypeInfo) -> Json {
+    match info {
+        VerificationTypeInfo::Top => object! { "tag" => "Top" },
+        VerificationTypeInfo::Integer => object! { "tag" => "Integer" },
+        VerificationTypeInfo::Float => object! { "tag" => "Float" },
+        VerificationTypeInfo::Double => object! { "tag" => "Double" },
+        VerificationTypeInfo::Long => object! { "tag" => "Long" },
+        VerificationTypeInfo::Null => object! { "tag" => "Null" },
+        VerificationTypeInfo::UninitializedThis => object! { "tag" => "UninitializedThis" },
+        VerificationTypeInfo::Object(class) => object! { "tag" => "Object", "class" => class },
+        VerificationTypeInfo::Uninitialized(offset) => {
+            object! { "tag" => "Uninitialized", "offset" => *offset }
+        }
+    }
+}
+
+fn annotation(annotation: &Annotation) -> Json {
+    let pairs = annotation.element_value_pairs.iter().map(|pair| {
+        object! {
+            "name" => &pair.name,
+            "value" => element_value(&pair.value),
+        }
+    });
+    object! {
+        "type" => &annotation.type_descriptor,
+        "element_value_pairs" => Json::array(pairs),
+    }
+}
+
+fn element_value(value: &ElementValue) -> Json {
+    match value {
+        ElementValue::Const(c) => {
+            let (tag, value) = match &c.value {
+                ConstValue::Byte(v) => ("B", Json::from(*v)),
+                ConstValue::Char(v) => ("C", Json::from(*v)),
+                ConstValue::Double(v) => ("D", Json::from(*v)),
+                ConstValue::Float(v) => ("F", Json::from(*v)),
+                ConstValue::Int(v) => ("I", Json::from(*v)),
+                ConstValue::Long(v) => ("J", Json::from(*v)),
+                ConstValue::Short(v) => ("S", Json::from(*v)),
+                ConstValue::Boolean(v) => ("Z", Json::from(*v)),
+                ConstValue::String(v) => ("s", Json::from(v)),
+            };
+            object! { "tag" => tag, "value" => value }
+        }
+        ElementValue::Enum {
+            type_name,
+            const_name,
+        } => object! {
+            "tag" => "e",
+            "type_name" => type_name,
+            "const_name" => const_name,
+        },
+        ElementValue::Class(class_info) => object! { "tag" => "c", "class_info" => class_info },
+        ElementValue::Annotation(a) => object! { "tag" => "@", "annotation" => annotation(a) },
+        ElementValue::Array(values) => object! {
+            "tag" => "[",
+            "values" => Json::array(values.iter().map(element_value)),
+        },
+    }
+}
+
+fn type_annotation(type_annotation: &TypeAnnotation) -> Json {
+    let target_info = match &type_annotation.target_info {
+        TargetInfo::TypeParameter {
+            type_parameter_index,
+        } => object! { "type_parameter_index" => *type_parameter_index },
+        TargetInfo::Supertype { supertype_index } => {
+            object! { "supertype_index" => *supertype_index }
+        }
+        TargetInfo::TypeParameterBound {
+            type_parameter_index,
+            bound_index,
+        } => object! {
+            "type_parameter_index" => *type_parameter_index,
+            "bound_index" => *bound_index,
+        },
+        TargetInfo::Empty => Json::Object(Vec::new()),
+        TargetInfo::FormalParameter {
+            formal_parameter_index,
+        } => object! { "formal_parameter_index" => *formal_parameter_index },
+        TargetInfo::Throws { throws_type_index } => {
+            object! { "throws_type_index" => *throws_type_index }
+        }
+        TargetInfo::Localvar(table) => {
+            let table = table.iter().map(|v| {
+                object! {
+                    "start_pc" => v.start_pc,
+                    "length" => v.length,
+                    "index" => v.index,
+                }
+            });
+            object! { "table" => Json::array(table) }
+        }
+        TargetInfo::Catch {
+            exception_table_index,
+        } => object! { "exception_table_index" => *exception_table_index },
+        TargetInfo::Offset { offset } => object! { "offset" => *offset },
+        TargetInfo::TypeArgument {
+            offset,
+            type_argument_index,
+        } => object! {
+            "offset" => *offset,
+            "type_argument_index" => *type_argument_index,
+        },
+    };
+    let type_path = type_annotation.type_path.iter().map(|entry| {
+        object! {
+            "type_path_kind" => entry.type_path_kind,
+            "type_argument_index" => entry.type_argument_index,
+        }
+    });
+    object! {
+        "target_type" => type_annotation.target_type,
+        "target_info" => target_info,
+        "type_path" => Json::array(type_path),
+        "annotation" => annotation(&type_annotation.annotation),
+    }
+}
+
+fn module(module: &AttributeModule) -> Vec<(&'static str, Json)> {
+    let requires = module.requires.iter().map(|r| {
+        object! {
+            "requires" => &r.module,
+            "requires_flags" => r.flags,
+            "requires_version" => r.version.as_ref(),
+        }
+    });
+    let exports = |exports: &[ModuleExports]| {
+        Json::array(exports.iter().map(|e| {
+            object! {
+                "package" => &e.package,
+                "flags" => e.flags,
+                "to" => Json::array(&e.to),
+            }
+        }))
+    };
+    let provides = module.provides.iter().map(|p| {
+        object! {
+            "provides" => &p.service,
+            "provides_with" => Json::array(&p.with),
+        }
+    });
+    vec![
+        ("module_name", (&module.module_name).into()),
+        ("module_flags", module.module_flags.into()),
+        ("module_version", module.module_version.as_ref().into()),
+        ("requires", Json::array(requires)),
+        ("exports", exports(&module.exports)),
+        ("opens", exports(&module.opens)),
+        ("uses", Json::array(&module.uses)),
+        ("provides", Json::array(provides)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        access_flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+        class_builder::ClassBuilder,
+    };
+
+    fn constants_class() -> Class {
+        let mut builder =
+            ClassBuilder::new(ClassAccessFlags::PUBLIC, "C", Some("java/lang/Object")).unwrap();
+        let flags = FieldAccessFlags::STATIC | FieldAccessFlags::FINAL;
+        let text: Vec<u16> = vec![0x22, 0x0a, 0x01, 0xd800];
+        let constants = [
+            ("NAN", "F", builder.constant_pool().float(f32::NAN).unwrap()),
+            (
+                "MINUS_INFINITY",
+                "D",
+                builder.constant_pool().double(f64::NEG_INFINITY).unwrap(),
+            ),
+            (
+                "TEXT",
+                "Ljava/lang/String;",
+                builder.constant_pool().string_utf16(&text).unwrap(),
+            ),
+        ];
+        for (name, descriptor, constantvalue_index) in constants {
+            builder
+                .field(flags, name, descriptor)
+                .unwrap()
+                .attributes
+                .push(Attribute::ConstantValue(AttributeConstantValue {
+                    constantvalue_index,
+                }));
+        }
+        builder
+            .method_with_code(
+                MethodAccessFlags::STATIC,
+                "text",
+                "()Ljava/lang/String;",
+                |code| {
+                    let null = code.new_label();
+                    code.ldc(&LoadableConstant::Float(f32::INFINITY))?;
+                    code.emit(Instruction::Pop)?;
+                    let text = code.constant_pool().string_utf16(&text)?;
+                    code.emit(Instruction::Ldc(text))?;
+                    code.emit(Instruction::Dup)?;
+                    code.jump(Instruction::Ifnull, null)?;
+                    code.bind(null)?;
+                    code.emit(Instruction::Areturn)
+                },
+            )
+            .unwrap();
+        builder.build().unwrap()
+    }
+
+    fn get<'a>(json: &'a Json, key: &str) -> &'a Json {
+        match json {
+            Json::Object(entries) => &entries.iter().find(|(k, _)| k == key).unwrap().1,
+            other => panic!("{} of {}", key, other),
+        }
+    }
+
+    fn at(json: &Json, i: usize) -> &Json {
+        match json {
+            Json::Array(values) => &values[i],
+            other => panic!("[{}] of {}", i, other),
+        }
+    }
+
+    #[test]
+    fn class_keys_follow_the_class_file() {
+        let json = class_to_json(&constants_class()).unwrap();
+        let Json::Object(entries) = &json else {
+            panic!("{}", json);
+        };
+        let keys: Vec<&str> = entries.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "minor_version",
+                "major_version",
+                "constant_pool",
+                "access_flags",
+                "this_class",
+                "super_class",
+                "interfaces",
+                "fields",
+                "methods",
+                "attributes",
+            ]
+        );
+        assert_eq!(
+            get(&json, "access_flags").to_string(),
+            r#"{"value":1,"names":["ACC_PUBLIC"]}"#
+        );
+        assert_eq!(
+            at(get(&json, "constant_pool"), 1).to_string(),
+            r#"{"index":2,"tag":"Class","name_index":1,"name":"C"}"#
+        );
+        assert_eq!(get(&json, "super_class"), &Json::from("java/lang/Object"));
+    }
+
+    #[test]
+    fn constant_values_with_non_finite_floats_and_escaped_strings() {
+        let json = class_to_json(&constants_class()).unwrap();
+        let fields = get(&json, "fields");
+        let constant_value = |i: usize| at(get(at(fields, i), "attributes"), 0).to_string();
+        assert_eq!(
+            constant_value(0),
+            r#"{"name":"ConstantValue","constantvalue_index":5,"value":{"index":5,"tag":"Float","value":"NaN"}}"#
+        );
+        assert_eq!(
+            constant_value(1),
+            r#"{"name":"ConstantValue","constantvalue_index":6,"value":{"index":6,"tag":"Double","value":"-Infinity"}}"#
+        );
+        // The pool keeps the exact string: a quote, a newline, U+0001 and a lone surrogate.
+        assert_eq!(
+            constant_value(2),
+            r#"{"name":"ConstantValue","constantvalue_index":9,"value":{"index":9,"tag":"String","string_index":8,"value":"\"\n\u0001\ud800"}}"#
+        );
+        assert_eq!(
+            at(get(&json, "constant_pool"), 6).to_string(),
+            r#"{"index":8,"tag":"Utf8","value":"\"\n\u0001\ud800"}"#
+        );
+    }
+
+    #[test]
+    fn code_with_resolved_constants_and_absolute_targets() {
+        let json = class_to_json(&constants_class()).unwrap();
+        let code = at(get(at(get(&json, "methods"), 0), "attributes"), 0);
+        assert_eq!(
+            code.pretty(),
+            r#"{
+  "name": "Code",
+  "max_stack": 2,
+  "max_locals": 0,
+  "code_length": 10,
+  "instructions": [
+    {
+      "pc": 0,
+      "opcode": "ldc",
+      "constant": {
+        "index": 16,
+        "tag": "Float",
+        "value": "Infinity"
+      }
+    },
+    {
+      "pc": 2,
+      "opcode": "pop"
+    },
+    {
+      "pc": 3,
+      "opcode": "ldc",
+      "constant": {
+        "index": 9,
+        "tag": "String",
+        "string_index": 8,
+        "value": "\"\n\u0001\ud800"
+      }
+    },
+    {
+      "pc": 5,
+      "opcode": "dup"
+    },
+    {
+      "pc": 6,
+      "opcode": "ifnull",
+      "target": 9
+    },
+    {
+      "pc": 9,
+      "opcode": "areturn"
+    }
+  ],
+  "exception_table": [],
+  "attributes": []
+}"#
+        );
+    }
+}
@@ -0,0 +1,153 @@
+pub mod asm;
+pub mod compat;
+pub mod diff;
+pub mod disasm;
+pub mod dump;
+
+use std::{collections::BTreeMap, fs, io::BufReader, path::Path};
+
+use crate::jvm::class::{version::VersionSupport, Class};
+
+// Reads the class a command line tool operates on. `target` is a .class file, an entry of a
+// jar written as "app.jar!/com/example/Main.class", or a class name such as "com.example.Main"
+// that is looked up on the classpath. Like the JVM, the lookup only opens the file or jar entry
+// the name maps to in each classpath entry, and the first one found is the class. Only the
+// classpath lookup applies `version_support`; a named file is read whatever its version, as
+// javap does.
+pub fn read_class(
+    target: &str,
+    class_path: &str,
+    version_support: VersionSupport,
+) -> Result<Class, String> {
+    if let Some((jar_path, entry_name)) = target.split_once("!/") {
+        let jar_file = fs::File::open(jar_path).map_err(|e| format!("{}: {}", jar_path, e))?;
+        let mut jar_archive = zip::ZipArchive::new(BufReader::new(jar_file))
+            .map_err(|e| format!("{}: {}", jar_path, e))?;
+        let entry = jar_archive
+            .by_name(entry_name)
+            .map_err(|e| format!("{}: {}", target, e))?;
+        return Class::new(entry).map_err(|e| format!("{}: {}", target, e));
+    }
+    if target.ends_with(".class") {
+        let file = fs::File::open(target).map_err(|e| format!("{}: {}", target, e))?;
+        return Class::new(file).map_err(|e| format!("{}: {}", target, e));
+    }
+    let entry_name = format!("{}.class", target.replace('.', "/"));
+    for path in class_path.split(';') {
+        let (location, class) = if path.ends_with(".jar") && Path::new(path).is_file() {
+            // A jar that cannot be opened cannot hold the class either.
+            let jar_archive = fs::File::open(path)
+                .ok()
+                .and_then(|jar_file| zip::ZipArchive::new(BufReader::new(jar_file)).ok());
+            let Some(mut jar_archive) = jar_archive else {
+                continue;
+            };
+            let location = format!("{}!/{}", path, entry_name);
+            let class = match jar_archive.by_name(&entry_name) {
+                Ok(entry) => Class::new(entry),
+                Err(zip::result::ZipError::FileNotFound) => continue,
+                Err(e) => return Err(format!("{}: {}", location, e)),
+            };
+            (location, class)
+        } else {
+            let file_path = Path::new(path).join(&entry_name);
+            if !file_path.is_file() {
+                continue;
+            }
+            let location = file_path.display().to_string();
+            let file = fs::File::open(&file_path).map_err(|e| format!("{}: {}", location, e))?;
+            (location, Class::new(file))
+        };
+        return class
+            .and_then(|class| {
+                version_support
+                    .check(class.version())
+                    .map_err(|e| e.within(format!("class {}", class.this_class)))?;
+                Ok(class)
+            })
+            .map_err(|e| format!("{}: {}", location, e));
+    }
+    Err(format!(
+        "class {} not found in classpath {}",
+        target, class_path
+    ))
+}
+
+// Reads every class of a jar, keyed by class name. The versioned entries of a multi-release
+// jar (META-INF/versions/) are left out so each class appears once.
+pub fn read_jar(path: &str) -> Result<BTreeMap<String, Class>, String> {
+    let jar_file = fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut jar_archive =
+        zip::ZipArchive::new(BufReader::new(jar_file)).map_err(|e| format!("{}: {}", path, e))?;
+    let mut classes = BTreeMap::new();
+    for i in 0..jar_archive.len() {
+        let entry = jar_archive
+            .by_index(i)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let entry_name = entry.name().to_string();
+        if !entry.is_file()
+            || !entry_name.ends_with(".class")
+            || entry_name.starts_with("META-INF/versions/")
+        {
+            continue;
+        }
+        let class = Class::new(entry).map_err(|e| format!("{}!/{}: {}", path, entry_name, e))?;
+        classes.insert(class.this_class.to_string(), class);
+    }
+    Ok(classes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class::{
+        access_flags::ClassAccessFlags, class_builder::ClassBuilder, version::ClassVersion,
+    };
+    use std::io::Write;
+
+    fn class_bytes(name: &str, major: u16) -> Vec<u8> {
+        let mut builder =
+            ClassBuilder::new(ClassAccessFlags::PUBLIC, name, Some("java/lang/Object")).unwrap();
+        builder.version(ClassVersion::new(major, 0));
+        builder.build().unwrap().to_bytes().unwrap()
+    }
+
+    #[test]
+    fn class_names_only_read_their_own_file_or_jar_entry() {
+        let dir =
+            std::env::temp_dir().join(format!("sth_like_jvm-read_class-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("classes/p")).unwrap();
+        fs::write(dir.join("classes/p/A.class"), class_bytes("p/A", 52)).unwrap();
+        fs::write(dir.join("classes/p/Broken.class"), b"\xca\xfe").unwrap();
+        fs::write(dir.join("classes/p/New.class"), class_bytes("p/New", 99)).unwrap();
+        fs::write(dir.join("broken.jar"), b"not a zip").unwrap();
+        let mut jar = zip::ZipWriter::new(fs::File::create(dir.join("b.jar")).unwrap());
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        jar.start_file("p/A.class", options).unwrap();
+        jar.write_all(&class_bytes("p/A", 49)).unwrap();
+        jar.start_file("p/B.class", options).unwrap();
+        jar.write_all(&class_bytes("p/B", 52)).unwrap();
+        jar.finish().unwrap();
+        let class_path = format!(
+            "{0}/missing;{0}/broken.jar;{0}/classes;{0}/b.jar",
+            dir.display()
+        );
+        let read = |name: &str| read_class(name, &class_path, VersionSupport::default());
+
+        // The first classpath entry holding the class wins, and the broken files and jars
+        // around it are never opened.
+        assert_eq!(read("p.A").unwrap().major_version, 52);
+        assert_eq!(read("p.B").unwrap().this_class, "p/B");
+        let broken = read("p.Broken").unwrap_err();
+        assert!(broken.starts_with(&format!("{}/classes/p/Broken.class: ", dir.display())));
+        let new = read("p.New").unwrap_err();
+        assert!(new.contains("UnsupportedClassVersionError"), "{}", new);
+        assert_eq!(
+            read("p.C").unwrap_err(),
+            format!("class p.C not found in classpath {}", class_path)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,8 @@
 use std::io::{BufReader, Read};
 
+use crate::jvm::class::error::ClassFormatError;
+use crate::utils::modified_utf8;
+
 pub struct BytecodeReader {
     pub offset: usize,
     pub size: usize,
@@ -14,72 +17,77 @@ impl BytecodeReader {
             data: raw_data,
         }
     }
-    pub fn read_from_file<T>(file: T) -> BytecodeReader
+    pub fn read_from_file<T>(file: T) -> Result<BytecodeReader, ClassFormatError>
     where
         T: Read,
     {
         let mut buffer: Vec<u8> = Vec::new();
-        BufReader::new(file).read_to_end(&mut buffer);
-        BytecodeReader::new(buffer)
+        BufReader::new(file)
+            .read_to_end(&mut buffer)
+            .map_err(|e| ClassFormatError::new(format!("failed to read class file: {}", e)))?;
+        Ok(BytecodeReader::new(buffer))
     }
 
     pub fn reset(&mut self) {
         self.offset = 0;
     }
 
-    pub fn read_as_vec(&mut self, step: usize) -> Vec<u8> {
+    pub fn read_as_vec(&mut self, step: usize) -> Result<Vec<u8>, ClassFormatError> {
         let end: usize = self.offset + step;
         if end > self.size {
-            panic!("BytecodeReader: incorrect length.")
+            return Err(ClassFormatError::at(
+                self.offset,
+                format!(
+                    "unexpected end of data: need {} bytes, {} left",
+                    step,
+                    self.size - self.offset
+                ),
+            ));
         }
         let v = self.data[self.offset..end].to_vec();
         self.offset = end;
-        v
+        Ok(v)
     }
 
-    pub fn read_as_string(&mut self, step: usize) -> String {
-        let utf8 = self.read_as_vec(step);
-        let s = String::from_utf8(utf8).unwrap();
-        s
+    // Decodes `step` bytes of modified UTF-8 into UTF-16 code units.
+    pub fn read_as_utf16(&mut self, step: usize) -> Result<Vec<u16>, ClassFormatError> {
+        let start = self.offset;
+        let utf8 = self.read_as_vec(step)?;
+        modified_utf8::decode(&utf8).map_err(|(index, reason)| {
+            ClassFormatError::at(
+                start + index,
+                format!("malformed modified UTF-8: {}", reason),
+            )
+        })
     }
 
-    pub fn read_as_u64(&mut self, step: usize) -> u64 {
-        if step < 1 || step > 8 {
-            return 0_u64;
+    pub fn read_as_u64(&mut self, step: usize) -> Result<u64, ClassFormatError> {
+        if !(1..=8).contains(&step) {
+            return Ok(0_u64);
         }
-        let mut result = self.read_as_vec(step);
-        result.reverse();
-        result.resize(8, 0u8);
-        u64::from_le_bytes(
-            result
-                .as_slice()
-                .try_into()
-                .expect("Failed try result into u64."),
-        )
+        let bytes = self.read_as_vec(step)?;
+        Ok(bytes
+            .iter()
+            .fold(0_u64, |result, byte| (result << 8) | *byte as u64))
     }
 
-    pub fn u8(&mut self) -> u8 {
-        self.read_as_u64(1) as u8
+    pub fn u8(&mut self) -> Result<u8, ClassFormatError> {
+        Ok(self.read_as_u64(1)? as u8)
     }
-    pub fn u16(&mut self) -> u16 {
-        self.read_as_u64(2) as u16
+    pub fn u16(&mut self) -> Result<u16, ClassFormatError> {
+        Ok(self.read_as_u64(2)? as u16)
     }
-    pub fn u32(&mut self) -> u32 {
-        self.read_as_u64(4) as u32
+    pub fn u32(&mut self) -> Result<u32, ClassFormatError> {
+        Ok(self.read_as_u64(4)? as u32)
     }
 
-    pub fn info(&mut self) {
-        let t = self.offset;
-        self.reset();
-        let mut i = 1;
-        while i <= self.size {
-            print!("{:0>2X} ", self.u8());
-            if i % 16 == 0 {
-                print!("\n");
+    pub fn info(&self) {
+        for (i, byte) in self.data.iter().enumerate() {
+            print!("{:0>2X} ", byte);
+            if (i + 1) % 16 == 0 {
+                println!();
             }
-            i += 1;
         }
-        print!("\n");
-        self.offset = t;
+        println!();
     }
 }
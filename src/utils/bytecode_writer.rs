@@ -0,0 +1,62 @@
+use crate::jvm::class::error::ClassFormatError;
+
+// The counterpart of BytecodeReader: big-endian u1/u2/u4 values appended to a buffer.
+#[derive(Debug, Default)]
+pub struct BytecodeWriter {
+    pub data: Vec<u8>,
+}
+
+impl BytecodeWriter {
+    pub fn new() -> BytecodeWriter {
+        BytecodeWriter { data: Vec::new() }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    // Writes a u2 count of `len` items, failing if it does not fit.
+    pub fn count(&mut self, len: usize, what: &str) -> Result<(), ClassFormatError> {
+        let count = u16::try_from(len).map_err(|_| {
+            ClassFormatError::new(format!("{} {} do not fit in a u2 count", len, what))
+        })?;
+        self.u16(count);
+        Ok(())
+    }
+
+    // Like count, for the u1 counts of MethodParameters and parameter annotations.
+    pub fn count_u8(&mut self, len: usize, what: &str) -> Result<(), ClassFormatError> {
+        let count = u8::try_from(len).map_err(|_| {
+            ClassFormatError::new(format!("{} {} do not fit in a u1 count", len, what))
+        })?;
+        self.u8(count);
+        Ok(())
+    }
+
+    // Overwrites the u2 at `offset`, used for branch offsets once the target is known.
+    pub fn patch_u16(&mut self, offset: usize, value: u16) {
+        self.data[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+    }
+
+    // Overwrites the u4 at `offset`, used for lengths that are known only after the data.
+    pub fn patch_u32(&mut self, offset: usize, value: u32) {
+        self.data[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+    }
+}
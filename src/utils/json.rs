@@ -0,0 +1,260 @@
+use std::fmt::{self, Write};
+
+// A JSON value (RFC 8259). Objects keep their keys in insertion order and numbers keep the
+// text they were written with, so a float constant prints as its shortest f32 form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    // A string with unpaired surrogates, which JSON can only write as \u escapes (RFC 8259
+    // section 8.2); made by Json::utf16.
+    Utf16(Vec<u16>),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object<K: Into<String>>(entries: Vec<(K, Json)>) -> Json {
+        Json::Object(entries.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    // A string of UTF-16 code units, such as a Utf8 constant, kept exact even when it holds
+    // unpaired surrogates.
+    pub fn utf16(units: &[u16]) -> Json {
+        match String::from_utf16(units) {
+            Ok(s) => Json::String(s),
+            Err(_) => Json::Utf16(units.to_vec()),
+        }
+    }
+
+    pub fn array<T: Into<Json>>(values: impl IntoIterator<Item = T>) -> Json {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+
+    // Indented by two spaces per level; Display gives the compact form.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Some(0)).unwrap();
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: Option<usize>) -> fmt::Result {
+        let newline = |out: &mut String, level: usize| -> fmt::Result {
+            match indent {
+                Some(_) => write!(out, "\n{:width$}", "", width = level * 2),
+                None => Ok(()),
+            }
+        };
+        let inner = indent.map(|level| level + 1);
+        match self {
+            Json::Null => out.write_str("null"),
+            Json::Bool(b) => write!(out, "{}", b),
+            Json::Number(n) => out.write_str(n),
+            Json::String(s) => write_string(out, s.chars().map(Ok)),
+            Json::Utf16(units) => write_string(
+                out,
+                char::decode_utf16(units.iter().copied())
+                    .map(|c| c.map_err(|e| e.unpaired_surrogate())),
+            ),
+            Json::Array(values) if values.is_empty() => out.write_str("[]"),
+            Json::Array(values) => {
+                out.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        out.write_char(',')?;
+                    }
+                    newline(out, inner.unwrap_or(0))?;
+                    value.write(out, inner)?;
+                }
+                newline(out, indent.unwrap_or(0))?;
+                out.write_char(']')
+            }
+            Json::Object(entries) if entries.is_empty() => out.write_str("{}"),
+            Json::Object(entries) => {
+                out.write_char('{')?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        out.write_char(',')?;
+                    }
+                    newline(out, inner.unwrap_or(0))?;
+                    write_string(out, key.chars().map(Ok))?;
+                    out.write_str(if indent.is_some() { ": " } else { ":" })?;
+                    value.write(out, inner)?;
+                }
+                newline(out, indent.unwrap_or(0))?;
+                out.write_char('}')
+            }
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, None)?;
+        f.write_str(&out)
+    }
+}
+
+// The characters of a string, or the unpaired surrogates in it.
+fn write_string(out: &mut String, chars: impl Iterator<Item = Result<char, u16>>) -> fmt::Result {
+    out.write_char('"')?;
+    for c in chars {
+        let c = match c {
+            Ok(c) => c,
+            Err(surrogate) => {
+                write!(out, "\\u{:04x}", surrogate)?;
+                continue;
+            }
+        };
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            '\u{8}' => out.write_str("\\b")?,
+            '\u{c}' => out.write_str("\\f")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+macro_rules! json_from_integer {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Json {
+            fn from(n: $t) -> Json {
+                Json::Number(n.to_string())
+            }
+        })*
+    };
+}
+
+json_from_integer!(u8, u16, u32, u64, usize, i8, i16, i32, i64);
+
+// NaN and the infinities have no JSON number, so they are written as the strings "NaN",
+// "Infinity" and "-Infinity".
+macro_rules! json_from_float {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Json {
+            fn from(n: $t) -> Json {
+                if n.is_nan() {
+                    Json::from("NaN")
+                } else if n.is_infinite() {
+                    Json::from(if n > 0.0 { "Infinity" } else { "-Infinity" })
+                } else {
+                    Json::Number(format!("{:?}", n))
+                }
+            }
+        })*
+    };
+}
+
+json_from_float!(f32, f64);
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::String(String::from(s))
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Json {
+        Json::String(s)
+    }
+}
+
+impl From<&String> for Json {
+    fn from(s: &String) -> Json {
+        Json::String(s.clone())
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Json {
+        Json::array(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_and_pretty_forms() {
+        let json = Json::object(vec![
+            ("name", Json::from("A")),
+            ("flags", Json::array([1u16, 32])),
+            ("empty", Json::array(Vec::<Json>::new())),
+            ("none", Json::from(None::<&str>)),
+            ("nested", Json::object(vec![("ok", Json::from(true))])),
+            ("object", Json::object(Vec::<(&str, Json)>::new())),
+        ]);
+        assert_eq!(
+            json.to_string(),
+            r#"{"name":"A","flags":[1,32],"empty":[],"none":null,"nested":{"ok":true},"object":{}}"#
+        );
+        assert_eq!(
+            json.pretty(),
+            r#"{
+  "name": "A",
+  "flags": [
+    1,
+    32
+  ],
+  "empty": [],
+  "none": null,
+  "nested": {
+    "ok": true
+  },
+  "object": {}
+}"#
+        );
+    }
+
+    #[test]
+    fn strings_escape_quotes_controls_and_unpaired_surrogates() {
+        assert_eq!(
+            Json::from("\"\\/\n\r\t\u{8}\u{c}\u{0}\u{1f}\u{7f}é😀").to_string(),
+            "\"\\\"\\\\/\\n\\r\\t\\b\\f\\u0000\\u001f\u{7f}é😀\""
+        );
+        // A surrogate pair is one character; a lone surrogate can only be escaped.
+        assert_eq!(Json::utf16(&[0xd83d, 0xde00]), Json::from("😀"));
+        let lone = Json::utf16(&[0x61, 0xdc00, 0xd83d, 0x62]);
+        assert_eq!(lone, Json::Utf16(vec![0x61, 0xdc00, 0xd83d, 0x62]));
+        assert_eq!(lone.to_string(), r#""a\udc00\ud83db""#);
+        assert_eq!(
+            Json::object(vec![("k\"ey", Json::utf16(&[0xd800]))]).to_string(),
+            r#"{"k\"ey":"\ud800"}"#
+        );
+    }
+
+    #[test]
+    fn numbers_and_non_finite_floats() {
+        assert_eq!(Json::from(-7i8).to_string(), "-7");
+        assert_eq!(Json::from(u64::MAX).to_string(), "18446744073709551615");
+        assert_eq!(Json::from(0.1f32).to_string(), "0.1");
+        assert_eq!(Json::from(1.0f64).to_string(), "1.0");
+        assert_eq!(Json::from(-0.0f64).to_string(), "-0.0");
+        assert_eq!(Json::from(1e300f64).to_string(), "1e300");
+        assert_eq!(Json::from(f32::NAN), Json::from("NaN"));
+        assert_eq!(Json::from(f64::INFINITY), Json::from("Infinity"));
+        assert_eq!(Json::from(f32::NEG_INFINITY).to_string(), r#""-Infinity""#);
+    }
+}
@@ -0,0 +1,131 @@
+// JVM "modified UTF-8" (JVMS 4.4.7): U+0000 is written as the two bytes C0 80, characters
+// outside the BMP are written as their UTF-16 surrogate pair with each surrogate encoded as
+// a three byte sequence, and four byte forms never appear. A Java string is a sequence of
+// UTF-16 code units which may contain unpaired surrogates, so decoding yields Vec<u16>.
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<u16>, (usize, &'static str)> {
+    let mut chars: Vec<u16> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let x = bytes[i];
+        if x == 0 {
+            return Err((i, "byte 0x00 is not allowed"));
+        } else if x < 0x80 {
+            chars.push(x as u16);
+            i += 1;
+        } else if x & 0xE0 == 0xC0 {
+            let y = continuation(bytes, i + 1)?;
+            let c = ((x as u16 & 0x1F) << 6) | y;
+            if c < 0x80 && c != 0 {
+                return Err((i, "overlong two byte sequence"));
+            }
+            chars.push(c);
+            i += 2;
+        } else if x & 0xF0 == 0xE0 {
+            let y = continuation(bytes, i + 1)?;
+            let z = continuation(bytes, i + 2)?;
+            let c = ((x as u16 & 0x0F) << 12) | (y << 6) | z;
+            if c < 0x800 {
+                return Err((i, "overlong three byte sequence"));
+            }
+            chars.push(c);
+            i += 3;
+        } else {
+            return Err((i, "invalid leading byte"));
+        }
+    }
+    Ok(chars)
+}
+
+fn continuation(bytes: &[u8], i: usize) -> Result<u16, (usize, &'static str)> {
+    match bytes.get(i) {
+        Some(b) if b & 0xC0 == 0x80 => Ok((b & 0x3F) as u16),
+        Some(_) => Err((i, "invalid continuation byte")),
+        None => Err((i, "truncated multi-byte sequence")),
+    }
+}
+
+pub fn encode(chars: &[u16]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(chars.len());
+    for &c in chars {
+        if c != 0 && c < 0x80 {
+            bytes.push(c as u8);
+        } else if c < 0x800 {
+            bytes.push(0xC0 | (c >> 6) as u8);
+            bytes.push(0x80 | (c & 0x3F) as u8);
+        } else {
+            bytes.push(0xE0 | (c >> 12) as u8);
+            bytes.push(0x80 | ((c >> 6) & 0x3F) as u8);
+            bytes.push(0x80 | (c & 0x3F) as u8);
+        }
+    }
+    bytes
+}
+
+pub fn encode_str(s: &str) -> Vec<u8> {
+    encode(&s.encode_utf16().collect::<Vec<u16>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nul_is_two_bytes() {
+        assert_eq!(encode(&[0]), vec![0xC0, 0x80]);
+        assert_eq!(decode(&[0xC0, 0x80]), Ok(vec![0]));
+        assert_eq!(decode(&[b'a', 0x00]), Err((1, "byte 0x00 is not allowed")));
+    }
+
+    #[test]
+    fn ascii_two_and_three_byte_forms() {
+        let s = "a\u{e9}\u{20ac}";
+        let bytes = encode_str(s);
+        assert_eq!(bytes, vec![b'a', 0xC3, 0xA9, 0xE2, 0x82, 0xAC]);
+        assert_eq!(decode(&bytes), Ok(s.encode_utf16().collect()));
+    }
+
+    #[test]
+    fn supplementary_characters_are_surrogate_pairs() {
+        // U+1F600 is D83D DE00 in UTF-16; each surrogate takes three bytes.
+        let bytes = encode_str("\u{1F600}");
+        assert_eq!(bytes, vec![0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]);
+        assert_eq!(decode(&bytes), Ok(vec![0xD83D, 0xDE00]));
+        // Standard UTF-8's four byte form is not valid modified UTF-8.
+        assert_eq!(
+            decode(&[0xF0, 0x9F, 0x98, 0x80]),
+            Err((0, "invalid leading byte"))
+        );
+    }
+
+    #[test]
+    fn lone_surrogates_round_trip() {
+        for unit in [0xD800, 0xDBFF, 0xDC00, 0xDFFF] {
+            let bytes = encode(&[b'x' as u16, unit]);
+            assert_eq!(bytes.len(), 4);
+            assert_eq!(decode(&bytes), Ok(vec![b'x' as u16, unit]));
+        }
+    }
+
+    #[test]
+    fn overlong_forms_are_rejected() {
+        assert_eq!(
+            decode(&[0xC1, 0xBF]),
+            Err((0, "overlong two byte sequence"))
+        );
+        assert_eq!(
+            decode(&[b'a', 0xE0, 0x81, 0x81]),
+            Err((1, "overlong three byte sequence"))
+        );
+    }
+
+    #[test]
+    fn truncated_and_invalid_continuations() {
+        assert_eq!(
+            decode(&[0xE2, 0x82]),
+            Err((2, "truncated multi-byte sequence"))
+        );
+        assert_eq!(decode(&[0xC3, 0x41]), Err((1, "invalid continuation byte")));
+        assert_eq!(decode(&[0x80]), Err((0, "invalid leading byte")));
+    }
+}